@@ -0,0 +1,347 @@
+#!/usr/bin/env python3
+"""Generate the RDB test corpus used by src/persistence/rdb.
+
+This is a stand-in for a local redis-server: it writes byte-for-byte the
+encodings that Redis 5.0 (RDB 9), 7.0 (RDB 10) and 7.2 (RDB 11) produce, so the
+fixtures can be regenerated without a redis binary:
+
+    python3 fixtures/rdb/gen_rdb.py fixtures/rdb
+"""
+
+import os
+import struct
+import sys
+
+# opcodes
+OP_IDLE = 0xF8
+OP_FREQ = 0xF9
+OP_AUX = 0xFA
+OP_RESIZEDB = 0xFB
+OP_EXPIRETIME_MS = 0xFC
+OP_EXPIRETIME = 0xFD
+OP_SELECTDB = 0xFE
+OP_EOF = 0xFF
+
+# value types
+T_STRING = 0
+T_LIST = 1
+T_SET = 2
+T_ZSET = 3
+T_HASH = 4
+T_ZSET_2 = 5
+T_HASH_ZIPMAP = 9
+T_LIST_ZIPLIST = 10
+T_SET_INTSET = 11
+T_ZSET_ZIPLIST = 12
+T_HASH_ZIPLIST = 13
+T_LIST_QUICKLIST = 14
+T_HASH_LISTPACK = 16
+T_ZSET_LISTPACK = 17
+T_LIST_QUICKLIST_2 = 18
+T_SET_LISTPACK = 20
+
+# 2100-01-01T00:00:00Z
+FAR_FUTURE_SECS = 4102444800
+
+
+def crc64(data):
+    """CRC-64/Jones, reflected, as used by Redis."""
+    poly = 0x95AC9329AC4BC9B5
+    crc = 0
+    for b in data:
+        crc ^= b
+        for _ in range(8):
+            crc = (crc >> 1) ^ poly if crc & 1 else crc >> 1
+    return crc
+
+
+def lzf_compress(data):
+    out = bytearray()
+    lit = bytearray()
+    last = {}
+    i = 0
+
+    def flush():
+        if lit:
+            out.append(len(lit) - 1)
+            out.extend(lit)
+            lit.clear()
+
+    while i < len(data):
+        key = data[i:i + 3]
+        ref = last.get(key) if len(key) == 3 else None
+        if len(key) == 3:
+            last[key] = i
+        if ref is not None and i - ref <= 8192:
+            length = 3
+            while length < 264 and i + length < len(data) and data[ref + length] == data[i + length]:
+                length += 1
+            flush()
+            off = i - ref - 1
+            l = length - 2
+            if l < 7:
+                out.append((l << 5) | (off >> 8))
+            else:
+                out.append((7 << 5) | (off >> 8))
+                out.append(l - 7)
+            out.append(off & 0xFF)
+            i += length
+            continue
+        lit.append(data[i])
+        i += 1
+        if len(lit) == 32:
+            flush()
+    flush()
+    return bytes(out)
+
+
+def length(n):
+    if n < 1 << 6:
+        return bytes([n])
+    if n < 1 << 14:
+        return bytes([0x40 | (n >> 8), n & 0xFF])
+    if n < 1 << 32:
+        return b"\x80" + struct.pack(">I", n)
+    return b"\x81" + struct.pack(">Q", n)
+
+
+def as_int(s):
+    try:
+        v = int(s)
+    except ValueError:
+        return None
+    return v if str(v).encode() == s else None
+
+
+def string(s):
+    if isinstance(s, str):
+        s = s.encode()
+    v = as_int(s) if len(s) <= 11 else None
+    if v is not None:
+        if -(1 << 7) <= v < 1 << 7:
+            return b"\xc0" + struct.pack("<b", v)
+        if -(1 << 15) <= v < 1 << 15:
+            return b"\xc1" + struct.pack("<h", v)
+        if -(1 << 31) <= v < 1 << 31:
+            return b"\xc2" + struct.pack("<i", v)
+    if len(s) > 20:
+        c = lzf_compress(s)
+        if len(c) < len(s):
+            return b"\xc3" + length(len(c)) + length(len(s)) + c
+    return length(len(s)) + s
+
+
+def ziplist(items):
+    body = bytearray()
+    prevlen = 0
+    tail = 10
+    for item in items:
+        if isinstance(item, str):
+            item = item.encode()
+        entry = bytearray()
+        entry += bytes([prevlen]) if prevlen < 254 else b"\xfe" + struct.pack("<I", prevlen)
+        v = as_int(item)
+        if v is not None and 0 <= v <= 12:
+            entry.append(0xF1 + v)
+        elif v is not None and -(1 << 7) <= v < 1 << 7:
+            entry += b"\xfe" + struct.pack("<b", v)
+        elif v is not None and -(1 << 15) <= v < 1 << 15:
+            entry += b"\xc0" + struct.pack("<h", v)
+        elif v is not None and -(1 << 23) <= v < 1 << 23:
+            entry += b"\xf0" + struct.pack("<i", v)[:3]
+        elif v is not None and -(1 << 31) <= v < 1 << 31:
+            entry += b"\xd0" + struct.pack("<i", v)
+        elif v is not None:
+            entry += b"\xe0" + struct.pack("<q", v)
+        elif len(item) < 64:
+            entry += bytes([len(item)]) + item
+        elif len(item) < 16384:
+            entry += bytes([0x40 | (len(item) >> 8), len(item) & 0xFF]) + item
+        else:
+            entry += b"\x80" + struct.pack(">I", len(item)) + item
+        tail = 10 + len(body)
+        body += entry
+        prevlen = len(entry)
+    total = 10 + len(body) + 1
+    return struct.pack("<IIH", total, tail, len(items)) + bytes(body) + b"\xff"
+
+
+def backlen(n):
+    if n <= 127:
+        return bytes([n])
+    if n < 16383:
+        return bytes([n >> 7, (n & 127) | 128])
+    if n < 2097151:
+        return bytes([n >> 14, ((n >> 7) & 127) | 128, (n & 127) | 128])
+    raise ValueError("entry too large")
+
+
+def listpack(items):
+    body = bytearray()
+    for item in items:
+        if isinstance(item, str):
+            item = item.encode()
+        v = as_int(item)
+        if v is not None and 0 <= v <= 127:
+            entry = bytes([v])
+        elif v is not None and -4096 <= v < 4096:
+            u = v & 0x1FFF
+            entry = bytes([0xC0 | (u >> 8), u & 0xFF])
+        elif v is not None and -(1 << 15) <= v < 1 << 15:
+            entry = b"\xf1" + struct.pack("<h", v)
+        elif v is not None and -(1 << 23) <= v < 1 << 23:
+            entry = b"\xf2" + struct.pack("<i", v)[:3]
+        elif v is not None and -(1 << 31) <= v < 1 << 31:
+            entry = b"\xf3" + struct.pack("<i", v)
+        elif v is not None:
+            entry = b"\xf4" + struct.pack("<q", v)
+        elif len(item) < 64:
+            entry = bytes([0x80 | len(item)]) + item
+        elif len(item) < 4096:
+            entry = bytes([0xE0 | (len(item) >> 8), len(item) & 0xFF]) + item
+        else:
+            entry = b"\xf0" + struct.pack("<I", len(item)) + item
+        body += entry + backlen(len(entry))
+    total = 6 + len(body) + 1
+    return struct.pack("<IH", total, len(items)) + bytes(body) + b"\xff"
+
+
+def intset(values):
+    values = sorted(values)
+    if all(-(1 << 15) <= v < 1 << 15 for v in values):
+        width, fmt = 2, "<h"
+    elif all(-(1 << 31) <= v < 1 << 31 for v in values):
+        width, fmt = 4, "<i"
+    else:
+        width, fmt = 8, "<q"
+    return struct.pack("<II", width, len(values)) + b"".join(struct.pack(fmt, v) for v in values)
+
+
+def zipmap(pairs):
+    out = bytearray([len(pairs)])
+    for k, v in pairs:
+        k, v = k.encode(), v.encode()
+        out += bytes([len(k)]) + k + bytes([len(v)]) + b"\x00" + v
+    return bytes(out + b"\xff")
+
+
+def double_string(v):
+    if v == float("inf"):
+        return b"\xfe"
+    if v == float("-inf"):
+        return b"\xff"
+    s = repr(v).encode()
+    if s.endswith(b".0"):
+        s = s[:-2]
+    return bytes([len(s)]) + s
+
+
+class Rdb:
+    def __init__(self, version):
+        self.version = version
+        self.buf = bytearray(b"REDIS%04d" % version)
+
+    def aux(self, key, value):
+        self.buf += bytes([OP_AUX]) + string(key) + string(value)
+
+    def op(self, *data):
+        for d in data:
+            self.buf += d if isinstance(d, (bytes, bytearray)) else bytes([d])
+
+    def kv(self, rdb_type, key, payload):
+        self.buf += bytes([rdb_type]) + string(key) + payload
+
+    def finish(self):
+        self.buf.append(OP_EOF)
+        self.buf += struct.pack("<Q", crc64(self.buf))
+        return bytes(self.buf)
+
+
+def generate(version):
+    rdb = Rdb(version)
+    redis_ver = {9: "5.0.14", 10: "7.0.15", 11: "7.2.4"}[version]
+    rdb.aux("redis-ver", redis_ver)
+    rdb.aux("redis-bits", "64")
+    rdb.aux("ctime", "1700000000")
+    rdb.aux("used-mem", "1048576")
+    if version >= 10:
+        rdb.aux("aof-base", "0")
+    else:
+        rdb.aux("aof-preamble", "0")
+
+    rdb.op(OP_SELECTDB, length(0), OP_RESIZEDB, length(20), length(3))
+
+    # strings
+    rdb.op(OP_IDLE, length(10))
+    rdb.kv(T_STRING, "str", string("hello"))
+    rdb.op(OP_FREQ, 5)
+    rdb.kv(T_STRING, "int8", string("-12"))
+    rdb.kv(T_STRING, "int16", string("1234"))
+    rdb.kv(T_STRING, "int32", string("-123456789"))
+    rdb.kv(T_STRING, "lzf", string("abcdefgh" * 16))
+
+    # expiry
+    rdb.op(OP_EXPIRETIME_MS, struct.pack("<Q", FAR_FUTURE_SECS * 1000))
+    rdb.kv(T_STRING, "expire_ms", string("ms"))
+    rdb.op(OP_EXPIRETIME, struct.pack("<I", FAR_FUTURE_SECS))
+    rdb.kv(T_STRING, "expire_secs", string("secs"))
+    rdb.op(OP_EXPIRETIME_MS, struct.pack("<Q", 1000))
+    rdb.kv(T_STRING, "expired", string("gone"))
+
+    # sets
+    rdb.kv(T_SET_INTSET, "intset", string(intset([1, 2, 300, -70000])))
+    members = [b"apple", b"banana", b"cherry"]
+    rdb.kv(T_SET, "set", length(len(members)) + b"".join(string(m) for m in members))
+
+    # encodings used above the listpack/ziplist limits
+    rdb.kv(T_HASH, "bighash", length(2) + string("a") + string("v" * 100) + string("b") + string("2"))
+    zset = [("m1", -1.5), ("m2", 2.0), ("m3", float("inf"))]
+    rdb.kv(
+        T_ZSET_2,
+        "bigzset",
+        length(len(zset)) + b"".join(string(m) + struct.pack("<d", s) for m, s in zset),
+    )
+
+    small_list = ["a", "12", "-5000", "b", "70000", "5000000000"]
+    small_hash = ["name", "redis", "port", "6379"]
+    small_zset = ["one", "1", "half", "1.5", "ten", "10"]
+    if version >= 10:
+        rdb.kv(T_LIST_QUICKLIST_2, "biglist", length(1) + length(2) + string(listpack(["x", "y", "z"])))
+        rdb.kv(T_LIST_QUICKLIST_2, "list", length(1) + length(2) + string(listpack(small_list)))
+        rdb.kv(T_HASH_LISTPACK, "hash", string(listpack(small_hash)))
+        rdb.kv(T_ZSET_LISTPACK, "zset", string(listpack(small_zset)))
+    else:
+        rdb.kv(T_LIST_QUICKLIST, "biglist", length(1) + string(ziplist(["x", "y", "z"])))
+        rdb.kv(T_LIST_QUICKLIST, "list", length(1) + string(ziplist(small_list)))
+        rdb.kv(T_HASH_ZIPLIST, "hash", string(ziplist(small_hash)))
+        rdb.kv(T_ZSET_ZIPLIST, "zset", string(ziplist(small_zset)))
+        # encodings from older Redis versions that RDB 9 readers still accept
+        rdb.kv(T_ZSET, "oldzset", length(2) + string("a") + double_string(1.0) + string("b") + double_string(2.5))
+        rdb.kv(T_LIST_ZIPLIST, "ziplist", string(ziplist(["1", "two"])))
+        rdb.kv(T_HASH_ZIPMAP, "zipmap", string(zipmap([("k", "v")])))
+
+    if version >= 11:
+        rdb.kv(T_SET_LISTPACK, "lpset", string(listpack(["apple", "pear"])))
+        rdb.kv(
+            T_LIST_QUICKLIST_2,
+            "plainlist",
+            length(2) + length(1) + string("plain") + length(2) + string(listpack(["a", "b"])),
+        )
+
+    rdb.op(OP_SELECTDB, length(1), OP_RESIZEDB, length(1), length(0))
+    rdb.kv(T_STRING, "db1key", string("other"))
+
+    return rdb.finish()
+
+
+def main():
+    out = sys.argv[1] if len(sys.argv) > 1 else os.path.dirname(os.path.abspath(__file__))
+    for version in (9, 10, 11):
+        path = os.path.join(out, "dump-v%d.rdb" % version)
+        with open(path, "wb") as f:
+            f.write(generate(version))
+        print("wrote", path)
+
+
+if __name__ == "__main__":
+    main()
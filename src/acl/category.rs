@@ -0,0 +1,60 @@
+use crate::cmd::CommandRegistry;
+
+/// 所有的ACL分类，ACL CAT 按这个顺序输出
+pub const CATEGORIES: &[&str] = &[
+    "keyspace",
+    "read",
+    "write",
+    "set",
+    "sortedset",
+    "list",
+    "hash",
+    "string",
+    "bitmap",
+    "hyperloglog",
+    "geo",
+    "stream",
+    "pubsub",
+    "admin",
+    "fast",
+    "slow",
+    "blocking",
+    "dangerous",
+    "connection",
+    "transaction",
+    "scripting",
+];
+
+/// 查找命令所属的分类，名字需要是小写，命令表中没有的子命令使用命令的分类
+pub fn command_categories(
+    commands: &CommandRegistry,
+    cmd: &str,
+    sub: Option<&str>,
+) -> &'static [&'static str] {
+    let Some(spec) = commands.lookup(cmd.as_bytes()) else {
+        return &[];
+    };
+    sub.and_then(|sub| spec.subcommand(sub.as_bytes()))
+        .map_or(spec.categories, |sub| sub.categories)
+}
+
+/// 分类下的所有命令，包括子命令
+pub fn category_commands(commands: &CommandRegistry, category: &str) -> Vec<&'static str> {
+    commands
+        .all()
+        .iter()
+        .flat_map(|spec| std::iter::once(spec).chain(spec.subcommands))
+        .filter(|spec| spec.categories.contains(&category))
+        .map(|spec| spec.name)
+        .collect()
+}
+
+pub fn is_category(name: &str) -> bool {
+    name == "all" || CATEGORIES.contains(&name)
+}
+
+/// 是否为已知的命令或者子命令
+pub fn is_command(commands: &CommandRegistry, name: &str) -> bool {
+    let cmd = name.split('|').next().unwrap_or_default();
+    commands.lookup(cmd.as_bytes()).is_some()
+}
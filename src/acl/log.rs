@@ -0,0 +1,109 @@
+use std::collections::VecDeque;
+
+use crate::now_ms;
+
+/// 相同的拒绝在这个时间内合并成一条记录
+const GROUPING_MAX_TIME_DELTA_MS: i64 = 60_000;
+
+/// 拒绝的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AclLogReason {
+    Command,
+    Key,
+    Auth,
+}
+
+impl AclLogReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AclLogReason::Command => "command",
+            AclLogReason::Key => "key",
+            AclLogReason::Auth => "auth",
+        }
+    }
+}
+
+/// ACL LOG 中的一条记录
+#[derive(Debug, Clone, PartialEq)]
+pub struct AclLogEntry {
+    pub count: u64,
+    pub reason: AclLogReason,
+    /// 被拒绝的命令、key或者频道
+    pub object: String,
+    pub username: String,
+    pub client_info: String,
+    pub entry_id: u64,
+    pub created: i64,
+    pub updated: i64,
+}
+
+/// 最近被拒绝的请求，新的在前面
+#[derive(Debug)]
+pub struct AclLog {
+    entries: VecDeque<AclLogEntry>,
+    next_id: u64,
+    max_len: usize,
+}
+
+impl Default for AclLog {
+    fn default() -> Self {
+        AclLog {
+            entries: VecDeque::new(),
+            next_id: 0,
+            max_len: 128,
+        }
+    }
+}
+
+impl AclLog {
+    pub fn add(&mut self, reason: AclLogReason, object: &str, username: &str, client_info: &str) {
+        let now = now_ms();
+        let pos = self.entries.iter().position(|e| {
+            e.reason == reason
+                && e.object == object
+                && e.username == username
+                && now - e.updated < GROUPING_MAX_TIME_DELTA_MS
+        });
+        let entry = match pos.and_then(|pos| self.entries.remove(pos)) {
+            Some(mut entry) => {
+                entry.count += 1;
+                entry.updated = now;
+                entry.client_info = client_info.to_string();
+                entry
+            }
+            None => {
+                let entry_id = self.next_id;
+                self.next_id += 1;
+                AclLogEntry {
+                    count: 1,
+                    reason,
+                    object: object.to_string(),
+                    username: username.to_string(),
+                    client_info: client_info.to_string(),
+                    entry_id,
+                    created: now,
+                    updated: now,
+                }
+            }
+        };
+        self.entries.push_front(entry);
+        self.entries.truncate(self.max_len);
+    }
+
+    pub fn entries(&self, count: usize) -> Vec<AclLogEntry> {
+        self.entries.iter().take(count).cloned().collect()
+    }
+
+    pub fn reset(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    pub fn set_max_len(&mut self, max_len: usize) {
+        self.max_len = max_len;
+        self.entries.truncate(max_len);
+    }
+}
@@ -0,0 +1,399 @@
+//! 认证和访问控制
+//!
+//! 每个连接以某个用户的身份执行命令，命令执行前检查用户是否有权限执行这个命令、访问这些key。
+//! `requirepass` 只是设置默认用户的密码，更多的用户可以通过ACL SETUSER或者ACL文件管理。
+
+mod category;
+mod log;
+mod user;
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Write,
+    path::Path,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use thiserror::Error;
+
+use crate::{
+    cmd::{CommandRegistry, ErrorCode},
+    RespArray, RespFrame,
+};
+
+pub use self::{
+    category::{category_commands, command_categories, CATEGORIES},
+    log::{AclLogEntry, AclLogReason},
+    user::{hash_password, User},
+};
+
+use self::log::AclLog;
+
+/// 默认用户的名字
+pub const DEFAULT_USER: &str = "default";
+
+/// ACL相关的异常
+#[derive(Error, Debug)]
+pub enum AclError {
+    #[error("IO Error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Error in ACL SETUSER modifier '{rule}': {reason}")]
+    InvalidRule { rule: String, reason: String },
+    #[error("The '{0}' user cannot be removed")]
+    DefaultUser(String),
+    #[error("{location}: {reason}")]
+    InvalidFile { location: String, reason: String },
+    #[error("This Redis instance is not configured to use an ACL file. You may want to specify users via the ACL SETUSER command and then issue a CONFIG REWRITE (assuming you have a Redis configuration file set) in order to store users in the Redis configuration.")]
+    NoAclFile,
+}
+
+/// 命令被拒绝的原因，回复给客户端时错误码为NOPERM
+#[derive(Error, Debug, PartialEq)]
+pub enum AclDenied {
+    #[error("User {user} has no permissions to run the '{command}' command")]
+    Command { user: String, command: String },
+    #[error("No permissions to access a key")]
+    Key(String),
+    /// 连接使用的用户已经被删除
+    #[error("User {0} no longer exists")]
+    NoUser(String),
+}
+
+impl From<AclDenied> for RespFrame {
+    fn from(denied: AclDenied) -> Self {
+        ErrorCode::NoPerm.reply(denied)
+    }
+}
+
+impl AclDenied {
+    /// 记录到ACL LOG中的原因和对象
+    fn log_reason(&self) -> Option<(AclLogReason, &str)> {
+        match self {
+            AclDenied::Command { command, .. } => Some((AclLogReason::Command, command)),
+            AclDenied::Key(key) => Some((AclLogReason::Key, key)),
+            AclDenied::NoUser(_) => None,
+        }
+    }
+}
+
+/// ACL运行时状态，挂在Backend上共享
+#[derive(Debug)]
+pub struct AclState {
+    users: RwLock<BTreeMap<String, User>>,
+    log: Mutex<AclLog>,
+    /// 和Backend共享，规则可以引用自定义命令
+    commands: Arc<CommandRegistry>,
+}
+
+impl Default for AclState {
+    fn default() -> Self {
+        AclState::new(Arc::default())
+    }
+}
+
+/// 命令的名字和子命令，都转换为小写
+fn command_name(args: &RespArray) -> (String, Option<String>) {
+    let arg = |i: usize| match args.get(i) {
+        Some(RespFrame::BulkString(s)) => Some(String::from_utf8_lossy(s).to_ascii_lowercase()),
+        _ => None,
+    };
+    (arg(0).unwrap_or_default(), arg(1))
+}
+
+impl AclState {
+    pub fn new(commands: Arc<CommandRegistry>) -> Self {
+        let mut users = BTreeMap::new();
+        users.insert(DEFAULT_USER.to_string(), User::new_default());
+        AclState {
+            users: RwLock::new(users),
+            log: Mutex::new(AclLog::default()),
+            commands,
+        }
+    }
+
+    pub fn user(&self, name: &str) -> Option<User> {
+        self.users.read().unwrap().get(name).cloned()
+    }
+
+    /// 所有用户，按名字排序
+    pub fn users(&self) -> Vec<User> {
+        self.users.read().unwrap().values().cloned().collect()
+    }
+
+    /// 默认用户不需要密码时，新连接自动以默认用户登录
+    pub fn default_nopass(&self) -> bool {
+        self.users
+            .read()
+            .unwrap()
+            .get(DEFAULT_USER)
+            .map(|u| u.is_enabled() && u.is_nopass())
+            .unwrap_or(false)
+    }
+
+    /// 校验用户名和密码
+    pub fn authenticate(&self, username: &str, password: &str) -> bool {
+        self.users
+            .read()
+            .unwrap()
+            .get(username)
+            .map(|u| u.check_password(password))
+            .unwrap_or(false)
+    }
+
+    /// 检查用户是否可以执行这条命令
+    pub fn check(&self, username: &str, args: &RespArray) -> Result<(), AclDenied> {
+        let users = self.users.read().unwrap();
+        let user = users
+            .get(username)
+            .ok_or_else(|| AclDenied::NoUser(username.to_string()))?;
+        let (cmd, sub) = command_name(args);
+        let commands = &self.commands;
+        if !user.can_run(&cmd, sub.as_deref(), commands) {
+            // 只有子命令被拒绝时带上子命令
+            let command = match sub {
+                Some(sub) if user.can_run(&cmd, None, commands) => format!("{}|{}", cmd, sub),
+                _ => cmd,
+            };
+            return Err(AclDenied::Command {
+                user: username.to_string(),
+                command,
+            });
+        }
+        let write = command_categories(commands, &cmd, sub.as_deref()).contains(&"write");
+        for key in commands.keys(args) {
+            if !user.can_access_key(key, write) {
+                return Err(AclDenied::Key(String::from_utf8_lossy(key).to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// 创建或者修改用户，所有规则都合法时才生效
+    pub fn set_user(&self, name: &str, rules: &[impl AsRef<str>]) -> Result<(), AclError> {
+        let mut users = self.users.write().unwrap();
+        let mut user = users.get(name).cloned().unwrap_or_else(|| User::new(name));
+        for rule in rules {
+            let rule = rule.as_ref();
+            user.apply_rule(rule, &self.commands)
+                .map_err(|reason| AclError::InvalidRule {
+                    rule: rule.to_string(),
+                    reason,
+                })?;
+        }
+        users.insert(name.to_string(), user);
+        Ok(())
+    }
+
+    /// 删除用户，返回删除的数量
+    pub fn del_users(&self, names: &[impl AsRef<str>]) -> Result<usize, AclError> {
+        if names.iter().any(|n| n.as_ref() == DEFAULT_USER) {
+            return Err(AclError::DefaultUser(DEFAULT_USER.to_string()));
+        }
+        let mut users = self.users.write().unwrap();
+        Ok(names
+            .iter()
+            .filter(|n| users.remove(n.as_ref()).is_some())
+            .count())
+    }
+
+    /// requirepass 设置默认用户的密码，为空时不需要密码
+    pub fn set_requirepass(&self, password: &str) {
+        let rules = if password.is_empty() {
+            vec!["nopass".to_string()]
+        } else {
+            vec!["resetpass".to_string(), format!(">{}", password)]
+        };
+        // 这些规则不会失败
+        let _ = self.set_user(DEFAULT_USER, &rules);
+    }
+
+    /// 记录被拒绝的命令
+    pub fn log_denied(&self, denied: &AclDenied, username: &str, client_info: &str) {
+        if let Some((reason, object)) = denied.log_reason() {
+            self.log
+                .lock()
+                .unwrap()
+                .add(reason, object, username, client_info);
+        }
+    }
+
+    /// 记录认证失败
+    pub fn log_auth_failure(&self, username: &str, client_info: &str) {
+        self.log
+            .lock()
+            .unwrap()
+            .add(AclLogReason::Auth, "AUTH", username, client_info);
+    }
+
+    /// 最近的count条记录
+    pub fn log_entries(&self, count: usize) -> Vec<AclLogEntry> {
+        self.log.lock().unwrap().entries(count)
+    }
+
+    pub fn reset_log(&self) {
+        self.log.lock().unwrap().reset();
+    }
+
+    pub fn log_max_len(&self) -> usize {
+        self.log.lock().unwrap().max_len()
+    }
+
+    pub fn set_log_max_len(&self, max_len: usize) {
+        self.log.lock().unwrap().set_max_len(max_len);
+    }
+
+    /// 从ACL文件加载所有用户，文件有错误时不做任何修改
+    pub fn load_file(&self, path: impl AsRef<Path>) -> Result<(), AclError> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)?;
+        let mut users = BTreeMap::new();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |reason: String| AclError::InvalidFile {
+                location: format!("{}:{}", path.display(), i + 1),
+                reason,
+            };
+            let mut parts = line.split_whitespace();
+            let (Some("user"), Some(name)) = (parts.next(), parts.next()) else {
+                return Err(invalid(
+                    "should start with user keyword followed by the username".to_string(),
+                ));
+            };
+            if users.contains_key(name) {
+                return Err(invalid(format!("Duplicate user '{}' found", name)));
+            }
+            let mut user = User::new(name);
+            for rule in parts {
+                user.apply_rule(rule, &self.commands)
+                    .map_err(|reason| invalid(format!("{}. Use ACL LOAD to fix it", reason)))?;
+            }
+            users.insert(name.to_string(), user);
+        }
+        // 文件中没有默认用户时使用默认的配置
+        users
+            .entry(DEFAULT_USER.to_string())
+            .or_insert_with(User::new_default);
+        *self.users.write().unwrap() = users;
+        Ok(())
+    }
+
+    /// 保存所有用户到ACL文件，先写临时文件再替换
+    pub fn save_file(&self, path: impl AsRef<Path>) -> Result<(), AclError> {
+        let path = path.as_ref();
+        let tmp = path.with_extension("tmp");
+        let mut file = fs::File::create(&tmp)?;
+        for user in self.users() {
+            writeln!(file, "{}", user.describe())?;
+        }
+        file.sync_all()?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BulkString;
+    use anyhow::Result;
+
+    fn args(args: &[&str]) -> RespArray {
+        RespArray::new(
+            args.iter()
+                .map(|arg| BulkString::new(*arg).into())
+                .collect::<Vec<RespFrame>>(),
+        )
+    }
+
+    #[test]
+    fn test_acl_check() -> Result<()> {
+        let acl = AclState::default();
+        assert!(acl.default_nopass());
+        acl.set_user("alice", &["on", ">secret", "~cache:*", "+@read", "+set"])?;
+        assert!(acl.authenticate("alice", "secret"));
+        assert!(!acl.authenticate("alice", "wrong"));
+        assert!(!acl.authenticate("bob", "secret"));
+
+        assert_eq!(acl.check("alice", &args(&["GET", "cache:1"])), Ok(()));
+        assert_eq!(
+            acl.check("alice", &args(&["get", "other"])),
+            Err(AclDenied::Key("other".to_string()))
+        );
+        let denied = acl.check("alice", &args(&["hset", "cache:1", "f", "v"]));
+        assert_eq!(
+            denied.unwrap_err().to_string(),
+            "User alice has no permissions to run the 'hset' command"
+        );
+        let denied = acl.check("alice", &args(&["config", "get", "port"]));
+        assert!(matches!(denied, Err(AclDenied::Command { command, .. }) if command == "config"));
+        assert_eq!(
+            acl.check("bob", &args(&["get", "cache:1"])),
+            Err(AclDenied::NoUser("bob".to_string()))
+        );
+
+        // 规则有错误时不修改用户
+        assert!(acl.set_user("alice", &["-get", "+foo"]).is_err());
+        assert_eq!(acl.check("alice", &args(&["get", "cache:1"])), Ok(()));
+
+        acl.set_requirepass("foobared");
+        assert!(!acl.default_nopass());
+        assert!(acl.authenticate(DEFAULT_USER, "foobared"));
+        assert!(acl.del_users(&[DEFAULT_USER]).is_err());
+        assert_eq!(acl.del_users(&["alice", "bob"])?, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_acl_log() {
+        let acl = AclState::default();
+        let denied = AclDenied::Key("secret".to_string());
+        acl.log_denied(&denied, "alice", "addr=127.0.0.1:1234");
+        acl.log_denied(&denied, "alice", "addr=127.0.0.1:1235");
+        acl.log_auth_failure("bob", "addr=127.0.0.1:1236");
+
+        let entries = acl.log_entries(10);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].reason, AclLogReason::Auth);
+        assert_eq!(entries[0].entry_id, 1);
+        assert_eq!(entries[1].count, 2);
+        assert_eq!(entries[1].client_info, "addr=127.0.0.1:1235");
+
+        acl.set_log_max_len(1);
+        assert_eq!(acl.log_entries(10).len(), 1);
+        acl.reset_log();
+        assert!(acl.log_entries(10).is_empty());
+    }
+
+    #[test]
+    fn test_acl_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("users.acl");
+        let acl = AclState::default();
+        acl.set_user(
+            "alice",
+            &["on", ">secret", "%R~cache:*", "+@all", "-@admin"],
+        )?;
+        acl.save_file(&path)?;
+
+        let loaded = AclState::default();
+        loaded.load_file(&path)?;
+        assert_eq!(loaded.users(), acl.users());
+
+        fs::write(&path, "user alice on\nuser alice off\n")?;
+        let err = loaded.load_file(&path).unwrap_err();
+        assert!(err.to_string().contains("users.acl:2"));
+        fs::write(&path, "user alice on +nosuchcommand\n")?;
+        assert!(loaded.load_file(&path).is_err());
+        assert_eq!(loaded.users(), acl.users());
+
+        // 没有默认用户时使用默认配置
+        fs::write(&path, "user alice on nopass\n")?;
+        loaded.load_file(&path)?;
+        assert!(loaded.default_nopass());
+        Ok(())
+    }
+}
@@ -0,0 +1,448 @@
+use std::fmt;
+
+use sha2::{Digest, Sha256};
+
+use crate::{cmd::CommandRegistry, config::glob_match};
+
+use super::category::{command_categories, is_category, is_command};
+
+/// SHA256十六进制的长度
+const HASH_LEN: usize = 64;
+
+/// 命令规则作用的对象
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CommandTarget {
+    All,
+    Category(String),
+    Command(String),
+    Subcommand(String, String),
+}
+
+/// +/-命令或者分类，按顺序应用，后面的覆盖前面的
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CommandRule {
+    allow: bool,
+    target: CommandTarget,
+}
+
+/// key的访问权限 ~pattern、%R~pattern、%W~pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct KeyPattern {
+    read: bool,
+    write: bool,
+    pattern: String,
+}
+
+/// ACL用户
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct User {
+    name: String,
+    enabled: bool,
+    nopass: bool,
+    /// 密码的SHA256
+    passwords: Vec<String>,
+    commands: Vec<CommandRule>,
+    keys: Vec<KeyPattern>,
+    channels: Vec<String>,
+}
+
+/// 计算密码的SHA256十六进制
+pub fn hash_password(password: &str) -> String {
+    Sha256::digest(password.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn validate_hash(hash: &str) -> Result<(), String> {
+    if hash.len() != HASH_LEN || !hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+        return Err("The password hash must be exactly 64 characters and contain only lowercase hexadecimal characters".to_string());
+    }
+    Ok(())
+}
+
+impl User {
+    /// 新建的用户处于关闭状态，没有任何权限
+    pub fn new(name: impl Into<String>) -> Self {
+        User {
+            name: name.into(),
+            enabled: false,
+            nopass: false,
+            passwords: vec![],
+            commands: vec![CommandRule {
+                allow: false,
+                target: CommandTarget::All,
+            }],
+            keys: vec![],
+            channels: vec![],
+        }
+    }
+
+    /// 默认用户 on nopass ~* &* +@all
+    pub fn new_default() -> Self {
+        let mut user = User::new("default");
+        // 固定的规则不会失败，也不引用具体的命令
+        let commands = CommandRegistry::default();
+        for rule in ["on", "nopass", "~*", "&*", "+@all"] {
+            let _ = user.apply_rule(rule, &commands);
+        }
+        user
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn is_nopass(&self) -> bool {
+        self.nopass
+    }
+
+    pub fn passwords(&self) -> &[String] {
+        &self.passwords
+    }
+
+    /// 应用一条ACL规则，例如 on、>password、~key:*、+@read，命令名在commands中查找
+    pub fn apply_rule(&mut self, rule: &str, commands: &CommandRegistry) -> Result<(), String> {
+        match rule.to_ascii_lowercase().as_str() {
+            "on" => self.enabled = true,
+            "off" => self.enabled = false,
+            "nopass" => {
+                self.nopass = true;
+                self.passwords.clear();
+            }
+            "resetpass" => {
+                self.nopass = false;
+                self.passwords.clear();
+            }
+            "allkeys" => return self.apply_rule("~*", commands),
+            "resetkeys" => self.keys.clear(),
+            "allchannels" => return self.apply_rule("&*", commands),
+            "resetchannels" => self.channels.clear(),
+            "allcommands" => return self.apply_rule("+@all", commands),
+            "nocommands" => return self.apply_rule("-@all", commands),
+            "reset" => {
+                for rule in ["resetpass", "resetkeys", "resetchannels", "off", "-@all"] {
+                    self.apply_rule(rule, commands)?;
+                }
+            }
+            _ => return self.apply_pattern_rule(rule, commands),
+        }
+        Ok(())
+    }
+
+    fn apply_pattern_rule(&mut self, rule: &str, commands: &CommandRegistry) -> Result<(), String> {
+        if let Some(password) = rule.strip_prefix('>') {
+            self.add_password(hash_password(password));
+        } else if let Some(password) = rule.strip_prefix('<') {
+            self.remove_password(&hash_password(password))?;
+        } else if let Some(hash) = rule.strip_prefix('#') {
+            validate_hash(hash)?;
+            self.add_password(hash.to_string());
+        } else if let Some(hash) = rule.strip_prefix('!') {
+            validate_hash(hash)?;
+            self.remove_password(hash)?;
+        } else if let Some(pattern) = rule.strip_prefix('~') {
+            self.add_key_pattern(true, true, pattern);
+        } else if let Some(rest) = rule.strip_prefix('%') {
+            let (flags, pattern) = rest.split_once('~').ok_or("Syntax error")?;
+            let flags = flags.to_ascii_uppercase();
+            let read = flags.contains('R');
+            let write = flags.contains('W');
+            if flags.is_empty() || flags.chars().any(|c| c != 'R' && c != 'W') {
+                return Err("Syntax error".to_string());
+            }
+            self.add_key_pattern(read, write, pattern);
+        } else if let Some(pattern) = rule.strip_prefix('&') {
+            if pattern == "*" {
+                self.channels.clear();
+            }
+            if !self.channels.iter().any(|p| p == pattern) {
+                self.channels.push(pattern.to_string());
+            }
+        } else if let Some(name) = rule.strip_prefix('+') {
+            self.add_command_rule(true, name, commands)?;
+        } else if let Some(name) = rule.strip_prefix('-') {
+            self.add_command_rule(false, name, commands)?;
+        } else {
+            return Err("Syntax error".to_string());
+        }
+        Ok(())
+    }
+
+    fn add_password(&mut self, hash: String) {
+        self.nopass = false;
+        if !self.passwords.contains(&hash) {
+            self.passwords.push(hash);
+        }
+    }
+
+    fn remove_password(&mut self, hash: &str) -> Result<(), String> {
+        let len = self.passwords.len();
+        self.passwords.retain(|p| p != hash);
+        if self.passwords.len() == len {
+            return Err(
+                "The password you are trying to remove from the user does not exist".to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    fn add_key_pattern(&mut self, read: bool, write: bool, pattern: &str) {
+        // ~* 覆盖了其他所有的pattern
+        if read && write && pattern == "*" {
+            self.keys.clear();
+        }
+        match self.keys.iter_mut().find(|k| k.pattern == pattern) {
+            Some(key) => {
+                key.read |= read;
+                key.write |= write;
+            }
+            None => self.keys.push(KeyPattern {
+                read,
+                write,
+                pattern: pattern.to_string(),
+            }),
+        }
+    }
+
+    fn add_command_rule(
+        &mut self,
+        allow: bool,
+        name: &str,
+        commands: &CommandRegistry,
+    ) -> Result<(), String> {
+        let name = name.to_ascii_lowercase();
+        let target = if let Some(category) = name.strip_prefix('@') {
+            if !is_category(category) {
+                return Err("Unknown command or category name in ACL".to_string());
+            }
+            match category {
+                "all" => CommandTarget::All,
+                _ => CommandTarget::Category(category.to_string()),
+            }
+        } else {
+            if !is_command(commands, &name) {
+                return Err("Unknown command or category name in ACL".to_string());
+            }
+            match name.split_once('|') {
+                Some((_, "")) => return Err("Syntax error".to_string()),
+                Some((cmd, sub)) => CommandTarget::Subcommand(cmd.to_string(), sub.to_string()),
+                None => CommandTarget::Command(name),
+            }
+        };
+        // +@all/-@all 之前的规则都不再生效，相同对象只保留最后一条
+        if target == CommandTarget::All {
+            self.commands.clear();
+        }
+        self.commands.retain(|r| r.target != target);
+        self.commands.push(CommandRule { allow, target });
+        Ok(())
+    }
+
+    /// 校验密码，关闭的用户不能登录
+    pub fn check_password(&self, password: &str) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        self.nopass || self.passwords.contains(&hash_password(password))
+    }
+
+    /// 是否可以执行命令，名字需要是小写，规则按顺序匹配，最后一个匹配的规则生效
+    pub fn can_run(&self, cmd: &str, sub: Option<&str>, commands: &CommandRegistry) -> bool {
+        let categories = command_categories(commands, cmd, sub);
+        self.commands.iter().fold(false, |allowed, rule| {
+            let matched = match &rule.target {
+                CommandTarget::All => true,
+                CommandTarget::Category(c) => categories.contains(&c.as_str()),
+                CommandTarget::Command(c) => c == cmd,
+                CommandTarget::Subcommand(c, s) => c == cmd && Some(s.as_str()) == sub,
+            };
+            if matched {
+                rule.allow
+            } else {
+                allowed
+            }
+        })
+    }
+
+    /// 是否可以读或者写key
+    pub fn can_access_key(&self, key: &[u8], write: bool) -> bool {
+        self.keys.iter().any(|k| {
+            (if write { k.write } else { k.read }) && glob_match(k.pattern.as_bytes(), key, false)
+        })
+    }
+
+    /// 是否可以访问pub/sub频道，还没有pub/sub命令，频道规则只保存不检查
+    pub fn can_access_channel(&self, channel: &[u8]) -> bool {
+        self.channels
+            .iter()
+            .any(|p| glob_match(p.as_bytes(), channel, false))
+    }
+
+    /// ACL GETUSER 中的 flags
+    pub fn flags(&self) -> Vec<&'static str> {
+        let mut flags = vec![if self.enabled { "on" } else { "off" }];
+        if self.nopass {
+            flags.push("nopass");
+        }
+        flags
+    }
+
+    pub fn describe_commands(&self) -> String {
+        self.commands
+            .iter()
+            .map(|r| r.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn describe_keys(&self) -> String {
+        self.keys
+            .iter()
+            .map(|k| k.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn describe_channels(&self) -> String {
+        self.channels
+            .iter()
+            .map(|c| format!("&{}", c))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// 可以重新应用的完整规则，用于 ACL LIST 和 ACL 文件
+    pub fn describe(&self) -> String {
+        let mut parts: Vec<String> = self.flags().into_iter().map(String::from).collect();
+        parts.extend(self.passwords.iter().map(|p| format!("#{}", p)));
+        parts.extend(self.keys.iter().map(|k| k.to_string()));
+        if self.channels.is_empty() {
+            parts.push("resetchannels".to_string());
+        } else {
+            parts.push(self.describe_channels());
+        }
+        parts.push(self.describe_commands());
+        format!("user {} {}", self.name, parts.join(" "))
+    }
+}
+
+impl fmt::Display for CommandRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.allow { '+' } else { '-' };
+        match &self.target {
+            CommandTarget::All => write!(f, "{}@all", sign),
+            CommandTarget::Category(c) => write!(f, "{}@{}", sign, c),
+            CommandTarget::Command(c) => write!(f, "{}{}", sign, c),
+            CommandTarget::Subcommand(c, s) => write!(f, "{}{}|{}", sign, c, s),
+        }
+    }
+}
+
+impl fmt::Display for KeyPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.read, self.write) {
+            (true, false) => write!(f, "%R~{}", self.pattern),
+            (false, true) => write!(f, "%W~{}", self.pattern),
+            _ => write!(f, "~{}", self.pattern),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(rules: &str) -> User {
+        let commands = CommandRegistry::default();
+        let mut user = User::new("alice");
+        for rule in rules.split_whitespace() {
+            user.apply_rule(rule, &commands).unwrap();
+        }
+        user
+    }
+
+    #[test]
+    fn test_user_commands() {
+        let commands = CommandRegistry::default();
+        let user = user("on +@all -@dangerous +config|get");
+        assert!(user.can_run("get", None, &commands));
+        assert!(user.can_run("config", Some("get"), &commands));
+        assert!(!user.can_run("config", Some("set"), &commands));
+        assert!(!user.can_run("shutdown", None, &commands));
+        // 子命令有自己的分类
+        assert!(user.can_run("acl", Some("whoami"), &commands));
+        assert_eq!(user.describe_commands(), "+@all -@dangerous +config|get");
+
+        let user = user_rules_after(&user, "-@all +get +@hash -hgetall");
+        assert!(user.can_run("get", None, &commands));
+        assert!(user.can_run("hset", None, &commands));
+        assert!(!user.can_run("hgetall", None, &commands));
+        assert!(!user.can_run("set", None, &commands));
+        assert_eq!(user.describe_commands(), "-@all +get +@hash -hgetall");
+
+        let mut user = User::new("bob");
+        assert!(user.apply_rule("+foo", &commands).is_err());
+        assert!(user.apply_rule("+@foo", &commands).is_err());
+        assert!(user.apply_rule("bar", &commands).is_err());
+    }
+
+    fn user_rules_after(user: &User, rules: &str) -> User {
+        let commands = CommandRegistry::default();
+        let mut user = user.clone();
+        for rule in rules.split_whitespace() {
+            user.apply_rule(rule, &commands).unwrap();
+        }
+        user
+    }
+
+    #[test]
+    fn test_user_keys_channels() {
+        let user = user("~cache:* %R~shared:* &news.*");
+        assert!(user.can_access_key(b"cache:1", true));
+        assert!(user.can_access_key(b"shared:1", false));
+        assert!(!user.can_access_key(b"shared:1", true));
+        assert!(!user.can_access_key(b"other", false));
+        assert!(user.can_access_channel(b"news.tech"));
+        assert!(!user.can_access_channel(b"sports"));
+        assert_eq!(user.describe_keys(), "~cache:* %R~shared:*");
+
+        let user = user_rules_after(&user, "allkeys resetchannels");
+        assert!(user.can_access_key(b"other", true));
+        assert!(!user.can_access_channel(b"news.tech"));
+    }
+
+    #[test]
+    fn test_user_passwords() {
+        let commands = CommandRegistry::default();
+        let mut user = user("on >secret");
+        assert!(user.check_password("secret"));
+        assert!(!user.check_password("wrong"));
+        assert_eq!(user.passwords(), &[hash_password("secret")]);
+
+        user.apply_rule("off", &commands).unwrap();
+        assert!(!user.check_password("secret"));
+        user.apply_rule("on", &commands).unwrap();
+        user.apply_rule("<secret", &commands).unwrap();
+        assert!(user.apply_rule("<secret", &commands).is_err());
+        assert!(!user.check_password("secret"));
+        user.apply_rule(&format!("#{}", hash_password("other")), &commands)
+            .unwrap();
+        assert!(user.check_password("other"));
+        assert!(user.apply_rule("#abc", &commands).is_err());
+        user.apply_rule("nopass", &commands).unwrap();
+        assert!(user.check_password("anything"));
+
+        // describe 的结果可以重新应用
+        let user = user_rules_after(&User::new("alice"), "on >secret ~* &* +@all -@admin");
+        let mut copy = User::new("alice");
+        for rule in user.describe().split_whitespace().skip(2) {
+            copy.apply_rule(rule, &commands).unwrap();
+        }
+        assert_eq!(copy, user);
+    }
+}
@@ -1,8 +1,35 @@
-use std::{ops::Deref, sync::Arc};
+mod stats;
+mod value;
+
+use std::{
+    collections::VecDeque,
+    ops::Deref,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 
 use dashmap::{DashMap, DashSet};
 
-use crate::{RespFrame, RespNull};
+use crate::{
+    acl::AclState,
+    clients::ClientRegistry,
+    cluster::ClusterState,
+    cmd::CommandRegistry,
+    config::ConfigState,
+    persistence::{AofState, PersistenceError, SnapshotState},
+    replication::ReplicationState,
+    shutdown::ShutdownState,
+    tls::TlsState,
+    RespArray, RespFrame, RespNull,
+};
+
+pub(crate) use self::value::now_ms;
+pub use self::{
+    stats::ServerStats,
+    value::{BackendValue, KeyType},
+};
 
 #[derive(Debug, Clone)]
 pub struct Backend(Arc<BackendInner>);
@@ -12,6 +39,35 @@ pub struct BackendInner {
     pub(crate) map: DashMap<String, RespFrame>,
     pub(crate) hmap: DashMap<String, DashMap<String, RespFrame>>,
     pub(crate) set: DashMap<String, DashSet<RespFrame>>,
+    pub(crate) list: DashMap<String, VecDeque<RespFrame>>,
+    /// 有序集合 member -> score
+    pub(crate) zset: DashMap<String, DashMap<String, f64>>,
+    /// key的过期时间(unix毫秒)
+    pub(crate) expires: DashMap<String, i64>,
+    /// 上次保存之后的修改次数
+    pub(crate) dirty: AtomicU64,
+    /// 快照持久化状态
+    pub(crate) snapshot: SnapshotState,
+    /// AOF持久化状态
+    pub(crate) aof: AofState,
+    /// 主从复制状态
+    pub(crate) replication: ReplicationState,
+    /// 集群状态
+    pub(crate) cluster: ClusterState,
+    /// 服务端配置
+    pub(crate) config: ConfigState,
+    /// 统计数据
+    pub(crate) stats: ServerStats,
+    /// 关闭状态
+    pub(crate) shutdown: ShutdownState,
+    /// 用户和权限
+    pub(crate) acl: AclState,
+    /// TLS配置和证书
+    pub(crate) tls: TlsState,
+    /// 客户端连接
+    pub(crate) clients: ClientRegistry,
+    /// 内置命令和注册的自定义命令，ACL也持有一份
+    pub(crate) commands: Arc<CommandRegistry>,
 }
 
 impl Deref for Backend {
@@ -30,10 +86,26 @@ impl Default for Backend {
 
 impl Default for BackendInner {
     fn default() -> Self {
+        let commands = Arc::new(CommandRegistry::default());
         BackendInner {
             map: DashMap::new(),
             hmap: DashMap::new(),
             set: DashMap::new(),
+            list: DashMap::new(),
+            zset: DashMap::new(),
+            expires: DashMap::new(),
+            dirty: AtomicU64::new(0),
+            snapshot: SnapshotState::default(),
+            aof: AofState::default(),
+            replication: ReplicationState::default(),
+            cluster: ClusterState::default(),
+            config: ConfigState::default(),
+            stats: ServerStats::default(),
+            shutdown: ShutdownState::default(),
+            acl: AclState::new(commands.clone()),
+            tls: TlsState::default(),
+            clients: ClientRegistry::default(),
+            commands,
         }
     }
 }
@@ -44,25 +116,33 @@ impl Backend {
     }
 
     pub fn get(&self, key: &str) -> Option<RespFrame> {
+        self.expire_if_needed(key);
         self.map.get(key).map(|v| v.value().clone())
     }
 
     pub fn set(&self, key: String, value: RespFrame) {
+        // SET会覆盖原有的过期时间和其他类型的值
+        self.remove_key(&key);
         self.map.insert(key, value);
+        self.incr_dirty(1);
     }
 
     pub fn hget(&self, key: &str, field: &str) -> Option<RespFrame> {
+        self.expire_if_needed(key);
         self.hmap
             .get(key)
             .and_then(|v| v.get(field).map(|v| v.value().clone()))
     }
 
     pub fn hset(&self, key: String, field: String, value: RespFrame) {
+        self.expire_if_needed(&key);
         let hmap = self.hmap.entry(key).or_default();
         hmap.insert(field, value);
+        self.incr_dirty(1);
     }
 
     pub fn hmget(&self, key: &str, fields: &[String]) -> Option<Vec<RespFrame>> {
+        self.expire_if_needed(key);
         match self.hmap.get(key) {
             Some(map) => {
                 let ret = fields
@@ -80,10 +160,12 @@ impl Backend {
     }
 
     pub fn hgetall(&self, key: &str) -> Option<DashMap<String, RespFrame>> {
+        self.expire_if_needed(key);
         self.hmap.get(key).map(|v| v.clone())
     }
 
     pub fn sadd(&self, key: String, members: Vec<RespFrame>) -> i64 {
+        self.expire_if_needed(&key);
         let set = self.set.entry(key).or_default();
         let mut count = 0;
         for member in members {
@@ -91,11 +173,89 @@ impl Backend {
                 count += 1;
             }
         }
+        self.incr_dirty(count as u64);
 
         count
     }
 
     pub fn sismembers(&self, key: &str) -> Option<DashSet<RespFrame>> {
+        self.expire_if_needed(key);
         self.set.get(key).map(|v| v.clone())
     }
+
+    /// 上次保存之后的修改次数
+    pub fn dirty(&self) -> u64 {
+        self.dirty.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn incr_dirty(&self, n: u64) {
+        self.dirty.fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// 保存完成后扣除保存开始前的修改次数，保存期间的修改会保留下来
+    pub(crate) fn decr_dirty(&self, n: u64) {
+        self.dirty.fetch_sub(n, Ordering::Relaxed);
+    }
+
+    /// 快照持久化状态
+    pub fn snapshot(&self) -> &SnapshotState {
+        &self.snapshot
+    }
+
+    /// AOF持久化状态
+    pub fn aof(&self) -> &AofState {
+        &self.aof
+    }
+
+    /// 主从复制状态
+    pub fn replication(&self) -> &ReplicationState {
+        &self.replication
+    }
+
+    /// 集群状态
+    pub fn cluster(&self) -> &ClusterState {
+        &self.cluster
+    }
+
+    /// 服务端配置
+    pub fn config(&self) -> &ConfigState {
+        &self.config
+    }
+
+    /// 统计数据
+    pub fn stats(&self) -> &ServerStats {
+        &self.stats
+    }
+
+    /// 关闭状态
+    pub fn shutdown(&self) -> &ShutdownState {
+        &self.shutdown
+    }
+
+    /// 用户和权限
+    pub fn acl(&self) -> &AclState {
+        &self.acl
+    }
+
+    /// TLS配置和证书
+    pub fn tls(&self) -> &TlsState {
+        &self.tls
+    }
+
+    /// 命令表，自定义命令注册到这里
+    pub fn commands(&self) -> &CommandRegistry {
+        &self.commands
+    }
+
+    /// 客户端连接
+    pub fn clients(&self) -> &ClientRegistry {
+        &self.clients
+    }
+
+    /// 将写命令追加到AOF和复制流
+    pub fn propagate(&self, args: &RespArray) -> Result<(), PersistenceError> {
+        self.aof.feed(args)?;
+        self.replication.feed_command(args);
+        Ok(())
+    }
 }
@@ -0,0 +1,60 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// 服务端的统计数据，INFO stats 中展示，CONFIG RESETSTAT 清零
+#[derive(Debug, Default)]
+pub struct ServerStats {
+    connections_received: AtomicU64,
+    rejected_connections: AtomicU64,
+    commands_processed: AtomicU64,
+    error_replies: AtomicU64,
+}
+
+impl ServerStats {
+    pub fn incr_connections_received(&self) {
+        self.connections_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn incr_rejected_connections(&self) {
+        self.rejected_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn incr_commands_processed(&self) {
+        self.commands_processed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn incr_error_replies(&self) {
+        self.error_replies.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn commands_processed(&self) -> u64 {
+        self.commands_processed.load(Ordering::Relaxed)
+    }
+
+    pub fn reset(&self) {
+        self.connections_received.store(0, Ordering::Relaxed);
+        self.rejected_connections.store(0, Ordering::Relaxed);
+        self.commands_processed.store(0, Ordering::Relaxed);
+        self.error_replies.store(0, Ordering::Relaxed);
+    }
+
+    /// INFO stats 的内容
+    pub fn info(&self) -> String {
+        let lines = [
+            "# Stats".to_string(),
+            format!(
+                "total_connections_received:{}",
+                self.connections_received.load(Ordering::Relaxed)
+            ),
+            format!(
+                "rejected_connections:{}",
+                self.rejected_connections.load(Ordering::Relaxed)
+            ),
+            format!("total_commands_processed:{}", self.commands_processed()),
+            format!(
+                "total_error_replies:{}",
+                self.error_replies.load(Ordering::Relaxed)
+            ),
+        ];
+        lines.join("\r\n") + "\r\n"
+    }
+}
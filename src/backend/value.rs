@@ -0,0 +1,257 @@
+use std::{
+    cmp::Ordering,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use dashmap::{DashMap, DashSet};
+
+use crate::RespFrame;
+
+use super::Backend;
+
+/// Backend中一个key对应的完整数据，用于快照、DUMP等按key整体读写的场景
+#[derive(Debug, Clone, PartialEq)]
+pub enum BackendValue {
+    String(RespFrame),
+    Hash(Vec<(String, RespFrame)>),
+    Set(Vec<RespFrame>),
+    List(Vec<RespFrame>),
+    /// 按 (score, member) 排序
+    ZSet(Vec<(String, f64)>),
+}
+
+/// key的数据类型，和Redis TYPE命令的结果对应
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyType {
+    String,
+    Hash,
+    Set,
+    List,
+    ZSet,
+}
+
+/// 当前的unix时间(毫秒)
+pub(crate) fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+impl Backend {
+    /// 如果key已经过期就删除，返回是否删除
+    pub(crate) fn expire_if_needed(&self, key: &str) -> bool {
+        let at = self.expires.get(key).map(|v| *v);
+        match at {
+            Some(at) if at <= now_ms() => {
+                self.remove_key(key);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// 从所有存储中删除key，返回key是否存在
+    pub(super) fn remove_key(&self, key: &str) -> bool {
+        self.expires.remove(key);
+        self.map.remove(key).is_some()
+            | self.hmap.remove(key).is_some()
+            | self.set.remove(key).is_some()
+            | self.list.remove(key).is_some()
+            | self.zset.remove(key).is_some()
+    }
+
+    /// 清空所有数据，全量同步之前使用，不计入修改次数
+    pub(crate) fn flush_all(&self) {
+        self.map.clear();
+        self.hmap.clear();
+        self.set.clear();
+        self.list.clear();
+        self.zset.clear();
+        self.expires.clear();
+    }
+
+    /// 删除key
+    pub fn del(&self, key: &str) -> bool {
+        let removed = self.remove_key(key);
+        if removed {
+            self.incr_dirty(1);
+        }
+        removed
+    }
+
+    /// key是否存在
+    pub fn exists(&self, key: &str) -> bool {
+        self.expire_if_needed(key);
+        self.map.contains_key(key)
+            || self.hmap.contains_key(key)
+            || self.set.contains_key(key)
+            || self.list.contains_key(key)
+            || self.zset.contains_key(key)
+    }
+
+    /// key的数据类型，不存在时返回None
+    pub fn key_type(&self, key: &str) -> Option<KeyType> {
+        self.expire_if_needed(key);
+        if self.map.contains_key(key) {
+            Some(KeyType::String)
+        } else if self.hmap.contains_key(key) {
+            Some(KeyType::Hash)
+        } else if self.set.contains_key(key) {
+            Some(KeyType::Set)
+        } else if self.list.contains_key(key) {
+            Some(KeyType::List)
+        } else if self.zset.contains_key(key) {
+            Some(KeyType::ZSet)
+        } else {
+            None
+        }
+    }
+
+    /// 所有未过期的key
+    pub fn keys(&self) -> Vec<String> {
+        let keys: Vec<String> = self
+            .map
+            .iter()
+            .map(|v| v.key().clone())
+            .chain(self.hmap.iter().map(|v| v.key().clone()))
+            .chain(self.set.iter().map(|v| v.key().clone()))
+            .chain(self.list.iter().map(|v| v.key().clone()))
+            .chain(self.zset.iter().map(|v| v.key().clone()))
+            .collect();
+        keys.into_iter()
+            .filter(|key| !self.expire_if_needed(key))
+            .collect()
+    }
+
+    /// 设置过期时间(unix毫秒)
+    pub fn set_expire_at(&self, key: &str, at_ms: i64) {
+        self.expires.insert(key.to_string(), at_ms);
+    }
+
+    /// 获取过期时间(unix毫秒)，没有设置过期时间返回None
+    pub fn expire_at(&self, key: &str) -> Option<i64> {
+        self.expires.get(key).map(|v| *v)
+    }
+
+    /// 读取key的完整数据，hash和set按顺序排列，保证同样的数据得到同样的结果
+    pub fn get_value(&self, key: &str) -> Option<BackendValue> {
+        self.expire_if_needed(key);
+        if let Some(v) = self.map.get(key) {
+            return Some(BackendValue::String(v.value().clone()));
+        }
+        if let Some(hmap) = self.hmap.get(key) {
+            let mut fields: Vec<_> = hmap
+                .iter()
+                .map(|v| (v.key().clone(), v.value().clone()))
+                .collect();
+            fields.sort_by(|a, b| a.0.cmp(&b.0));
+            return Some(BackendValue::Hash(fields));
+        }
+        if let Some(set) = self.set.get(key) {
+            let mut members: Vec<_> = set.iter().map(|v| v.key().clone()).collect();
+            members.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+            return Some(BackendValue::Set(members));
+        }
+        if let Some(list) = self.list.get(key) {
+            return Some(BackendValue::List(list.iter().cloned().collect()));
+        }
+        if let Some(zset) = self.zset.get(key) {
+            let mut members: Vec<_> = zset.iter().map(|v| (v.key().clone(), *v.value())).collect();
+            members.sort_by(|a, b| {
+                a.1.partial_cmp(&b.1)
+                    .unwrap_or(Ordering::Equal)
+                    .then_with(|| a.0.cmp(&b.0))
+            });
+            return Some(BackendValue::ZSet(members));
+        }
+        None
+    }
+
+    /// 写入key的完整数据，会覆盖已有的数据和过期时间
+    pub fn put_value(&self, key: String, value: BackendValue, expire_at: Option<i64>) {
+        self.load_value(key, value, expire_at);
+        self.incr_dirty(1);
+    }
+
+    /// 加载持久化数据时使用，不计入修改次数
+    pub(crate) fn load_value(&self, key: String, value: BackendValue, expire_at: Option<i64>) {
+        self.remove_key(&key);
+        if let Some(at) = expire_at {
+            self.expires.insert(key.clone(), at);
+        }
+        match value {
+            BackendValue::String(v) => {
+                self.map.insert(key, v);
+            }
+            BackendValue::Hash(fields) => {
+                self.hmap
+                    .insert(key, fields.into_iter().collect::<DashMap<_, _>>());
+            }
+            BackendValue::Set(members) => {
+                self.set
+                    .insert(key, members.into_iter().collect::<DashSet<_>>());
+            }
+            BackendValue::List(items) => {
+                self.list.insert(key, items.into_iter().collect());
+            }
+            BackendValue::ZSet(members) => {
+                self.zset
+                    .insert(key, members.into_iter().collect::<DashMap<_, _>>());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BulkString;
+
+    #[test]
+    fn test_get_put_value() {
+        let backend = Backend::new();
+        backend.hset("map".to_string(), "b".to_string(), RespFrame::Integer(2));
+        backend.hset("map".to_string(), "a".to_string(), RespFrame::Integer(1));
+
+        let value = backend.get_value("map").unwrap();
+        assert_eq!(
+            value,
+            BackendValue::Hash(vec![
+                ("a".to_string(), RespFrame::Integer(1)),
+                ("b".to_string(), RespFrame::Integer(2)),
+            ])
+        );
+
+        // 覆盖为其他类型
+        backend.put_value(
+            "map".to_string(),
+            BackendValue::String(BulkString::new("hello").into()),
+            None,
+        );
+        assert_eq!(backend.hget("map", "a"), None);
+        assert_eq!(backend.get("map"), Some(BulkString::new("hello").into()));
+    }
+
+    #[test]
+    fn test_expire() {
+        let backend = Backend::new();
+        backend.put_value(
+            "hello".to_string(),
+            BackendValue::String(BulkString::new("world").into()),
+            Some(now_ms() - 1),
+        );
+        assert!(!backend.exists("hello"));
+        assert_eq!(backend.get("hello"), None);
+        assert_eq!(backend.expire_at("hello"), None);
+
+        backend.set("hello".to_string(), BulkString::new("world").into());
+        backend.set_expire_at("hello", now_ms() + 10_000);
+        assert!(backend.exists("hello"));
+        assert_eq!(backend.keys(), vec!["hello".to_string()]);
+
+        // SET会清除过期时间
+        backend.set("hello".to_string(), BulkString::new("rust").into());
+        assert_eq!(backend.expire_at("hello"), None);
+    }
+}
@@ -0,0 +1,25 @@
+use anyhow::{anyhow, Result};
+use simple_redis::sentinel::{self, Sentinel};
+use tokio::net::TcpListener;
+
+/// simple-redis-sentinel <sentinel.conf>
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt::init();
+
+    let path = std::env::args()
+        .nth(1)
+        .ok_or_else(|| anyhow!("Usage: simple-redis-sentinel <sentinel.conf>"))?;
+    let sentinel = Sentinel::load(&path)?;
+
+    let (bind, port) = sentinel.bind_addr();
+    let listener = TcpListener::bind((bind.as_str(), port)).await?;
+    tracing::info!(
+        "Simple-Redis-Sentinel {} listening on: {}:{}",
+        sentinel.myid(),
+        bind,
+        port
+    );
+    sentinel::run_sentinel(sentinel, listener).await?;
+    Ok(())
+}
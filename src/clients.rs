@@ -0,0 +1,466 @@
+//! 客户端连接的注册表
+//!
+//! 每个连接在注册表中登记一个ClientInfo，记录 CLIENT LIST 展示的信息。
+//! CLIENT KILL 通过ClientInfo通知连接退出，CLIENT PAUSE 暂停客户端的命令。
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicI64, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::Duration,
+};
+
+use tokio::{sync::Notify, time::Instant};
+
+use crate::{acl::DEFAULT_USER, now_ms};
+
+type ClientMap = Arc<RwLock<BTreeMap<u64, Arc<ClientInfo>>>>;
+
+/// 客户端的类型，CLIENT LIST/KILL TYPE 使用
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientType {
+    Normal,
+    Replica,
+    Master,
+    PubSub,
+}
+
+impl FromStr for ClientType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "normal" => Ok(ClientType::Normal),
+            "replica" | "slave" => Ok(ClientType::Replica),
+            "master" => Ok(ClientType::Master),
+            "pubsub" => Ok(ClientType::PubSub),
+            _ => Err(format!("Unknown client type '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for ClientType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ClientType::Normal => "normal",
+            ClientType::Replica => "replica",
+            ClientType::Master => "master",
+            ClientType::PubSub => "pubsub",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// CLIENT PAUSE 的模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PauseMode {
+    /// 只暂停写命令
+    Write,
+    /// 暂停所有命令
+    All,
+}
+
+/// CLIENT LIST 和 CLIENT KILL 的过滤条件，所有条件都满足才匹配
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClientFilter {
+    pub ids: Vec<u64>,
+    pub addr: Option<String>,
+    pub laddr: Option<String>,
+    pub user: Option<String>,
+    pub client_type: Option<ClientType>,
+    /// 连接时间超过这个秒数
+    pub maxage: Option<u64>,
+    /// 跳过执行命令的客户端
+    pub skip: Option<u64>,
+}
+
+impl ClientFilter {
+    fn matches(&self, client: &ClientInfo) -> bool {
+        (self.ids.is_empty() || self.ids.contains(&client.id))
+            && self.addr.as_ref().is_none_or(|addr| *addr == client.addr)
+            && self.laddr.as_ref().is_none_or(|addr| *addr == client.laddr)
+            && self.user.as_ref().is_none_or(|user| *user == client.user())
+            && self.client_type.is_none_or(|t| t == client.client_type())
+            && self.maxage.is_none_or(|age| client.age().as_secs() > age)
+            && self.skip != Some(client.id)
+    }
+}
+
+/// 客户端可以修改的信息
+#[derive(Debug)]
+struct ClientMeta {
+    name: Option<String>,
+    user: String,
+    protocol: i64,
+    last_cmd: String,
+    lib_name: String,
+    lib_ver: String,
+}
+
+/// 一个客户端连接的信息
+#[derive(Debug)]
+pub struct ClientInfo {
+    id: u64,
+    /// 客户端的地址
+    addr: String,
+    /// 客户端连接的服务端地址
+    laddr: String,
+    /// 连接的时间(unix毫秒)
+    created: i64,
+    /// 上次收到命令的时间(unix毫秒)
+    last_interaction: AtomicI64,
+    meta: Mutex<ClientMeta>,
+    replica: AtomicBool,
+    no_evict: AtomicBool,
+    /// 读缓冲区中未处理的字节数和剩余容量
+    qbuf: AtomicUsize,
+    qbuf_free: AtomicUsize,
+    /// 写缓冲区中未发送的字节数
+    obuf: AtomicUsize,
+    killed: AtomicBool,
+    kill_notify: Notify,
+}
+
+impl ClientInfo {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn addr(&self) -> &str {
+        &self.addr
+    }
+
+    pub fn name(&self) -> Option<String> {
+        self.meta.lock().unwrap().name.clone()
+    }
+
+    pub fn set_name(&self, name: Option<String>) {
+        self.meta.lock().unwrap().name = name;
+    }
+
+    pub fn user(&self) -> String {
+        self.meta.lock().unwrap().user.clone()
+    }
+
+    pub fn set_user(&self, user: &str) {
+        self.meta.lock().unwrap().user = user.to_string();
+    }
+
+    /// HELLO 选择的协议版本
+    pub fn protocol(&self) -> i64 {
+        self.meta.lock().unwrap().protocol
+    }
+
+    pub fn set_protocol(&self, protocol: i64) {
+        self.meta.lock().unwrap().protocol = protocol;
+    }
+
+    pub fn set_last_cmd(&self, cmd: String) {
+        self.meta.lock().unwrap().last_cmd = cmd;
+    }
+
+    /// CLIENT SETINFO LIB-NAME|LIB-VER
+    pub fn set_lib(&self, name: Option<String>, ver: Option<String>) {
+        let mut meta = self.meta.lock().unwrap();
+        if let Some(name) = name {
+            meta.lib_name = name;
+        }
+        if let Some(ver) = ver {
+            meta.lib_ver = ver;
+        }
+    }
+
+    pub fn set_replica(&self) {
+        self.replica.store(true, Ordering::Relaxed);
+    }
+
+    pub fn set_no_evict(&self, on: bool) {
+        self.no_evict.store(on, Ordering::Relaxed);
+    }
+
+    pub fn client_type(&self) -> ClientType {
+        if self.replica.load(Ordering::Relaxed) {
+            ClientType::Replica
+        } else {
+            ClientType::Normal
+        }
+    }
+
+    pub fn age(&self) -> Duration {
+        Duration::from_millis((now_ms() - self.created).max(0) as u64)
+    }
+
+    pub fn idle(&self) -> Duration {
+        let last = self.last_interaction.load(Ordering::Relaxed);
+        Duration::from_millis((now_ms() - last).max(0) as u64)
+    }
+
+    /// 收到命令时更新空闲时间
+    pub fn touch(&self) {
+        self.last_interaction.store(now_ms(), Ordering::Relaxed);
+    }
+
+    /// 记录连接缓冲区的大小
+    pub fn set_buffers(&self, qbuf: usize, qbuf_free: usize, obuf: usize) {
+        self.qbuf.store(qbuf, Ordering::Relaxed);
+        self.qbuf_free.store(qbuf_free, Ordering::Relaxed);
+        self.obuf.store(obuf, Ordering::Relaxed);
+    }
+
+    fn flags(&self) -> String {
+        let mut flags = String::new();
+        if self.replica.load(Ordering::Relaxed) {
+            flags.push('S');
+        }
+        if self.no_evict.load(Ordering::Relaxed) {
+            flags.push('e');
+        }
+        if flags.is_empty() {
+            flags.push('N');
+        }
+        flags
+    }
+
+    /// 通知连接退出，连接发送完当前命令的回复之后断开
+    pub fn kill(&self) {
+        self.killed.store(true, Ordering::Relaxed);
+        self.kill_notify.notify_one();
+    }
+
+    /// 等待连接被 CLIENT KILL
+    pub async fn killed(&self) {
+        if !self.killed.load(Ordering::Relaxed) {
+            self.kill_notify.notified().await;
+        }
+    }
+
+    /// CLIENT LIST 和 CLIENT INFO 中的一行
+    pub fn info(&self) -> String {
+        let meta = self.meta.lock().unwrap();
+        let obuf = self.obuf.load(Ordering::Relaxed);
+        format!(
+            "id={} addr={} laddr={} name={} age={} idle={} flags={} db=0 sub=0 psub=0 multi=-1 qbuf={} qbuf-free={} obl={} omem={} cmd={} user={} resp={} lib-name={} lib-ver={}",
+            self.id,
+            self.addr,
+            self.laddr,
+            meta.name.as_deref().unwrap_or_default(),
+            self.age().as_secs(),
+            self.idle().as_secs(),
+            self.flags(),
+            self.qbuf.load(Ordering::Relaxed),
+            self.qbuf_free.load(Ordering::Relaxed),
+            obuf,
+            obuf,
+            meta.last_cmd,
+            meta.user,
+            meta.protocol,
+            meta.lib_name,
+            meta.lib_ver,
+        )
+    }
+}
+
+/// 连接存在期间持有，drop时从注册表中移除
+#[derive(Debug)]
+pub struct ClientHandle {
+    clients: ClientMap,
+    client: Arc<ClientInfo>,
+}
+
+impl std::ops::Deref for ClientHandle {
+    type Target = ClientInfo;
+
+    fn deref(&self) -> &Self::Target {
+        &self.client
+    }
+}
+
+impl Drop for ClientHandle {
+    fn drop(&mut self) {
+        self.clients.write().unwrap().remove(&self.client.id);
+    }
+}
+
+/// CLIENT PAUSE 的状态
+#[derive(Debug, Clone, Copy)]
+struct ClientPause {
+    mode: PauseMode,
+    until: Instant,
+}
+
+/// 客户端注册表，挂在Backend上共享
+#[derive(Debug)]
+pub struct ClientRegistry {
+    next_id: AtomicU64,
+    clients: ClientMap,
+    pause: Mutex<Option<ClientPause>>,
+}
+
+impl Default for ClientRegistry {
+    fn default() -> Self {
+        ClientRegistry {
+            // 客户端ID从1开始递增
+            next_id: AtomicU64::new(1),
+            clients: ClientMap::default(),
+            pause: Mutex::new(None),
+        }
+    }
+}
+
+impl ClientRegistry {
+    /// 登记新连接
+    pub fn register(&self, addr: String, laddr: String) -> ClientHandle {
+        let now = now_ms();
+        let client = Arc::new(ClientInfo {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            addr,
+            laddr,
+            created: now,
+            last_interaction: AtomicI64::new(now),
+            meta: Mutex::new(ClientMeta {
+                name: None,
+                user: DEFAULT_USER.to_string(),
+                protocol: 2,
+                last_cmd: "NULL".to_string(),
+                lib_name: String::new(),
+                lib_ver: String::new(),
+            }),
+            replica: AtomicBool::new(false),
+            no_evict: AtomicBool::new(false),
+            qbuf: AtomicUsize::new(0),
+            qbuf_free: AtomicUsize::new(0),
+            obuf: AtomicUsize::new(0),
+            killed: AtomicBool::new(false),
+            kill_notify: Notify::new(),
+        });
+        self.clients
+            .write()
+            .unwrap()
+            .insert(client.id, client.clone());
+        ClientHandle {
+            clients: self.clients.clone(),
+            client,
+        }
+    }
+
+    /// 所有连接数，包括replica，用于检查maxclients
+    pub fn len(&self) -> usize {
+        self.clients.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 和Redis一样，connected_clients不包括replica
+    pub fn connected_clients(&self) -> usize {
+        self.clients
+            .read()
+            .unwrap()
+            .values()
+            .filter(|c| c.client_type() != ClientType::Replica)
+            .count()
+    }
+
+    /// 按ID排序的匹配的客户端
+    pub fn list(&self, filter: &ClientFilter) -> Vec<Arc<ClientInfo>> {
+        self.clients
+            .read()
+            .unwrap()
+            .values()
+            .filter(|c| filter.matches(c))
+            .cloned()
+            .collect()
+    }
+
+    /// 断开匹配的客户端，返回断开的数量
+    pub fn kill(&self, filter: &ClientFilter) -> usize {
+        let clients = self.list(filter);
+        clients.iter().for_each(|c| c.kill());
+        clients.len()
+    }
+
+    /// 暂停客户端的命令，已经暂停时取更严格的模式和更晚的结束时间
+    pub fn pause(&self, mode: PauseMode, timeout: Duration) {
+        let until = Instant::now() + timeout;
+        let mut pause = self.pause.lock().unwrap();
+        *pause = match *pause {
+            Some(p) if p.until > Instant::now() => Some(ClientPause {
+                mode: p.mode.max(mode),
+                until: p.until.max(until),
+            }),
+            _ => Some(ClientPause { mode, until }),
+        };
+    }
+
+    pub fn unpause(&self) {
+        *self.pause.lock().unwrap() = None;
+    }
+
+    /// 命令是否需要等待 CLIENT PAUSE 结束
+    pub fn is_paused(&self, is_write: bool) -> bool {
+        match *self.pause.lock().unwrap() {
+            Some(p) if p.until > Instant::now() => p.mode == PauseMode::All || is_write,
+            _ => false,
+        }
+    }
+}
+
+/// 客户端名字不能包含空格和特殊字符
+pub fn validate_name(name: &str) -> Result<(), &'static str> {
+    if name.bytes().all(|b| (b'!'..=b'~').contains(&b)) {
+        Ok(())
+    } else {
+        Err("Client names cannot contain spaces, newlines or special characters.")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_registry() {
+        let registry = ClientRegistry::default();
+        let a = registry.register("127.0.0.1:1000".to_string(), "127.0.0.1:6379".to_string());
+        let b = registry.register("127.0.0.1:1001".to_string(), "127.0.0.1:6379".to_string());
+        b.set_user("alice");
+        b.set_replica();
+        assert_eq!(registry.len(), 2);
+        assert_eq!(registry.connected_clients(), 1);
+
+        let filter = ClientFilter {
+            user: Some("alice".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(registry.list(&filter).len(), 1);
+        let filter = ClientFilter {
+            client_type: Some(ClientType::Normal),
+            skip: Some(a.id()),
+            ..Default::default()
+        };
+        assert_eq!(registry.kill(&filter), 0);
+        let filter = ClientFilter {
+            addr: Some("127.0.0.1:1001".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(registry.kill(&filter), 1);
+        assert!(b.killed.load(Ordering::Relaxed));
+        assert!(b.info().contains("flags=S"));
+
+        registry.pause(PauseMode::Write, Duration::from_secs(10));
+        assert!(registry.is_paused(true));
+        assert!(!registry.is_paused(false));
+        registry.pause(PauseMode::All, Duration::from_millis(1));
+        assert!(registry.is_paused(false));
+        registry.unpause();
+        assert!(!registry.is_paused(true));
+
+        drop(b);
+        assert_eq!(registry.len(), 1);
+    }
+}
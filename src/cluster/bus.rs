@@ -0,0 +1,405 @@
+//! 集群总线
+//!
+//! 每个节点监听 端口+10000，并主动连接其他所有节点。
+//! 通过主动建立的连接发送PING、FAIL、投票请求等消息，对方在同一个连接上回复PONG。
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+    time,
+};
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+use crate::{replication, Backend};
+
+use super::{
+    failover::{Actions, Outgoing},
+    ClusterError, ClusterMessage, ClusterNode, FailoverMode, MessageType, NodeRole,
+};
+
+/// 集群定时任务的间隔
+const CRON_PERIOD: Duration = Duration::from_millis(100);
+/// 连接其他节点的超时时间
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+type BusFramed = Framed<TcpStream, LengthDelimitedCodec>;
+
+/// 处理集群总线的连接，并定期执行集群定时任务
+pub async fn run_cluster_bus(backend: Backend, listener: TcpListener) {
+    sync_replication(&backend);
+    tokio::spawn(run_cluster_cron(backend.clone()));
+    loop {
+        match listener.accept().await {
+            Ok((stream, addr)) => {
+                let backend = backend.clone();
+                tokio::spawn(async move {
+                    let peer_ip = addr.ip().to_string();
+                    let framed = Framed::new(stream, LengthDelimitedCodec::new());
+                    if let Err(e) = serve_connection(&backend, framed, &peer_ip, None).await {
+                        tracing::debug!("Cluster bus connection from {} closed: {}", addr, e);
+                    }
+                });
+            }
+            Err(e) => tracing::warn!("Cluster bus accept error: {}", e),
+        }
+    }
+}
+
+async fn run_cluster_cron(backend: Backend) {
+    let mut interval = time::interval(CRON_PERIOD);
+    loop {
+        interval.tick().await;
+
+        let cluster = backend.cluster();
+        if let Some(config) = cluster.config() {
+            for node in config.nodes.iter().filter(|n| !n.myself) {
+                ensure_link(&backend, node);
+            }
+        }
+        let actions = cluster.cron(backend.replication().offset());
+        dispatch(&backend, actions);
+        if cluster.take_todo_save() {
+            if let Err(e) = cluster.save_config() {
+                tracing::warn!("Failed to save cluster config: {}", e);
+            }
+        }
+    }
+}
+
+/// 没有到节点的连接时创建连接
+fn ensure_link(backend: &Backend, node: &ClusterNode) {
+    let mut links = backend.cluster().links.lock().unwrap();
+    if links.contains_key(&node.id) {
+        return;
+    }
+    let (tx, rx) = mpsc::unbounded_channel();
+    links.insert(node.id.clone(), tx.clone());
+    let addr = format!("{}:{}", node.ip, node.cport);
+    tokio::spawn(run_link(backend.clone(), node.id.clone(), addr, tx, rx));
+}
+
+/// 到其他节点的连接，断开之后由定时任务重新建立
+async fn run_link(
+    backend: Backend,
+    id: String,
+    addr: String,
+    tx: mpsc::UnboundedSender<ClusterMessage>,
+    rx: mpsc::UnboundedReceiver<ClusterMessage>,
+) {
+    let cluster = backend.cluster();
+    match time::timeout(CONNECT_TIMEOUT, TcpStream::connect(&addr)).await {
+        Ok(Ok(stream)) => {
+            cluster.set_connected(&id, true);
+            let peer_ip = addr.rsplit_once(':').map(|(ip, _)| ip).unwrap_or_default();
+            let framed = Framed::new(stream, LengthDelimitedCodec::new());
+            if let Err(e) = serve_connection(&backend, framed, peer_ip, Some(rx)).await {
+                tracing::debug!("Cluster bus link to {} closed: {}", addr, e);
+            }
+        }
+        Ok(Err(e)) => tracing::debug!("Failed to connect cluster bus {}: {}", addr, e),
+        Err(_) => tracing::debug!("Timeout connecting cluster bus {}", addr),
+    }
+    cluster.set_connected(&id, false);
+
+    let mut links = cluster.links.lock().unwrap();
+    if links.get(&id).is_some_and(|link| link.same_channel(&tx)) {
+        links.remove(&id);
+    }
+}
+
+/// 处理连接上收到的消息，主动建立的连接还需要发送队列中的消息
+async fn serve_connection(
+    backend: &Backend,
+    mut framed: BusFramed,
+    peer_ip: &str,
+    mut rx: Option<mpsc::UnboundedReceiver<ClusterMessage>>,
+) -> Result<(), ClusterError> {
+    loop {
+        tokio::select! {
+            msg = recv(&mut rx) => match msg {
+                Some(msg) => framed.send(Bytes::from(msg.encode())).await?,
+                None => return Ok(()),
+            },
+            frame = framed.next() => {
+                let Some(frame) = frame else {
+                    return Ok(());
+                };
+                let msg = ClusterMessage::decode(&frame?)?;
+                let offset = backend.replication().offset();
+                let actions = backend.cluster().handle_message(msg, peer_ip, offset);
+                for reply in dispatch(backend, actions) {
+                    framed.send(Bytes::from(reply.encode())).await?;
+                }
+            }
+        }
+    }
+}
+
+async fn recv(rx: &mut Option<mpsc::UnboundedReceiver<ClusterMessage>>) -> Option<ClusterMessage> {
+    match rx {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// 发送消息，返回需要在当前连接上回复的消息
+fn dispatch(backend: &Backend, actions: Actions) -> Vec<ClusterMessage> {
+    if actions.role_changed {
+        sync_replication(backend);
+    }
+    let links = backend.cluster().links.lock().unwrap();
+    let mut replies = Vec::new();
+    for outgoing in actions.messages {
+        match outgoing {
+            Outgoing::Reply(msg) => replies.push(msg),
+            Outgoing::To(id, msg) => {
+                if let Some(link) = links.get(&id) {
+                    let _ = link.send(msg);
+                }
+            }
+            Outgoing::Broadcast(msg) => {
+                for link in links.values() {
+                    let _ = link.send(msg.clone());
+                }
+            }
+        }
+    }
+    replies
+}
+
+/// 根据集群中的角色调整主从复制
+fn sync_replication(backend: &Backend) {
+    let Some(myself) = backend.cluster().myself() else {
+        return;
+    };
+    let state = backend.replication();
+    match myself.role {
+        NodeRole::Master => {
+            if state.is_replica() {
+                tracing::info!("Promoted to master by cluster failover");
+                state.promote();
+            }
+        }
+        NodeRole::Replica => {
+            let master = myself.master_id.and_then(|id| backend.cluster().node(&id));
+            if let Some(master) = master {
+                if state.set_master(&master.ip, master.port) {
+                    replication::start_replication(backend);
+                }
+            }
+        }
+    }
+}
+
+/// CLUSTER MEET: 向指定的集群总线地址发送MEET，对方回复PONG后加入集群
+pub fn meet(backend: &Backend, ip: &str, cport: u16) -> Result<(), ClusterError> {
+    let offset = backend.replication().offset();
+    let msg = backend
+        .cluster()
+        .build_message(MessageType::Meet, offset)
+        .ok_or(ClusterError::Disabled)?;
+    let addr = format!("{}:{}", ip, cport);
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => {
+            let backend = backend.clone();
+            handle.spawn(async move {
+                if let Err(e) = run_meet(&backend, &addr, msg).await {
+                    tracing::warn!("CLUSTER MEET {} failed: {}", addr, e);
+                }
+            });
+        }
+        Err(_) => tracing::warn!("No runtime available, CLUSTER MEET is ignored"),
+    }
+    Ok(())
+}
+
+async fn run_meet(backend: &Backend, addr: &str, msg: ClusterMessage) -> Result<(), ClusterError> {
+    let stream = time::timeout(CONNECT_TIMEOUT, TcpStream::connect(addr))
+        .await
+        .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))??;
+    let peer_ip = stream.peer_addr()?.ip().to_string();
+    let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+    framed.send(Bytes::from(msg.encode())).await?;
+    if let Some(frame) = framed.next().await {
+        let reply = ClusterMessage::decode(&frame?)?;
+        let offset = backend.replication().offset();
+        let actions = backend.cluster().handle_message(reply, &peer_ip, offset);
+        dispatch(backend, actions);
+    }
+    Ok(())
+}
+
+/// CLUSTER FAILOVER: 只能在replica上执行
+pub fn manual_failover(backend: &Backend, mode: FailoverMode) -> Result<(), ClusterError> {
+    let offset = backend.replication().offset();
+    let actions = backend.cluster().manual_failover(mode, offset)?;
+    dispatch(backend, actions);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cluster::parse_nodes_conf, replication::ReplicationRole};
+    use anyhow::Result;
+    use tokio::runtime::Handle;
+
+    struct Node {
+        id: String,
+        backend: Backend,
+        port: u16,
+        cport: u16,
+        listeners: Option<(std::net::TcpListener, std::net::TcpListener)>,
+    }
+
+    fn bind() -> Result<(std::net::TcpListener, u16)> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+        Ok((listener, port))
+    }
+
+    /// 创建节点，slots为None表示第一个节点的replica
+    fn cluster(slots: &[Option<&str>]) -> Result<Vec<Node>> {
+        let mut nodes = Vec::new();
+        for (i, _) in slots.iter().enumerate() {
+            let (main, port) = bind()?;
+            let (bus, cport) = bind()?;
+            nodes.push(Node {
+                id: format!("{:040x}", i + 1),
+                backend: Backend::new(),
+                port,
+                cport,
+                listeners: Some((main, bus)),
+            });
+        }
+        for node in &nodes {
+            let mut conf = String::new();
+            for (i, (other, slots)) in nodes.iter().zip(slots).enumerate() {
+                let myself = if other.id == node.id { "myself," } else { "" };
+                let (role, master) = match slots {
+                    Some(_) => ("master", "-".to_string()),
+                    None => ("slave", nodes[0].id.clone()),
+                };
+                conf.push_str(&format!(
+                    "{} 127.0.0.1:{}@{} {}{} {} 0 0 {} disconnected {}\n",
+                    other.id,
+                    other.port,
+                    other.cport,
+                    myself,
+                    role,
+                    master,
+                    i + 1,
+                    slots.unwrap_or_default()
+                ));
+            }
+            node.backend.cluster().set_config(parse_nodes_conf(&conf)?);
+            node.backend.cluster().set_node_timeout(500);
+        }
+        Ok(nodes)
+    }
+
+    fn start(node: &mut Node, handle: &Handle) {
+        let (main, bus) = node.listeners.take().expect("node already started");
+        let backend = node.backend.clone();
+        handle.spawn(async move {
+            let main = TcpListener::from_std(main).unwrap();
+            let bus = TcpListener::from_std(bus).unwrap();
+            tokio::spawn(run_cluster_bus(backend.clone(), bus));
+            while let Ok((stream, _)) = main.accept().await {
+                tokio::spawn(crate::network::stream_handler(stream, backend.clone()));
+            }
+        });
+    }
+
+    async fn wait_until(f: impl Fn() -> bool) {
+        for _ in 0..200 {
+            if f() {
+                return;
+            }
+            time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("condition not met in time");
+    }
+
+    fn is_connected(node: &Node) -> bool {
+        let config = node.backend.cluster().config().unwrap();
+        config.nodes.iter().all(|n| n.myself || n.connected)
+            && matches!(
+                node.backend.replication().role(),
+                ReplicationRole::Master
+                    | ReplicationRole::Replica(replication::MasterLink {
+                        state: replication::LinkState::Connected,
+                        ..
+                    })
+            )
+    }
+
+    fn role(node: &Node) -> NodeRole {
+        node.backend.cluster().myself().unwrap().role
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_automatic_failover() -> Result<()> {
+        let mut nodes = cluster(&[
+            Some("0-5460"),
+            Some("5461-10922"),
+            Some("10923-16383"),
+            None,
+        ])?;
+        // master单独运行在一个运行时中，关闭运行时模拟进程退出
+        let master_runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()?;
+        start(&mut nodes[0], master_runtime.handle());
+        for node in nodes.iter_mut().skip(1) {
+            start(node, &Handle::current());
+        }
+        wait_until(|| nodes.iter().all(is_connected)).await;
+
+        master_runtime.shutdown_background();
+        let (replica, other) = (&nodes[3], &nodes[1]);
+        wait_until(|| role(replica) == NodeRole::Master).await;
+        assert_eq!(
+            replica.backend.cluster().myself().unwrap().slots,
+            vec![(0, 5460)]
+        );
+        assert!(!replica.backend.replication().is_replica());
+
+        // 其他master也接受了新的配置
+        wait_until(|| {
+            other.backend.cluster().slot_owner(0).map(|n| n.id) == Some(replica.id.clone())
+        })
+        .await;
+        let failed = other.backend.cluster().node(&nodes[0].id).unwrap();
+        assert!(failed.fail);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_manual_failover() -> Result<()> {
+        let mut nodes = cluster(&[Some("0-16383"), None])?;
+        for node in nodes.iter_mut() {
+            start(node, &Handle::current());
+        }
+        wait_until(|| nodes.iter().all(is_connected)).await;
+
+        let (master, replica) = (&nodes[0], &nodes[1]);
+        assert!(manual_failover(&master.backend, FailoverMode::Default).is_err());
+        manual_failover(&replica.backend, FailoverMode::Default)?;
+        // 原来的master发现slot被接管后成为replica
+        wait_until(|| role(replica) == NodeRole::Master && role(master) == NodeRole::Replica).await;
+        assert_eq!(
+            master.backend.cluster().myself().unwrap().master_id,
+            Some(replica.id.clone())
+        );
+        wait_until(|| is_connected(master)).await;
+        assert!(!master.backend.cluster().is_write_paused());
+        Ok(())
+    }
+}
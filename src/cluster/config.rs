@@ -0,0 +1,200 @@
+//! 集群配置文件，格式与Redis的nodes.conf一致:
+//! `<id> <ip:port@cport[,hostname]> <flags> <master> <ping-sent> <pong-recv> <config-epoch> <link-state> <slot> ...`
+//! 当前节点的行中还可以有 `[slot->-node]`(迁出) 和 `[slot-<-node]`(迁入)
+
+use std::collections::BTreeMap;
+
+use super::{ClusterError, ClusterNode, NodeRole, CLUSTER_SLOTS};
+
+/// 解析后的集群配置
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClusterConfig {
+    pub nodes: Vec<ClusterNode>,
+    pub current_epoch: u64,
+    pub last_vote_epoch: u64,
+    /// 正在迁出的slot -> 目标节点ID
+    pub migrating: BTreeMap<u16, String>,
+    /// 正在迁入的slot -> 源节点ID
+    pub importing: BTreeMap<u16, String>,
+}
+
+fn invalid(line: usize, msg: impl std::fmt::Display) -> ClusterError {
+    ClusterError::InvalidConfig(format!("line {}: {}", line, msg))
+}
+
+/// 解析 ip:port@cport[,hostname]
+fn parse_addr(addr: &str) -> Option<(String, u16, u16, Option<String>)> {
+    let (addr, hostname) = match addr.split_once(',') {
+        Some((addr, hostname)) if !hostname.is_empty() => (addr, Some(hostname.to_string())),
+        Some((addr, _)) => (addr, None),
+        None => (addr, None),
+    };
+    let (addr, cport) = addr.split_once('@')?;
+    let (ip, port) = addr.rsplit_once(':')?;
+    Some((
+        ip.to_string(),
+        port.parse().ok()?,
+        cport.parse().ok()?,
+        hostname,
+    ))
+}
+
+/// 解析slot范围 "0-5460" 或 "5461"
+pub(crate) fn parse_slot_range(s: &str) -> Option<(u16, u16)> {
+    let (start, end) = match s.split_once('-') {
+        Some((start, end)) => (start.parse().ok()?, end.parse().ok()?),
+        None => {
+            let slot = s.parse().ok()?;
+            (slot, slot)
+        }
+    };
+    (start <= end && (end as usize) < CLUSTER_SLOTS).then_some((start, end))
+}
+
+/// 解析集群配置文件的内容
+pub fn parse_nodes_conf(content: &str) -> Result<ClusterConfig, ClusterError> {
+    let mut config = ClusterConfig::default();
+
+    for (i, line) in content.lines().enumerate() {
+        let lineno = i + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parts: Vec<&str> = line.split_whitespace().collect();
+
+        if parts[0] == "vars" {
+            for pair in parts[1..].chunks(2) {
+                let [name, value] = pair else {
+                    return Err(invalid(lineno, "vars must be name value pairs"));
+                };
+                let value = value
+                    .parse()
+                    .map_err(|_| invalid(lineno, "invalid epoch"))?;
+                match *name {
+                    "currentEpoch" => config.current_epoch = value,
+                    "lastVoteEpoch" => config.last_vote_epoch = value,
+                    _ => {}
+                }
+            }
+            continue;
+        }
+
+        if parts.len() < 8 {
+            return Err(invalid(lineno, "not enough fields"));
+        }
+        let (ip, port, cport, hostname) =
+            parse_addr(parts[1]).ok_or_else(|| invalid(lineno, "invalid address"))?;
+        let flags: Vec<&str> = parts[2].split(',').collect();
+        let role = if flags.contains(&"slave") {
+            NodeRole::Replica
+        } else {
+            NodeRole::Master
+        };
+        let master_id = match parts[3] {
+            "-" => None,
+            id => Some(id.to_string()),
+        };
+        let parse_num = |s: &str| s.parse().map_err(|_| invalid(lineno, "invalid number"));
+
+        let mut slots = Vec::new();
+        for slot in &parts[8..] {
+            // [slot->-node] 和 [slot-<-node] 是迁移中的slot
+            if let Some(state) = slot.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+                let parsed = if let Some((slot, node)) = state.split_once("->-") {
+                    slot.parse()
+                        .ok()
+                        .map(|slot| config.migrating.insert(slot, node.to_string()))
+                } else if let Some((slot, node)) = state.split_once("-<-") {
+                    slot.parse()
+                        .ok()
+                        .map(|slot| config.importing.insert(slot, node.to_string()))
+                } else {
+                    None
+                };
+                if parsed.is_none() {
+                    return Err(invalid(lineno, "invalid migrating slot"));
+                }
+                continue;
+            }
+            slots.push(parse_slot_range(slot).ok_or_else(|| invalid(lineno, "invalid slot"))?);
+        }
+
+        config.nodes.push(ClusterNode {
+            id: parts[0].to_string(),
+            ip,
+            port,
+            cport,
+            hostname,
+            role,
+            myself: flags.contains(&"myself"),
+            pfail: flags.contains(&"fail?"),
+            fail: flags.contains(&"fail"),
+            fail_time: 0,
+            master_id,
+            ping_sent: parse_num(parts[4])?,
+            pong_recv: parse_num(parts[5])?,
+            config_epoch: parse_num(parts[6])? as u64,
+            connected: parts[7] == "connected",
+            slots,
+        });
+    }
+
+    match config.nodes.iter().filter(|n| n.myself).count() {
+        1 => Ok(config),
+        0 => Err(ClusterError::InvalidConfig("no myself node".to_string())),
+        _ => Err(ClusterError::InvalidConfig(
+            "more than one myself node".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    const NODES_CONF: &str = "\
+07c37dfeb235213a872192d90877d0cd55635b91 127.0.0.1:30004@31004,host-4 slave e7d1eecce10fd6bb5eb35b9f99a514335d9ba9ca 0 1426238317239 4 connected
+67ed2db8d677e59ec4a4cefb06858cf2a1a89fa1 127.0.0.1:30002@31002 master - 0 1426238316232 2 connected 5461-10922
+292f8b365bb7edb5e285caf0b7e6ddc7265e2f4f 127.0.0.1:30003@31003 master - 0 1426238318243 3 connected 10923-16383
+e7d1eecce10fd6bb5eb35b9f99a514335d9ba9ca 127.0.0.1:30001@31001,host-1 myself,master - 0 0 1 connected 0-5460 [93->-67ed2db8d677e59ec4a4cefb06858cf2a1a89fa1] [6000-<-67ed2db8d677e59ec4a4cefb06858cf2a1a89fa1]
+vars currentEpoch 6 lastVoteEpoch 0
+";
+
+    #[test]
+    fn test_parse_nodes_conf() -> Result<()> {
+        let config = parse_nodes_conf(NODES_CONF)?;
+        assert_eq!(config.nodes.len(), 4);
+        assert_eq!(config.current_epoch, 6);
+
+        let replica = &config.nodes[0];
+        assert_eq!(replica.role, NodeRole::Replica);
+        assert_eq!(replica.hostname.as_deref(), Some("host-4"));
+        assert_eq!(
+            replica.master_id.as_deref(),
+            Some("e7d1eecce10fd6bb5eb35b9f99a514335d9ba9ca")
+        );
+
+        let myself = &config.nodes[3];
+        assert!(myself.myself);
+        assert_eq!((myself.port, myself.cport), (30001, 31001));
+        assert_eq!(myself.slots, vec![(0, 5460)]);
+        assert_eq!(
+            config.migrating.get(&93).map(String::as_str),
+            Some("67ed2db8d677e59ec4a4cefb06858cf2a1a89fa1")
+        );
+        assert!(config.importing.contains_key(&6000));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_nodes_conf_invalid() {
+        assert!(parse_nodes_conf("").is_err());
+        assert!(parse_nodes_conf("abc 127.0.0.1:30001 myself,master - 0 0 1 connected").is_err());
+        assert!(parse_nodes_conf(
+            "abc 127.0.0.1:30001@31001 myself,master - 0 0 1 connected 0-16384"
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,966 @@
+//! 故障检测与故障转移
+//!
+//! - 超过node_timeout没有响应PING的节点被标记为PFAIL
+//! - 负责slot的master通过gossip报告PFAIL，超过半数master报告后标记为FAIL并广播
+//! - master下线后replica增加currentEpoch发起选举，获得半数以上master投票后接管slot
+//! - 每个master在一个纪元中只投一票，slot冲突时配置纪元大的节点获胜
+
+use super::{
+    message::{
+        node_flags, ClusterMessage, GossipEntry, MessageType, FLAG_FAIL, FLAG_MASTER, FLAG_PFAIL,
+        FLAG_REPLICA, MFLAG_FORCEACK, MFLAG_PAUSED,
+    },
+    ClusterError, ClusterInner, ClusterNode, ClusterState, NodeRole,
+};
+use crate::now_ms;
+
+/// 手动故障转移的超时时间(毫秒)
+const MANUAL_FAILOVER_TIMEOUT_MS: i64 = 5000;
+/// master下线后replica等待多久再发起选举，等待FAIL消息传播到其他节点
+const ELECTION_DELAY_MS: i64 = 500;
+/// 随机PING的间隔(毫秒)
+const GOSSIP_PERIOD_MS: i64 = 1000;
+
+/// replica发起的选举
+#[derive(Debug, Default)]
+pub(super) struct Election {
+    /// 计划发起选举的时间，0表示没有选举
+    auth_time: i64,
+    auth_sent: bool,
+    auth_epoch: u64,
+    auth_count: usize,
+}
+
+/// 手动故障转移的状态
+#[derive(Debug)]
+pub(super) struct ManualFailover {
+    end: i64,
+    /// master: 发起手动故障转移的replica
+    replica: Option<String>,
+    /// replica: master暂停写入后的复制偏移量
+    master_offset: Option<u64>,
+    /// replica: FORCE模式不需要等待master
+    force: bool,
+}
+
+/// CLUSTER FAILOVER 的模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailoverMode {
+    /// 等待master暂停写入并同步完成后再发起选举
+    Default,
+    /// 不等待master，直接发起选举
+    Force,
+    /// 不经过选举，直接接管slot
+    Takeover,
+}
+
+/// 需要通过集群总线发送的消息
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Outgoing {
+    /// 通过当前连接回复
+    Reply(ClusterMessage),
+    To(String, ClusterMessage),
+    Broadcast(ClusterMessage),
+}
+
+/// 处理消息或定时任务的结果
+#[derive(Debug, Default)]
+pub(crate) struct Actions {
+    pub(crate) messages: Vec<Outgoing>,
+    /// 当前节点的角色或者master发生了变化，需要调整复制
+    pub(crate) role_changed: bool,
+}
+
+impl ClusterInner {
+    /// 多数派的数量，只有负责slot的master参与投票
+    fn quorum(&self) -> usize {
+        let size = self
+            .config
+            .nodes
+            .iter()
+            .filter(|n| n.role == NodeRole::Master && !n.slots.is_empty())
+            .count();
+        size / 2 + 1
+    }
+
+    fn is_voter(node: &ClusterNode) -> bool {
+        node.role == NodeRole::Master && !node.slots.is_empty()
+    }
+
+    fn master_index(&self, node: &ClusterNode) -> Option<usize> {
+        node.master_id.as_deref().and_then(|id| self.node_index(id))
+    }
+
+    /// 以当前节点的身份构造消息
+    pub(super) fn build_message(&self, kind: MessageType, offset: u64) -> ClusterMessage {
+        let myself = self.myself();
+        // replica发送它的master的slot和配置纪元
+        let master = match myself.role {
+            NodeRole::Replica => self.master_index(myself).map(|i| &self.config.nodes[i]),
+            NodeRole::Master => Some(myself),
+        };
+        let gossip = match kind {
+            MessageType::Ping | MessageType::Pong | MessageType::Meet => self
+                .config
+                .nodes
+                .iter()
+                .filter(|n| !n.myself)
+                .map(|n| GossipEntry {
+                    id: n.id.clone(),
+                    ip: n.ip.clone(),
+                    port: n.port,
+                    cport: n.cport,
+                    flags: node_flags(n),
+                    ping_sent: n.ping_sent,
+                    pong_recv: n.pong_recv,
+                })
+                .collect(),
+            _ => vec![],
+        };
+        let mut mflags = 0;
+        if matches!(&self.manual, Some(m) if m.replica.is_some()) {
+            mflags |= MFLAG_PAUSED;
+        }
+        if matches!(&self.manual, Some(m) if m.replica.is_none())
+            && kind == MessageType::FailoverAuthRequest
+        {
+            mflags |= MFLAG_FORCEACK;
+        }
+        ClusterMessage {
+            kind,
+            sender: myself.id.clone(),
+            ip: myself.ip.clone(),
+            port: myself.port,
+            cport: myself.cport,
+            flags: node_flags(myself),
+            master_id: myself.master_id.clone(),
+            current_epoch: self.config.current_epoch,
+            config_epoch: master.map(|m| m.config_epoch).unwrap_or_default(),
+            offset,
+            mflags,
+            slots: master.map(|m| m.slots.clone()).unwrap_or_default(),
+            gossip,
+            fail_node: None,
+        }
+    }
+
+    fn add_node(&mut self, id: &str, ip: &str, port: u16, cport: u16, flags: u8) -> usize {
+        self.config.nodes.push(ClusterNode {
+            id: id.to_string(),
+            ip: ip.to_string(),
+            port,
+            cport,
+            hostname: None,
+            role: if flags & FLAG_REPLICA != 0 {
+                NodeRole::Replica
+            } else {
+                NodeRole::Master
+            },
+            myself: false,
+            pfail: false,
+            fail: false,
+            fail_time: 0,
+            master_id: None,
+            ping_sent: 0,
+            pong_recv: 0,
+            config_epoch: 0,
+            connected: false,
+            slots: vec![],
+        });
+        self.todo_save = true;
+        self.config.nodes.len() - 1
+    }
+
+    /// 处理其他节点发来的消息
+    pub(super) fn handle_message(
+        &mut self,
+        msg: ClusterMessage,
+        peer_ip: &str,
+        now: i64,
+        offset: u64,
+    ) -> Actions {
+        let mut actions = Actions::default();
+        if msg.sender == self.myself().id {
+            return actions;
+        }
+        if msg.current_epoch > self.config.current_epoch {
+            self.config.current_epoch = msg.current_epoch;
+            self.todo_save = true;
+        }
+
+        let sender = match self.node_index(&msg.sender) {
+            Some(index) => Some(index),
+            // MEET和MEET的回复中的节点加入集群
+            None if matches!(msg.kind, MessageType::Meet | MessageType::Pong) => {
+                let ip = if msg.ip.is_empty() { peer_ip } else { &msg.ip };
+                Some(self.add_node(&msg.sender, ip, msg.port, msg.cport, msg.flags))
+            }
+            None => None,
+        };
+        if matches!(msg.kind, MessageType::Ping | MessageType::Meet) {
+            actions.messages.push(Outgoing::Reply(
+                self.build_message(MessageType::Pong, offset),
+            ));
+        }
+        let Some(sender) = sender else {
+            return actions;
+        };
+
+        match msg.kind {
+            MessageType::Ping | MessageType::Pong | MessageType::Meet => {
+                if msg.kind == MessageType::Pong {
+                    self.handle_pong(sender, now);
+                }
+                self.update_node(sender, &msg, &mut actions);
+                self.handle_gossip(sender, &msg.gossip, now);
+                // master暂停写入之后的复制偏移量
+                let from_master = self.myself().master_id.as_deref() == Some(&msg.sender);
+                if let (true, true, Some(manual)) = (
+                    from_master,
+                    msg.mflags & MFLAG_PAUSED != 0,
+                    self.manual.as_mut(),
+                ) {
+                    manual.master_offset = Some(msg.offset);
+                }
+            }
+            MessageType::Fail => {
+                let index = msg.fail_node.as_deref().and_then(|id| self.node_index(id));
+                if let Some(index) = index.filter(|i| !self.config.nodes[*i].myself) {
+                    let node = &mut self.config.nodes[index];
+                    if !node.fail {
+                        tracing::info!("FAIL message received about {}", node.id);
+                        node.fail = true;
+                        node.pfail = false;
+                        node.fail_time = now;
+                        self.todo_save = true;
+                    }
+                }
+            }
+            MessageType::FailoverAuthRequest => {
+                if let Some(ack) = self.handle_auth_request(sender, &msg, now, offset) {
+                    actions.messages.push(ack);
+                }
+            }
+            MessageType::FailoverAuthAck => {
+                if Self::is_voter(&self.config.nodes[sender])
+                    && msg.current_epoch >= self.election.auth_epoch
+                    && self.election.auth_sent
+                {
+                    self.election.auth_count += 1;
+                }
+            }
+            MessageType::MfStart => {
+                let is_my_replica = self.config.nodes[sender].master_id.as_deref()
+                    == Some(self.myself().id.as_str());
+                if is_my_replica && self.myself().role == NodeRole::Master {
+                    tracing::info!("Manual failover requested by replica {}", msg.sender);
+                    self.manual = Some(ManualFailover {
+                        end: now + MANUAL_FAILOVER_TIMEOUT_MS,
+                        replica: Some(msg.sender.clone()),
+                        master_offset: None,
+                        force: false,
+                    });
+                    actions.messages.push(Outgoing::To(
+                        msg.sender,
+                        self.build_message(MessageType::Ping, offset),
+                    ));
+                }
+            }
+        }
+        actions
+    }
+
+    /// 收到PONG说明节点正常，清除PFAIL，必要时清除FAIL
+    fn handle_pong(&mut self, index: usize, now: i64) {
+        let timeout = self.node_timeout as i64;
+        let node = &mut self.config.nodes[index];
+        node.pong_recv = now;
+        node.ping_sent = 0;
+        node.pfail = false;
+        // 下线的master恢复时slot可能已经被replica接管，等待一段时间再清除FAIL
+        if node.fail
+            && (node.role == NodeRole::Replica
+                || node.slots.is_empty()
+                || now - node.fail_time > timeout * 2)
+        {
+            tracing::info!("Clear FAIL state for node {}", node.id);
+            node.fail = false;
+            self.todo_save = true;
+        }
+        self.fail_reports.remove(&node.id.clone());
+    }
+
+    /// 根据消息头更新发送者的角色、纪元和slot
+    fn update_node(&mut self, index: usize, msg: &ClusterMessage, actions: &mut Actions) {
+        let myself = self.myself_index();
+        let node = &mut self.config.nodes[index];
+        if msg.flags & FLAG_REPLICA != 0 {
+            if node.role == NodeRole::Master || node.master_id != msg.master_id {
+                node.role = NodeRole::Replica;
+                node.master_id = msg.master_id.clone();
+                self.todo_save = true;
+                if !self.config.nodes[index].slots.is_empty() {
+                    let slots = self.node_slots(index);
+                    self.assign_slots(&slots, None);
+                }
+            }
+            return;
+        }
+        if msg.flags & FLAG_MASTER == 0 {
+            return;
+        }
+        if node.role == NodeRole::Replica {
+            node.role = NodeRole::Master;
+            node.master_id = None;
+            self.todo_save = true;
+        }
+        if node.config_epoch != msg.config_epoch {
+            node.config_epoch = msg.config_epoch;
+            self.todo_save = true;
+        }
+
+        // 配置纪元冲突时ID较小的节点增加纪元
+        let me = &self.config.nodes[myself];
+        if me.role == NodeRole::Master
+            && me.config_epoch == msg.config_epoch
+            && msg.config_epoch > 0
+            && msg.sender < me.id
+        {
+            self.config.current_epoch += 1;
+            self.config.nodes[myself].config_epoch = self.config.current_epoch;
+            self.todo_save = true;
+        }
+
+        self.update_slots(index, &msg.slots, msg.config_epoch, actions);
+    }
+
+    fn node_slots(&self, index: usize) -> Vec<u16> {
+        self.config.nodes[index]
+            .slots
+            .iter()
+            .flat_map(|(start, end)| *start..=*end)
+            .collect()
+    }
+
+    /// master声明负责的slot，配置纪元更大的声明覆盖已有的分配
+    fn update_slots(
+        &mut self,
+        sender: usize,
+        slots: &[(u16, u16)],
+        config_epoch: u64,
+        actions: &mut Actions,
+    ) {
+        let myself = self.myself_index();
+        // 当前节点或者当前节点的master
+        let current_master = match self.config.nodes[myself].role {
+            NodeRole::Master => Some(myself),
+            NodeRole::Replica => self.master_index(&self.config.nodes[myself]),
+        };
+
+        let mut claimed = Vec::new();
+        let mut lost = false;
+        for slot in slots.iter().flat_map(|(start, end)| *start..=*end) {
+            match self.slots[slot as usize] {
+                Some(owner) if owner == sender => continue,
+                Some(owner) if self.config.nodes[owner].config_epoch >= config_epoch => continue,
+                // 正在迁入的slot由SETSLOT NODE完成分配
+                _ if self.config.importing.contains_key(&slot) => continue,
+                owner => {
+                    lost |= owner.is_some() && owner == current_master;
+                    claimed.push(slot);
+                }
+            }
+        }
+        if claimed.is_empty() {
+            return;
+        }
+        self.assign_slots(&claimed, Some(sender));
+        self.todo_save = true;
+
+        // 当前节点(或者它的master)的slot全部被接管，成为新master的replica
+        let Some(current_master) = current_master.filter(|_| lost) else {
+            return;
+        };
+        if self.config.nodes[current_master].slots.is_empty() {
+            let new_master = self.config.nodes[sender].id.clone();
+            tracing::info!(
+                "Configuration change detected, following new master {}",
+                new_master
+            );
+            let me = &mut self.config.nodes[myself];
+            me.role = NodeRole::Replica;
+            me.master_id = Some(new_master);
+            self.manual = None;
+            self.election = Election::default();
+            actions.role_changed = true;
+        }
+    }
+
+    /// 负责slot的master报告的PFAIL/FAIL是下线报告
+    fn handle_gossip(&mut self, sender: usize, gossip: &[GossipEntry], now: i64) {
+        let reporter = self.config.nodes[sender].id.clone();
+        let is_voter = Self::is_voter(&self.config.nodes[sender]);
+        for entry in gossip {
+            match self.node_index(&entry.id) {
+                Some(index) if self.config.nodes[index].myself => {}
+                Some(_) if is_voter => {
+                    let reports = self.fail_reports.entry(entry.id.clone()).or_default();
+                    if entry.flags & (FLAG_PFAIL | FLAG_FAIL) != 0 {
+                        reports.insert(reporter.clone(), now);
+                    } else {
+                        reports.remove(&reporter);
+                    }
+                }
+                Some(_) => {}
+                // 通过gossip发现新的节点
+                None if entry.flags & FLAG_FAIL == 0 && !entry.ip.is_empty() => {
+                    self.add_node(&entry.id, &entry.ip, entry.port, entry.cport, entry.flags);
+                }
+                None => {}
+            }
+        }
+    }
+
+    /// 超过半数的master报告PFAIL时标记为FAIL，返回是否标记
+    fn mark_fail_if_needed(&mut self, index: usize, now: i64) -> bool {
+        let timeout = self.node_timeout as i64;
+        let id = self.config.nodes[index].id.clone();
+        let reports = self.fail_reports.entry(id).or_default();
+        // 过期的报告不再有效
+        reports.retain(|_, at| now - *at <= timeout * 2);
+        let count = reports.len() + Self::is_voter(self.myself()) as usize;
+        if count < self.quorum() {
+            return false;
+        }
+        let node = &mut self.config.nodes[index];
+        tracing::info!("Marking node {} as failing (quorum reached)", node.id);
+        node.fail = true;
+        node.pfail = false;
+        node.fail_time = now;
+        self.todo_save = true;
+        true
+    }
+
+    /// master给replica投票
+    fn handle_auth_request(
+        &mut self,
+        sender: usize,
+        msg: &ClusterMessage,
+        now: i64,
+        offset: u64,
+    ) -> Option<Outgoing> {
+        let timeout = self.node_timeout as i64;
+        if !Self::is_voter(self.myself()) {
+            return None;
+        }
+        // 请求的纪元过期或者这个纪元已经投过票
+        if msg.current_epoch < self.config.current_epoch
+            || self.config.last_vote_epoch == self.config.current_epoch
+        {
+            return None;
+        }
+        let requester = &self.config.nodes[sender];
+        if requester.role != NodeRole::Replica {
+            return None;
+        }
+        let master = self
+            .master_index(requester)
+            .map(|i| &self.config.nodes[i])?;
+        if !master.fail && msg.mflags & MFLAG_FORCEACK == 0 {
+            return None;
+        }
+        // 同一个master的replica在两倍超时时间内只投一次
+        if matches!(self.voted_time.get(&master.id), Some(at) if now - at < timeout * 2) {
+            return None;
+        }
+        // 请求接管的slot已经由配置纪元更大的节点负责
+        let stale = msg
+            .slots
+            .iter()
+            .flat_map(|(start, end)| *start..=*end)
+            .filter_map(|slot| self.slots[slot as usize])
+            .any(|owner| self.config.nodes[owner].config_epoch > msg.config_epoch);
+        if stale {
+            return None;
+        }
+
+        tracing::info!(
+            "Failover auth granted to {} for epoch {}",
+            msg.sender,
+            self.config.current_epoch
+        );
+        self.voted_time.insert(master.id.clone(), now);
+        self.config.last_vote_epoch = self.config.current_epoch;
+        self.todo_save = true;
+        Some(Outgoing::To(
+            msg.sender.clone(),
+            self.build_message(MessageType::FailoverAuthAck, offset),
+        ))
+    }
+
+    /// 提升为master，接管原master的slot
+    fn failover(&mut self, epoch: u64, actions: &mut Actions) {
+        let myself = self.myself_index();
+        let Some(master) = self.master_index(&self.config.nodes[myself]) else {
+            return;
+        };
+        tracing::info!(
+            "Failover: taking over slots of {}",
+            self.config.nodes[master].id
+        );
+        let slots = self.node_slots(master);
+        let me = &mut self.config.nodes[myself];
+        me.role = NodeRole::Master;
+        me.master_id = None;
+        me.config_epoch = me.config_epoch.max(epoch);
+        self.assign_slots(&slots, Some(myself));
+        self.election = Election::default();
+        self.manual = None;
+        self.todo_save = true;
+        actions.role_changed = true;
+    }
+
+    /// replica检查是否需要发起选举
+    fn replica_cron(&mut self, now: i64, offset: u64, actions: &mut Actions) {
+        let timeout = self.node_timeout as i64;
+        let Some(master) = self.master_index(self.myself()) else {
+            return;
+        };
+        let master = &self.config.nodes[master];
+        let manual_ready = match &self.manual {
+            Some(m) => m.force || m.master_offset.is_some_and(|o| offset >= o),
+            None => false,
+        };
+        if !(master.fail || manual_ready) || master.slots.is_empty() {
+            self.election = Election::default();
+            return;
+        }
+
+        // 选举超时后重新发起
+        if self.election.auth_sent && now - self.election.auth_time > timeout * 2 {
+            self.election = Election::default();
+        }
+        if self.election.auth_time == 0 {
+            self.election.auth_time = now + if manual_ready { 0 } else { ELECTION_DELAY_MS };
+            return;
+        }
+        if now < self.election.auth_time {
+            return;
+        }
+        if !self.election.auth_sent {
+            self.config.current_epoch += 1;
+            self.election.auth_epoch = self.config.current_epoch;
+            self.election.auth_sent = true;
+            self.election.auth_time = now;
+            self.todo_save = true;
+            tracing::info!(
+                "Starting a failover election for epoch {}",
+                self.election.auth_epoch
+            );
+            actions.messages.push(Outgoing::Broadcast(
+                self.build_message(MessageType::FailoverAuthRequest, offset),
+            ));
+            return;
+        }
+        if self.election.auth_count >= self.quorum() {
+            let epoch = self.election.auth_epoch;
+            self.failover(epoch, actions);
+            actions.messages.push(Outgoing::Broadcast(
+                self.build_message(MessageType::Pong, offset),
+            ));
+        }
+    }
+
+    /// 定时任务: PING其他节点，检测下线，replica检查是否需要故障转移
+    pub(super) fn cron(&mut self, now: i64, offset: u64) -> Actions {
+        let timeout = self.node_timeout as i64;
+        let mut actions = Actions::default();
+
+        let mut ping = Vec::new();
+        for (i, node) in self.config.nodes.iter_mut().enumerate() {
+            if node.myself {
+                continue;
+            }
+            if node.ping_sent == 0 && now - node.pong_recv > timeout / 2 {
+                ping.push(i);
+            }
+            if node.ping_sent > 0 && now - node.ping_sent > timeout && !node.pfail && !node.fail {
+                tracing::info!("Marking node {} as PFAIL", node.id);
+                node.pfail = true;
+            }
+        }
+        // 每秒PING一个最久没有收到PONG的节点
+        if now - self.last_gossip >= GOSSIP_PERIOD_MS {
+            self.last_gossip = now;
+            let oldest = self
+                .config
+                .nodes
+                .iter()
+                .enumerate()
+                .filter(|(i, n)| !n.myself && n.ping_sent == 0 && !ping.contains(i))
+                .min_by_key(|(_, n)| n.pong_recv)
+                .map(|(i, _)| i);
+            ping.extend(oldest);
+        }
+        // 手动故障转移期间master持续发送复制偏移量
+        if let Some(replica) = self.manual.as_ref().and_then(|m| m.replica.as_deref()) {
+            if let Some(i) = self.node_index(replica).filter(|i| !ping.contains(i)) {
+                ping.push(i);
+            }
+        }
+        for i in ping {
+            if self.config.nodes[i].ping_sent == 0 {
+                self.config.nodes[i].ping_sent = now;
+            }
+            actions.messages.push(Outgoing::To(
+                self.config.nodes[i].id.clone(),
+                self.build_message(MessageType::Ping, offset),
+            ));
+        }
+
+        let pfail: Vec<usize> = (0..self.config.nodes.len())
+            .filter(|i| self.config.nodes[*i].pfail)
+            .collect();
+        for i in pfail {
+            if self.mark_fail_if_needed(i, now) {
+                let mut msg = self.build_message(MessageType::Fail, offset);
+                msg.fail_node = Some(self.config.nodes[i].id.clone());
+                actions.messages.push(Outgoing::Broadcast(msg));
+            }
+        }
+
+        if matches!(&self.manual, Some(m) if now > m.end) {
+            tracing::warn!("Manual failover timed out");
+            self.manual = None;
+        }
+        if self.myself().role == NodeRole::Replica {
+            self.replica_cron(now, offset, &mut actions);
+        }
+        actions
+    }
+
+    fn manual_failover(
+        &mut self,
+        mode: FailoverMode,
+        now: i64,
+        offset: u64,
+    ) -> Result<Actions, ClusterError> {
+        let invalid = |msg: &str| Err(ClusterError::InvalidSlotState(msg.to_string()));
+        let myself = self.myself();
+        if myself.role != NodeRole::Replica {
+            return invalid("You should send CLUSTER FAILOVER to a replica");
+        }
+        let Some(master) = self.master_index(myself).map(|i| &self.config.nodes[i]) else {
+            return invalid("I'm a replica but my master is unknown to me");
+        };
+        if mode == FailoverMode::Default && (master.fail || master.pfail || !master.connected) {
+            return invalid("Master is down or failed, please use CLUSTER FAILOVER FORCE");
+        }
+        let master_id = master.id.clone();
+
+        let mut actions = Actions::default();
+        self.election = Election::default();
+        match mode {
+            FailoverMode::Takeover => {
+                // 不经过其他master同意，直接使用新的纪元接管
+                self.config.current_epoch += 1;
+                let epoch = self.config.current_epoch;
+                self.failover(epoch, &mut actions);
+                actions.messages.push(Outgoing::Broadcast(
+                    self.build_message(MessageType::Pong, offset),
+                ));
+            }
+            FailoverMode::Force | FailoverMode::Default => {
+                self.manual = Some(ManualFailover {
+                    end: now + MANUAL_FAILOVER_TIMEOUT_MS,
+                    replica: None,
+                    master_offset: None,
+                    force: mode == FailoverMode::Force,
+                });
+                if mode == FailoverMode::Default {
+                    actions.messages.push(Outgoing::To(
+                        master_id,
+                        self.build_message(MessageType::MfStart, offset),
+                    ));
+                }
+            }
+        }
+        Ok(actions)
+    }
+}
+
+impl ClusterState {
+    /// 处理集群总线收到的消息
+    pub(crate) fn handle_message(
+        &self,
+        msg: ClusterMessage,
+        peer_ip: &str,
+        offset: u64,
+    ) -> Actions {
+        let mut inner = self.inner.write().unwrap();
+        match inner.as_mut() {
+            Some(inner) => {
+                inner.node_timeout = self.node_timeout();
+                inner.handle_message(msg, peer_ip, now_ms(), offset)
+            }
+            None => Actions::default(),
+        }
+    }
+
+    /// 集群定时任务
+    pub(crate) fn cron(&self, offset: u64) -> Actions {
+        let mut inner = self.inner.write().unwrap();
+        match inner.as_mut() {
+            Some(inner) => {
+                inner.node_timeout = self.node_timeout();
+                inner.cron(now_ms(), offset)
+            }
+            None => Actions::default(),
+        }
+    }
+
+    /// CLUSTER FAILOVER
+    pub(crate) fn manual_failover(
+        &self,
+        mode: FailoverMode,
+        offset: u64,
+    ) -> Result<Actions, ClusterError> {
+        let mut inner = self.inner.write().unwrap();
+        let inner = inner.as_mut().ok_or(ClusterError::Disabled)?;
+        inner.manual_failover(mode, now_ms(), offset)
+    }
+
+    /// 以当前节点的身份构造消息
+    pub(crate) fn build_message(&self, kind: MessageType, offset: u64) -> Option<ClusterMessage> {
+        let inner = self.inner.read().unwrap();
+        inner.as_ref().map(|i| i.build_message(kind, offset))
+    }
+
+    /// 手动故障转移期间master暂停客户端的写命令
+    pub fn is_write_paused(&self) -> bool {
+        let inner = self.inner.read().unwrap();
+        matches!(inner.as_ref().and_then(|i| i.manual.as_ref()), Some(m) if m.replica.is_some() && now_ms() <= m.end)
+    }
+
+    /// 集群总线连接状态
+    pub(crate) fn set_connected(&self, id: &str, connected: bool) {
+        let mut inner = self.inner.write().unwrap();
+        if let Some(inner) = inner.as_mut() {
+            if let Some(i) = inner.node_index(id) {
+                inner.config.nodes[i].connected = connected;
+            }
+        }
+    }
+
+    /// 取出配置是否需要保存的标记
+    pub(crate) fn take_todo_save(&self) -> bool {
+        let mut inner = self.inner.write().unwrap();
+        inner
+            .as_mut()
+            .map(|i| std::mem::take(&mut i.todo_save))
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster::parse_nodes_conf;
+    use anyhow::Result;
+
+    const A: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    const B: &str = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+    const C: &str = "cccccccccccccccccccccccccccccccccccccccc";
+    const R: &str = "dddddddddddddddddddddddddddddddddddddddd";
+
+    /// 三个master和A的replica R，myself为指定的节点
+    fn inner(myself: &str) -> Result<ClusterInner> {
+        let flag = |id: &str| if id == myself { "myself," } else { "" };
+        let conf = format!(
+            "{A} 127.0.0.1:30001@40001 {}master - 0 0 1 connected 0-5460\n\
+             {B} 127.0.0.1:30002@40002 {}master - 0 0 2 connected 5461-10922\n\
+             {C} 127.0.0.1:30003@40003 {}master - 0 0 3 connected 10923-16383\n\
+             {R} 127.0.0.1:30004@40004 {}slave {A} 0 0 1 connected\n\
+             vars currentEpoch 3 lastVoteEpoch 0\n",
+            flag(A),
+            flag(B),
+            flag(C),
+            flag(R)
+        );
+        let mut inner = ClusterInner::new(parse_nodes_conf(&conf)?);
+        inner.node_timeout = 1000;
+        Ok(inner)
+    }
+
+    /// 将发给to的消息投递过去，返回to产生的消息
+    fn deliver(
+        from: &mut ClusterInner,
+        to: &mut ClusterInner,
+        kind: MessageType,
+        now: i64,
+    ) -> Actions {
+        let msg = from.build_message(kind, 0);
+        to.handle_message(msg, "127.0.0.1", now, 0)
+    }
+
+    #[test]
+    fn test_fail_detection() -> Result<()> {
+        let mut b = inner(B)?;
+        let mut c = inner(C)?;
+
+        // B PING A超时之后标记PFAIL
+        b.cron(10_000, 0);
+        assert!(b.config.nodes[0].ping_sent > 0);
+        b.cron(11_001, 0);
+        assert!(b.config.nodes[0].pfail);
+        // 只有B自己的报告，达不到多数派
+        assert!(!b.mark_fail_if_needed(0, 11_001));
+
+        // C通过gossip得知B认为A下线
+        deliver(&mut b, &mut c, MessageType::Ping, 11_100);
+        c.config.nodes[0].pfail = true;
+        let actions = c.cron(11_200, 0);
+        assert!(c.config.nodes[0].fail);
+        let Some(Outgoing::Broadcast(fail)) = actions
+            .messages
+            .into_iter()
+            .find(|m| matches!(m, Outgoing::Broadcast(msg) if msg.kind == MessageType::Fail))
+        else {
+            panic!("FAIL message expected");
+        };
+        assert_eq!(fail.fail_node.as_deref(), Some(A));
+
+        // FAIL消息传播到B
+        b.handle_message(fail, "127.0.0.1", 11_300, 0);
+        assert!(b.config.nodes[0].fail);
+
+        // A恢复后，没有slot被接管之前需要等待一段时间才清除FAIL
+        deliver(&mut inner(A)?, &mut b, MessageType::Pong, 11_400);
+        assert!(b.config.nodes[0].fail);
+        deliver(&mut inner(A)?, &mut b, MessageType::Pong, 14_000);
+        assert!(!b.config.nodes[0].fail);
+        Ok(())
+    }
+
+    #[test]
+    fn test_replica_election() -> Result<()> {
+        let mut r = inner(R)?;
+        let mut b = inner(B)?;
+        let mut c = inner(C)?;
+        for node in [&mut r, &mut b, &mut c] {
+            node.config.nodes[0].fail = true;
+        }
+
+        // 等待一段时间后发起选举
+        r.cron(1000, 0);
+        assert!(r.cron(1000, 0).messages.is_empty());
+        let actions = r.cron(1000 + ELECTION_DELAY_MS, 0);
+        assert_eq!(r.config.current_epoch, 4);
+        assert!(actions.messages.iter().any(|m| matches!(m,
+            Outgoing::Broadcast(msg) if msg.kind == MessageType::FailoverAuthRequest)));
+
+        // B和C投票，同一个纪元只投一次
+        for voter in [&mut b, &mut c] {
+            let req = r.build_message(MessageType::FailoverAuthRequest, 0);
+            let actions = voter.handle_message(req.clone(), "127.0.0.1", 1600, 0);
+            let [Outgoing::To(to, ack)] = actions.messages.as_slice() else {
+                panic!("ack expected");
+            };
+            assert_eq!(to, R);
+            assert_eq!(voter.config.last_vote_epoch, 4);
+            assert!(voter
+                .handle_message(req, "127.0.0.1", 1600, 0)
+                .messages
+                .is_empty());
+            r.handle_message(ack.clone(), "127.0.0.1", 1700, 0);
+        }
+
+        let actions = r.cron(1800, 0);
+        assert!(actions.role_changed);
+        assert_eq!(r.myself().role, NodeRole::Master);
+        assert_eq!(r.myself().slots, vec![(0, 5460)]);
+        assert_eq!(r.myself().config_epoch, 4);
+
+        // 新的配置通过PONG传播，恢复的A发现slot被接管后成为R的replica
+        deliver(&mut r, &mut b, MessageType::Pong, 1900);
+        assert_eq!(b.slots[0].map(|i| b.config.nodes[i].id.as_str()), Some(R));
+        let mut a = inner(A)?;
+        let actions = deliver(&mut r, &mut a, MessageType::Pong, 1900);
+        assert!(actions.role_changed);
+        assert_eq!(a.myself().role, NodeRole::Replica);
+        assert_eq!(a.myself().master_id.as_deref(), Some(R));
+        Ok(())
+    }
+
+    #[test]
+    fn test_manual_failover() -> Result<()> {
+        let mut r = inner(R)?;
+        let mut a = inner(A)?;
+        assert!(a.manual_failover(FailoverMode::Default, 0, 0).is_err());
+
+        let actions = r.manual_failover(FailoverMode::Default, 1000, 10)?;
+        let [Outgoing::To(to, start)] = actions.messages.as_slice() else {
+            panic!("MFSTART expected");
+        };
+        assert_eq!(to, A);
+
+        // master暂停写入，并把复制偏移量发给replica
+        let actions = a.handle_message(start.clone(), "127.0.0.1", 1000, 20);
+        let [Outgoing::To(_, ping)] = actions.messages.as_slice() else {
+            panic!("PING expected");
+        };
+        assert!(ping.mflags & MFLAG_PAUSED != 0);
+        r.handle_message(ping.clone(), "127.0.0.1", 1000, 10);
+
+        // 复制偏移量追上之后才发起选举
+        r.cron(1100, 10);
+        assert_eq!(r.election.auth_time, 0);
+        r.cron(1100, 20);
+        let actions = r.cron(1100, 20);
+        let Some(Outgoing::Broadcast(req)) = actions.messages.last() else {
+            panic!("auth request expected");
+        };
+        assert!(req.mflags & MFLAG_FORCEACK != 0);
+
+        // master没有下线也会投票
+        assert!(!a
+            .handle_message(req.clone(), "127.0.0.1", 1200, 20)
+            .messages
+            .is_empty());
+
+        // TAKEOVER不需要投票
+        let mut r = inner(R)?;
+        let actions = r.manual_failover(FailoverMode::Takeover, 1000, 0)?;
+        assert!(actions.role_changed);
+        assert_eq!(r.myself().config_epoch, 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_meet_and_gossip() -> Result<()> {
+        let mut a = inner(A)?;
+        let conf = format!(
+            "{} 127.0.0.1:30005@40005 myself,master - 0 0 0 connected\n",
+            "e".repeat(40)
+        );
+        let mut e = ClusterInner::new(parse_nodes_conf(&conf)?);
+
+        // E收到A的MEET后通过gossip认识了其他节点，回复PONG，A收到PONG后也认识了E
+        let actions = deliver(&mut a, &mut e, MessageType::Meet, 0);
+        assert_eq!(e.config.nodes.len(), 5);
+        assert_eq!(e.slots[0].map(|i| e.config.nodes[i].id.as_str()), Some(A));
+        assert!(e.todo_save);
+        let [Outgoing::Reply(pong)] = actions.messages.as_slice() else {
+            panic!("PONG expected");
+        };
+        a.handle_message(pong.clone(), "127.0.0.1", 0, 0);
+        assert!(a.node_index(&"e".repeat(40)).is_some());
+
+        // 不认识的节点发来的PING不会加入集群
+        let conf = conf.replace(&"e".repeat(40), &"f".repeat(40));
+        let mut f = ClusterInner::new(parse_nodes_conf(&conf)?);
+        deliver(&mut f, &mut a, MessageType::Ping, 0);
+        assert!(a.node_index(&"f".repeat(40)).is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,287 @@
+//! 集群总线消息
+//!
+//! 每条消息由LengthDelimitedCodec分帧，内容为: 签名"RCmb" + 版本 + 消息头 + gossip条目。
+//! 消息头中包含发送者的角色、纪元、复制偏移量和负责的slot，
+//! gossip部分是发送者对其他节点的看法，用于发现新节点和传播PFAIL。
+
+use crate::persistence::encoding::{write_bytes, write_u32, write_u64, write_u8, Reader};
+
+use super::{slot::CLUSTER_SLOTS, ClusterError, ClusterNode, NodeRole};
+
+const SIGNATURE: &[u8; 4] = b"RCmb";
+const VERSION: u8 = 1;
+
+/// 节点标记
+pub(crate) const FLAG_MASTER: u8 = 1;
+pub(crate) const FLAG_REPLICA: u8 = 2;
+pub(crate) const FLAG_PFAIL: u8 = 4;
+pub(crate) const FLAG_FAIL: u8 = 8;
+
+/// 手动故障转移期间master暂停了客户端写入
+pub(crate) const MFLAG_PAUSED: u8 = 1;
+/// 手动故障转移，master没有下线也可以投票
+pub(crate) const MFLAG_FORCEACK: u8 = 2;
+
+/// 消息类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageType {
+    Ping,
+    Pong,
+    /// 请求对方加入集群
+    Meet,
+    /// 通知集群某个节点已经下线
+    Fail,
+    /// replica请求master投票
+    FailoverAuthRequest,
+    /// master同意投票
+    FailoverAuthAck,
+    /// replica通知master开始手动故障转移
+    MfStart,
+}
+
+impl MessageType {
+    fn code(self) -> u8 {
+        match self {
+            MessageType::Ping => 0,
+            MessageType::Pong => 1,
+            MessageType::Meet => 2,
+            MessageType::Fail => 3,
+            MessageType::FailoverAuthRequest => 5,
+            MessageType::FailoverAuthAck => 6,
+            MessageType::MfStart => 9,
+        }
+    }
+
+    fn from_code(code: u8) -> Option<Self> {
+        Some(match code {
+            0 => MessageType::Ping,
+            1 => MessageType::Pong,
+            2 => MessageType::Meet,
+            3 => MessageType::Fail,
+            5 => MessageType::FailoverAuthRequest,
+            6 => MessageType::FailoverAuthAck,
+            9 => MessageType::MfStart,
+            _ => return None,
+        })
+    }
+}
+
+/// 发送者对其他节点的看法
+#[derive(Debug, Clone, PartialEq)]
+pub struct GossipEntry {
+    pub id: String,
+    pub ip: String,
+    pub port: u16,
+    pub cport: u16,
+    pub flags: u8,
+    pub ping_sent: i64,
+    pub pong_recv: i64,
+}
+
+/// 集群总线消息
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClusterMessage {
+    pub kind: MessageType,
+    pub sender: String,
+    pub ip: String,
+    pub port: u16,
+    pub cport: u16,
+    pub flags: u8,
+    pub master_id: Option<String>,
+    pub current_epoch: u64,
+    /// 发送者(replica则是它的master)的配置纪元
+    pub config_epoch: u64,
+    /// 发送者的复制偏移量
+    pub offset: u64,
+    pub mflags: u8,
+    /// 发送者(replica则是它的master)负责的slot
+    pub slots: Vec<(u16, u16)>,
+    pub gossip: Vec<GossipEntry>,
+    /// FAIL消息中下线的节点
+    pub fail_node: Option<String>,
+}
+
+/// 节点的标记
+pub(crate) fn node_flags(node: &ClusterNode) -> u8 {
+    let mut flags = match node.role {
+        NodeRole::Master => FLAG_MASTER,
+        NodeRole::Replica => FLAG_REPLICA,
+    };
+    if node.pfail {
+        flags |= FLAG_PFAIL;
+    }
+    if node.fail {
+        flags |= FLAG_FAIL;
+    }
+    flags
+}
+
+fn write_str(buf: &mut Vec<u8>, s: &str) {
+    write_bytes(buf, s.as_bytes());
+}
+
+impl ClusterMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = SIGNATURE.to_vec();
+        write_u8(&mut buf, VERSION);
+        write_u8(&mut buf, self.kind.code());
+        write_str(&mut buf, &self.sender);
+        write_str(&mut buf, &self.ip);
+        write_u32(&mut buf, self.port as u32);
+        write_u32(&mut buf, self.cport as u32);
+        write_u8(&mut buf, self.flags);
+        write_str(&mut buf, self.master_id.as_deref().unwrap_or_default());
+        write_u64(&mut buf, self.current_epoch);
+        write_u64(&mut buf, self.config_epoch);
+        write_u64(&mut buf, self.offset);
+        write_u8(&mut buf, self.mflags);
+        write_u32(&mut buf, self.slots.len() as u32);
+        for (start, end) in &self.slots {
+            write_u32(&mut buf, *start as u32);
+            write_u32(&mut buf, *end as u32);
+        }
+        write_u32(&mut buf, self.gossip.len() as u32);
+        for entry in &self.gossip {
+            write_str(&mut buf, &entry.id);
+            write_str(&mut buf, &entry.ip);
+            write_u32(&mut buf, entry.port as u32);
+            write_u32(&mut buf, entry.cport as u32);
+            write_u8(&mut buf, entry.flags);
+            write_u64(&mut buf, entry.ping_sent as u64);
+            write_u64(&mut buf, entry.pong_recv as u64);
+        }
+        write_str(&mut buf, self.fail_node.as_deref().unwrap_or_default());
+        buf
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, ClusterError> {
+        if data.len() < 6 || &data[..4] != SIGNATURE || data[4] != VERSION {
+            return Err(ClusterError::InvalidMessage(
+                "invalid signature or version".to_string(),
+            ));
+        }
+        let mut reader = Reader::new(&data[5..]);
+        Self::read(&mut reader).map_err(|e| ClusterError::InvalidMessage(e.to_string()))
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, crate::persistence::PersistenceError> {
+        let invalid = |msg: &str| crate::persistence::PersistenceError::InvalidFormat(msg.into());
+        let optional = |s: String| (!s.is_empty()).then_some(s);
+        // 端口按u32写入，超出u16范围的消息不合法
+        let read_port = |reader: &mut Reader| {
+            u16::try_from(reader.read_u32()?).map_err(|_| invalid("invalid port"))
+        };
+
+        let kind = MessageType::from_code(reader.read_u8()?)
+            .ok_or_else(|| invalid("unknown message type"))?;
+        let sender = reader.read_string()?;
+        let ip = reader.read_string()?;
+        let port = read_port(reader)?;
+        let cport = read_port(reader)?;
+        let flags = reader.read_u8()?;
+        let master_id = optional(reader.read_string()?);
+        let current_epoch = reader.read_u64()?;
+        let config_epoch = reader.read_u64()?;
+        let offset = reader.read_u64()?;
+        let mflags = reader.read_u8()?;
+        let len = reader.read_u32()? as usize;
+        let mut slots = Vec::with_capacity(len.min(1024));
+        for _ in 0..len {
+            let start = reader.read_u32()?;
+            let end = reader.read_u32()?;
+            if start > end || end as usize >= CLUSTER_SLOTS {
+                return Err(invalid("invalid slot range"));
+            }
+            slots.push((start as u16, end as u16));
+        }
+        let len = reader.read_u32()? as usize;
+        let mut gossip = Vec::with_capacity(len.min(1024));
+        for _ in 0..len {
+            gossip.push(GossipEntry {
+                id: reader.read_string()?,
+                ip: reader.read_string()?,
+                port: read_port(reader)?,
+                cport: read_port(reader)?,
+                flags: reader.read_u8()?,
+                ping_sent: reader.read_u64()? as i64,
+                pong_recv: reader.read_u64()? as i64,
+            });
+        }
+        let fail_node = optional(reader.read_string()?);
+
+        Ok(ClusterMessage {
+            kind,
+            sender,
+            ip,
+            port,
+            cport,
+            flags,
+            master_id,
+            current_epoch,
+            config_epoch,
+            offset,
+            mflags,
+            slots,
+            gossip,
+            fail_node,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_message_roundtrip() -> Result<()> {
+        let msg = ClusterMessage {
+            kind: MessageType::Ping,
+            sender: "a".repeat(40),
+            ip: "127.0.0.1".to_string(),
+            port: 30001,
+            cport: 40001,
+            flags: FLAG_REPLICA,
+            master_id: Some("b".repeat(40)),
+            current_epoch: 5,
+            config_epoch: 3,
+            offset: 1024,
+            mflags: MFLAG_FORCEACK,
+            slots: vec![(0, 100), (200, 200)],
+            gossip: vec![GossipEntry {
+                id: "c".repeat(40),
+                ip: "127.0.0.1".to_string(),
+                port: 30003,
+                cport: 40003,
+                flags: FLAG_MASTER | FLAG_PFAIL,
+                ping_sent: 1,
+                pong_recv: 2,
+            }],
+            fail_node: None,
+        };
+        assert_eq!(ClusterMessage::decode(&msg.encode())?, msg);
+
+        assert!(ClusterMessage::decode(b"RCmb").is_err());
+        assert!(ClusterMessage::decode(&msg.encode()[..20]).is_err());
+
+        // slot超出范围或者起止颠倒
+        for slots in [vec![(16000, 20000)], vec![(16384, 16384)], vec![(200, 100)]] {
+            let msg = ClusterMessage {
+                slots,
+                ..msg.clone()
+            };
+            assert!(ClusterMessage::decode(&msg.encode()).is_err());
+        }
+        let msg = ClusterMessage {
+            slots: vec![(0, 16383)],
+            ..msg
+        };
+        assert_eq!(ClusterMessage::decode(&msg.encode())?, msg);
+
+        // 端口超出u16: 签名 + 版本 + 类型 + sender(4+40) + ip(4+9)之后是端口的u32
+        let mut data = msg.encode();
+        data[4 + 1 + 1 + 44 + 13 + 2] = 1;
+        assert!(ClusterMessage::decode(&data).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,783 @@
+//! 集群模式
+//!
+//! key通过CRC16映射到16384个slot，每个slot由一个master负责，
+//! 执行命令之前检查key所在的slot是否由当前节点负责，否则返回-MOVED让客户端重定向。
+//!
+//! 节点之间通过集群总线(端口+10000)交换PING/PONG，检测节点下线并在master下线后提升replica。
+
+mod bus;
+mod config;
+mod failover;
+mod message;
+mod slot;
+
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, RwLock,
+    },
+};
+
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+use crate::{cmd::ErrorCode, RespFrame};
+
+pub use self::{
+    bus::{manual_failover, meet, run_cluster_bus},
+    config::{parse_nodes_conf, ClusterConfig},
+    failover::FailoverMode,
+    message::{ClusterMessage, GossipEntry, MessageType},
+    slot::{crc16, key_hash_slot, CLUSTER_SLOTS},
+};
+
+/// 默认的节点超时时间(毫秒)，超过这个时间没有响应PING的节点被标记为PFAIL
+const DEFAULT_NODE_TIMEOUT_MS: u64 = 15000;
+
+/// 集群相关的异常
+#[derive(Error, Debug)]
+pub enum ClusterError {
+    #[error("IO Error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid cluster config: {0}")]
+    InvalidConfig(String),
+    #[error("This instance has cluster support disabled")]
+    Disabled,
+    #[error("{0}")]
+    InvalidSlotState(String),
+    #[error("Invalid cluster message: {0}")]
+    InvalidMessage(String),
+}
+
+/// 节点角色
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeRole {
+    Master,
+    Replica,
+}
+
+/// 集群中的一个节点
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClusterNode {
+    /// 40位十六进制的节点ID
+    pub id: String,
+    pub ip: String,
+    pub port: u16,
+    /// 集群总线端口
+    pub cport: u16,
+    pub hostname: Option<String>,
+    pub role: NodeRole,
+    /// 是否是当前节点
+    pub myself: bool,
+    /// 当前节点认为该节点可能下线
+    pub pfail: bool,
+    /// 集群确认该节点已经下线
+    pub fail: bool,
+    /// 被标记为FAIL的时间(unix毫秒)，不写入配置文件
+    pub fail_time: i64,
+    /// replica对应的master
+    pub master_id: Option<String>,
+    /// 上次发送PING的时间(unix毫秒)
+    pub ping_sent: i64,
+    /// 上次收到PONG的时间(unix毫秒)
+    pub pong_recv: i64,
+    pub config_epoch: u64,
+    /// 集群总线连接是否正常
+    pub connected: bool,
+    /// 负责的slot范围，包含两端
+    pub slots: Vec<(u16, u16)>,
+}
+
+impl ClusterNode {
+    /// CLUSTER NODES 中的标记
+    fn flags(&self) -> String {
+        let mut flags = Vec::new();
+        if self.myself {
+            flags.push("myself");
+        }
+        flags.push(match self.role {
+            NodeRole::Master => "master",
+            NodeRole::Replica => "slave",
+        });
+        if self.pfail {
+            flags.push("fail?");
+        }
+        if self.fail {
+            flags.push("fail");
+        }
+        flags.join(",")
+    }
+
+    /// CLUSTER NODES 中的一行，也是集群配置文件中的一行
+    pub fn to_line(&self) -> String {
+        let mut line = format!(
+            "{} {}:{}@{}{} {} {} {} {} {} {}",
+            self.id,
+            self.ip,
+            self.port,
+            self.cport,
+            self.hostname
+                .as_ref()
+                .map(|h| format!(",{}", h))
+                .unwrap_or_default(),
+            self.flags(),
+            self.master_id.as_deref().unwrap_or("-"),
+            self.ping_sent,
+            self.pong_recv,
+            self.config_epoch,
+            if self.connected {
+                "connected"
+            } else {
+                "disconnected"
+            }
+        );
+        for (start, end) in &self.slots {
+            if start == end {
+                line.push_str(&format!(" {}", start));
+            } else {
+                line.push_str(&format!(" {}-{}", start, end));
+            }
+        }
+        line
+    }
+}
+
+/// 命令无法在当前节点执行的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClusterRedirect {
+    /// slot由其他节点负责
+    Moved { slot: u16, addr: String },
+    /// 多个key不在同一个slot
+    CrossSlot,
+    /// slot没有分配给任何节点
+    Down { slot: u16 },
+    /// slot正在迁移，key已经不在当前节点，需要带着ASKING到目标节点重试
+    Ask { slot: u16, addr: String },
+    /// slot正在迁移，多个key中只有一部分在当前节点
+    TryAgain,
+}
+
+impl ClusterRedirect {
+    /// 回复给客户端的错误码
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ClusterRedirect::Moved { .. } => ErrorCode::Moved,
+            ClusterRedirect::CrossSlot => ErrorCode::CrossSlot,
+            ClusterRedirect::Down { .. } => ErrorCode::ClusterDown,
+            ClusterRedirect::Ask { .. } => ErrorCode::Ask,
+            ClusterRedirect::TryAgain => ErrorCode::TryAgain,
+        }
+    }
+}
+
+/// 不带错误码的错误信息
+impl fmt::Display for ClusterRedirect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClusterRedirect::Moved { slot, addr } | ClusterRedirect::Ask { slot, addr } => {
+                write!(f, "{} {}", slot, addr)
+            }
+            ClusterRedirect::CrossSlot => write!(f, "Keys in request don't hash to the same slot"),
+            ClusterRedirect::Down { slot } => write!(f, "Hash slot not served: {}", slot),
+            ClusterRedirect::TryAgain => {
+                write!(f, "Multiple keys request during rehashing of slot")
+            }
+        }
+    }
+}
+
+impl From<ClusterRedirect> for RespFrame {
+    fn from(redirect: ClusterRedirect) -> Self {
+        redirect.code().reply(redirect)
+    }
+}
+
+#[derive(Debug)]
+struct ClusterInner {
+    config: ClusterConfig,
+    /// 每个slot对应的master在config.nodes中的下标
+    slots: Vec<Option<usize>>,
+    /// 下线报告: 节点ID -> (报告者ID -> 报告时间)
+    fail_reports: HashMap<String, HashMap<String, i64>>,
+    /// replica发起的选举
+    election: failover::Election,
+    /// 正在进行的手动故障转移
+    manual: Option<failover::ManualFailover>,
+    /// 上次给某个master的replica投票的时间，master ID -> unix毫秒
+    voted_time: HashMap<String, i64>,
+    /// 上次随机PING的时间
+    last_gossip: i64,
+    /// 节点超时时间(毫秒)，由ClusterState同步过来
+    node_timeout: u64,
+    /// 配置发生了变化，需要保存到配置文件
+    todo_save: bool,
+}
+
+impl ClusterInner {
+    fn new(config: ClusterConfig) -> Self {
+        let mut slots = vec![None; CLUSTER_SLOTS];
+        for (i, node) in config.nodes.iter().enumerate() {
+            for (start, end) in &node.slots {
+                for slot in *start..=*end {
+                    slots[slot as usize] = Some(i);
+                }
+            }
+        }
+        ClusterInner {
+            config,
+            slots,
+            fail_reports: HashMap::new(),
+            election: failover::Election::default(),
+            manual: None,
+            voted_time: HashMap::new(),
+            last_gossip: 0,
+            node_timeout: DEFAULT_NODE_TIMEOUT_MS,
+            todo_save: false,
+        }
+    }
+
+    fn nodes_text(&self) -> String {
+        let mut text = String::new();
+        for node in &self.config.nodes {
+            text.push_str(&node.to_line());
+            if node.myself {
+                for (slot, id) in &self.config.migrating {
+                    text.push_str(&format!(" [{}->-{}]", slot, id));
+                }
+                for (slot, id) in &self.config.importing {
+                    text.push_str(&format!(" [{}-<-{}]", slot, id));
+                }
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    fn node_index(&self, id: &str) -> Option<usize> {
+        self.config.nodes.iter().position(|n| n.id == id)
+    }
+
+    /// 将slot分配给节点(None表示不分配)，并重新计算相关节点的slot范围
+    fn assign_slots(&mut self, slots: &[u16], index: Option<usize>) {
+        let mut affected: Vec<usize> = index.into_iter().collect();
+        for slot in slots {
+            let old = std::mem::replace(&mut self.slots[*slot as usize], index);
+            if let Some(old) = old.filter(|old| !affected.contains(old)) {
+                affected.push(old);
+            }
+        }
+        for i in affected {
+            let mut ranges: Vec<(u16, u16)> = Vec::new();
+            for (s, owner) in self.slots.iter().enumerate() {
+                if *owner != Some(i) {
+                    continue;
+                }
+                let s = s as u16;
+                match ranges.last_mut() {
+                    Some((_, end)) if *end + 1 == s => *end = s,
+                    _ => ranges.push((s, s)),
+                }
+            }
+            self.config.nodes[i].slots = ranges;
+        }
+    }
+
+    fn myself(&self) -> &ClusterNode {
+        &self.config.nodes[self.myself_index()]
+    }
+
+    fn myself_index(&self) -> usize {
+        self.config
+            .nodes
+            .iter()
+            .position(|n| n.myself)
+            .expect("cluster config always has myself")
+    }
+}
+
+/// 集群运行时状态，挂在Backend上共享，没有加载配置时集群模式关闭
+#[derive(Debug)]
+pub struct ClusterState {
+    inner: RwLock<Option<ClusterInner>>,
+    /// 节点超时时间(毫秒)
+    node_timeout: AtomicU64,
+    /// 到其他节点的集群总线连接，节点ID -> 发送队列
+    links: Mutex<HashMap<String, mpsc::UnboundedSender<ClusterMessage>>>,
+    /// 集群配置文件，配置变化时自动保存
+    config_file: Mutex<Option<PathBuf>>,
+}
+
+impl Default for ClusterState {
+    fn default() -> Self {
+        ClusterState {
+            inner: RwLock::new(None),
+            node_timeout: AtomicU64::new(DEFAULT_NODE_TIMEOUT_MS),
+            links: Mutex::new(HashMap::new()),
+            config_file: Mutex::new(None),
+        }
+    }
+}
+
+impl ClusterState {
+    pub fn is_enabled(&self) -> bool {
+        self.inner.read().unwrap().is_some()
+    }
+
+    /// 使用配置开启集群模式，会替换已有的配置
+    pub fn set_config(&self, config: ClusterConfig) {
+        *self.inner.write().unwrap() = Some(ClusterInner::new(config));
+    }
+
+    /// 从集群配置文件加载配置并开启集群模式，之后配置变化时会写回这个文件
+    pub fn load_config_file(&self, path: impl AsRef<Path>) -> Result<(), ClusterError> {
+        let content = std::fs::read_to_string(path.as_ref())?;
+        self.set_config(parse_nodes_conf(&content)?);
+        *self.config_file.lock().unwrap() = Some(path.as_ref().to_path_buf());
+        Ok(())
+    }
+
+    /// 加载集群配置文件，文件不存在时生成新的节点ID并创建配置文件
+    pub fn load_or_create(
+        &self,
+        path: impl AsRef<Path>,
+        ip: &str,
+        port: u16,
+        cport: u16,
+    ) -> Result<(), ClusterError> {
+        if path.as_ref().exists() {
+            return self.load_config_file(path);
+        }
+        let myself = ClusterNode {
+            id: crate::replication::gen_replid(),
+            ip: ip.to_string(),
+            port,
+            cport,
+            hostname: None,
+            role: NodeRole::Master,
+            myself: true,
+            pfail: false,
+            fail: false,
+            fail_time: 0,
+            master_id: None,
+            ping_sent: 0,
+            pong_recv: 0,
+            config_epoch: 0,
+            connected: true,
+            slots: vec![],
+        };
+        self.set_config(ClusterConfig {
+            nodes: vec![myself],
+            ..Default::default()
+        });
+        *self.config_file.lock().unwrap() = Some(path.as_ref().to_path_buf());
+        self.save_config()
+    }
+
+    /// 将配置写入集群配置文件，没有配置文件时忽略
+    pub fn save_config(&self) -> Result<(), ClusterError> {
+        let path = self.config_file.lock().unwrap().clone();
+        if let Some(path) = path {
+            let tmp = path.with_extension("tmp");
+            std::fs::write(&tmp, self.config_text())?;
+            std::fs::rename(tmp, path)?;
+        }
+        Ok(())
+    }
+
+    pub fn node_timeout(&self) -> u64 {
+        self.node_timeout.load(Ordering::Relaxed)
+    }
+
+    pub fn set_node_timeout(&self, timeout_ms: u64) {
+        self.node_timeout.store(timeout_ms, Ordering::Relaxed);
+    }
+
+    /// 根据ID查找节点
+    pub fn node(&self, id: &str) -> Option<ClusterNode> {
+        let inner = self.inner.read().unwrap();
+        let inner = inner.as_ref()?;
+        inner.node_index(id).map(|i| inner.config.nodes[i].clone())
+    }
+
+    /// 当前的集群配置
+    pub fn config(&self) -> Option<ClusterConfig> {
+        self.inner
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|i| i.config.clone())
+    }
+
+    pub fn myself(&self) -> Option<ClusterNode> {
+        self.inner
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|i| i.myself().clone())
+    }
+
+    /// 负责slot的master
+    pub fn slot_owner(&self, slot: u16) -> Option<ClusterNode> {
+        let inner = self.inner.read().unwrap();
+        let inner = inner.as_ref()?;
+        inner.slots[slot as usize].map(|i| inner.config.nodes[i].clone())
+    }
+
+    /// 检查key是否都由当前节点负责，集群模式关闭时总是通过
+    /// replica负责的slot是它的master的slot，但是仍然需要重定向到master
+    /// slot迁移期间:
+    /// - 迁出节点上不存在的key返回-ASK，部分存在返回-TRYAGAIN
+    /// - 迁入节点只接受带有ASKING的请求
+    pub fn check_keys(
+        &self,
+        keys: &[&[u8]],
+        asking: bool,
+        exists: impl Fn(&[u8]) -> bool,
+    ) -> Result<(), ClusterRedirect> {
+        let inner = self.inner.read().unwrap();
+        let Some(inner) = inner.as_ref() else {
+            return Ok(());
+        };
+        let Some((first, rest)) = keys.split_first() else {
+            return Ok(());
+        };
+
+        let slot = key_hash_slot(first);
+        if rest.iter().any(|key| key_hash_slot(key) != slot) {
+            return Err(ClusterRedirect::CrossSlot);
+        }
+
+        let owner = inner.slots[slot as usize].map(|i| &inner.config.nodes[i]);
+        let migrating_to = inner
+            .config
+            .migrating
+            .get(&slot)
+            .filter(|_| matches!(owner, Some(n) if n.myself));
+        let importing = inner.config.importing.contains_key(&slot);
+        if migrating_to.is_some() || importing {
+            let missing = keys.iter().filter(|key| !exists(key)).count();
+            if let Some(target) = migrating_to.filter(|_| missing > 0) {
+                if missing < keys.len() {
+                    return Err(ClusterRedirect::TryAgain);
+                }
+                if let Some(node) = inner.node_index(target).map(|i| &inner.config.nodes[i]) {
+                    return Err(ClusterRedirect::Ask {
+                        slot,
+                        addr: format!("{}:{}", node.ip, node.port),
+                    });
+                }
+            }
+            if importing && asking {
+                if keys.len() > 1 && missing > 0 {
+                    return Err(ClusterRedirect::TryAgain);
+                }
+                return Ok(());
+            }
+        }
+
+        match owner {
+            Some(node) if node.myself => Ok(()),
+            Some(node) => Err(ClusterRedirect::Moved {
+                slot,
+                addr: format!("{}:{}", node.ip, node.port),
+            }),
+            None => Err(ClusterRedirect::Down { slot }),
+        }
+    }
+
+    /// CLUSTER SETSLOT <slot> MIGRATING <node-id>
+    pub fn set_slot_migrating(&self, slot: u16, id: &str) -> Result<(), ClusterError> {
+        let mut inner = self.inner.write().unwrap();
+        let inner = inner.as_mut().ok_or(ClusterError::Disabled)?;
+        if !matches!(inner.slots[slot as usize], Some(i) if inner.config.nodes[i].myself) {
+            return Err(ClusterError::InvalidSlotState(format!(
+                "I'm not the owner of hash slot {}",
+                slot
+            )));
+        }
+        if inner.node_index(id).is_none() {
+            return Err(ClusterError::InvalidSlotState(format!(
+                "I don't know about node {}",
+                id
+            )));
+        }
+        if inner.myself().id == id {
+            return Err(ClusterError::InvalidSlotState(
+                "Target node can't be myself".to_string(),
+            ));
+        }
+        inner.config.migrating.insert(slot, id.to_string());
+        inner.todo_save = true;
+        Ok(())
+    }
+
+    /// CLUSTER SETSLOT <slot> IMPORTING <node-id>
+    pub fn set_slot_importing(&self, slot: u16, id: &str) -> Result<(), ClusterError> {
+        let mut inner = self.inner.write().unwrap();
+        let inner = inner.as_mut().ok_or(ClusterError::Disabled)?;
+        if matches!(inner.slots[slot as usize], Some(i) if inner.config.nodes[i].myself) {
+            return Err(ClusterError::InvalidSlotState(format!(
+                "I'm already the owner of hash slot {}",
+                slot
+            )));
+        }
+        if inner.node_index(id).is_none() {
+            return Err(ClusterError::InvalidSlotState(format!(
+                "I don't know about node {}",
+                id
+            )));
+        }
+        if inner.myself().id == id {
+            return Err(ClusterError::InvalidSlotState(
+                "Source node can't be myself".to_string(),
+            ));
+        }
+        inner.config.importing.insert(slot, id.to_string());
+        inner.todo_save = true;
+        Ok(())
+    }
+
+    /// CLUSTER SETSLOT <slot> STABLE
+    pub fn set_slot_stable(&self, slot: u16) -> Result<(), ClusterError> {
+        let mut inner = self.inner.write().unwrap();
+        let inner = inner.as_mut().ok_or(ClusterError::Disabled)?;
+        inner.config.migrating.remove(&slot);
+        inner.config.importing.remove(&slot);
+        inner.todo_save = true;
+        Ok(())
+    }
+
+    /// CLUSTER SETSLOT <slot> NODE <node-id>
+    /// 迁出节点上还有这个slot的key时不能分配给其他节点
+    pub fn set_slot_node(&self, slot: u16, id: &str, has_keys: bool) -> Result<(), ClusterError> {
+        let mut inner = self.inner.write().unwrap();
+        let inner = inner.as_mut().ok_or(ClusterError::Disabled)?;
+        let index = inner.node_index(id).ok_or_else(|| {
+            ClusterError::InvalidSlotState(format!("I don't know about node {}", id))
+        })?;
+        if inner.config.nodes[index].role != NodeRole::Master {
+            return Err(ClusterError::InvalidSlotState(
+                "Target node is not a master".to_string(),
+            ));
+        }
+        let owned = matches!(inner.slots[slot as usize], Some(i) if inner.config.nodes[i].myself);
+        let to_myself = inner.config.nodes[index].myself;
+        if owned && !to_myself && has_keys {
+            return Err(ClusterError::InvalidSlotState(format!(
+                "Can't assign hashslot {} to a different node while I still hold keys for this hash slot.",
+                slot
+            )));
+        }
+
+        if !to_myself {
+            inner.config.migrating.remove(&slot);
+        }
+        // 迁入完成，成为slot新的负责节点，增加配置纪元让其他节点接受新配置
+        if to_myself && inner.config.importing.remove(&slot).is_some() {
+            inner.config.current_epoch += 1;
+            let epoch = inner.config.current_epoch;
+            inner.config.nodes[index].config_epoch = epoch;
+        }
+        inner.assign_slots(&[slot], Some(index));
+        inner.todo_save = true;
+        Ok(())
+    }
+
+    /// CLUSTER NODES 的内容
+    pub fn nodes_text(&self) -> String {
+        let inner = self.inner.read().unwrap();
+        inner.as_ref().map(|i| i.nodes_text()).unwrap_or_default()
+    }
+
+    /// 集群配置文件的内容
+    pub fn config_text(&self) -> String {
+        let inner = self.inner.read().unwrap();
+        let Some(inner) = inner.as_ref() else {
+            return String::new();
+        };
+        format!(
+            "{}vars currentEpoch {} lastVoteEpoch {}\n",
+            inner.nodes_text(),
+            inner.config.current_epoch,
+            inner.config.last_vote_epoch
+        )
+    }
+
+    /// INFO cluster 的内容
+    pub fn info(&self) -> String {
+        format!(
+            "# Cluster\r\ncluster_enabled:{}\r\n",
+            self.is_enabled() as u8
+        )
+    }
+
+    /// CLUSTER INFO 的内容
+    pub fn cluster_info(&self) -> String {
+        let inner = self.inner.read().unwrap();
+        let Some(inner) = inner.as_ref() else {
+            return String::new();
+        };
+        let assigned = inner.slots.iter().filter(|s| s.is_some()).count();
+        let failed = |pred: fn(&ClusterNode) -> bool| {
+            inner
+                .slots
+                .iter()
+                .flatten()
+                .filter(|i| pred(&inner.config.nodes[**i]))
+                .count()
+        };
+        let pfail = failed(|n| n.pfail && !n.fail);
+        let fail = failed(|n| n.fail);
+        let masters = inner
+            .config
+            .nodes
+            .iter()
+            .filter(|n| n.role == NodeRole::Master && !n.slots.is_empty())
+            .count();
+        let state = if assigned == CLUSTER_SLOTS && fail == 0 {
+            "ok"
+        } else {
+            "fail"
+        };
+
+        let lines = [
+            format!("cluster_state:{}", state),
+            format!("cluster_slots_assigned:{}", assigned),
+            format!("cluster_slots_ok:{}", assigned - pfail - fail),
+            format!("cluster_slots_pfail:{}", pfail),
+            format!("cluster_slots_fail:{}", fail),
+            format!("cluster_known_nodes:{}", inner.config.nodes.len()),
+            format!("cluster_size:{}", masters),
+            format!("cluster_current_epoch:{}", inner.config.current_epoch),
+            format!("cluster_my_epoch:{}", inner.myself().config_epoch),
+        ];
+        lines.join("\r\n") + "\r\n"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SimpleError;
+    use anyhow::Result;
+
+    const NODES_CONF: &str = "\
+aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa 127.0.0.1:30001@40001 myself,master - 0 0 1 connected 0-8191
+bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb 127.0.0.1:30002@40002 master - 0 0 2 connected 8192-16382
+vars currentEpoch 2 lastVoteEpoch 0
+";
+
+    #[test]
+    fn test_check_keys() -> Result<()> {
+        let state = ClusterState::default();
+        assert_eq!(state.check_keys(&[b"foo"], false, |_| true), Ok(()));
+
+        state.set_config(parse_nodes_conf(NODES_CONF)?);
+        // bar -> 5061, foo -> 12182
+        assert_eq!(state.check_keys(&[b"bar"], false, |_| true), Ok(()));
+        assert_eq!(state.check_keys(&[], false, |_| true), Ok(()));
+        assert_eq!(
+            state.check_keys(&[b"foo"], false, |_| true),
+            Err(ClusterRedirect::Moved {
+                slot: 12182,
+                addr: "127.0.0.1:30002".to_string()
+            })
+        );
+        assert_eq!(
+            state.check_keys(&[b"foo", b"bar"], false, |_| true),
+            Err(ClusterRedirect::CrossSlot)
+        );
+        // 回复给客户端的错误带错误码
+        assert_eq!(
+            RespFrame::from(ClusterRedirect::Moved {
+                slot: 12182,
+                addr: "127.0.0.1:30002".to_string()
+            }),
+            SimpleError::new("MOVED 12182 127.0.0.1:30002").into()
+        );
+        assert_eq!(
+            RespFrame::from(ClusterRedirect::CrossSlot),
+            SimpleError::new("CROSSSLOT Keys in request don't hash to the same slot").into()
+        );
+        assert_eq!(
+            state.check_keys(&[b"{bar}a", b"{bar}b"], false, |_| true),
+            Ok(())
+        );
+
+        // 16383没有分配
+        let key = (0..)
+            .map(|i| format!("key{}", i))
+            .find(|k| key_hash_slot(k.as_bytes()) == 16383)
+            .unwrap();
+        assert_eq!(
+            state.check_keys(&[key.as_bytes()], false, |_| true),
+            Err(ClusterRedirect::Down { slot: 16383 })
+        );
+        assert!(state.cluster_info().contains("cluster_state:fail"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_slot_migration() -> Result<()> {
+        let state = ClusterState::default();
+        state.set_config(parse_nodes_conf(NODES_CONF)?);
+        let other = "b".repeat(40);
+        let exists = |key: &[u8]| key == b"bar";
+
+        // 迁出: 不存在的key返回ASK，部分存在返回TRYAGAIN
+        state.set_slot_migrating(5061, &other)?;
+        assert_eq!(state.check_keys(&[b"bar"], false, exists), Ok(()));
+        let key = (0..)
+            .map(|i| format!("{{bar}}{}", i))
+            .find(|k| key_hash_slot(k.as_bytes()) == 5061)
+            .unwrap();
+        assert_eq!(
+            state.check_keys(&[key.as_bytes()], false, exists),
+            Err(ClusterRedirect::Ask {
+                slot: 5061,
+                addr: "127.0.0.1:30002".to_string()
+            })
+        );
+        assert_eq!(
+            state.check_keys(&[b"bar", key.as_bytes()], false, exists),
+            Err(ClusterRedirect::TryAgain)
+        );
+        assert!(state.config_text().contains(&format!("[5061->-{}]", other)));
+
+        // 还有key时不能分配给其他节点
+        assert!(state.set_slot_node(5061, &other, true).is_err());
+        state.set_slot_node(5061, &other, false)?;
+        assert!(matches!(
+            state.check_keys(&[b"bar"], false, exists),
+            Err(ClusterRedirect::Moved { slot: 5061, .. })
+        ));
+        assert!(state.config_text().contains("0-5060 5062-8191"));
+
+        // 迁入: 只接受ASKING
+        assert!(state.set_slot_migrating(12182, &other).is_err());
+        state.set_slot_importing(12182, &other)?;
+        assert!(matches!(
+            state.check_keys(&[b"foo"], false, |_| false),
+            Err(ClusterRedirect::Moved { .. })
+        ));
+        assert_eq!(state.check_keys(&[b"foo"], true, |_| false), Ok(()));
+        state.set_slot_node(12182, &"a".repeat(40), false)?;
+        assert_eq!(state.check_keys(&[b"foo"], false, |_| false), Ok(()));
+        assert_eq!(state.config().unwrap().current_epoch, 3);
+        assert!(state.config().unwrap().importing.is_empty());
+
+        assert!(state.set_slot_importing(0, &"c".repeat(40)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_text_roundtrip() -> Result<()> {
+        let state = ClusterState::default();
+        state.set_config(parse_nodes_conf(NODES_CONF)?);
+        assert_eq!(state.config_text(), NODES_CONF);
+        Ok(())
+    }
+}
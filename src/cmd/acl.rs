@@ -0,0 +1,287 @@
+use crate::{
+    acl::{category_commands, AclError, CATEGORIES},
+    now_ms, Backend, BulkString, RespArray, RespDouble, RespFrame, RespMap, RespNull,
+};
+
+use super::{extract_args, frame_to_string, CommandError, CommandExecutor, ErrorCode, RESP_OK};
+
+/// ACL LOG 默认返回的记录数
+const DEFAULT_LOG_COUNT: usize = 10;
+
+/// ACL 的子命令
+#[derive(Debug, PartialEq)]
+enum AclSubcommand {
+    SetUser(String, Vec<String>),
+    GetUser(String),
+    DelUser(Vec<String>),
+    List,
+    Users,
+    WhoAmI,
+    Cat(Option<String>),
+    Log(Option<usize>),
+    LogReset,
+    Load,
+    Save,
+}
+
+/// Acl 命令 acl setuser|getuser|deluser|list|users|whoami|cat|log|load|save [args]
+#[derive(Debug, PartialEq)]
+pub struct Acl {
+    sub: AclSubcommand,
+}
+
+impl Acl {
+    /// WHOAMI 需要知道连接的用户，由连接执行
+    pub fn is_whoami(&self) -> bool {
+        self.sub == AclSubcommand::WhoAmI
+    }
+}
+
+fn bulk_array(items: impl IntoIterator<Item = impl Into<BulkString>>) -> RespFrame {
+    RespArray::new(
+        items
+            .into_iter()
+            .map(|s| s.into().into())
+            .collect::<Vec<RespFrame>>(),
+    )
+    .into()
+}
+
+fn error_reply(e: AclError) -> RespFrame {
+    ErrorCode::Err.reply(e)
+}
+
+fn get_user(backend: &Backend, name: &str) -> RespFrame {
+    let Some(user) = backend.acl().user(name) else {
+        return RespNull.into();
+    };
+    let mut map = RespMap::default();
+    map.insert("flags".to_string(), bulk_array(user.flags()));
+    map.insert(
+        "passwords".to_string(),
+        bulk_array(user.passwords().iter().map(|p| p.as_str())),
+    );
+    map.insert(
+        "commands".to_string(),
+        BulkString::new(user.describe_commands()).into(),
+    );
+    map.insert(
+        "keys".to_string(),
+        BulkString::new(user.describe_keys()).into(),
+    );
+    map.insert(
+        "channels".to_string(),
+        BulkString::new(user.describe_channels()).into(),
+    );
+    map.insert("selectors".to_string(), RespArray::new(vec![]).into());
+    map.into()
+}
+
+fn log_entries(backend: &Backend, count: usize) -> RespFrame {
+    let now = now_ms();
+    let entries: Vec<RespFrame> = backend
+        .acl()
+        .log_entries(count)
+        .into_iter()
+        .map(|e| {
+            let mut map = RespMap::default();
+            map.insert("count".to_string(), RespFrame::Integer(e.count as i64));
+            map.insert(
+                "reason".to_string(),
+                BulkString::new(e.reason.as_str()).into(),
+            );
+            map.insert("context".to_string(), BulkString::new("toplevel").into());
+            map.insert("object".to_string(), BulkString::new(e.object).into());
+            map.insert("username".to_string(), BulkString::new(e.username).into());
+            map.insert(
+                "age-seconds".to_string(),
+                RespDouble::new((now - e.created) as f64 / 1000.0).into(),
+            );
+            map.insert(
+                "client-info".to_string(),
+                BulkString::new(e.client_info).into(),
+            );
+            map.insert(
+                "entry-id".to_string(),
+                RespFrame::Integer(e.entry_id as i64),
+            );
+            map.insert(
+                "timestamp-created".to_string(),
+                RespFrame::Integer(e.created),
+            );
+            map.insert(
+                "timestamp-last-updated".to_string(),
+                RespFrame::Integer(e.updated),
+            );
+            map.into()
+        })
+        .collect();
+    RespArray::new(entries).into()
+}
+
+impl CommandExecutor for Acl {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let acl = backend.acl();
+        let aclfile = || {
+            let file = backend.config().current().aclfile;
+            if file.is_empty() {
+                Err(AclError::NoAclFile)
+            } else {
+                Ok(file)
+            }
+        };
+        let ret = match self.sub {
+            AclSubcommand::SetUser(name, rules) => acl.set_user(&name, &rules),
+            AclSubcommand::GetUser(name) => return get_user(backend, &name),
+            AclSubcommand::DelUser(names) => {
+                return match acl.del_users(&names) {
+                    Ok(n) => RespFrame::Integer(n as i64),
+                    Err(e) => error_reply(e),
+                }
+            }
+            AclSubcommand::List => return bulk_array(acl.users().iter().map(|u| u.describe())),
+            AclSubcommand::Users => {
+                return bulk_array(acl.users().iter().map(|u| u.name().to_string()))
+            }
+            AclSubcommand::WhoAmI => {
+                return ErrorCode::Err.reply("ACL WHOAMI is only allowed from client connections")
+            }
+            AclSubcommand::Cat(None) => return bulk_array(CATEGORIES.iter().copied()),
+            AclSubcommand::Cat(Some(category)) => {
+                if !CATEGORIES.contains(&category.as_str()) {
+                    return ErrorCode::Err.reply(format!("Unknown category '{}'", category));
+                }
+                return bulk_array(category_commands(backend.commands(), &category));
+            }
+            AclSubcommand::Log(count) => {
+                return log_entries(backend, count.unwrap_or(DEFAULT_LOG_COUNT))
+            }
+            AclSubcommand::LogReset => {
+                acl.reset_log();
+                Ok(())
+            }
+            AclSubcommand::Load => aclfile().and_then(|file| acl.load_file(file)),
+            AclSubcommand::Save => aclfile().and_then(|file| acl.save_file(file)),
+        };
+        match ret {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => error_reply(e),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Acl {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        super::validate_command(&value, &["acl"], 1)?;
+
+        let mut args = extract_args(value, 1)?
+            .into_iter()
+            .map(frame_to_string)
+            .collect::<Result<Vec<String>, CommandError>>()?
+            .into_iter();
+        let name = args.next().unwrap_or_default().to_ascii_lowercase();
+        let mut args: Vec<String> = args.collect();
+        let wrong_args = || CommandError::WrongArity(format!("acl|{}", name));
+        let sub = match (name.as_str(), args.len()) {
+            ("setuser", 1..) => {
+                let name = args.remove(0);
+                AclSubcommand::SetUser(name, args)
+            }
+            ("getuser", 1) => AclSubcommand::GetUser(args.remove(0)),
+            ("deluser", 1..) => AclSubcommand::DelUser(args),
+            ("list", 0) => AclSubcommand::List,
+            ("users", 0) => AclSubcommand::Users,
+            ("whoami", 0) => AclSubcommand::WhoAmI,
+            ("cat", 0) => AclSubcommand::Cat(None),
+            ("cat", 1) => AclSubcommand::Cat(Some(args.remove(0).to_ascii_lowercase())),
+            ("log", 0) => AclSubcommand::Log(None),
+            ("log", 1) if args[0].eq_ignore_ascii_case("reset") => AclSubcommand::LogReset,
+            ("log", 1) => AclSubcommand::Log(Some(args[0].parse().map_err(|_| {
+                CommandError::InvalidArgument("value is out of range, must be positive".to_string())
+            })?)),
+            ("load", 0) => AclSubcommand::Load,
+            ("save", 0) => AclSubcommand::Save,
+            (
+                "setuser" | "getuser" | "deluser" | "list" | "users" | "whoami" | "cat" | "log"
+                | "load" | "save",
+                _,
+            ) => return Err(wrong_args()),
+            _ => {
+                return Err(CommandError::UnknownSubcommand {
+                    cmd: "ACL".to_string(),
+                    sub: name,
+                })
+            }
+        };
+        Ok(Acl { sub })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    fn cmd(args: &[&str]) -> Result<Acl, CommandError> {
+        Acl::try_from(RespArray::new(
+            args.iter()
+                .map(|arg| BulkString::new(*arg).into())
+                .collect::<Vec<RespFrame>>(),
+        ))
+    }
+
+    #[test]
+    fn test_acl_command() -> Result<()> {
+        let backend = Backend::new();
+        let ret = cmd(&[
+            "acl", "SETUSER", "alice", "on", ">secret", "~cache:*", "+get",
+        ])?
+        .execute(&backend);
+        assert_eq!(ret, RESP_OK.clone());
+        let ret = cmd(&["acl", "setuser", "alice", "+nosuchcommand"])?.execute(&backend);
+        assert!(matches!(ret, RespFrame::Error(e) if e.contains("modifier '+nosuchcommand'")));
+
+        let RespFrame::Map(user) = cmd(&["acl", "getuser", "alice"])?.execute(&backend) else {
+            panic!("ACL GETUSER should return a map");
+        };
+        assert_eq!(user["commands"], BulkString::new("-@all +get").into());
+        assert_eq!(user["keys"], BulkString::new("~cache:*").into());
+        assert_eq!(
+            cmd(&["acl", "getuser", "bob"])?.execute(&backend),
+            RespNull.into()
+        );
+
+        let ret = cmd(&["acl", "list"])?.execute(&backend);
+        let expected = bulk_array([
+            format!(
+                "user alice on #{} ~cache:* resetchannels -@all +get",
+                crate::acl::hash_password("secret")
+            ),
+            "user default on nopass ~* &* +@all".to_string(),
+        ]);
+        assert_eq!(ret, expected);
+
+        let ret = cmd(&["acl", "cat", "hash"])?.execute(&backend);
+        assert_eq!(ret, bulk_array(["hget", "hset", "hmget", "hgetall"]));
+        assert!(matches!(
+            cmd(&["acl", "cat", "foo"])?.execute(&backend),
+            RespFrame::Error(_)
+        ));
+
+        let ret = cmd(&["acl", "deluser", "alice", "bob"])?.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(1));
+        let ret = cmd(&["acl", "deluser", "default"])?.execute(&backend);
+        assert!(matches!(ret, RespFrame::Error(_)));
+        let ret = cmd(&["acl", "save"])?.execute(&backend);
+        assert!(
+            matches!(ret, RespFrame::Error(e) if e.contains("not configured to use an ACL file"))
+        );
+
+        assert!(cmd(&["acl", "whoami"])?.is_whoami());
+        assert!(cmd(&["acl", "getuser"]).is_err());
+        assert!(cmd(&["acl", "foo"]).is_err());
+        Ok(())
+    }
+}
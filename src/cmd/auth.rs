@@ -0,0 +1,162 @@
+use crate::{acl::DEFAULT_USER, Backend, RespArray, RespFrame};
+
+use super::{extract_args, frame_to_string, CommandError, CommandExecutor, ErrorCode};
+
+/// Auth 命令 auth [username] password
+/// 需要修改连接的用户，由连接执行
+#[derive(Debug, PartialEq)]
+pub struct Auth {
+    username: Option<String>,
+    password: String,
+}
+
+impl Auth {
+    /// 没有指定用户名时使用默认用户
+    pub fn username(&self) -> &str {
+        self.username.as_deref().unwrap_or(DEFAULT_USER)
+    }
+
+    /// 只有密码的旧格式
+    pub fn is_legacy(&self) -> bool {
+        self.username.is_none()
+    }
+
+    pub fn password(&self) -> &str {
+        &self.password
+    }
+}
+
+impl CommandExecutor for Auth {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        ErrorCode::Err.reply("AUTH is only allowed from client connections")
+    }
+}
+
+impl TryFrom<RespArray> for Auth {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        super::validate_command(&value, &["auth"], 1)?;
+
+        let mut args = extract_args(value, 1)?
+            .into_iter()
+            .map(frame_to_string)
+            .collect::<Result<Vec<String>, CommandError>>()?;
+        match args.len() {
+            1 => Ok(Auth {
+                username: None,
+                password: args.remove(0),
+            }),
+            2 => Ok(Auth {
+                password: args.remove(1),
+                username: Some(args.remove(0)),
+            }),
+            _ => Err(CommandError::InvalidArgument("syntax error".to_string())),
+        }
+    }
+}
+
+/// Hello 命令 hello [protover [AUTH username password] [SETNAME clientname]]
+/// 需要修改连接的状态，由连接执行
+#[derive(Debug, PartialEq)]
+pub struct Hello {
+    protover: Option<i64>,
+    auth: Option<(String, String)>,
+    setname: Option<String>,
+}
+
+impl Hello {
+    pub fn protover(&self) -> Option<i64> {
+        self.protover
+    }
+
+    pub fn auth(&self) -> Option<(&str, &str)> {
+        self.auth.as_ref().map(|(u, p)| (u.as_str(), p.as_str()))
+    }
+
+    pub fn setname(&self) -> Option<&str> {
+        self.setname.as_deref()
+    }
+}
+
+impl CommandExecutor for Hello {
+    fn execute(self, _backend: &Backend) -> RespFrame {
+        ErrorCode::Err.reply("HELLO is only allowed from client connections")
+    }
+}
+
+impl TryFrom<RespArray> for Hello {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        super::validate_command(&value, &["hello"], 0)?;
+
+        let syntax_error = || CommandError::InvalidArgument("syntax error".to_string());
+        let mut args = extract_args(value, 1)?
+            .into_iter()
+            .map(frame_to_string)
+            .collect::<Result<Vec<String>, CommandError>>()?
+            .into_iter();
+        let mut hello = Hello {
+            protover: None,
+            auth: None,
+            setname: None,
+        };
+        if let Some(protover) = args.next() {
+            hello.protover = Some(protover.parse().map_err(|_| {
+                CommandError::InvalidArgument(
+                    "Protocol version is not an integer or out of range".to_string(),
+                )
+            })?);
+        }
+        while let Some(option) = args.next() {
+            match option.to_ascii_lowercase().as_str() {
+                "auth" => {
+                    let (Some(username), Some(password)) = (args.next(), args.next()) else {
+                        return Err(syntax_error());
+                    };
+                    hello.auth = Some((username, password));
+                }
+                "setname" => hello.setname = Some(args.next().ok_or_else(syntax_error)?),
+                _ => return Err(syntax_error()),
+            }
+        }
+        Ok(hello)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BulkString;
+    use anyhow::Result;
+
+    fn args(args: &[&str]) -> RespArray {
+        RespArray::new(
+            args.iter()
+                .map(|arg| BulkString::new(*arg).into())
+                .collect::<Vec<RespFrame>>(),
+        )
+    }
+
+    #[test]
+    fn test_auth_hello_try_from() -> Result<()> {
+        let auth = Auth::try_from(args(&["auth", "secret"]))?;
+        assert!(auth.is_legacy());
+        assert_eq!((auth.username(), auth.password()), ("default", "secret"));
+        let auth = Auth::try_from(args(&["auth", "alice", "secret"]))?;
+        assert_eq!((auth.username(), auth.password()), ("alice", "secret"));
+        assert!(Auth::try_from(args(&["auth", "a", "b", "c"])).is_err());
+
+        let hello = Hello::try_from(args(&[
+            "hello", "3", "AUTH", "alice", "secret", "setname", "app",
+        ]))?;
+        assert_eq!(hello.protover(), Some(3));
+        assert_eq!(hello.auth(), Some(("alice", "secret")));
+        assert_eq!(hello.setname(), Some("app"));
+        assert_eq!(Hello::try_from(args(&["hello"]))?.protover(), None);
+        assert!(Hello::try_from(args(&["hello", "three"])).is_err());
+        assert!(Hello::try_from(args(&["hello", "3", "auth", "alice"])).is_err());
+        Ok(())
+    }
+}
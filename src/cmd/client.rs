@@ -0,0 +1,363 @@
+use std::time::Duration;
+
+use crate::{
+    clients::{validate_name, ClientFilter, ClientInfo, ClientType, PauseMode},
+    Backend, BulkString, RespArray, RespFrame, RespNull,
+};
+
+use super::{extract_args, frame_to_string, CommandError, CommandExecutor, ErrorCode, RESP_OK};
+
+/// CLIENT REPLY 的模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplyMode {
+    On,
+    Off,
+    /// 不回复下一条命令
+    Skip,
+}
+
+/// CLIENT 的子命令
+#[derive(Debug, PartialEq)]
+enum ClientSubcommand {
+    Id,
+    Info,
+    List(ClientFilter),
+    SetName(String),
+    GetName,
+    /// 旧格式 CLIENT KILL ip:port
+    KillAddr(String),
+    /// 过滤条件以及是否跳过执行命令的客户端
+    Kill(ClientFilter, bool),
+    Pause(Duration, PauseMode),
+    Unpause,
+    Reply(ReplyMode),
+    NoEvict(bool),
+    SetInfo(String, String),
+}
+
+/// Client 命令 client id|info|list|setname|getname|kill|pause|unpause|reply|no-evict|setinfo [args]
+#[derive(Debug, PartialEq)]
+pub struct Client {
+    sub: ClientSubcommand,
+}
+
+impl Client {
+    /// CLIENT REPLY 需要修改连接的回复模式，由连接处理
+    pub fn reply_mode(&self) -> Option<ReplyMode> {
+        match self.sub {
+            ClientSubcommand::Reply(mode) => Some(mode),
+            _ => None,
+        }
+    }
+
+    /// 执行命令，current为执行命令的客户端，不是从客户端连接执行时为None
+    pub fn execute_for(&self, backend: &Backend, current: Option<&ClientInfo>) -> RespFrame {
+        let clients = backend.clients();
+        match &self.sub {
+            ClientSubcommand::List(filter) => {
+                let lines: String = clients
+                    .list(filter)
+                    .iter()
+                    .map(|c| c.info() + "\n")
+                    .collect();
+                return BulkString::new(lines).into();
+            }
+            ClientSubcommand::KillAddr(addr) => {
+                let filter = ClientFilter {
+                    addr: Some(addr.clone()),
+                    ..Default::default()
+                };
+                return if clients.kill(&filter) > 0 {
+                    RESP_OK.clone()
+                } else {
+                    ErrorCode::Err.reply("No such client")
+                };
+            }
+            ClientSubcommand::Kill(filter, skipme) => {
+                let mut filter = filter.clone();
+                if *skipme {
+                    filter.skip = current.map(|c| c.id());
+                }
+                return RespFrame::Integer(clients.kill(&filter) as i64);
+            }
+            ClientSubcommand::Pause(timeout, mode) => {
+                clients.pause(*mode, *timeout);
+                return RESP_OK.clone();
+            }
+            ClientSubcommand::Unpause => {
+                clients.unpause();
+                return RESP_OK.clone();
+            }
+            _ => {}
+        }
+
+        let Some(client) = current else {
+            return ErrorCode::Err.reply("CLIENT is only allowed from client connections");
+        };
+        match &self.sub {
+            ClientSubcommand::Id => RespFrame::Integer(client.id() as i64),
+            ClientSubcommand::Info => BulkString::new(client.info() + "\n").into(),
+            ClientSubcommand::SetName(name) => {
+                if let Err(e) = validate_name(name) {
+                    return ErrorCode::Err.reply(e);
+                }
+                client.set_name((!name.is_empty()).then(|| name.clone()));
+                RESP_OK.clone()
+            }
+            ClientSubcommand::GetName => match client.name() {
+                Some(name) => BulkString::new(name).into(),
+                None => RespNull.into(),
+            },
+            ClientSubcommand::NoEvict(on) => {
+                client.set_no_evict(*on);
+                RESP_OK.clone()
+            }
+            ClientSubcommand::SetInfo(attr, value) => {
+                if value.contains(|c: char| c == ' ' || c.is_control()) {
+                    return ErrorCode::Err.reply(format!(
+                        "{} cannot contain spaces, newlines or special characters.",
+                        attr
+                    ));
+                }
+                match attr.as_str() {
+                    "lib-name" => client.set_lib(Some(value.clone()), None),
+                    _ => client.set_lib(None, Some(value.clone())),
+                }
+                RESP_OK.clone()
+            }
+            // 回复模式由连接处理
+            _ => RESP_OK.clone(),
+        }
+    }
+}
+
+impl CommandExecutor for Client {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        self.execute_for(backend, None)
+    }
+}
+
+fn syntax_error() -> CommandError {
+    CommandError::InvalidArgument("syntax error".to_string())
+}
+
+fn parse_on_off(value: &str) -> Result<bool, CommandError> {
+    match value.to_ascii_lowercase().as_str() {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(syntax_error()),
+    }
+}
+
+fn parse_id(value: &str) -> Result<u64, CommandError> {
+    match value.parse() {
+        Ok(id) if id > 0 => Ok(id),
+        _ => Err(CommandError::InvalidArgument(
+            "client-id should be greater than 0".to_string(),
+        )),
+    }
+}
+
+fn parse_type(value: &str) -> Result<ClientType, CommandError> {
+    value.parse().map_err(CommandError::InvalidArgument)
+}
+
+/// CLIENT KILL 的过滤条件 <option value> ...，SKIPME默认为yes
+fn parse_kill_filter(args: Vec<String>) -> Result<(ClientFilter, bool), CommandError> {
+    if !args.len().is_multiple_of(2) {
+        return Err(syntax_error());
+    }
+    let mut filter = ClientFilter::default();
+    let mut skipme = true;
+    for pair in args.chunks(2) {
+        let value = &pair[1];
+        match pair[0].to_ascii_lowercase().as_str() {
+            "id" => filter.ids.push(parse_id(value)?),
+            "addr" => filter.addr = Some(value.clone()),
+            "laddr" => filter.laddr = Some(value.clone()),
+            "user" => filter.user = Some(value.clone()),
+            "type" => filter.client_type = Some(parse_type(value)?),
+            "maxage" => {
+                filter.maxage = Some(value.parse().map_err(|_| {
+                    CommandError::InvalidArgument(
+                        "value is not an integer or out of range".to_string(),
+                    )
+                })?)
+            }
+            "skipme" => {
+                skipme = match value.to_ascii_lowercase().as_str() {
+                    "yes" => true,
+                    "no" => false,
+                    _ => return Err(syntax_error()),
+                }
+            }
+            _ => return Err(syntax_error()),
+        }
+    }
+    Ok((filter, skipme))
+}
+
+/// CLIENT LIST [TYPE type] [ID id [id ...]]
+fn parse_list_filter(args: Vec<String>) -> Result<ClientFilter, CommandError> {
+    let mut filter = ClientFilter::default();
+    let mut args = args.into_iter();
+    match args.next().map(|a| a.to_ascii_lowercase()).as_deref() {
+        None => {}
+        Some("type") => {
+            filter.client_type = Some(parse_type(&args.next().ok_or_else(syntax_error)?)?);
+            if args.next().is_some() {
+                return Err(syntax_error());
+            }
+        }
+        Some("id") => {
+            filter.ids = args.map(|id| parse_id(&id)).collect::<Result<_, _>>()?;
+            if filter.ids.is_empty() {
+                return Err(syntax_error());
+            }
+        }
+        Some(_) => return Err(syntax_error()),
+    }
+    Ok(filter)
+}
+
+impl TryFrom<RespArray> for Client {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        super::validate_command(&value, &["client"], 1)?;
+
+        let mut args = extract_args(value, 1)?
+            .into_iter()
+            .map(frame_to_string)
+            .collect::<Result<Vec<String>, CommandError>>()?
+            .into_iter();
+        let name = args.next().unwrap_or_default().to_ascii_lowercase();
+        let mut args: Vec<String> = args.collect();
+        let sub = match (name.as_str(), args.len()) {
+            ("id", 0) => ClientSubcommand::Id,
+            ("info", 0) => ClientSubcommand::Info,
+            ("list", _) => ClientSubcommand::List(parse_list_filter(args)?),
+            ("setname", 1) => ClientSubcommand::SetName(args.remove(0)),
+            ("getname", 0) => ClientSubcommand::GetName,
+            ("kill", 1) => ClientSubcommand::KillAddr(args.remove(0)),
+            ("kill", 2..) => {
+                let (filter, skipme) = parse_kill_filter(args)?;
+                ClientSubcommand::Kill(filter, skipme)
+            }
+            ("pause", 1 | 2) => {
+                let timeout: u64 = args[0].parse().map_err(|_| {
+                    CommandError::InvalidArgument(
+                        "timeout is not an integer or out of range".to_string(),
+                    )
+                })?;
+                let mode = match args.get(1).map(|m| m.to_ascii_lowercase()).as_deref() {
+                    None | Some("all") => PauseMode::All,
+                    Some("write") => PauseMode::Write,
+                    Some(_) => return Err(syntax_error()),
+                };
+                ClientSubcommand::Pause(Duration::from_millis(timeout), mode)
+            }
+            ("unpause", 0) => ClientSubcommand::Unpause,
+            ("reply", 1) => ClientSubcommand::Reply(match args[0].to_ascii_lowercase().as_str() {
+                "on" => ReplyMode::On,
+                "off" => ReplyMode::Off,
+                "skip" => ReplyMode::Skip,
+                _ => return Err(syntax_error()),
+            }),
+            ("no-evict", 1) => ClientSubcommand::NoEvict(parse_on_off(&args[0])?),
+            ("setinfo", 2) => {
+                let attr = args.remove(0).to_ascii_lowercase();
+                if attr != "lib-name" && attr != "lib-ver" {
+                    return Err(CommandError::InvalidArgument(format!(
+                        "Unrecognized option '{}'",
+                        attr
+                    )));
+                }
+                ClientSubcommand::SetInfo(attr, args.remove(0))
+            }
+            _ => {
+                return Err(CommandError::InvalidCommand(format!(
+                    "unknown subcommand or wrong number of arguments for '{}'. Try CLIENT HELP.",
+                    name
+                )))
+            }
+        };
+        Ok(Client { sub })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    fn cmd(args: &[&str]) -> Result<Client, CommandError> {
+        Client::try_from(RespArray::new(
+            args.iter()
+                .map(|arg| BulkString::new(*arg).into())
+                .collect::<Vec<RespFrame>>(),
+        ))
+    }
+
+    #[test]
+    fn test_client_command() -> Result<()> {
+        let backend = Backend::new();
+        let first = backend
+            .clients()
+            .register("127.0.0.1:1000".to_string(), "127.0.0.1:6379".to_string());
+        let second = backend
+            .clients()
+            .register("127.0.0.1:1001".to_string(), "127.0.0.1:6379".to_string());
+
+        let ret = cmd(&["client", "SETNAME", "worker"])?.execute_for(&backend, Some(&first));
+        assert_eq!(ret, RESP_OK.clone());
+        let ret = cmd(&["client", "setname", "bad name"])?.execute_for(&backend, Some(&first));
+        assert!(matches!(ret, RespFrame::Error(_)));
+        let ret = cmd(&["client", "getname"])?.execute_for(&backend, Some(&first));
+        assert_eq!(ret, BulkString::new("worker").into());
+        let ret = cmd(&["client", "id"])?.execute_for(&backend, Some(&second));
+        assert_eq!(ret, RespFrame::Integer(second.id() as i64));
+
+        let RespFrame::BulkString(list) = cmd(&["client", "list"])?.execute(&backend) else {
+            panic!("CLIENT LIST should return a bulk string");
+        };
+        let list = String::from_utf8(list.into_vec())?;
+        assert_eq!(list.lines().count(), 2);
+        assert!(list.starts_with(&format!(
+            "id={} addr=127.0.0.1:1000 laddr=127.0.0.1:6379 name=worker ",
+            first.id()
+        )));
+        let id = second.id().to_string();
+        let RespFrame::BulkString(list) = cmd(&["client", "list", "id", &id])?.execute(&backend)
+        else {
+            panic!("CLIENT LIST should return a bulk string");
+        };
+        assert_eq!(String::from_utf8(list.into_vec())?.lines().count(), 1);
+
+        // 默认跳过执行命令的客户端
+        let ret = cmd(&["client", "kill", "laddr", "127.0.0.1:6379"])?
+            .execute_for(&backend, Some(&first));
+        assert_eq!(ret, RespFrame::Integer(1));
+        let ret = cmd(&["client", "kill", "127.0.0.1:9999"])?.execute(&backend);
+        assert!(matches!(ret, RespFrame::Error(e) if e.contains("No such client")));
+
+        cmd(&["client", "pause", "1000", "write"])?.execute(&backend);
+        assert!(backend.clients().is_paused(true));
+        assert!(!backend.clients().is_paused(false));
+        cmd(&["client", "unpause"])?.execute(&backend);
+        assert!(!backend.clients().is_paused(true));
+
+        assert_eq!(
+            cmd(&["client", "reply", "skip"])?.reply_mode(),
+            Some(ReplyMode::Skip)
+        );
+        assert!(matches!(
+            cmd(&["client", "id"])?.execute(&backend),
+            RespFrame::Error(_)
+        ));
+        assert!(cmd(&["client", "kill", "id", "0"]).is_err());
+        assert!(cmd(&["client", "setinfo", "lib-foo", "x"]).is_err());
+        assert!(cmd(&["client", "foo"]).is_err());
+        Ok(())
+    }
+}
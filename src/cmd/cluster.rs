@@ -0,0 +1,484 @@
+use crate::{
+    cluster::{self, key_hash_slot, ClusterNode, FailoverMode, NodeRole, CLUSTER_SLOTS},
+    Backend, BulkString, RespArray, RespFrame, RespNull,
+};
+
+use super::{
+    extract_args, frame_to_i64, frame_to_string, CommandError, CommandExecutor, ErrorCode, RESP_OK,
+};
+
+/// CLUSTER 的子命令
+#[derive(Debug, PartialEq)]
+enum ClusterSubcommand {
+    Info,
+    MyId,
+    Nodes,
+    Slots,
+    Shards,
+    KeySlot(String),
+    CountKeysInSlot(u16),
+    GetKeysInSlot(u16, usize),
+    SetSlot(u16, SetSlotAction),
+    Meet(String, u16),
+    Failover(FailoverMode),
+}
+
+/// CLUSTER SETSLOT <slot> IMPORTING|MIGRATING|NODE <node-id> | STABLE
+#[derive(Debug, PartialEq)]
+enum SetSlotAction {
+    Importing(String),
+    Migrating(String),
+    Node(String),
+    Stable,
+}
+
+/// Cluster 命令 cluster <subcommand> [args]
+#[derive(Debug, PartialEq)]
+pub struct Cluster {
+    sub: ClusterSubcommand,
+}
+
+fn bulk(s: impl Into<String>) -> RespFrame {
+    BulkString::new(s.into()).into()
+}
+
+/// CLUSTER SLOTS 中的节点: ip port id metadata
+fn slots_node(node: &ClusterNode) -> RespFrame {
+    let metadata = match &node.hostname {
+        Some(hostname) => vec![bulk("hostname"), bulk(hostname.clone())],
+        None => vec![],
+    };
+    RespArray::new(vec![
+        bulk(node.ip.clone()),
+        RespFrame::Integer(node.port as i64),
+        bulk(node.id.clone()),
+        RespArray::new(metadata).into(),
+    ])
+    .into()
+}
+
+/// CLUSTER SHARDS 中的节点属性
+fn shards_node(node: &ClusterNode, offset: u64) -> RespFrame {
+    let mut fields = vec![
+        bulk("id"),
+        bulk(node.id.clone()),
+        bulk("port"),
+        RespFrame::Integer(node.port as i64),
+        bulk("ip"),
+        bulk(node.ip.clone()),
+        bulk("endpoint"),
+        bulk(node.ip.clone()),
+    ];
+    if let Some(hostname) = &node.hostname {
+        fields.push(bulk("hostname"));
+        fields.push(bulk(hostname.clone()));
+    }
+    fields.extend([
+        bulk("role"),
+        bulk(match node.role {
+            NodeRole::Master => "master",
+            NodeRole::Replica => "replica",
+        }),
+        bulk("replication-offset"),
+        RespFrame::Integer(offset as i64),
+        bulk("health"),
+        bulk(if node.fail { "fail" } else { "online" }),
+    ]);
+    RespArray::new(fields).into()
+}
+
+/// slot所在的key
+fn keys_in_slot(backend: &Backend, slot: u16) -> impl Iterator<Item = String> {
+    backend
+        .keys()
+        .into_iter()
+        .filter(move |key| key_hash_slot(key.as_bytes()) == slot)
+}
+
+impl CommandExecutor for Cluster {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let cluster = backend.cluster();
+        let Some(config) = cluster.config() else {
+            return ErrorCode::Err.reply("This instance has cluster support disabled");
+        };
+        let masters = || {
+            config
+                .nodes
+                .iter()
+                .filter(|n| n.role == NodeRole::Master && !n.slots.is_empty())
+        };
+        let replicas_of = |id: &str| {
+            config
+                .nodes
+                .iter()
+                .filter(|n| n.master_id.as_deref() == Some(id))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        match self.sub {
+            ClusterSubcommand::Info => bulk(cluster.cluster_info()),
+            ClusterSubcommand::MyId => bulk(cluster.myself().map(|n| n.id).unwrap_or_default()),
+            ClusterSubcommand::Nodes => bulk(cluster.nodes_text()),
+            ClusterSubcommand::Slots => {
+                let mut ranges = Vec::new();
+                for master in masters() {
+                    let replicas = replicas_of(&master.id);
+                    for (start, end) in &master.slots {
+                        let mut range = vec![
+                            RespFrame::Integer(*start as i64),
+                            RespFrame::Integer(*end as i64),
+                            slots_node(master),
+                        ];
+                        range.extend(replicas.iter().filter(|r| !r.fail).map(slots_node));
+                        ranges.push(RespArray::new(range).into());
+                    }
+                }
+                RespArray::new(ranges).into()
+            }
+            ClusterSubcommand::Shards => {
+                let offset = backend.replication().offset();
+                let node_offset = |n: &ClusterNode| if n.myself { offset } else { 0 };
+                let shards: Vec<RespFrame> = masters()
+                    .map(|master| {
+                        let slots = master
+                            .slots
+                            .iter()
+                            .flat_map(|(s, e)| {
+                                [RespFrame::Integer(*s as i64), RespFrame::Integer(*e as i64)]
+                            })
+                            .collect::<Vec<_>>();
+                        let mut nodes = vec![shards_node(master, node_offset(master))];
+                        nodes.extend(
+                            replicas_of(&master.id)
+                                .iter()
+                                .map(|r| shards_node(r, node_offset(r))),
+                        );
+                        RespArray::new(vec![
+                            bulk("slots"),
+                            RespArray::new(slots).into(),
+                            bulk("nodes"),
+                            RespArray::new(nodes).into(),
+                        ])
+                        .into()
+                    })
+                    .collect();
+                RespArray::new(shards).into()
+            }
+            ClusterSubcommand::KeySlot(key) => {
+                RespFrame::Integer(key_hash_slot(key.as_bytes()) as i64)
+            }
+            ClusterSubcommand::CountKeysInSlot(slot) => {
+                RespFrame::Integer(keys_in_slot(backend, slot).count() as i64)
+            }
+            ClusterSubcommand::GetKeysInSlot(slot, count) => RespArray::new(
+                keys_in_slot(backend, slot)
+                    .take(count)
+                    .map(bulk)
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            ClusterSubcommand::Meet(ip, cport) => match cluster::meet(backend, &ip, cport) {
+                Ok(_) => RESP_OK.clone(),
+                Err(e) => ErrorCode::Err.reply(e),
+            },
+            ClusterSubcommand::Failover(mode) => match cluster::manual_failover(backend, mode) {
+                Ok(_) => RESP_OK.clone(),
+                Err(e) => ErrorCode::Err.reply(e),
+            },
+            ClusterSubcommand::SetSlot(slot, action) => {
+                let ret = match action {
+                    SetSlotAction::Importing(id) => cluster.set_slot_importing(slot, &id),
+                    SetSlotAction::Migrating(id) => cluster.set_slot_migrating(slot, &id),
+                    SetSlotAction::Node(id) => {
+                        let has_keys = keys_in_slot(backend, slot).next().is_some();
+                        cluster.set_slot_node(slot, &id, has_keys)
+                    }
+                    SetSlotAction::Stable => cluster.set_slot_stable(slot),
+                };
+                match ret {
+                    Ok(_) => RESP_OK.clone(),
+                    Err(e) => ErrorCode::Err.reply(e),
+                }
+            }
+        }
+    }
+}
+
+/// Asking 命令 允许下一条命令访问正在迁入的slot，标记由连接保存
+#[derive(Debug, PartialEq)]
+pub struct Asking;
+
+impl CommandExecutor for Asking {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if backend.cluster().is_enabled() {
+            RESP_OK.clone()
+        } else {
+            ErrorCode::Err.reply("This instance has cluster support disabled")
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Asking {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        super::validate_command(&value, &["asking"], 0)?;
+        Ok(Asking)
+    }
+}
+
+fn parse_slot(frame: RespFrame) -> Result<u16, CommandError> {
+    match frame_to_i64(frame) {
+        Ok(slot) if (0..CLUSTER_SLOTS as i64).contains(&slot) => Ok(slot as u16),
+        _ => Err(CommandError::InvalidArgument(
+            "Invalid or out of range slot".to_string(),
+        )),
+    }
+}
+
+impl TryFrom<RespArray> for Cluster {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        super::validate_command(&value, &["cluster"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let name = frame_to_string(args.next().unwrap_or(RespNull.into()))?.to_ascii_lowercase();
+        let mut next = || args.next().unwrap_or(RespNull.into());
+        let sub = match name.as_str() {
+            "info" => ClusterSubcommand::Info,
+            "myid" => ClusterSubcommand::MyId,
+            "nodes" => ClusterSubcommand::Nodes,
+            "slots" => ClusterSubcommand::Slots,
+            "shards" => ClusterSubcommand::Shards,
+            "keyslot" => ClusterSubcommand::KeySlot(frame_to_string(next())?),
+            "countkeysinslot" => ClusterSubcommand::CountKeysInSlot(parse_slot(next())?),
+            "getkeysinslot" => {
+                let slot = parse_slot(next())?;
+                let count = usize::try_from(frame_to_i64(next())?).map_err(|_| {
+                    CommandError::InvalidArgument("Invalid number of keys".to_string())
+                })?;
+                ClusterSubcommand::GetKeysInSlot(slot, count)
+            }
+            "meet" => {
+                let ip = frame_to_string(next())?;
+                let port = frame_to_i64(next())?;
+                // 没有指定集群总线端口时使用 端口+10000
+                let cport = match next() {
+                    RespFrame::Null(_) => port + 10000,
+                    cport => frame_to_i64(cport)?,
+                };
+                let cport = u16::try_from(cport).map_err(|_| {
+                    CommandError::InvalidArgument(format!("Invalid node address specified: {}", ip))
+                })?;
+                ClusterSubcommand::Meet(ip, cport)
+            }
+            "failover" => {
+                let mode = match next() {
+                    RespFrame::Null(_) => FailoverMode::Default,
+                    mode => match frame_to_string(mode)?.to_ascii_lowercase().as_str() {
+                        "force" => FailoverMode::Force,
+                        "takeover" => FailoverMode::Takeover,
+                        _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+                    },
+                };
+                ClusterSubcommand::Failover(mode)
+            }
+            "setslot" => {
+                let slot = parse_slot(next())?;
+                let action = frame_to_string(next())?.to_ascii_lowercase();
+                let action = match action.as_str() {
+                    "importing" => SetSlotAction::Importing(frame_to_string(next())?),
+                    "migrating" => SetSlotAction::Migrating(frame_to_string(next())?),
+                    "node" => SetSlotAction::Node(frame_to_string(next())?),
+                    "stable" => SetSlotAction::Stable,
+                    _ => {
+                        return Err(CommandError::InvalidArgument(
+                            "Invalid CLUSTER SETSLOT action or number of arguments".to_string(),
+                        ))
+                    }
+                };
+                ClusterSubcommand::SetSlot(slot, action)
+            }
+            _ => {
+                return Err(CommandError::UnknownSubcommand {
+                    cmd: "CLUSTER".to_string(),
+                    sub: name,
+                })
+            }
+        };
+        Ok(Cluster { sub })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cluster::parse_nodes_conf, SimpleError};
+    use anyhow::Result;
+
+    const NODES_CONF: &str = "\
+aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa 127.0.0.1:30001@40001 myself,master - 0 0 1 connected 0-8191
+bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb 127.0.0.1:30002@40002 master - 0 0 2 connected 8192-16383
+cccccccccccccccccccccccccccccccccccccccc 127.0.0.1:30003@40003,replica-host slave aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa 0 0 1 connected
+";
+
+    fn cluster(args: &[&str]) -> Result<Cluster> {
+        let mut frames: Vec<RespFrame> = vec![bulk("cluster")];
+        frames.extend(args.iter().map(|a| bulk(*a)));
+        Ok(Cluster::try_from(RespArray::new(frames))?)
+    }
+
+    fn backend() -> Result<Backend> {
+        let backend = Backend::new();
+        backend.cluster().set_config(parse_nodes_conf(NODES_CONF)?);
+        Ok(backend)
+    }
+
+    #[test]
+    fn test_cluster_disabled() -> Result<()> {
+        let ret = cluster(&["slots"])?.execute(&Backend::new());
+        assert!(matches!(ret, RespFrame::Error(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cluster_keyslot() -> Result<()> {
+        let backend = backend()?;
+        assert_eq!(
+            cluster(&["KEYSLOT", "foo"])?.execute(&backend),
+            RespFrame::Integer(12182)
+        );
+
+        backend.set("{user}1".to_string(), bulk("a"));
+        backend.set("{user}2".to_string(), bulk("b"));
+        backend.set("other".to_string(), bulk("c"));
+        let slot = key_hash_slot(b"user").to_string();
+        assert_eq!(
+            cluster(&["countkeysinslot", &slot])?.execute(&backend),
+            RespFrame::Integer(2)
+        );
+        let RespFrame::Array(keys) = cluster(&["getkeysinslot", &slot, "1"])?.execute(&backend)
+        else {
+            panic!("array expected");
+        };
+        assert_eq!(keys.len(), 1);
+
+        assert!(cluster(&["countkeysinslot", "16384"]).is_err());
+        assert!(cluster(&["unknown"]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cluster_slots() -> Result<()> {
+        let backend = backend()?;
+        let RespFrame::Array(slots) = cluster(&["slots"])?.execute(&backend) else {
+            panic!("array expected");
+        };
+        assert_eq!(slots.len(), 2);
+        let RespFrame::Array(first) = &slots[0] else {
+            panic!("array expected");
+        };
+        assert_eq!(first[0], RespFrame::Integer(0));
+        assert_eq!(first[1], RespFrame::Integer(8191));
+        // master和它的replica
+        assert_eq!(first.len(), 4);
+        assert_eq!(
+            first[3],
+            RespArray::new(vec![
+                bulk("127.0.0.1"),
+                RespFrame::Integer(30003),
+                bulk("cccccccccccccccccccccccccccccccccccccccc"),
+                RespArray::new(vec![bulk("hostname"), bulk("replica-host")]).into(),
+            ])
+            .into()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cluster_shards_and_nodes() -> Result<()> {
+        let backend = backend()?;
+        let RespFrame::Array(shards) = cluster(&["shards"])?.execute(&backend) else {
+            panic!("array expected");
+        };
+        assert_eq!(shards.len(), 2);
+        let RespFrame::Array(shard) = &shards[0] else {
+            panic!("array expected");
+        };
+        assert_eq!(shard[0], bulk("slots"));
+        assert_eq!(
+            shard[1],
+            RespArray::new(vec![RespFrame::Integer(0), RespFrame::Integer(8191)]).into()
+        );
+        let RespFrame::Array(nodes) = &shard[3] else {
+            panic!("array expected");
+        };
+        assert_eq!(nodes.len(), 2);
+
+        let RespFrame::BulkString(text) = cluster(&["nodes"])?.execute(&backend) else {
+            panic!("bulk string expected");
+        };
+        assert_eq!(String::from_utf8(text.into_vec())?, NODES_CONF);
+
+        assert_eq!(
+            cluster(&["myid"])?.execute(&backend),
+            bulk("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cluster_setslot() -> Result<()> {
+        let backend = backend()?;
+        let b = "b".repeat(40);
+        assert_eq!(
+            cluster(&["setslot", "100", "migrating", &b])?.execute(&backend),
+            RESP_OK.clone()
+        );
+        assert_eq!(
+            cluster(&["setslot", "9000", "migrating", &b])?.execute(&backend),
+            SimpleError::new("ERR I'm not the owner of hash slot 9000").into()
+        );
+        assert_eq!(
+            cluster(&["setslot", "9000", "importing", "unknown"])?.execute(&backend),
+            SimpleError::new("ERR I don't know about node unknown").into()
+        );
+        assert_eq!(
+            cluster(&["setslot", "100", "stable"])?.execute(&backend),
+            RESP_OK.clone()
+        );
+        assert!(backend.cluster().config().unwrap().migrating.is_empty());
+
+        // 还有key时不能把slot交给其他节点
+        backend.set("foo{bar}".to_string(), bulk("bar"));
+        let slot = key_hash_slot(b"bar").to_string();
+        assert!(matches!(
+            cluster(&["setslot", &slot, "node", &b])?.execute(&backend),
+            RespFrame::Error(_)
+        ));
+        assert_eq!(
+            cluster(&["setslot", "100", "NODE", &b])?.execute(&backend),
+            RESP_OK.clone()
+        );
+        assert_eq!(
+            backend.cluster().slot_owner(100).map(|n| n.id),
+            Some(b.clone())
+        );
+
+        assert!(cluster(&["setslot", "100", "move", &b]).is_err());
+
+        // 只有replica可以执行FAILOVER
+        assert!(matches!(
+            cluster(&["failover", "force"])?.execute(&backend),
+            RespFrame::Error(_)
+        ));
+        assert_eq!(
+            cluster(&["meet", "127.0.0.1", "30005"])?.sub,
+            ClusterSubcommand::Meet("127.0.0.1".to_string(), 40005)
+        );
+        assert!(cluster(&["failover", "now"]).is_err());
+        Ok(())
+    }
+}
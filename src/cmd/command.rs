@@ -1,8 +1,9 @@
 use crate::{RespArray, RespFrame};
 
 use super::{
-    hmap::HMGet, CommandError, Echo, Get, HGet, HGetAll, HSet, Ping, SAdd, SISMember, Set,
-    Unrecognized,
+    command_spec, Acl, Asking, Auth, BgRewriteAof, BgSave, Client, Cluster, CommandError, Commands,
+    Config, Custom, Del, Dump, Echo, Get, HGet, HGetAll, HMGet, HSet, Hello, Info, LastSave,
+    Migrate, Ping, ReplicaOf, Restore, Role, SAdd, SISMember, Save, Set, Shutdown,
 };
 
 /// 创建支持的命令
@@ -18,8 +19,28 @@ pub enum Command {
     SAdd(SAdd),
     SISMember(SISMember),
     Ping(Ping),
-    Unrecognized(Unrecognized),
     Echo(Echo),
+    Save(Save),
+    BgSave(BgSave),
+    LastSave(LastSave),
+    BgRewriteAof(BgRewriteAof),
+    Dump(Dump),
+    Restore(Restore),
+    ReplicaOf(ReplicaOf),
+    Role(Role),
+    Info(Info),
+    Cluster(Cluster),
+    Asking(Asking),
+    Del(Del),
+    Migrate(Migrate),
+    Config(Config),
+    Shutdown(Shutdown),
+    Auth(Auth),
+    Hello(Hello),
+    Acl(Acl),
+    Client(Client),
+    Commands(Commands),
+    Custom(Custom),
 }
 
 /// 实现从Command到RespFrame的转换，只要RespArray
@@ -36,30 +57,14 @@ impl TryFrom<RespFrame> for Command {
     }
 }
 
-/// 尝试将RespArray 转换为对应的Command
+/// 尝试将RespArray 转换为对应的Command，命令名不区分大小写，在命令表中查找之后检查参数个数再解析
 impl TryFrom<RespArray> for Command {
     type Error = CommandError;
 
     fn try_from(value: RespArray) -> Result<Self, Self::Error> {
-        // 取RespArray的第一个元素，根据协议 RespArray中的命令部分必然是一个BulkString
-        match value.first() {
-            // 然后判断这个字符串是否为我们支持的命令
-            Some(RespFrame::BulkString(ref cmd)) => match cmd.as_ref() {
-                b"get" => Ok(Get::try_from(value)?.into()),
-                b"set" => Ok(Set::try_from(value)?.into()),
-                b"hget" => Ok(HGet::try_from(value)?.into()),
-                b"hset" => Ok(HSet::try_from(value)?.into()),
-                b"hmget" => Ok(HGetAll::try_from(value)?.into()),
-                b"hgetall" => Ok(HGetAll::try_from(value)?.into()),
-                b"ping" => Ok(Ping::try_from(value)?.into()),
-                b"echo" => Ok(Echo::try_from(value)?.into()),
-                b"sadd" => Ok(SAdd::try_from(value)?.into()),
-                b"sismember" => Ok(SISMember::try_from(value)?.into()),
-                _ => Ok(Unrecognized.into()),
-            },
-            _ => Err(CommandError::InvalidCommand(
-                "Command must be a bulk string with first args".to_string(),
-            )),
+        match command_spec(&value) {
+            Some(spec) => spec.parse(value),
+            None => Err(CommandError::unknown_command(&value)),
         }
     }
 }
@@ -97,4 +102,18 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_dispatch_case_insensitive() -> Result<()> {
+        let backend = Backend::new();
+        backend.hset("h".to_owned(), "f".to_owned(), "v".into());
+        let mut buf = BytesMut::from("*4\r\n$5\r\nHMGET\r\n$1\r\nh\r\n$1\r\nf\r\n$1\r\nx\r\n");
+        let cmd: Command = RespArray::decode(&mut buf)?.try_into()?;
+        assert!(matches!(cmd, Command::HMGet(_)));
+        assert_eq!(
+            cmd.execute(&backend),
+            RespArray::new(vec!["v".into(), RespNull.into()]).into()
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,384 @@
+use crate::{
+    config::glob_match, Backend, BulkString, RespArray, RespFrame, RespMap, RespNull, RespSet,
+    SimpleString,
+};
+
+use super::{
+    custom::CommandRegistry, extract_args, frame_to_string, table::CommandSpec, CommandError,
+    CommandExecutor, ErrorCode,
+};
+
+/// COMMAND LIST FILTERBY 的条件
+#[derive(Debug, PartialEq)]
+enum ListFilter {
+    Module(String),
+    AclCat(String),
+    Pattern(String),
+}
+
+/// COMMAND 的子命令
+#[derive(Debug, PartialEq)]
+enum CommandsSubcommand {
+    Info(Vec<String>),
+    Count,
+    Docs(Vec<String>),
+    List(Option<ListFilter>),
+    GetKeys(RespArray),
+}
+
+/// Command 命令 command [count|docs|getkeys|info|list] [args]
+#[derive(Debug, PartialEq)]
+pub struct Commands {
+    sub: CommandsSubcommand,
+}
+
+/// 按名字查找命令或者子命令，子命令写成 `命令|子命令`
+fn lookup(commands: &CommandRegistry, name: &str) -> Option<CommandSpec> {
+    match name.split_once('|') {
+        Some((cmd, sub)) => commands
+            .lookup(cmd.as_bytes())?
+            .subcommand(sub.as_bytes())
+            .copied(),
+        None => commands.lookup(name.as_bytes()),
+    }
+}
+
+/// 所有命令和子命令
+fn all_commands(specs: &[CommandSpec]) -> impl Iterator<Item = &CommandSpec> {
+    specs
+        .iter()
+        .flat_map(|spec| std::iter::once(spec).chain(spec.subcommands))
+}
+
+fn status_set(items: impl IntoIterator<Item = String>) -> RespFrame {
+    RespSet::new(
+        items
+            .into_iter()
+            .map(|s| SimpleString::new(s).into())
+            .collect::<Vec<RespFrame>>(),
+    )
+    .into()
+}
+
+fn map(entries: impl IntoIterator<Item = (&'static str, RespFrame)>) -> RespFrame {
+    let mut map = RespMap::default();
+    for (key, value) in entries {
+        map.insert(key.to_string(), value);
+    }
+    map.into()
+}
+
+/// key的位置，和Redis的key spec格式一致
+fn key_specs(spec: &CommandSpec) -> RespFrame {
+    let keys = spec.keys;
+    if keys.first <= 0 {
+        return RespArray::new(vec![]).into();
+    }
+    // lastkey是相对第一个key的位置，负数表示从结尾倒数
+    let lastkey = if keys.last < 0 {
+        keys.last
+    } else {
+        keys.last - keys.first
+    };
+    let flags = if spec.is_write() { "RW" } else { "RO" };
+    let key_spec = map([
+        ("flags", status_set([flags.to_string()])),
+        (
+            "begin_search",
+            map([
+                ("type", BulkString::new("index").into()),
+                ("spec", map([("index", RespFrame::Integer(keys.first))])),
+            ]),
+        ),
+        (
+            "find_keys",
+            map([
+                ("type", BulkString::new("range").into()),
+                (
+                    "spec",
+                    map([
+                        ("lastkey", RespFrame::Integer(lastkey)),
+                        ("keystep", RespFrame::Integer(keys.step)),
+                        ("limit", RespFrame::Integer(0)),
+                    ]),
+                ),
+            ]),
+        ),
+    ]);
+    RespArray::new(vec![key_spec]).into()
+}
+
+/// COMMAND INFO 中一个命令的信息
+/// [名字, arity, flags, 第一个key, 最后一个key, key的间隔, ACL分类, tips, key specs, 子命令]
+fn command_info(spec: &CommandSpec) -> RespFrame {
+    RespArray::new(vec![
+        BulkString::new(spec.name).into(),
+        RespFrame::Integer(spec.arity),
+        status_set(spec.flags.iter().map(|f| f.to_string())),
+        RespFrame::Integer(spec.keys.first),
+        RespFrame::Integer(spec.keys.last),
+        RespFrame::Integer(spec.keys.step),
+        status_set(spec.categories.iter().map(|c| format!("@{}", c))),
+        RespArray::new(vec![]).into(),
+        key_specs(spec),
+        RespArray::new(
+            spec.subcommands
+                .iter()
+                .map(command_info)
+                .collect::<Vec<_>>(),
+        )
+        .into(),
+    ])
+    .into()
+}
+
+/// COMMAND DOCS 中一个命令的文档，子命令使用父命令的分组
+fn command_docs(spec: &CommandSpec, group: &'static str) -> RespFrame {
+    let mut docs = vec![
+        ("summary", BulkString::new(spec.summary).into()),
+        ("since", BulkString::new(spec.since).into()),
+        ("group", BulkString::new(group).into()),
+    ];
+    if !spec.subcommands.is_empty() {
+        let mut subcommands = RespMap::default();
+        for sub in spec.subcommands {
+            subcommands.insert(sub.name.to_string(), command_docs(sub, group));
+        }
+        docs.push(("subcommands", subcommands.into()));
+    }
+    map(docs)
+}
+
+fn list_commands(commands: &CommandRegistry, filter: Option<&ListFilter>) -> RespFrame {
+    let specs = commands.all();
+    let names = all_commands(&specs)
+        .filter(|spec| match filter {
+            None => true,
+            // 没有模块
+            Some(ListFilter::Module(_)) => false,
+            Some(ListFilter::AclCat(category)) => spec.categories.contains(&category.as_str()),
+            Some(ListFilter::Pattern(pattern)) => {
+                glob_match(pattern.as_bytes(), spec.name.as_bytes(), true)
+            }
+        })
+        .map(|spec| BulkString::new(spec.name).into())
+        .collect::<Vec<RespFrame>>();
+    RespArray::new(names).into()
+}
+
+/// COMMAND GETKEYS 请求中的key
+fn get_keys(commands: &CommandRegistry, args: &RespArray) -> RespFrame {
+    let Some(spec) = commands.spec(args) else {
+        return ErrorCode::Err.reply("Invalid command specified");
+    };
+    if !spec.check_arity(args.len()) {
+        return ErrorCode::Err.reply("Invalid number of arguments specified for command");
+    }
+    if !spec.has_keys() {
+        return ErrorCode::Err.reply("The command has no key arguments");
+    }
+    let keys = spec
+        .keys(args)
+        .into_iter()
+        .map(|key| BulkString::new(key).into())
+        .collect::<Vec<RespFrame>>();
+    RespArray::new(keys).into()
+}
+
+impl CommandExecutor for Commands {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let commands = backend.commands();
+        match self.sub {
+            CommandsSubcommand::Info(names) if names.is_empty() => {
+                RespArray::new(commands.all().iter().map(command_info).collect::<Vec<_>>()).into()
+            }
+            CommandsSubcommand::Info(names) => RespArray::new(
+                names
+                    .iter()
+                    .map(|name| match lookup(commands, name) {
+                        Some(spec) => command_info(&spec),
+                        None => RespNull.into(),
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
+            CommandsSubcommand::Count => RespFrame::Integer(commands.all().len() as i64),
+            CommandsSubcommand::Docs(names) => {
+                let mut docs = RespMap::default();
+                let specs: Vec<CommandSpec> = if names.is_empty() {
+                    commands.all()
+                } else {
+                    names
+                        .iter()
+                        .filter_map(|name| lookup(commands, name))
+                        .collect()
+                };
+                for spec in specs {
+                    // 子命令的分组和父命令一样
+                    let parent = spec.name.split('|').next().unwrap_or_default();
+                    let group = commands
+                        .lookup(parent.as_bytes())
+                        .map(|parent| parent.group)
+                        .unwrap_or(spec.group);
+                    docs.insert(spec.name.to_string(), command_docs(&spec, group));
+                }
+                docs.into()
+            }
+            CommandsSubcommand::List(filter) => list_commands(commands, filter.as_ref()),
+            CommandsSubcommand::GetKeys(args) => get_keys(commands, &args),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Commands {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let mut args = extract_args(value, 1)?.into_iter();
+        let Some(name) = args.next() else {
+            return Ok(Commands {
+                sub: CommandsSubcommand::Info(vec![]),
+            });
+        };
+        let name = frame_to_string(name)?.to_ascii_lowercase();
+        let syntax_error = || CommandError::InvalidArgument("syntax error".to_string());
+        let sub = match name.as_str() {
+            "getkeys" => CommandsSubcommand::GetKeys(RespArray::new(args.collect::<Vec<_>>())),
+            "count" | "info" | "docs" | "list" => {
+                let args = args
+                    .map(frame_to_string)
+                    .collect::<Result<Vec<String>, _>>()?;
+                match (name.as_str(), args.as_slice()) {
+                    ("count", []) => CommandsSubcommand::Count,
+                    ("count", _) => {
+                        return Err(CommandError::WrongArity("command|count".to_string()))
+                    }
+                    ("info", _) => CommandsSubcommand::Info(
+                        args.iter().map(|name| name.to_ascii_lowercase()).collect(),
+                    ),
+                    ("docs", _) => CommandsSubcommand::Docs(
+                        args.iter().map(|name| name.to_ascii_lowercase()).collect(),
+                    ),
+                    ("list", []) => CommandsSubcommand::List(None),
+                    ("list", [filterby, kind, value])
+                        if filterby.eq_ignore_ascii_case("filterby") =>
+                    {
+                        let filter = match kind.to_ascii_lowercase().as_str() {
+                            "module" => ListFilter::Module(value.clone()),
+                            "aclcat" => ListFilter::AclCat(value.to_ascii_lowercase()),
+                            "pattern" => ListFilter::Pattern(value.clone()),
+                            _ => return Err(syntax_error()),
+                        };
+                        CommandsSubcommand::List(Some(filter))
+                    }
+                    _ => return Err(syntax_error()),
+                }
+            }
+            _ => {
+                return Err(CommandError::UnknownSubcommand {
+                    cmd: "COMMAND".to_string(),
+                    sub: name,
+                })
+            }
+        };
+        Ok(Commands { sub })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::COMMAND_TABLE;
+    use anyhow::Result;
+
+    fn cmd(args: &[&str]) -> Result<Commands, CommandError> {
+        Commands::try_from(RespArray::new(
+            args.iter()
+                .map(|arg| BulkString::new(*arg).into())
+                .collect::<Vec<RespFrame>>(),
+        ))
+    }
+
+    #[test]
+    fn test_command_info() -> Result<()> {
+        let backend = Backend::new();
+        let RespFrame::Array(all) = cmd(&["command"])?.execute(&backend) else {
+            panic!("COMMAND should return an array");
+        };
+        assert_eq!(all.len(), COMMAND_TABLE.len());
+        assert_eq!(
+            cmd(&["command", "count"])?.execute(&backend),
+            RespFrame::Integer(COMMAND_TABLE.len() as i64)
+        );
+
+        let RespFrame::Array(infos) =
+            cmd(&["command", "info", "GET", "config|get", "nosuch"])?.execute(&backend)
+        else {
+            panic!("COMMAND INFO should return an array");
+        };
+        let RespFrame::Array(get) = &infos[0] else {
+            panic!("expect array, got {:?}", infos[0]);
+        };
+        assert_eq!(get[0], BulkString::new("get").into());
+        assert_eq!(get[1], RespFrame::Integer(2));
+        assert_eq!(
+            get[2],
+            status_set(["readonly".to_string(), "fast".to_string()])
+        );
+        assert_eq!(get[3..6], vec![RespFrame::Integer(1); 3]);
+        let RespFrame::Array(config_get) = &infos[1] else {
+            panic!("expect array, got {:?}", infos[1]);
+        };
+        assert_eq!(config_get[0], BulkString::new("config|get").into());
+        assert_eq!(config_get[1], RespFrame::Integer(-3));
+        assert_eq!(infos[2], RespNull.into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_getkeys_and_list() -> Result<()> {
+        let backend = Backend::new();
+        assert_eq!(
+            cmd(&["command", "getkeys", "del", "a", "b"])?.execute(&backend),
+            RespArray::new(vec![
+                BulkString::new("a").into(),
+                BulkString::new("b").into()
+            ])
+            .into()
+        );
+        let ret = cmd(&["command", "getkeys", "ping"])?.execute(&backend);
+        assert!(matches!(ret, RespFrame::Error(e) if e.contains("no key arguments")));
+        let ret = cmd(&["command", "getkeys", "get"])?.execute(&backend);
+        assert!(matches!(ret, RespFrame::Error(e) if e.contains("Invalid number of arguments")));
+
+        assert_eq!(
+            cmd(&["command", "list", "filterby", "pattern", "h*get*"])?.execute(&backend),
+            RespArray::new(vec![
+                BulkString::new("hget").into(),
+                BulkString::new("hmget").into(),
+                BulkString::new("hgetall").into(),
+            ])
+            .into()
+        );
+        let RespFrame::Array(names) =
+            cmd(&["command", "list", "FILTERBY", "ACLCAT", "hash"])?.execute(&backend)
+        else {
+            panic!("COMMAND LIST should return an array");
+        };
+        assert_eq!(names.len(), 4);
+        assert!(cmd(&["command", "list", "filterby", "foo", "bar"]).is_err());
+        assert!(cmd(&["command", "foo"]).is_err());
+
+        let RespFrame::Map(docs) = cmd(&["command", "docs", "config"])?.execute(&backend) else {
+            panic!("COMMAND DOCS should return a map");
+        };
+        let RespFrame::Map(config) = &docs["config"] else {
+            panic!("expect map");
+        };
+        assert_eq!(config["group"], BulkString::new("server").into());
+        assert!(
+            matches!(&config["subcommands"], RespFrame::Map(subs) if subs.contains_key("config|get"))
+        );
+        Ok(())
+    }
+}
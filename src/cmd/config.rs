@@ -0,0 +1,122 @@
+use crate::{config, Backend, BulkString, RespArray, RespFrame, RespMap};
+
+use super::{extract_args, frame_to_string, CommandError, CommandExecutor, ErrorCode, RESP_OK};
+
+/// CONFIG 的子命令
+#[derive(Debug, PartialEq)]
+enum ConfigSubcommand {
+    Get(Vec<String>),
+    Set(Vec<(String, String)>),
+    Rewrite,
+    ResetStat,
+}
+
+/// Config 命令 config get|set|rewrite|resetstat [args]
+#[derive(Debug, PartialEq)]
+pub struct Config {
+    sub: ConfigSubcommand,
+}
+
+impl CommandExecutor for Config {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let ret = match self.sub {
+            // RESP2的连接收到的是key value交替的数组
+            ConfigSubcommand::Get(patterns) => {
+                let mut map = RespMap::default();
+                for (k, v) in config::config_get(backend, &patterns) {
+                    map.insert(k, BulkString::new(v).into());
+                }
+                return map.into();
+            }
+            ConfigSubcommand::Set(pairs) => config::config_set(backend, pairs),
+            ConfigSubcommand::Rewrite => config::config_rewrite(backend),
+            ConfigSubcommand::ResetStat => {
+                backend.stats().reset();
+                Ok(())
+            }
+        };
+        match ret {
+            Ok(()) => RESP_OK.clone(),
+            Err(e) => ErrorCode::Err.reply(e),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Config {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        super::validate_command(&value, &["config"], 1)?;
+
+        let mut args = extract_args(value, 1)?
+            .into_iter()
+            .map(frame_to_string)
+            .collect::<Result<Vec<String>, CommandError>>()?
+            .into_iter();
+        let name = args.next().unwrap_or_default().to_ascii_lowercase();
+        let args: Vec<String> = args.collect();
+        let wrong_args = || CommandError::WrongArity(format!("config|{}", name));
+        let sub = match name.as_str() {
+            "get" if !args.is_empty() => ConfigSubcommand::Get(args),
+            "set" if !args.is_empty() && args.len().is_multiple_of(2) => ConfigSubcommand::Set(
+                args.chunks(2)
+                    .map(|pair| (pair[0].clone(), pair[1].clone()))
+                    .collect(),
+            ),
+            "rewrite" if args.is_empty() => ConfigSubcommand::Rewrite,
+            "resetstat" if args.is_empty() => ConfigSubcommand::ResetStat,
+            "get" | "set" | "rewrite" | "resetstat" => return Err(wrong_args()),
+            _ => {
+                return Err(CommandError::UnknownSubcommand {
+                    cmd: "CONFIG".to_string(),
+                    sub: name,
+                })
+            }
+        };
+        Ok(Config { sub })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    fn cmd(args: &[&str]) -> Result<Config, CommandError> {
+        Config::try_from(RespArray::new(
+            args.iter()
+                .map(|arg| BulkString::new(*arg).into())
+                .collect::<Vec<RespFrame>>(),
+        ))
+    }
+
+    #[test]
+    fn test_config_command() -> Result<()> {
+        let backend = Backend::new();
+        let ret = cmd(&["config", "SET", "appendfsync", "no", "save", "10 1"])?.execute(&backend);
+        assert_eq!(ret, RESP_OK.clone());
+
+        let ret = cmd(&["config", "get", "appendfs*", "save"])?.execute(&backend);
+        let mut expected = RespMap::default();
+        expected.insert("save".to_string(), BulkString::new("10 1").into());
+        expected.insert("appendfsync".to_string(), BulkString::new("no").into());
+        assert_eq!(ret, expected.into());
+
+        let ret = cmd(&["config", "set", "port", "7000"])?.execute(&backend);
+        assert!(matches!(ret, RespFrame::Error(e) if e.contains("can't set immutable config")));
+        let ret = cmd(&["config", "rewrite"])?.execute(&backend);
+        assert!(matches!(ret, RespFrame::Error(e) if e.contains("without a config file")));
+
+        backend.stats().incr_commands_processed();
+        assert_eq!(
+            cmd(&["config", "resetstat"])?.execute(&backend),
+            RESP_OK.clone()
+        );
+        assert_eq!(backend.stats().commands_processed(), 0);
+
+        assert!(cmd(&["config", "set", "save"]).is_err());
+        assert!(cmd(&["config", "get"]).is_err());
+        assert!(cmd(&["config", "foo"]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,265 @@
+//! 库的使用者注册的自定义命令
+//!
+//! 自定义命令和内置命令一样通过 `TryFrom<RespArray>` 解析，解析结果实现 [`CustomExecutor`]，
+//! 执行时可以访问Backend和发送命令的连接。注册表由Backend持有，分发时先查找内置命令再查找自定义命令，
+//! COMMAND、ACL和集群的key检查都会用到注册的命令描述。
+
+use std::{fmt, sync::RwLock};
+
+use thiserror::Error;
+
+use crate::{acl::CATEGORIES, clients::ClientInfo, Backend, RespArray, RespFrame};
+
+use super::{
+    table::{lookup_command, CommandSpec, COMMAND_TABLE},
+    Command, CommandError, CommandExecutor,
+};
+
+/// 自定义命令执行时的上下文
+#[derive(Debug)]
+pub struct CommandContext<'a> {
+    pub backend: &'a Backend,
+    /// 发送命令的客户端，加载AOF和执行复制流时为None
+    pub client: Option<&'a ClientInfo>,
+}
+
+/// 自定义命令的执行器
+pub trait CustomExecutor: fmt::Debug + Send {
+    /// 执行命令
+    fn execute(self: Box<Self>, ctx: &CommandContext) -> RespFrame;
+}
+
+/// 解析好的自定义命令
+#[derive(Debug)]
+pub struct Custom(Box<dyn CustomExecutor>);
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum RegisterError {
+    #[error("command '{0}' already exists")]
+    AlreadyExists(String),
+    #[error("invalid command name '{0}'")]
+    InvalidName(String),
+    #[error("invalid arity for command '{0}'")]
+    InvalidArity(String),
+    #[error("unknown ACL category '{0}'")]
+    UnknownCategory(String),
+}
+
+impl Custom {
+    /// 执行命令，client为发送命令的客户端，不是从客户端连接执行时为None
+    pub fn execute_for(self, backend: &Backend, client: Option<&ClientInfo>) -> RespFrame {
+        self.0.execute(&CommandContext { backend, client })
+    }
+}
+
+impl CommandExecutor for Custom {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        self.execute_for(backend, None)
+    }
+}
+
+fn parse_custom<T>(args: RespArray) -> Result<Command, CommandError>
+where
+    T: TryFrom<RespArray, Error = CommandError> + CustomExecutor + 'static,
+{
+    Ok(Command::Custom(Custom(Box::new(T::try_from(args)?))))
+}
+
+/// 命令名只能是小写字母、数字和 `.-_:`
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b".-_:".contains(&b))
+}
+
+fn validate(spec: &CommandSpec) -> Result<(), RegisterError> {
+    if !is_valid_name(spec.name) {
+        return Err(RegisterError::InvalidName(spec.name.to_string()));
+    }
+    if spec.arity == 0 {
+        return Err(RegisterError::InvalidArity(spec.name.to_string()));
+    }
+    for sub in spec.subcommands {
+        let valid = sub
+            .name
+            .strip_prefix(spec.name)
+            .and_then(|sub| sub.strip_prefix('|'))
+            .is_some_and(is_valid_name);
+        if !valid {
+            return Err(RegisterError::InvalidName(sub.name.to_string()));
+        }
+        if sub.arity.abs() < 2 {
+            return Err(RegisterError::InvalidArity(sub.name.to_string()));
+        }
+    }
+    let categories = std::iter::once(spec)
+        .chain(spec.subcommands)
+        .flat_map(|spec| spec.categories);
+    for category in categories {
+        if !CATEGORIES.contains(category) {
+            return Err(RegisterError::UnknownCategory(category.to_string()));
+        }
+    }
+    Ok(())
+}
+
+/// 内置命令和注册的自定义命令
+#[derive(Debug, Default)]
+pub struct CommandRegistry {
+    custom: RwLock<Vec<CommandSpec>>,
+}
+
+impl CommandRegistry {
+    /// 注册自定义命令，请求由T解析，解析之前按spec检查参数个数
+    ///
+    /// spec通常由 [`CommandSpec::new`] 创建，再按需设置flags、categories和keys，
+    /// 带write标记的命令会追加到AOF并传播给replica。命令名不能和已有的命令重复。
+    pub fn register<T>(&self, mut spec: CommandSpec) -> Result<(), RegisterError>
+    where
+        T: TryFrom<RespArray, Error = CommandError> + CustomExecutor + 'static,
+    {
+        validate(&spec)?;
+        let mut custom = self.custom.write().unwrap();
+        let exists = COMMAND_TABLE
+            .iter()
+            .chain(custom.iter())
+            .any(|cmd| cmd.name == spec.name);
+        if exists {
+            return Err(RegisterError::AlreadyExists(spec.name.to_string()));
+        }
+        spec.parse = Some(parse_custom::<T>);
+        custom.push(spec);
+        Ok(())
+    }
+
+    /// 按名字查找命令，不区分大小写，内置命令中没有时查找注册的自定义命令
+    pub fn lookup(&self, name: &[u8]) -> Option<CommandSpec> {
+        if let Some(spec) = lookup_command(name) {
+            return Some(*spec);
+        }
+        self.custom
+            .read()
+            .unwrap()
+            .iter()
+            .find(|spec| spec.name.as_bytes().eq_ignore_ascii_case(name))
+            .copied()
+    }
+
+    /// 请求对应的命令
+    pub fn spec(&self, args: &RespArray) -> Option<CommandSpec> {
+        match args.first() {
+            Some(RespFrame::BulkString(name)) => self.lookup(name),
+            _ => None,
+        }
+    }
+
+    /// 请求中包含的key
+    pub fn keys<'a>(&self, args: &'a RespArray) -> Vec<&'a [u8]> {
+        self.spec(args)
+            .map(|spec| spec.keys(args))
+            .unwrap_or_default()
+    }
+
+    /// 把请求解析为命令，包括自定义命令，和 `Command::try_from` 一样只接受数组
+    pub fn parse(&self, frame: impl Into<RespFrame>) -> Result<Command, CommandError> {
+        let RespFrame::Array(args) = frame.into() else {
+            return Err(CommandError::InvalidCommand(
+                "Command must be an array".to_string(),
+            ));
+        };
+        match self.spec(&args) {
+            Some(spec) => spec.parse(args),
+            None => Err(CommandError::unknown_command(&args)),
+        }
+    }
+
+    /// 内置命令和注册的自定义命令，自定义命令按注册顺序排在最后
+    pub fn all(&self) -> Vec<CommandSpec> {
+        COMMAND_TABLE
+            .iter()
+            .chain(self.custom.read().unwrap().iter())
+            .copied()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmd::KeySpec, BulkString};
+
+    #[derive(Debug)]
+    struct Noop;
+
+    impl CustomExecutor for Noop {
+        fn execute(self: Box<Self>, _ctx: &CommandContext) -> RespFrame {
+            RespFrame::Integer(0)
+        }
+    }
+
+    impl TryFrom<RespArray> for Noop {
+        type Error = CommandError;
+
+        fn try_from(_value: RespArray) -> Result<Self, Self::Error> {
+            Ok(Noop)
+        }
+    }
+
+    #[test]
+    fn test_register_command() {
+        let commands = CommandRegistry::default();
+        let mut spec = CommandSpec::new("test.noop", -2);
+        spec.categories = &["read", "fast"];
+        spec.keys = KeySpec {
+            first: 1,
+            last: -1,
+            step: 1,
+        };
+        assert_eq!(commands.register::<Noop>(spec), Ok(()));
+        assert_eq!(
+            commands.register::<Noop>(CommandSpec::new("test.noop", 1)),
+            Err(RegisterError::AlreadyExists("test.noop".to_string()))
+        );
+        assert_eq!(
+            commands.register::<Noop>(CommandSpec::new("get", 2)),
+            Err(RegisterError::AlreadyExists("get".to_string()))
+        );
+        assert_eq!(
+            commands.register::<Noop>(CommandSpec::new("Test.Upper", 1)),
+            Err(RegisterError::InvalidName("Test.Upper".to_string()))
+        );
+        assert_eq!(
+            commands.register::<Noop>(CommandSpec::new("test.zero", 0)),
+            Err(RegisterError::InvalidArity("test.zero".to_string()))
+        );
+        let mut spec = CommandSpec::new("test.category", 1);
+        spec.categories = &["nosuch"];
+        assert_eq!(
+            commands.register::<Noop>(spec),
+            Err(RegisterError::UnknownCategory("nosuch".to_string()))
+        );
+
+        // 内置命令之后查找，不区分大小写
+        let spec = commands.lookup(b"TEST.NOOP").unwrap();
+        assert_eq!(spec.name, "test.noop");
+        let args = RespArray::new(vec![
+            BulkString::new("test.noop").into(),
+            BulkString::new("a").into(),
+            BulkString::new("b").into(),
+        ]);
+        assert_eq!(spec.keys(&args), vec![&b"a"[..], &b"b"[..]]);
+        assert_eq!(commands.keys(&args), vec![&b"a"[..], &b"b"[..]]);
+        assert_eq!(commands.all().len(), COMMAND_TABLE.len() + 1);
+        // 只有注册的Registry能解析
+        assert!(Command::try_from(args.clone()).is_err());
+        assert!(CommandRegistry::default().parse(args.clone()).is_err());
+        let cmd = commands.parse(args).unwrap();
+        assert!(matches!(cmd, Command::Custom(_)));
+        assert_eq!(cmd.execute(&Backend::new()), RespFrame::Integer(0));
+        assert!(matches!(
+            commands.parse(RespArray::new(vec![BulkString::new("test.noop").into()])),
+            Err(CommandError::WrongArity(name)) if name == "test.noop"
+        ));
+    }
+}
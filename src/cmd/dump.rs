@@ -0,0 +1,279 @@
+use crate::{now_ms, persistence, Backend, BulkString, RespArray, RespFrame, RespNull};
+
+use super::{
+    extract_args, frame_to_i64, frame_to_string, validate_command, CommandError, CommandExecutor,
+    ErrorCode, RESP_OK,
+};
+
+/// Dump 命令  dump key
+#[derive(Debug)]
+pub struct Dump {
+    key: String,
+}
+
+impl CommandExecutor for Dump {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match backend.get_value(&self.key) {
+            Some(value) => BulkString::new(persistence::dump_value(&value)).into(),
+            None => RespNull.into(),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Dump {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["dump"], 1)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        match args.next() {
+            Some(key) => Ok(Dump {
+                key: frame_to_string(key)?,
+            }),
+            _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
+        }
+    }
+}
+
+/// Restore 命令
+/// restore key ttl serialized-value [REPLACE] [ABSTTL] [IDLETIME seconds] [FREQ frequency]
+#[derive(Debug, PartialEq)]
+pub struct Restore {
+    key: String,
+    ttl: i64,
+    payload: Vec<u8>,
+    replace: bool,
+    absttl: bool,
+    /// 没有LRU/LFU淘汰策略，IDLETIME和FREQ只做校验
+    idletime: Option<i64>,
+    freq: Option<i64>,
+}
+
+impl CommandExecutor for Restore {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if !self.replace && backend.exists(&self.key) {
+            return ErrorCode::BusyKey.reply("Target key name already exists.");
+        }
+
+        let value = match persistence::restore_value(&self.payload) {
+            Ok(value) => value,
+            Err(_) => return ErrorCode::Err.reply("DUMP payload version or checksum are wrong"),
+        };
+
+        let expire_at = match (self.ttl, self.absttl) {
+            (0, _) => None,
+            (ttl, true) => Some(ttl),
+            (ttl, false) => match now_ms().checked_add(ttl) {
+                Some(at) => Some(at),
+                None => return ErrorCode::Err.reply("invalid expire time in 'restore' command"),
+            },
+        };
+
+        // 绝对过期时间已经过去，相当于写入后立即过期
+        if matches!(expire_at, Some(at) if at <= now_ms()) {
+            backend.del(&self.key);
+            return RESP_OK.clone();
+        }
+
+        backend.put_value(self.key, value, expire_at);
+        RESP_OK.clone()
+    }
+}
+
+impl TryFrom<RespArray> for Restore {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        // MIGRATE发送的RESTORE-ASKING与RESTORE相同，只是可以访问正在迁入的slot
+        let name = match value.first() {
+            Some(RespFrame::BulkString(cmd)) if cmd.eq_ignore_ascii_case(b"restore-asking") => {
+                "restore-asking"
+            }
+            _ => "restore",
+        };
+        validate_command(&value, &[name], 3)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let key = frame_to_string(args.next().unwrap_or(RespNull.into()))?;
+        let ttl = frame_to_i64(args.next().unwrap_or(RespNull.into()))?;
+        if ttl < 0 {
+            return Err(CommandError::InvalidArgument(
+                "Invalid TTL value, must be >= 0".to_string(),
+            ));
+        }
+        let payload = match args.next() {
+            Some(RespFrame::BulkString(payload)) => payload.into_vec(),
+            _ => {
+                return Err(CommandError::InvalidArgument(
+                    "Invalid serialized value".to_string(),
+                ))
+            }
+        };
+
+        let mut restore = Restore {
+            key,
+            ttl,
+            payload,
+            replace: false,
+            absttl: false,
+            idletime: None,
+            freq: None,
+        };
+
+        while let Some(arg) = args.next() {
+            match frame_to_string(arg)?.to_ascii_lowercase().as_str() {
+                "replace" => restore.replace = true,
+                "absttl" => restore.absttl = true,
+                "idletime" if restore.freq.is_none() => {
+                    let idletime = frame_to_i64(args.next().unwrap_or(RespNull.into()))?;
+                    if idletime < 0 {
+                        return Err(CommandError::InvalidArgument(
+                            "Invalid IDLETIME value, must be >= 0".to_string(),
+                        ));
+                    }
+                    restore.idletime = Some(idletime);
+                }
+                "freq" if restore.idletime.is_none() => {
+                    let freq = frame_to_i64(args.next().unwrap_or(RespNull.into()))?;
+                    if !(0..=255).contains(&freq) {
+                        return Err(CommandError::InvalidArgument(
+                            "Invalid FREQ value, must be >= 0 and <= 255".to_string(),
+                        ));
+                    }
+                    restore.freq = Some(freq);
+                }
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            }
+        }
+
+        Ok(restore)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BackendValue, SimpleError};
+    use anyhow::Result;
+
+    fn restore_args(key: &str, ttl: &str, payload: Vec<u8>, options: &[&str]) -> RespArray {
+        let mut args: Vec<RespFrame> = vec![
+            BulkString::new("restore").into(),
+            BulkString::new(key).into(),
+            BulkString::new(ttl).into(),
+            BulkString::new(payload).into(),
+        ];
+        args.extend(options.iter().map(|o| BulkString::new(*o).into()));
+        RespArray::new(args)
+    }
+
+    #[test]
+    fn test_dump_restore() -> Result<()> {
+        let backend = Backend::new();
+        backend.hset("map".to_string(), "a".to_string(), RespFrame::Integer(1));
+
+        let payload = match Dump::try_from(RespArray::new(vec![
+            BulkString::new("dump").into(),
+            BulkString::new("map").into(),
+        ]))?
+        .execute(&backend)
+        {
+            RespFrame::BulkString(payload) => payload.into_vec(),
+            frame => panic!("unexpected frame: {:?}", frame),
+        };
+
+        // 目标存在时返回BUSYKEY
+        let cmd = Restore::try_from(restore_args("map", "0", payload.clone(), &[]))?;
+        assert_eq!(
+            cmd.execute(&backend),
+            SimpleError::new("BUSYKEY Target key name already exists.").into()
+        );
+
+        // 复制到另一个实例
+        let other = Backend::new();
+        let cmd = Restore::try_from(restore_args("map", "10000", payload.clone(), &[]))?;
+        assert_eq!(cmd.execute(&other), RESP_OK.clone());
+        assert_eq!(other.get_value("map"), backend.get_value("map"));
+        assert!(other.expire_at("map").is_some());
+
+        // 再次DUMP得到完全相同的字节
+        let again = Dump {
+            key: "map".to_string(),
+        }
+        .execute(&other);
+        assert_eq!(again, BulkString::new(payload.clone()).into());
+
+        // REPLACE覆盖已有的key
+        backend.set("hello".to_string(), BulkString::new("world").into());
+        let cmd = Restore::try_from(restore_args("hello", "0", payload, &["REPLACE"]))?;
+        assert_eq!(cmd.execute(&backend), RESP_OK.clone());
+        assert_eq!(backend.get("hello"), None);
+        assert!(matches!(
+            backend.get_value("hello"),
+            Some(BackendValue::Hash(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_absttl() -> Result<()> {
+        let backend = Backend::new();
+        let payload =
+            persistence::dump_value(&BackendValue::String(BulkString::new("world").into()));
+
+        let at = (now_ms() + 10_000).to_string();
+        let cmd = Restore::try_from(restore_args("hello", &at, payload.clone(), &["ABSTTL"]))?;
+        assert_eq!(cmd.execute(&backend), RESP_OK.clone());
+        assert_eq!(backend.expire_at("hello"), Some(at.parse()?));
+
+        // 过期时间已经过去的key不会被创建
+        let cmd = Restore::try_from(restore_args("gone", "1", payload.clone(), &["ABSTTL"]))?;
+        assert_eq!(cmd.execute(&backend), RESP_OK.clone());
+        assert!(!backend.exists("gone"));
+
+        // 相对过期时间溢出
+        let ttl = i64::MAX.to_string();
+        let cmd = Restore::try_from(restore_args("overflow", &ttl, payload, &[]))?;
+        assert_eq!(
+            cmd.execute(&backend),
+            SimpleError::new("ERR invalid expire time in 'restore' command").into()
+        );
+        assert!(!backend.exists("overflow"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_options() -> Result<()> {
+        let payload = b"payload".to_vec();
+
+        let cmd = Restore::try_from(restore_args("k", "0", payload.clone(), &["IDLETIME", "10"]))?;
+        assert_eq!(cmd.idletime, Some(10));
+
+        let cmd = Restore::try_from(restore_args("k", "0", payload.clone(), &["FREQ", "5"]))?;
+        assert_eq!(cmd.freq, Some(5));
+
+        assert!(Restore::try_from(restore_args(
+            "k",
+            "0",
+            payload.clone(),
+            &["IDLETIME", "10", "FREQ", "5"]
+        ))
+        .is_err());
+        assert!(
+            Restore::try_from(restore_args("k", "0", payload.clone(), &["FREQ", "256"])).is_err()
+        );
+        assert!(Restore::try_from(restore_args("k", "-1", payload.clone(), &[])).is_err());
+
+        // 错误的payload
+        let cmd = Restore::try_from(restore_args("k", "0", payload, &[]))?;
+        assert_eq!(
+            cmd.execute(&Backend::new()),
+            SimpleError::new("ERR DUMP payload version or checksum are wrong").into()
+        );
+
+        Ok(())
+    }
+}
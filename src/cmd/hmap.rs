@@ -1,6 +1,6 @@
-use crate::{Backend, BulkString, RespArray, RespFrame};
+use crate::{Backend, BulkString, KeyType, RespArray, RespFrame, RespNull};
 
-use super::{extract_args, validate_command, CommandError, CommandExecutor, RESP_OK};
+use super::{check_type, extract_args, validate_command, CommandError, CommandExecutor, RESP_OK};
 
 /// HGet Command
 #[derive(Debug)]
@@ -12,6 +12,9 @@ pub struct HGet {
 /// 为HGet实现Executor 实际上就是去Backend中获取数据
 impl CommandExecutor for HGet {
     fn execute(self, backend: &Backend) -> RespFrame {
+        if let Err(e) = check_type(backend, &self.key, KeyType::Hash) {
+            return e.into();
+        }
         match backend.hget(&self.key, &self.field) {
             Some(value) => value,
             None => RespFrame::Null(crate::RespNull),
@@ -29,8 +32,8 @@ impl TryFrom<RespArray> for HGet {
         let mut args = extract_args(value, 1)?.into_iter();
         match (args.next(), args.next()) {
             (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(field))) => Ok(HGet {
-                key: String::from_utf8(key.0)?,
-                field: String::from_utf8(field.0)?,
+                key: String::from_utf8(key.into_vec())?,
+                field: String::from_utf8(field.into_vec())?,
             }),
             _ => Err(CommandError::InvalidArgument(
                 "Invalid key or field".to_string(),
@@ -49,6 +52,9 @@ pub struct HGetAll {
 /// 为HGetAll实现Executor 实际上就是去Backend中获取内部的DashMap
 impl CommandExecutor for HGetAll {
     fn execute(self, backend: &Backend) -> RespFrame {
+        if let Err(e) = check_type(backend, &self.key, KeyType::Hash) {
+            return e.into();
+        }
         let hmap = backend.hgetall(&self.key);
         match hmap {
             Some(hmap) => {
@@ -84,7 +90,7 @@ impl TryFrom<RespArray> for HGetAll {
         let mut args = extract_args(value, 1)?.into_iter();
         match args.next() {
             Some(RespFrame::BulkString(key)) => Ok(HGetAll {
-                key: String::from_utf8(key.0)?,
+                key: String::from_utf8(key.into_vec())?,
                 sort: false,
             }),
             _ => Err(CommandError::InvalidArgument("Invalid key".to_string())),
@@ -102,6 +108,9 @@ pub struct HSet {
 /// 为HSet实现Executor 实际上就是去Backend中设置数据
 impl CommandExecutor for HSet {
     fn execute(self, backend: &Backend) -> RespFrame {
+        if let Err(e) = check_type(backend, &self.key, KeyType::Hash) {
+            return e.into();
+        }
         backend.hset(self.key, self.field, self.value);
         RESP_OK.clone()
     }
@@ -118,8 +127,8 @@ impl TryFrom<RespArray> for HSet {
         match (args.next(), args.next(), args.next()) {
             (Some(RespFrame::BulkString(key)), Some(RespFrame::BulkString(field)), Some(value)) => {
                 Ok(HSet {
-                    key: String::from_utf8(key.0)?,
-                    field: String::from_utf8(field.0)?,
+                    key: String::from_utf8(key.into_vec())?,
+                    field: String::from_utf8(field.into_vec())?,
                     value,
                 })
             }
@@ -139,9 +148,13 @@ pub struct HMGet {
 
 impl CommandExecutor for HMGet {
     fn execute(self, backend: &Backend) -> RespFrame {
+        if let Err(e) = check_type(backend, &self.key, KeyType::Hash) {
+            return e.into();
+        }
         match backend.hmget(&self.key, &self.fields) {
             Some(values) => RespArray::new(values).into(),
-            None => RespArray::new(vec![]).into(),
+            // key不存在时每个field都返回null
+            None => RespArray::new(vec![RespNull.into(); self.fields.len()]).into(),
         }
     }
 }
@@ -158,13 +171,15 @@ impl TryFrom<RespArray> for HMGet {
                 // 剩余的数据就是fields
                 let fields = args
                     .map(|frame| match frame {
-                        RespFrame::BulkString(field) => String::from_utf8(field.0).unwrap(),
+                        RespFrame::BulkString(field) => {
+                            String::from_utf8(field.into_vec()).unwrap()
+                        }
                         _ => "".to_string(),
                     })
                     .collect();
 
                 Ok(HMGet {
-                    key: String::from_utf8(key.0)?,
+                    key: String::from_utf8(key.into_vec())?,
                     fields,
                 })
             }
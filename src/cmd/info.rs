@@ -0,0 +1,157 @@
+use crate::{Backend, BulkString, RespArray, RespFrame};
+
+use super::{extract_args, frame_to_string, validate_command, CommandError, CommandExecutor};
+
+/// 支持的INFO章节
+const SECTIONS: &[&str] = &[
+    "server",
+    "clients",
+    "persistence",
+    "stats",
+    "replication",
+    "cluster",
+];
+
+/// Info 命令 info [section ...]
+#[derive(Debug)]
+pub struct Info {
+    sections: Vec<String>,
+}
+
+/// INFO server 的内容
+fn server_info(backend: &Backend) -> String {
+    let config = backend.config();
+    let lines = [
+        "# Server".to_string(),
+        format!(
+            "redis_mode:{}",
+            if backend.cluster().is_enabled() {
+                "cluster"
+            } else {
+                "standalone"
+            }
+        ),
+        format!("process_id:{}", std::process::id()),
+        format!("tcp_port:{}", config.current().port),
+        format!(
+            "config_file:{}",
+            config
+                .file()
+                .map(|path| path.display().to_string())
+                .unwrap_or_default()
+        ),
+    ];
+    lines.join("\r\n") + "\r\n"
+}
+
+/// INFO clients 的内容
+fn clients_info(backend: &Backend) -> String {
+    let lines = [
+        "# Clients".to_string(),
+        format!(
+            "connected_clients:{}",
+            backend.clients().connected_clients()
+        ),
+        format!("maxclients:{}", backend.config().read(|c| c.maxclients)),
+    ];
+    lines.join("\r\n") + "\r\n"
+}
+
+/// INFO persistence 的内容
+fn persistence_info(backend: &Backend) -> String {
+    let snapshot = backend.snapshot();
+    let aof = backend.aof();
+    let lines = [
+        "# Persistence".to_string(),
+        format!("loading:{}", snapshot.is_loading() as u8),
+        format!("rdb_changes_since_last_save:{}", backend.dirty()),
+        format!(
+            "rdb_bgsave_in_progress:{}",
+            snapshot.is_bgsave_in_progress() as u8
+        ),
+        format!("rdb_last_save_time:{}", snapshot.last_save()),
+        format!(
+            "rdb_last_bgsave_status:{}",
+            if snapshot.last_bgsave_ok() {
+                "ok"
+            } else {
+                "err"
+            }
+        ),
+        format!("aof_enabled:{}", aof.is_enabled() as u8),
+        format!(
+            "aof_rewrite_in_progress:{}",
+            aof.is_rewrite_in_progress() as u8
+        ),
+    ];
+    lines.join("\r\n") + "\r\n"
+}
+
+impl CommandExecutor for Info {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let all = self.sections.is_empty()
+            || self
+                .sections
+                .iter()
+                .any(|s| matches!(s.as_str(), "all" | "default" | "everything"));
+
+        let sections: Vec<String> = SECTIONS
+            .iter()
+            .filter(|s| all || self.sections.iter().any(|name| name == *s))
+            .map(|s| match *s {
+                "server" => server_info(backend),
+                "clients" => clients_info(backend),
+                "persistence" => persistence_info(backend),
+                "stats" => backend.stats().info(),
+                "replication" => backend.replication().info(),
+                _ => backend.cluster().info(),
+            })
+            .collect();
+        BulkString::new(sections.join("\r\n")).into()
+    }
+}
+
+impl TryFrom<RespArray> for Info {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["info"], 0)?;
+
+        let sections = extract_args(value, 1)?
+            .into_iter()
+            .map(|arg| Ok(frame_to_string(arg)?.to_ascii_lowercase()))
+            .collect::<Result<_, CommandError>>()?;
+        Ok(Info { sections })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_info() -> Result<()> {
+        let backend = Backend::new();
+        let cmd = Info::try_from(RespArray::new(vec![
+            BulkString::new("info").into(),
+            BulkString::new("Replication").into(),
+        ]))?;
+        let RespFrame::BulkString(info) = cmd.execute(&backend) else {
+            panic!("bulk string expected");
+        };
+        let info = String::from_utf8(info.into_vec())?;
+        assert!(info.starts_with("# Replication\r\nrole:master\r\n"));
+        assert!(!info.contains("# Persistence"));
+
+        let cmd = Info::try_from(RespArray::new(vec![BulkString::new("info").into()]))?;
+        let RespFrame::BulkString(info) = cmd.execute(&backend) else {
+            panic!("bulk string expected");
+        };
+        let info = String::from_utf8(info.into_vec())?;
+        assert!(info.contains("# Persistence"));
+        assert!(info.contains("# Replication"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+use crate::{Backend, RespArray, RespFrame};
+
+use super::{extract_args, frame_to_string, validate_command, CommandError, CommandExecutor};
+
+/// Del 命令 del key [key ...]，返回删除的key数量
+#[derive(Debug, PartialEq)]
+pub struct Del {
+    keys: Vec<String>,
+}
+
+impl CommandExecutor for Del {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let count = self
+            .keys
+            .iter()
+            .filter(|key| {
+                backend.expire_if_needed(key);
+                backend.del(key)
+            })
+            .count();
+        RespFrame::Integer(count as i64)
+    }
+}
+
+impl TryFrom<RespArray> for Del {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["del"], 1)?;
+
+        let keys = extract_args(value, 1)?
+            .into_iter()
+            .map(frame_to_string)
+            .collect::<Result<_, _>>()?;
+        Ok(Del { keys })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BulkString;
+    use anyhow::Result;
+
+    #[test]
+    fn test_del() -> Result<()> {
+        let backend = Backend::new();
+        backend.set("a".to_string(), BulkString::new("1").into());
+        backend.hset("b".to_string(), "f".to_string(), RespFrame::Integer(1));
+
+        let cmd = Del::try_from(RespArray::new(vec![
+            BulkString::new("del").into(),
+            BulkString::new("a").into(),
+            BulkString::new("b").into(),
+            BulkString::new("c").into(),
+        ]))?;
+        assert_eq!(cmd.execute(&backend), RespFrame::Integer(2));
+        assert!(!backend.exists("a"));
+        assert!(!backend.exists("b"));
+        Ok(())
+    }
+}
@@ -1,6 +1,6 @@
-use crate::{backend::Backend, RespArray, RespFrame};
+use crate::{backend::Backend, KeyType, RespArray, RespFrame};
 
-use super::{extract_args, validate_command, CommandError, CommandExecutor, RESP_OK};
+use super::{check_type, extract_args, validate_command, CommandError, CommandExecutor, RESP_OK};
 
 /// Get Command
 #[derive(Debug)]
@@ -11,6 +11,9 @@ pub struct Get {
 /// 为Get实现Executor 实际上就是去Backend中获取数据
 impl CommandExecutor for Get {
     fn execute(self, backend: &Backend) -> RespFrame {
+        if let Err(e) = check_type(backend, &self.key, KeyType::String) {
+            return e.into();
+        }
         match backend.get(&self.key) {
             Some(v) => v,
             None => RespFrame::Null(crate::RespNull),
@@ -31,7 +34,7 @@ impl TryFrom<RespArray> for Get {
         // 如果成功解析就构建Get
         match args.next() {
             Some(RespFrame::BulkString(key)) => Ok(Get {
-                key: String::from_utf8(key.0)?,
+                key: String::from_utf8(key.into_vec())?,
             }),
             _ => Err(CommandError::InvalidCommand("Missing key".to_string())),
         }
@@ -66,7 +69,7 @@ impl TryFrom<RespArray> for Set {
         // 这里需要解析出2个参数，如果不足或者不为BulkString就返回错误
         match (args.next(), args.next()) {
             (Some(RespFrame::BulkString(key)), Some(value)) => Ok(Set {
-                key: String::from_utf8(key.0)?,
+                key: String::from_utf8(key.into_vec())?,
                 value,
             }),
             _ => Err(CommandError::InvalidCommand(
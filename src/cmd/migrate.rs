@@ -0,0 +1,385 @@
+use std::{
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use bytes::BytesMut;
+use tokio::runtime::{Handle, RuntimeFlavor};
+
+use crate::{
+    now_ms, persistence, Backend, BulkString, RespArray, RespEncode, RespFrame, RespNull,
+    RespParser, SimpleString,
+};
+
+use super::{
+    extract_args, frame_to_i64, frame_to_string, CommandError, CommandExecutor, ErrorCode, RESP_OK,
+};
+
+/// 没有指定超时时间时使用的默认值(毫秒)
+const DEFAULT_TIMEOUT_MS: u64 = 1000;
+
+/// Migrate 命令 将key序列化后通过RESTORE-ASKING写入目标实例，成功后删除本地的key
+/// migrate host port key|"" destination-db timeout [COPY] [REPLACE]
+///     [AUTH password] [AUTH2 username password] [KEYS key [key ...]]
+#[derive(Debug, PartialEq)]
+pub struct Migrate {
+    host: String,
+    port: u16,
+    keys: Vec<String>,
+    timeout_ms: u64,
+    copy: bool,
+    replace: bool,
+    auth: Option<Vec<String>>,
+}
+
+/// 迁移失败的原因
+enum MigrateError {
+    Io(&'static str, io::Error),
+    Target(String),
+}
+
+impl CommandExecutor for Migrate {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        // 不存在的key直接跳过
+        let now = now_ms();
+        let items: Vec<_> = self
+            .keys
+            .iter()
+            .filter_map(|key| {
+                let value = backend.get_value(key)?;
+                let ttl = match backend.expire_at(key) {
+                    Some(at) => (at - now).max(1),
+                    None => 0,
+                };
+                Some((key.clone(), ttl, persistence::dump_value(&value)))
+            })
+            .collect();
+        if items.is_empty() {
+            return SimpleString::new("NOKEY").into();
+        }
+
+        let mut migrated = Vec::new();
+        let ret = blocking(|| self.transfer(&items, &mut migrated));
+
+        // 目标实例已经写入成功的key才能删除
+        if !self.copy && !migrated.is_empty() {
+            let mut del: Vec<RespFrame> = vec![BulkString::new("DEL").into()];
+            for key in migrated {
+                backend.del(key);
+                del.push(BulkString::new(key.as_str()).into());
+            }
+            if let Err(e) = backend.propagate(&RespArray::new(del)) {
+                tracing::warn!("Failed to propagate MIGRATE: {}", e);
+            }
+        }
+
+        match ret {
+            Ok(_) => RESP_OK.clone(),
+            Err(MigrateError::Io(action, e)) => {
+                tracing::warn!("MIGRATE IO error: {}", e);
+                ErrorCode::IoErr.reply(format!("error or timeout {} target instance", action))
+            }
+            Err(MigrateError::Target(e)) => {
+                ErrorCode::Err.reply(format!("Target instance replied with error: {}", e))
+            }
+        }
+    }
+}
+
+impl Migrate {
+    /// 通过阻塞连接发送RESTORE-ASKING，目标实例已经写入的key记录到migrated
+    fn transfer<'a>(
+        &self,
+        items: &'a [(String, i64, Vec<u8>)],
+        migrated: &mut Vec<&'a String>,
+    ) -> Result<(), MigrateError> {
+        let timeout = Duration::from_millis(self.timeout_ms);
+        let mut stream = connect(&self.host, self.port, timeout)
+            .map_err(|e| MigrateError::Io("connecting to", e))?;
+        let mut buf = BytesMut::new();
+
+        // 所有命令一次性写入，再按顺序读取回复，命令名使用小写与命令分发保持一致
+        let mut pipeline = Vec::new();
+        if let Some(auth) = &self.auth {
+            let mut args = vec!["auth".as_bytes()];
+            args.extend(auth.iter().map(|s| s.as_bytes()));
+            pipeline.extend(command(&args));
+        }
+        for (key, ttl, payload) in items {
+            let ttl = ttl.to_string();
+            let mut args = vec![
+                "restore-asking".as_bytes(),
+                key.as_bytes(),
+                ttl.as_bytes(),
+                payload.as_slice(),
+            ];
+            if self.replace {
+                args.push(b"replace");
+            }
+            pipeline.extend(command(&args));
+        }
+        stream
+            .write_all(&pipeline)
+            .map_err(|e| MigrateError::Io("writing to", e))?;
+
+        if self.auth.is_some() {
+            check_reply(read_reply(&mut stream, &mut buf)?)?;
+        }
+        let mut error = None;
+        for (key, _, _) in items {
+            match check_reply(read_reply(&mut stream, &mut buf)?) {
+                Ok(_) => migrated.push(key),
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        error.map_or(Ok(()), Err)
+    }
+}
+
+/// 编码一条命令
+fn command(args: &[&[u8]]) -> Vec<u8> {
+    let args = args
+        .iter()
+        .map(|arg| BulkString::new(arg.to_vec()).into())
+        .collect::<Vec<RespFrame>>();
+    RespArray::new(args).encode()
+}
+
+fn connect(host: &str, port: u16, timeout: Duration) -> io::Result<TcpStream> {
+    let mut last = io::Error::new(io::ErrorKind::NotFound, "no address resolved");
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(timeout))?;
+                stream.set_write_timeout(Some(timeout))?;
+                return Ok(stream);
+            }
+            Err(e) => last = e,
+        }
+    }
+    Err(last)
+}
+
+/// 阻塞读取一个完整的回复
+fn read_reply(stream: &mut TcpStream, buf: &mut BytesMut) -> Result<RespFrame, MigrateError> {
+    let mut chunk = [0u8; 4096];
+    let mut parser = RespParser::default();
+    loop {
+        match parser.parse(buf) {
+            Ok(Some(frame)) => return Ok(frame),
+            Ok(None) => {}
+            Err(e) => return Err(MigrateError::Target(e.to_string())),
+        }
+        let n = stream
+            .read(&mut chunk)
+            .map_err(|e| MigrateError::Io("reading from", e))?;
+        if n == 0 {
+            return Err(MigrateError::Io(
+                "reading from",
+                io::ErrorKind::UnexpectedEof.into(),
+            ));
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+}
+
+fn check_reply(frame: RespFrame) -> Result<(), MigrateError> {
+    match frame {
+        RespFrame::Error(e) => Err(MigrateError::Target(e.0)),
+        _ => Ok(()),
+    }
+}
+
+/// 多线程运行时中阻塞会占用工作线程，需要交给block_in_place
+fn blocking<T>(f: impl FnOnce() -> T) -> T {
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(f)
+        }
+        _ => f(),
+    }
+}
+
+impl TryFrom<RespArray> for Migrate {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        super::validate_command(&value, &["migrate"], 5)?;
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let mut next = || args.next().unwrap_or(RespNull.into());
+        let host = frame_to_string(next())?;
+        let port = u16::try_from(frame_to_i64(next())?)
+            .map_err(|_| CommandError::InvalidArgument("Invalid port".to_string()))?;
+        let key = frame_to_string(next())?;
+        if frame_to_i64(next())? != 0 {
+            return Err(CommandError::InvalidArgument(
+                "Only destination-db 0 is supported".to_string(),
+            ));
+        }
+        let timeout_ms = match frame_to_i64(next())? {
+            t if t <= 0 => DEFAULT_TIMEOUT_MS,
+            t => t as u64,
+        };
+
+        let mut migrate = Migrate {
+            host,
+            port,
+            keys: vec![],
+            timeout_ms,
+            copy: false,
+            replace: false,
+            auth: None,
+        };
+        let mut keys_option = false;
+        loop {
+            let arg = match next() {
+                RespFrame::Null(_) => break,
+                arg => frame_to_string(arg)?,
+            };
+            match arg.to_ascii_lowercase().as_str() {
+                "copy" => migrate.copy = true,
+                "replace" => migrate.replace = true,
+                "auth" => migrate.auth = Some(vec![frame_to_string(next())?]),
+                "auth2" => {
+                    migrate.auth = Some(vec![frame_to_string(next())?, frame_to_string(next())?])
+                }
+                "keys" => {
+                    if !key.is_empty() {
+                        return Err(CommandError::InvalidArgument(
+                            "When using MIGRATE KEYS option, the key argument must be set to the empty string".to_string(),
+                        ));
+                    }
+                    keys_option = true;
+                    loop {
+                        match next() {
+                            RespFrame::Null(_) => break,
+                            key => migrate.keys.push(frame_to_string(key)?),
+                        }
+                    }
+                }
+                _ => return Err(CommandError::InvalidArgument("syntax error".to_string())),
+            }
+        }
+        if !keys_option {
+            migrate.keys.push(key);
+        }
+        Ok(migrate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BackendValue;
+    use anyhow::Result;
+    use tokio::net::TcpListener;
+
+    fn args(items: &[&str]) -> RespArray {
+        RespArray::new(
+            items
+                .iter()
+                .map(|s| BulkString::new(*s).into())
+                .collect::<Vec<RespFrame>>(),
+        )
+    }
+
+    #[test]
+    fn test_migrate_try_from() -> Result<()> {
+        let cmd = Migrate::try_from(args(&[
+            "migrate",
+            "127.0.0.1",
+            "6380",
+            "",
+            "0",
+            "0",
+            "COPY",
+            "AUTH2",
+            "user",
+            "pw",
+            "KEYS",
+            "a",
+            "b",
+        ]))?;
+        assert_eq!(
+            cmd,
+            Migrate {
+                host: "127.0.0.1".to_string(),
+                port: 6380,
+                keys: vec!["a".to_string(), "b".to_string()],
+                timeout_ms: DEFAULT_TIMEOUT_MS,
+                copy: true,
+                replace: false,
+                auth: Some(vec!["user".to_string(), "pw".to_string()]),
+            }
+        );
+
+        assert!(Migrate::try_from(args(&["migrate", "h", "1", "k", "1", "0"])).is_err());
+        assert!(
+            Migrate::try_from(args(&["migrate", "h", "1", "k", "0", "0", "KEYS", "a"])).is_err()
+        );
+        assert!(Migrate::try_from(args(&["migrate", "h", "1", "k", "0", "0", "MOVE"])).is_err());
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_migrate() -> Result<()> {
+        let target = Backend::new();
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port().to_string();
+        let server = target.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(crate::network::stream_handler(stream, server.clone()));
+            }
+        });
+
+        let source = Backend::new();
+        source.set("hello".to_string(), BulkString::new("world").into());
+        source.set_expire_at("hello", now_ms() + 10_000);
+        source.hset("map".to_string(), "a".to_string(), RespFrame::Integer(1));
+
+        // 单个key
+        let ret = Migrate::try_from(args(&["migrate", "127.0.0.1", &port, "hello", "0", "1000"]))?
+            .execute(&source);
+        assert_eq!(ret, RESP_OK.clone());
+        assert!(!source.exists("hello"));
+        assert_eq!(target.get("hello"), Some(BulkString::new("world").into()));
+        assert!(target.expire_at("hello").is_some());
+
+        // COPY保留本地的key，目标已存在时需要REPLACE
+        target.set("map".to_string(), BulkString::new("old").into());
+        let copy = [
+            "migrate",
+            "127.0.0.1",
+            &port,
+            "",
+            "0",
+            "1000",
+            "COPY",
+            "KEYS",
+            "map",
+        ];
+        let ret = Migrate::try_from(args(&copy))?.execute(&source);
+        assert!(matches!(ret, RespFrame::Error(e) if e.0.contains("BUSYKEY")));
+        let mut replace = copy.to_vec();
+        replace.insert(7, "REPLACE");
+        let ret = Migrate::try_from(args(&replace))?.execute(&source);
+        assert_eq!(ret, RESP_OK.clone());
+        assert!(source.exists("map"));
+        assert_eq!(target.get_value("map"), source.get_value("map"));
+        assert!(matches!(
+            target.get_value("map"),
+            Some(BackendValue::Hash(_))
+        ));
+
+        // 不存在的key
+        let ret = Migrate::try_from(args(&["migrate", "127.0.0.1", &port, "none", "0", "1000"]))?
+            .execute(&source);
+        assert_eq!(ret, SimpleString::new("NOKEY").into());
+        Ok(())
+    }
+}
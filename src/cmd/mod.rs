@@ -1,25 +1,57 @@
+mod acl;
+mod auth;
+mod client;
+mod cluster;
 mod command;
+mod commands;
+mod config;
+mod custom;
+mod dump;
 mod echo;
 mod hmap;
+mod info;
+mod keyspace;
 mod map;
+mod migrate;
+mod persistence;
 mod ping;
+mod replication;
 mod set;
-mod unrecognized;
+mod shutdown;
+mod table;
+
+use std::fmt;
 
 use lazy_static::lazy_static;
 
 use thiserror::Error;
 
-use crate::{backend::Backend, RespArray, RespError, RespFrame, SimpleString};
+use crate::{
+    backend::Backend, KeyType, RespArray, RespError, RespFrame, SimpleError, SimpleString,
+};
 
 pub use self::{
+    acl::Acl,
+    auth::{Auth, Hello},
+    client::{Client, ReplyMode},
+    cluster::{Asking, Cluster},
     command::Command,
+    commands::Commands,
+    config::Config,
+    custom::{CommandContext, CommandRegistry, Custom, CustomExecutor, RegisterError},
+    dump::{Dump, Restore},
     echo::Echo,
     hmap::{HGet, HGetAll, HMGet, HSet},
+    info::Info,
+    keyspace::Del,
     map::{Get, Set},
+    migrate::Migrate,
+    persistence::{BgRewriteAof, BgSave, LastSave, Save},
     ping::Ping,
+    replication::{ReplicaOf, Role},
     set::{SAdd, SISMember},
-    unrecognized::Unrecognized,
+    shutdown::Shutdown,
+    table::{command_keys, command_spec, lookup_command, CommandSpec, KeySpec, COMMAND_TABLE},
 };
 lazy_static! {
     /// RESP OK 简单字符串的全局变量，这里当做常量使用
@@ -33,19 +65,146 @@ pub trait CommandExecutor {
     fn execute(self, backend: &Backend) -> RespFrame;
 }
 
-///  命令解析过程中的异常
+/// 错误回复的错误码，回复的格式为 "-<CODE> <message>"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// 通用错误
+    Err,
+    /// 操作的key类型不对
+    WrongType,
+    /// 连接还没有认证
+    NoAuth,
+    /// 只读的replica不接受写命令
+    ReadOnly,
+    /// 正在加载数据
+    Loading,
+    /// RESTORE的目标key已经存在
+    BusyKey,
+    /// MIGRATE和目标节点通信失败
+    IoErr,
+    /// ACL不允许执行命令或者访问key
+    NoPerm,
+    /// 用户名或者密码不对
+    WrongPass,
+    /// HELLO指定了不支持的协议版本
+    NoProto,
+    /// slot由其他节点负责
+    Moved,
+    /// slot正在迁移，需要到目标节点重试
+    Ask,
+    /// 多个key不在同一个slot
+    CrossSlot,
+    /// slot正在迁移，多个key只有一部分在当前节点
+    TryAgain,
+    /// 集群不可用
+    ClusterDown,
+    /// 写AOF或者传播到replica失败
+    MisConf,
+    /// 故障转移已经在进行
+    InProg,
+    /// 没有可以提升为master的replica
+    NoGoodSlave,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::Err => "ERR",
+            ErrorCode::WrongType => "WRONGTYPE",
+            ErrorCode::NoAuth => "NOAUTH",
+            ErrorCode::ReadOnly => "READONLY",
+            ErrorCode::Loading => "LOADING",
+            ErrorCode::BusyKey => "BUSYKEY",
+            ErrorCode::IoErr => "IOERR",
+            ErrorCode::NoPerm => "NOPERM",
+            ErrorCode::WrongPass => "WRONGPASS",
+            ErrorCode::NoProto => "NOPROTO",
+            ErrorCode::Moved => "MOVED",
+            ErrorCode::Ask => "ASK",
+            ErrorCode::CrossSlot => "CROSSSLOT",
+            ErrorCode::TryAgain => "TRYAGAIN",
+            ErrorCode::ClusterDown => "CLUSTERDOWN",
+            ErrorCode::MisConf => "MISCONF",
+            ErrorCode::InProg => "INPROG",
+            ErrorCode::NoGoodSlave => "NOGOODSLAVE",
+        }
+    }
+
+    /// 生成带错误码的错误回复
+    pub fn reply(self, msg: impl fmt::Display) -> RespFrame {
+        SimpleError::new(format!("{} {}", self, msg)).into()
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+///  命令解析和执行过程中的异常，回复给客户端时转换为错误回复
 #[derive(Error, Debug)]
 pub enum CommandError {
-    #[error("Invalid command {0}")]
+    #[error("unknown command '{name}', with args beginning with: {args}")]
+    UnknownCommand { name: String, args: String },
+    #[error("unknown subcommand '{sub}'. Try {cmd} HELP.")]
+    UnknownSubcommand { cmd: String, sub: String },
+    #[error("wrong number of arguments for '{0}' command")]
+    WrongArity(String),
+    #[error("Operation against a key holding the wrong kind of value")]
+    WrongType,
+    #[error("{0}")]
     InvalidCommand(String),
-    #[error("Invalid argument {0}")]
-    InvalidArgument(String),
     #[error("{0}")]
+    InvalidArgument(String),
+    #[error("Protocol error: {0}")]
     RespError(#[from] RespError),
-    #[error("From Utf8 Error:{0}")]
+    #[error("invalid UTF-8 argument: {0}")]
     Utf8Error(#[from] std::string::FromUtf8Error),
 }
 
+impl CommandError {
+    /// 未知命令，和Redis一样在错误信息中带上前几个参数
+    pub fn unknown_command(args: &RespArray) -> Self {
+        let name = |frame: &RespFrame| match frame {
+            RespFrame::BulkString(s) => String::from_utf8_lossy(s).into_owned(),
+            frame => format!("{:?}", frame),
+        };
+        CommandError::UnknownCommand {
+            name: args.first().map(name).unwrap_or_default(),
+            args: args
+                .iter()
+                .skip(1)
+                .take(16)
+                .map(|arg| format!("'{}' ", name(arg)))
+                .collect(),
+        }
+    }
+
+    /// 错误对应的错误码
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            CommandError::WrongType => ErrorCode::WrongType,
+            _ => ErrorCode::Err,
+        }
+    }
+}
+
+/// 转换为错误回复，连接继续处理之后的命令
+impl From<CommandError> for RespFrame {
+    fn from(e: CommandError) -> Self {
+        e.code().reply(&e)
+    }
+}
+
+/// key存在并且类型不是expected时返回WRONGTYPE
+fn check_type(backend: &Backend, key: &str, expected: KeyType) -> Result<(), CommandError> {
+    match backend.key_type(key) {
+        Some(ty) if ty != expected => Err(CommandError::WrongType),
+        _ => Ok(()),
+    }
+}
+
 /// 验证命令是否正确 格式为 [Command .. n   Args .. n]
 fn validate_command(
     value: &RespArray,
@@ -55,11 +214,7 @@ fn validate_command(
     // 判断实际的Array长度  如果小于 期望的命令的数量 + 期望的参数的最小数量 则出错
     // 大于无所谓 可以抛弃 不然无法支持HMGet这样的命令
     if value.len() < min_args + names.len() {
-        return Err(CommandError::InvalidArgument(format!(
-            "{} command must have exactly {} argument",
-            names.join(" "),
-            min_args
-        )));
+        return Err(CommandError::WrongArity(names.join("|")));
     }
 
     // 判断是否包含命令
@@ -87,6 +242,23 @@ fn validate_command(
     Ok(())
 }
 
+/// 将BulkString参数转换为String
+fn frame_to_string(frame: RespFrame) -> Result<String, CommandError> {
+    match frame {
+        RespFrame::BulkString(s) => Ok(String::from_utf8(s.into_vec())?),
+        _ => Err(CommandError::InvalidArgument(
+            "argument must be a bulk string".to_string(),
+        )),
+    }
+}
+
+/// 将BulkString参数转换为i64
+fn frame_to_i64(frame: RespFrame) -> Result<i64, CommandError> {
+    frame_to_string(frame)?.parse().map_err(|_| {
+        CommandError::InvalidArgument("value is not an integer or out of range".to_string())
+    })
+}
+
 /// 解析出命令之后的所有args
 fn extract_args(value: RespArray, start: usize) -> Result<Vec<RespFrame>, CommandError> {
     let args = value.0.into_iter().skip(start).collect();
@@ -0,0 +1,118 @@
+use crate::{persistence, Backend, RespArray, RespFrame, SimpleString};
+
+use super::{validate_command, CommandError, CommandExecutor, ErrorCode, RESP_OK};
+
+/// Save 命令 同步保存快照
+#[derive(Debug)]
+pub struct Save;
+
+impl CommandExecutor for Save {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if backend.snapshot().is_bgsave_in_progress() {
+            return ErrorCode::Err.reply("Background save already in progress");
+        }
+        match persistence::save(backend) {
+            Ok(_) => RESP_OK.clone(),
+            Err(e) => ErrorCode::Err.reply(e),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Save {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["save"], 0)?;
+        Ok(Save)
+    }
+}
+
+/// BgSave 命令 在后台保存快照
+#[derive(Debug)]
+pub struct BgSave;
+
+impl CommandExecutor for BgSave {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match persistence::bgsave(backend) {
+            Ok(_) => SimpleString::new("Background saving started").into(),
+            Err(e) => ErrorCode::Err.reply(e),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for BgSave {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["bgsave"], 0)?;
+        Ok(BgSave)
+    }
+}
+
+/// LastSave 命令 返回上次成功保存的unix时间
+#[derive(Debug)]
+pub struct LastSave;
+
+impl CommandExecutor for LastSave {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        RespFrame::Integer(backend.snapshot().last_save())
+    }
+}
+
+impl TryFrom<RespArray> for LastSave {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["lastsave"], 0)?;
+        Ok(LastSave)
+    }
+}
+
+/// BgRewriteAof 命令 在后台重写AOF
+#[derive(Debug)]
+pub struct BgRewriteAof;
+
+impl CommandExecutor for BgRewriteAof {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        match persistence::bgrewriteaof(backend) {
+            Ok(_) => SimpleString::new("Background append only file rewriting started").into(),
+            Err(e) => ErrorCode::Err.reply(e),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for BgRewriteAof {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        validate_command(&value, &["bgrewriteaof"], 0)?;
+        Ok(BgRewriteAof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{persistence::SnapshotConfig, BulkString};
+    use anyhow::Result;
+
+    #[test]
+    fn test_save_lastsave() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let backend = Backend::new();
+        backend.snapshot().set_config(SnapshotConfig {
+            dir: dir.path().to_path_buf(),
+            ..Default::default()
+        });
+        backend.set("hello".to_string(), BulkString::new("world").into());
+
+        let cmd = Save::try_from(RespArray::new(vec![BulkString::new("save").into()]))?;
+        assert_eq!(cmd.execute(&backend), RESP_OK.clone());
+        assert_eq!(backend.dirty(), 0);
+
+        let ret = LastSave.execute(&backend);
+        assert_eq!(ret, RespFrame::Integer(backend.snapshot().last_save()));
+
+        Ok(())
+    }
+}
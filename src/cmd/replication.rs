@@ -0,0 +1,249 @@
+use crate::{
+    replication::{self, ReplicationRole},
+    Backend, BulkString, RespArray, RespFrame, RespNull, SimpleString,
+};
+
+use super::{extract_args, frame_to_string, CommandError, CommandExecutor, ErrorCode, RESP_OK};
+
+/// ReplicaOf 命令
+/// replicaof host port | replicaof no one，slaveof 是同样的命令
+#[derive(Debug, PartialEq)]
+pub struct ReplicaOf {
+    /// None表示 NO ONE，提升为master
+    master: Option<(String, u16)>,
+}
+
+impl CommandExecutor for ReplicaOf {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        // 集群模式下由集群决定主从关系
+        if backend.cluster().is_enabled() {
+            return ErrorCode::Err.reply("REPLICAOF not allowed in cluster mode.");
+        }
+        let state = backend.replication();
+        match self.master {
+            None => {
+                if state.is_replica() {
+                    state.promote();
+                    tracing::info!("MASTER MODE enabled");
+                }
+                RESP_OK.clone()
+            }
+            Some((host, port)) => {
+                if !state.set_master(&host, port) {
+                    return SimpleString::new("OK Already connected to specified master").into();
+                }
+                tracing::info!("REPLICAOF {}:{} enabled", host, port);
+                replication::start_replication(backend);
+                RESP_OK.clone()
+            }
+        }
+    }
+}
+
+impl TryFrom<RespArray> for ReplicaOf {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        match value.first() {
+            Some(RespFrame::BulkString(cmd))
+                if cmd.eq_ignore_ascii_case(b"replicaof")
+                    || cmd.eq_ignore_ascii_case(b"slaveof") => {}
+            _ => {
+                return Err(CommandError::InvalidCommand(
+                    "Invalid command: expected replicaof".to_string(),
+                ))
+            }
+        }
+        if value.len() != 3 {
+            return Err(CommandError::WrongArity("replicaof".to_string()));
+        }
+
+        let mut args = extract_args(value, 1)?.into_iter();
+        let host = frame_to_string(args.next().unwrap_or(RespNull.into()))?;
+        let port = frame_to_string(args.next().unwrap_or(RespNull.into()))?;
+        if host.eq_ignore_ascii_case("no") && port.eq_ignore_ascii_case("one") {
+            return Ok(ReplicaOf { master: None });
+        }
+        let port = port
+            .parse()
+            .map_err(|_| CommandError::InvalidArgument("Invalid master port".to_string()))?;
+        Ok(ReplicaOf {
+            master: Some((host, port)),
+        })
+    }
+}
+
+/// Role 命令 返回当前实例在复制中的角色
+#[derive(Debug)]
+pub struct Role;
+
+impl CommandExecutor for Role {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        let state = backend.replication();
+        match state.role() {
+            ReplicationRole::Master => {
+                let replicas: Vec<RespFrame> = state
+                    .replicas()
+                    .into_iter()
+                    .map(|r| {
+                        RespArray::new(vec![
+                            BulkString::new(r.ip).into(),
+                            BulkString::new(r.port.to_string()).into(),
+                            BulkString::new(r.ack_offset.to_string()).into(),
+                        ])
+                        .into()
+                    })
+                    .collect();
+                RespArray::new(vec![
+                    BulkString::new("master").into(),
+                    RespFrame::Integer(state.offset() as i64),
+                    RespArray::new(replicas).into(),
+                ])
+                .into()
+            }
+            ReplicationRole::Replica(link) => RespArray::new(vec![
+                BulkString::new("slave").into(),
+                BulkString::new(link.host).into(),
+                RespFrame::Integer(link.port as i64),
+                BulkString::new(link.state.as_str()).into(),
+                RespFrame::Integer(state.offset() as i64),
+            ])
+            .into(),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Role {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        super::validate_command(&value, &["role"], 0)?;
+        Ok(Role)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{network::RedisCodec, RespEncode};
+    use anyhow::Result;
+    use futures::StreamExt;
+    use std::time::Duration;
+    use tokio::{
+        io::AsyncWriteExt,
+        net::{TcpListener, TcpStream},
+    };
+    use tokio_util::codec::Framed;
+
+    fn args(items: &[&str]) -> RespArray {
+        RespArray::new(
+            items
+                .iter()
+                .map(|s| BulkString::new(*s).into())
+                .collect::<Vec<RespFrame>>(),
+        )
+    }
+
+    #[test]
+    fn test_replicaof_try_from() -> Result<()> {
+        let cmd = ReplicaOf::try_from(args(&["replicaof", "NO", "ONE"]))?;
+        assert_eq!(cmd, ReplicaOf { master: None });
+
+        let cmd = ReplicaOf::try_from(args(&["slaveof", "127.0.0.1", "6380"]))?;
+        assert_eq!(
+            cmd,
+            ReplicaOf {
+                master: Some(("127.0.0.1".to_string(), 6380))
+            }
+        );
+
+        assert!(ReplicaOf::try_from(args(&["replicaof", "127.0.0.1", "port"])).is_err());
+        assert!(ReplicaOf::try_from(args(&["replicaof", "127.0.0.1"])).is_err());
+        Ok(())
+    }
+
+    /// 启动一个master，返回监听的端口
+    async fn start_master(backend: Backend) -> Result<u16> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let backend = backend.clone();
+                tokio::spawn(crate::network::stream_handler(stream, backend));
+            }
+        });
+        Ok(port)
+    }
+
+    async fn wait_until(f: impl Fn() -> bool) {
+        for _ in 0..100 {
+            if f() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("condition not met in time");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_replication() -> Result<()> {
+        let master = Backend::new();
+        master.set("hello".to_string(), BulkString::new("world").into());
+        let port = start_master(master.clone()).await?;
+
+        // 全量同步
+        let replica = Backend::new();
+        let ret = ReplicaOf::try_from(args(&["replicaof", "127.0.0.1", &port.to_string()]))?
+            .execute(&replica);
+        assert_eq!(ret, RESP_OK.clone());
+        wait_until(|| replica.get("hello").is_some()).await;
+        wait_until(|| matches!(replica.replication().role(), ReplicationRole::Replica(link) if link.state == replication::LinkState::Connected)).await;
+        assert_eq!(
+            replica.replication().replid(),
+            master.replication().replid()
+        );
+
+        // 客户端写入master后同步到replica
+        let stream = TcpStream::connect(("127.0.0.1", port)).await?;
+        let mut client = Framed::new(stream, RedisCodec::default());
+        client
+            .get_mut()
+            .write_all(&args(&["hset", "map", "field", "value"]).encode())
+            .await?;
+        assert_eq!(client.next().await.transpose()?, Some(RESP_OK.clone()));
+        wait_until(|| replica.hget("map", "field").is_some()).await;
+        wait_until(|| {
+            master
+                .replication()
+                .replicas()
+                .first()
+                .map(|r| r.ack_offset == master.replication().offset())
+                .unwrap_or(false)
+        })
+        .await;
+        assert_eq!(
+            replica.replication().offset(),
+            master.replication().offset()
+        );
+
+        // ROLE和INFO
+        let RespFrame::Array(role) = Role.execute(&master) else {
+            panic!("array expected");
+        };
+        assert_eq!(role[0], BulkString::new("master").into());
+        let info = replica.replication().info();
+        assert!(info.contains("role:slave"));
+        assert!(info.contains("master_link_status:up"));
+        assert!(master.replication().info().contains("connected_slaves:1"));
+
+        // replica默认只读
+        assert!(replica.replication().is_read_only());
+
+        // 提升为master
+        ReplicaOf::try_from(args(&["replicaof", "no", "one"]))?.execute(&replica);
+        assert!(!replica.replication().is_replica());
+        wait_until(|| master.replication().replicas().is_empty()).await;
+
+        Ok(())
+    }
+}
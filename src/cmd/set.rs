@@ -1,6 +1,6 @@
-use crate::{RespArray, RespFrame};
+use crate::{KeyType, RespArray, RespFrame};
 
-use super::{extract_args, CommandError, CommandExecutor};
+use super::{check_type, extract_args, CommandError, CommandExecutor};
 
 /// SAdd 命令  sadd key member [member ...]
 #[derive(Debug)]
@@ -11,6 +11,9 @@ pub struct SAdd {
 
 impl CommandExecutor for SAdd {
     fn execute(self, backend: &crate::Backend) -> RespFrame {
+        if let Err(e) = check_type(backend, &self.key, KeyType::Set) {
+            return e.into();
+        }
         backend.sadd(self.key, self.members).into()
     }
 }
@@ -26,7 +29,7 @@ impl TryFrom<RespArray> for SAdd {
 
         match args.next() {
             Some(RespFrame::BulkString(key)) => {
-                let key = String::from_utf8(key.0)?;
+                let key = String::from_utf8(key.into_vec())?;
                 let members = args.collect();
                 Ok(SAdd { key, members })
             }
@@ -44,7 +47,10 @@ pub struct SISMember {
 
 impl CommandExecutor for SISMember {
     fn execute(self, backend: &crate::Backend) -> RespFrame {
-        let set = backend.set.get(&self.key);
+        if let Err(e) = check_type(backend, &self.key, KeyType::Set) {
+            return e.into();
+        }
+        let set = backend.sismembers(&self.key);
         match set {
             Some(set) => {
                 if set.contains(&self.member) {
@@ -69,7 +75,7 @@ impl TryFrom<RespArray> for SISMember {
 
         match (args.next(), args.next()) {
             (Some(RespFrame::BulkString(key)), Some(member)) => {
-                let key = String::from_utf8(key.0)?;
+                let key = String::from_utf8(key.into_vec())?;
                 Ok(SISMember { key, member })
             }
             _ => Err(CommandError::InvalidCommand("Invalid command".to_string())),
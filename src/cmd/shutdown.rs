@@ -0,0 +1,99 @@
+use crate::{shutdown::ShutdownFlags, Backend, RespArray, RespFrame};
+
+use super::{extract_args, frame_to_string, CommandError, CommandExecutor, ErrorCode, RESP_OK};
+
+/// Shutdown 命令 shutdown [NOSAVE|SAVE] [NOW] [FORCE] [ABORT]
+/// 除了ABORT之外需要等待replica，由连接异步执行，见 `shutdown::shutdown`
+#[derive(Debug, PartialEq)]
+pub struct Shutdown {
+    flags: ShutdownFlags,
+    abort: bool,
+}
+
+impl Shutdown {
+    pub fn flags(&self) -> ShutdownFlags {
+        self.flags
+    }
+
+    pub fn is_abort(&self) -> bool {
+        self.abort
+    }
+}
+
+impl CommandExecutor for Shutdown {
+    fn execute(self, backend: &Backend) -> RespFrame {
+        if !self.abort {
+            return ErrorCode::Err.reply("SHUTDOWN is only allowed from client connections");
+        }
+        if backend.shutdown().abort() {
+            RESP_OK.clone()
+        } else {
+            ErrorCode::Err.reply("No shutdown in progress.")
+        }
+    }
+}
+
+impl TryFrom<RespArray> for Shutdown {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        super::validate_command(&value, &["shutdown"], 0)?;
+
+        let syntax_error = || CommandError::InvalidArgument("syntax error".to_string());
+        let mut cmd = Shutdown {
+            flags: ShutdownFlags::default(),
+            abort: false,
+        };
+        for arg in extract_args(value, 1)? {
+            match frame_to_string(arg)?.to_ascii_lowercase().as_str() {
+                "nosave" if cmd.flags.save.is_none() => cmd.flags.save = Some(false),
+                "save" if cmd.flags.save.is_none() => cmd.flags.save = Some(true),
+                "now" => cmd.flags.now = true,
+                "force" => cmd.flags.force = true,
+                "abort" => cmd.abort = true,
+                _ => return Err(syntax_error()),
+            }
+        }
+        // ABORT 不能和其他选项一起使用
+        if cmd.abort && cmd.flags != ShutdownFlags::default() {
+            return Err(syntax_error());
+        }
+        Ok(cmd)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BulkString, SimpleError};
+    use anyhow::Result;
+
+    fn cmd(args: &[&str]) -> Result<Shutdown, CommandError> {
+        Shutdown::try_from(RespArray::new(
+            args.iter()
+                .map(|arg| BulkString::new(*arg).into())
+                .collect::<Vec<RespFrame>>(),
+        ))
+    }
+
+    #[test]
+    fn test_shutdown_try_from() -> Result<()> {
+        let shutdown = cmd(&["shutdown", "NOSAVE", "now", "force"])?;
+        assert_eq!(
+            shutdown.flags(),
+            ShutdownFlags {
+                save: Some(false),
+                now: true,
+                force: true,
+            }
+        );
+        assert!(cmd(&["shutdown", "abort"])?.is_abort());
+        assert!(cmd(&["shutdown", "save", "nosave"]).is_err());
+        assert!(cmd(&["shutdown", "abort", "now"]).is_err());
+        assert!(cmd(&["shutdown", "later"]).is_err());
+
+        let ret = cmd(&["shutdown", "abort"])?.execute(&Backend::new());
+        assert_eq!(ret, SimpleError::new("ERR No shutdown in progress.").into());
+        Ok(())
+    }
+}
@@ -0,0 +1,1105 @@
+use crate::{RespArray, RespFrame};
+
+use super::{
+    Acl, Asking, Auth, BgRewriteAof, BgSave, Client, Cluster, Command, CommandError, Commands,
+    Config, Del, Dump, Echo, Get, HGet, HGetAll, HMGet, HSet, Hello, Info, LastSave, Migrate, Ping,
+    ReplicaOf, Restore, Role, SAdd, SISMember, Save, Set, Shutdown,
+};
+
+/// 把请求解析为命令
+type Parser = fn(RespArray) -> Result<Command, CommandError>;
+
+/// 无法用位置描述key的命令，自己从参数中找出key
+type KeysGetter = fn(&RespArray) -> Vec<&[u8]>;
+
+/// 命令表中的一项，COMMAND INFO/DOCS 的内容也来自这里
+#[derive(Debug, Clone, Copy)]
+pub struct CommandSpec {
+    /// 小写的命令名，子命令写成 `命令|子命令`
+    pub name: &'static str,
+    /// 参数个数，包括命令名本身，负数表示至少 -arity 个
+    pub arity: i64,
+    /// write, readonly, denyoom, admin, noscript, loading, stale, fast, no_auth, asking 等
+    pub flags: &'static [&'static str],
+    /// ACL分类，不带@
+    pub categories: &'static [&'static str],
+    pub keys: KeySpec,
+    /// COMMAND DOCS 的分组
+    pub group: &'static str,
+    /// 从哪个Redis版本开始提供
+    pub since: &'static str,
+    pub summary: &'static str,
+    pub subcommands: &'static [CommandSpec],
+    /// 子命令和由连接直接处理的命令没有解析函数
+    pub(super) parse: Option<Parser>,
+    getkeys: Option<KeysGetter>,
+}
+
+/// key在参数中的位置，first为0表示没有key，last为负数表示从结尾倒数
+#[derive(Debug, Clone, Copy)]
+pub struct KeySpec {
+    pub first: i64,
+    pub last: i64,
+    pub step: i64,
+}
+
+const NO_KEYS: KeySpec = KeySpec {
+    first: 0,
+    last: 0,
+    step: 0,
+};
+
+/// 只有第一个参数是key
+const FIRST_KEY: KeySpec = KeySpec {
+    first: 1,
+    last: 1,
+    step: 1,
+};
+
+fn parse<T>(args: RespArray) -> Result<Command, CommandError>
+where
+    T: TryFrom<RespArray, Error = CommandError>,
+    Command: From<T>,
+{
+    Ok(T::try_from(args)?.into())
+}
+
+/// 所有支持的命令，ACL CAT 按这个顺序输出分类下的命令
+pub static COMMAND_TABLE: &[CommandSpec] = &[
+    CommandSpec {
+        name: "get",
+        arity: 2,
+        flags: &["readonly", "fast"],
+        categories: &["read", "string", "fast"],
+        keys: FIRST_KEY,
+        group: "string",
+        since: "1.0.0",
+        summary: "Returns the string value of a key.",
+        subcommands: &[],
+        parse: Some(parse::<Get>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "set",
+        arity: -3,
+        flags: &["write", "denyoom"],
+        categories: &["write", "string", "slow"],
+        keys: FIRST_KEY,
+        group: "string",
+        since: "1.0.0",
+        summary: "Sets the string value of a key, ignoring its type.",
+        subcommands: &[],
+        parse: Some(parse::<Set>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "hget",
+        arity: 3,
+        flags: &["readonly", "fast"],
+        categories: &["read", "hash", "fast"],
+        keys: FIRST_KEY,
+        group: "hash",
+        since: "2.0.0",
+        summary: "Returns the value of a field in a hash.",
+        subcommands: &[],
+        parse: Some(parse::<HGet>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "hset",
+        arity: -4,
+        flags: &["write", "denyoom", "fast"],
+        categories: &["write", "hash", "fast"],
+        keys: FIRST_KEY,
+        group: "hash",
+        since: "2.0.0",
+        summary: "Creates or modifies the value of a field in a hash.",
+        subcommands: &[],
+        parse: Some(parse::<HSet>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "hmget",
+        arity: -3,
+        flags: &["readonly", "fast"],
+        categories: &["read", "hash", "fast"],
+        keys: FIRST_KEY,
+        group: "hash",
+        since: "2.0.0",
+        summary: "Returns the values of all fields in a hash.",
+        subcommands: &[],
+        parse: Some(parse::<HMGet>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "hgetall",
+        arity: 2,
+        flags: &["readonly"],
+        categories: &["read", "hash", "slow"],
+        keys: FIRST_KEY,
+        group: "hash",
+        since: "2.0.0",
+        summary: "Returns all fields and values in a hash.",
+        subcommands: &[],
+        parse: Some(parse::<HGetAll>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "sadd",
+        arity: -3,
+        flags: &["write", "denyoom", "fast"],
+        categories: &["write", "set", "fast"],
+        keys: FIRST_KEY,
+        group: "set",
+        since: "1.0.0",
+        summary: "Adds one or more members to a set. Creates the key if it doesn't exist.",
+        subcommands: &[],
+        parse: Some(parse::<SAdd>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "sismember",
+        arity: 3,
+        flags: &["readonly", "fast"],
+        categories: &["read", "set", "fast"],
+        keys: FIRST_KEY,
+        group: "set",
+        since: "1.0.0",
+        summary: "Determines whether a member belongs to a set.",
+        subcommands: &[],
+        parse: Some(parse::<SISMember>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "del",
+        arity: -2,
+        flags: &["write"],
+        categories: &["keyspace", "write", "slow"],
+        keys: KeySpec {
+            first: 1,
+            last: -1,
+            step: 1,
+        },
+        group: "generic",
+        since: "1.0.0",
+        summary: "Deletes one or more keys.",
+        subcommands: &[],
+        parse: Some(parse::<Del>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "dump",
+        arity: 2,
+        flags: &["readonly"],
+        categories: &["keyspace", "read", "slow"],
+        keys: FIRST_KEY,
+        group: "generic",
+        since: "2.6.0",
+        summary: "Returns a serialized representation of the value stored at a key.",
+        subcommands: &[],
+        parse: Some(parse::<Dump>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "restore",
+        arity: -4,
+        flags: &["write", "denyoom"],
+        categories: &["keyspace", "write", "slow", "dangerous"],
+        keys: FIRST_KEY,
+        group: "generic",
+        since: "2.6.0",
+        summary: "Creates a key from the serialized representation of a value.",
+        subcommands: &[],
+        parse: Some(parse::<Restore>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "restore-asking",
+        arity: -4,
+        flags: &["write", "denyoom", "asking"],
+        categories: &["keyspace", "write", "slow", "dangerous"],
+        keys: FIRST_KEY,
+        group: "server",
+        since: "3.0.0",
+        summary: "An internal command for migrating keys in a cluster.",
+        subcommands: &[],
+        parse: Some(parse::<Restore>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "migrate",
+        arity: -6,
+        flags: &["write", "movablekeys"],
+        categories: &["keyspace", "write", "slow", "dangerous"],
+        keys: KeySpec {
+            first: 3,
+            last: 3,
+            step: 1,
+        },
+        group: "generic",
+        since: "2.6.0",
+        summary: "Atomically transfers a key from one Redis instance to another.",
+        subcommands: &[],
+        parse: Some(parse::<Migrate>),
+        getkeys: Some(migrate_keys),
+    },
+    CommandSpec {
+        name: "ping",
+        arity: -1,
+        flags: &["loading", "stale", "fast"],
+        categories: &["fast", "connection"],
+        keys: NO_KEYS,
+        group: "connection",
+        since: "1.0.0",
+        summary: "Returns the server's liveliness response.",
+        subcommands: &[],
+        parse: Some(parse::<Ping>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "echo",
+        arity: 2,
+        flags: &["fast"],
+        categories: &["fast", "connection"],
+        keys: NO_KEYS,
+        group: "connection",
+        since: "1.0.0",
+        summary: "Returns the given string.",
+        subcommands: &[],
+        parse: Some(parse::<Echo>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "auth",
+        arity: -2,
+        flags: &["noscript", "loading", "stale", "fast", "no_auth"],
+        categories: &["fast", "connection"],
+        keys: NO_KEYS,
+        group: "connection",
+        since: "1.0.0",
+        summary: "Authenticates the connection.",
+        subcommands: &[],
+        parse: Some(parse::<Auth>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "hello",
+        arity: -1,
+        flags: &["noscript", "loading", "stale", "fast", "no_auth"],
+        categories: &["fast", "connection"],
+        keys: NO_KEYS,
+        group: "connection",
+        since: "6.0.0",
+        summary: "Handshakes with the Redis server.",
+        subcommands: &[],
+        parse: Some(parse::<Hello>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "asking",
+        arity: 1,
+        flags: &["fast"],
+        categories: &["fast", "connection"],
+        keys: NO_KEYS,
+        group: "cluster",
+        since: "3.0.0",
+        summary: "Signals that a cluster client is following an -ASK redirect.",
+        subcommands: &[],
+        parse: Some(parse::<Asking>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "save",
+        arity: 1,
+        flags: &["admin", "noscript"],
+        categories: &["admin", "slow", "dangerous"],
+        keys: NO_KEYS,
+        group: "server",
+        since: "1.0.0",
+        summary: "Synchronously saves the database(s) to disk.",
+        subcommands: &[],
+        parse: Some(parse::<Save>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "bgsave",
+        arity: -1,
+        flags: &["admin", "noscript"],
+        categories: &["admin", "slow", "dangerous"],
+        keys: NO_KEYS,
+        group: "server",
+        since: "1.0.0",
+        summary: "Asynchronously saves the database(s) to disk.",
+        subcommands: &[],
+        parse: Some(parse::<BgSave>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "bgrewriteaof",
+        arity: 1,
+        flags: &["admin", "noscript"],
+        categories: &["admin", "slow", "dangerous"],
+        keys: NO_KEYS,
+        group: "server",
+        since: "1.0.0",
+        summary: "Asynchronously rewrites the append-only file to disk.",
+        subcommands: &[],
+        parse: Some(parse::<BgRewriteAof>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "lastsave",
+        arity: 1,
+        flags: &["loading", "stale", "fast"],
+        categories: &["admin", "fast", "dangerous"],
+        keys: NO_KEYS,
+        group: "server",
+        since: "1.0.0",
+        summary: "Returns the Unix timestamp of the last successful save to disk.",
+        subcommands: &[],
+        parse: Some(parse::<LastSave>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "replicaof",
+        arity: 3,
+        flags: &["admin", "noscript", "stale"],
+        categories: &["admin", "slow", "dangerous"],
+        keys: NO_KEYS,
+        group: "server",
+        since: "5.0.0",
+        summary: "Configures a server as replica of another, or promotes it to a master.",
+        subcommands: &[],
+        parse: Some(parse::<ReplicaOf>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "slaveof",
+        arity: 3,
+        flags: &["admin", "noscript", "stale"],
+        categories: &["admin", "slow", "dangerous"],
+        keys: NO_KEYS,
+        group: "server",
+        since: "1.0.0",
+        summary: "Sets a Redis server as a replica of another, or promotes it to being a master.",
+        subcommands: &[],
+        parse: Some(parse::<ReplicaOf>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "replconf",
+        arity: -1,
+        flags: &["admin", "noscript", "loading", "stale"],
+        categories: &["admin", "slow", "dangerous"],
+        keys: NO_KEYS,
+        group: "server",
+        since: "3.0.0",
+        summary: "An internal command for configuring the replication stream.",
+        subcommands: &[],
+        parse: None,
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "psync",
+        arity: -3,
+        flags: &["admin", "noscript"],
+        categories: &["admin", "slow", "dangerous"],
+        keys: NO_KEYS,
+        group: "server",
+        since: "2.8.0",
+        summary: "An internal command used in replication.",
+        subcommands: &[],
+        parse: None,
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "role",
+        arity: 1,
+        flags: &["noscript", "loading", "stale", "fast"],
+        categories: &["admin", "fast", "dangerous"],
+        keys: NO_KEYS,
+        group: "server",
+        since: "2.8.12",
+        summary: "Returns the replication role.",
+        subcommands: &[],
+        parse: Some(parse::<Role>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "info",
+        arity: -1,
+        flags: &["loading", "stale"],
+        categories: &["slow", "dangerous"],
+        keys: NO_KEYS,
+        group: "server",
+        since: "1.0.0",
+        summary: "Returns information and statistics about the server.",
+        subcommands: &[],
+        parse: Some(parse::<Info>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "config",
+        arity: -2,
+        flags: &[],
+        categories: &["admin", "slow", "dangerous"],
+        keys: NO_KEYS,
+        group: "server",
+        since: "2.0.0",
+        summary: "A container for server configuration commands.",
+        subcommands: CONFIG_SUBCOMMANDS,
+        parse: Some(parse::<Config>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "shutdown",
+        arity: -1,
+        flags: &["admin", "noscript", "loading", "stale"],
+        categories: &["admin", "slow", "dangerous"],
+        keys: NO_KEYS,
+        group: "server",
+        since: "1.0.0",
+        summary: "Synchronously saves the database(s) to disk and shuts down the Redis server.",
+        subcommands: &[],
+        parse: Some(parse::<Shutdown>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "cluster",
+        arity: -2,
+        flags: &[],
+        categories: &["admin", "slow", "dangerous"],
+        keys: NO_KEYS,
+        group: "cluster",
+        since: "3.0.0",
+        summary: "A container for Redis Cluster commands.",
+        subcommands: CLUSTER_SUBCOMMANDS,
+        parse: Some(parse::<Cluster>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "acl",
+        arity: -2,
+        flags: &[],
+        categories: &["admin", "slow", "dangerous"],
+        keys: NO_KEYS,
+        group: "server",
+        since: "6.0.0",
+        summary: "A container for Access List Control commands.",
+        subcommands: ACL_SUBCOMMANDS,
+        parse: Some(parse::<Acl>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "client",
+        arity: -2,
+        flags: &[],
+        categories: &["admin", "slow", "dangerous", "connection"],
+        keys: NO_KEYS,
+        group: "connection",
+        since: "2.4.0",
+        summary: "A container for client connection commands.",
+        subcommands: CLIENT_SUBCOMMANDS,
+        parse: Some(parse::<Client>),
+        getkeys: None,
+    },
+    CommandSpec {
+        name: "command",
+        arity: -1,
+        flags: &["loading", "stale"],
+        categories: &["slow", "connection"],
+        keys: NO_KEYS,
+        group: "server",
+        since: "2.8.13",
+        summary: "Returns detailed information about all commands.",
+        subcommands: COMMAND_SUBCOMMANDS,
+        parse: Some(parse::<Commands>),
+        getkeys: None,
+    },
+];
+
+/// 子命令，没有解析函数，由父命令解析
+const fn subcommand(
+    name: &'static str,
+    arity: i64,
+    flags: &'static [&'static str],
+    categories: &'static [&'static str],
+    since: &'static str,
+    summary: &'static str,
+) -> CommandSpec {
+    CommandSpec {
+        name,
+        arity,
+        flags,
+        categories,
+        keys: NO_KEYS,
+        group: "",
+        since,
+        summary,
+        subcommands: &[],
+        parse: None,
+        getkeys: None,
+    }
+}
+
+const ADMIN: &[&str] = &["admin", "noscript", "loading", "stale"];
+const ADMIN_CATEGORIES: &[&str] = &["admin", "slow", "dangerous"];
+
+static CONFIG_SUBCOMMANDS: &[CommandSpec] = &[
+    subcommand(
+        "config|get",
+        -3,
+        ADMIN,
+        ADMIN_CATEGORIES,
+        "2.0.0",
+        "Returns the effective values of configuration parameters.",
+    ),
+    subcommand(
+        "config|set",
+        -4,
+        ADMIN,
+        ADMIN_CATEGORIES,
+        "2.0.0",
+        "Sets configuration parameters in-flight.",
+    ),
+    subcommand(
+        "config|rewrite",
+        2,
+        ADMIN,
+        ADMIN_CATEGORIES,
+        "2.8.0",
+        "Persists the effective configuration to file.",
+    ),
+    subcommand(
+        "config|resetstat",
+        2,
+        ADMIN,
+        ADMIN_CATEGORIES,
+        "2.0.0",
+        "Resets the server's statistics.",
+    ),
+];
+
+const CLUSTER_READ: &[&str] = &["loading", "stale"];
+const CLUSTER_ADMIN: &[&str] = &["admin", "noscript", "stale"];
+
+static CLUSTER_SUBCOMMANDS: &[CommandSpec] = &[
+    subcommand(
+        "cluster|info",
+        2,
+        CLUSTER_READ,
+        &["slow"],
+        "3.0.0",
+        "Returns information about the state of a node.",
+    ),
+    subcommand(
+        "cluster|myid",
+        2,
+        CLUSTER_READ,
+        &["slow"],
+        "3.0.0",
+        "Returns the ID of a node.",
+    ),
+    subcommand(
+        "cluster|nodes",
+        2,
+        CLUSTER_READ,
+        &["slow"],
+        "3.0.0",
+        "Returns the cluster configuration for a node.",
+    ),
+    subcommand(
+        "cluster|slots",
+        2,
+        CLUSTER_READ,
+        &["slow"],
+        "3.0.0",
+        "Returns the mapping of cluster slots to nodes.",
+    ),
+    subcommand(
+        "cluster|shards",
+        2,
+        CLUSTER_READ,
+        &["slow"],
+        "7.0.0",
+        "Returns the mapping of cluster slots to shards.",
+    ),
+    subcommand(
+        "cluster|keyslot",
+        3,
+        CLUSTER_READ,
+        &["slow"],
+        "3.0.0",
+        "Returns the hash slot for a key.",
+    ),
+    subcommand(
+        "cluster|countkeysinslot",
+        3,
+        CLUSTER_READ,
+        &["slow"],
+        "3.0.0",
+        "Returns the number of keys in a hash slot.",
+    ),
+    subcommand(
+        "cluster|getkeysinslot",
+        4,
+        CLUSTER_READ,
+        &["slow"],
+        "3.0.0",
+        "Returns the key names in a hash slot.",
+    ),
+    subcommand(
+        "cluster|meet",
+        -4,
+        CLUSTER_ADMIN,
+        ADMIN_CATEGORIES,
+        "3.0.0",
+        "Forces a node to handshake with another node.",
+    ),
+    subcommand(
+        "cluster|failover",
+        -2,
+        CLUSTER_ADMIN,
+        ADMIN_CATEGORIES,
+        "3.0.0",
+        "Forces a replica to perform a manual failover of its master.",
+    ),
+    subcommand(
+        "cluster|setslot",
+        -4,
+        CLUSTER_ADMIN,
+        ADMIN_CATEGORIES,
+        "3.0.0",
+        "Binds a hash slot to a node.",
+    ),
+];
+
+const ACL_READ: &[&str] = &["noscript", "loading", "stale"];
+
+static ACL_SUBCOMMANDS: &[CommandSpec] = &[
+    subcommand(
+        "acl|setuser",
+        -3,
+        ADMIN,
+        ADMIN_CATEGORIES,
+        "6.0.0",
+        "Creates and modifies an ACL user and its rules.",
+    ),
+    subcommand(
+        "acl|getuser",
+        3,
+        ADMIN,
+        ADMIN_CATEGORIES,
+        "6.0.0",
+        "Lists the ACL rules of a user.",
+    ),
+    subcommand(
+        "acl|deluser",
+        -3,
+        ADMIN,
+        ADMIN_CATEGORIES,
+        "6.0.0",
+        "Deletes ACL users, and terminates their connections.",
+    ),
+    subcommand(
+        "acl|list",
+        2,
+        ADMIN,
+        ADMIN_CATEGORIES,
+        "6.0.0",
+        "Dumps the effective rules in ACL file format.",
+    ),
+    subcommand(
+        "acl|users",
+        2,
+        ADMIN,
+        ADMIN_CATEGORIES,
+        "6.0.0",
+        "Lists all ACL users.",
+    ),
+    subcommand(
+        "acl|whoami",
+        2,
+        ACL_READ,
+        &["slow"],
+        "6.0.0",
+        "Returns the authenticated username of the current connection.",
+    ),
+    subcommand(
+        "acl|cat",
+        -2,
+        ACL_READ,
+        &["slow"],
+        "6.0.0",
+        "Lists the ACL categories, or the commands inside a category.",
+    ),
+    subcommand(
+        "acl|log",
+        -2,
+        ADMIN,
+        ADMIN_CATEGORIES,
+        "6.0.0",
+        "Lists recent security events generated due to ACL rules.",
+    ),
+    subcommand(
+        "acl|load",
+        2,
+        ADMIN,
+        ADMIN_CATEGORIES,
+        "6.0.0",
+        "Reloads the rules from the configured ACL file.",
+    ),
+    subcommand(
+        "acl|save",
+        2,
+        ADMIN,
+        ADMIN_CATEGORIES,
+        "6.0.0",
+        "Saves the effective ACL rules in the configured ACL file.",
+    ),
+];
+
+const CLIENT_READ: &[&str] = &["noscript", "loading", "stale"];
+const CLIENT_CATEGORIES: &[&str] = &["slow", "connection"];
+const CLIENT_ADMIN_CATEGORIES: &[&str] = &["admin", "slow", "dangerous", "connection"];
+
+static CLIENT_SUBCOMMANDS: &[CommandSpec] = &[
+    subcommand(
+        "client|id",
+        2,
+        CLIENT_READ,
+        CLIENT_CATEGORIES,
+        "5.0.0",
+        "Returns the unique client ID of the connection.",
+    ),
+    subcommand(
+        "client|info",
+        2,
+        CLIENT_READ,
+        CLIENT_CATEGORIES,
+        "6.2.0",
+        "Returns information about the connection.",
+    ),
+    subcommand(
+        "client|list",
+        -2,
+        ADMIN,
+        CLIENT_ADMIN_CATEGORIES,
+        "2.4.0",
+        "Lists open connections.",
+    ),
+    subcommand(
+        "client|setname",
+        3,
+        CLIENT_READ,
+        CLIENT_CATEGORIES,
+        "2.6.9",
+        "Sets the connection name.",
+    ),
+    subcommand(
+        "client|getname",
+        2,
+        CLIENT_READ,
+        CLIENT_CATEGORIES,
+        "2.6.9",
+        "Returns the name of the connection.",
+    ),
+    subcommand(
+        "client|kill",
+        -3,
+        ADMIN,
+        CLIENT_ADMIN_CATEGORIES,
+        "2.4.0",
+        "Terminates open connections.",
+    ),
+    subcommand(
+        "client|pause",
+        -3,
+        ADMIN,
+        CLIENT_ADMIN_CATEGORIES,
+        "3.0.0",
+        "Suspends commands processing.",
+    ),
+    subcommand(
+        "client|unpause",
+        2,
+        ADMIN,
+        CLIENT_ADMIN_CATEGORIES,
+        "6.2.0",
+        "Resumes processing commands from paused clients.",
+    ),
+    subcommand(
+        "client|reply",
+        3,
+        CLIENT_READ,
+        CLIENT_CATEGORIES,
+        "3.2.0",
+        "Instructs the server whether to reply to commands.",
+    ),
+    subcommand(
+        "client|no-evict",
+        3,
+        ADMIN,
+        CLIENT_ADMIN_CATEGORIES,
+        "7.0.0",
+        "Sets the client eviction mode of the connection.",
+    ),
+    subcommand(
+        "client|setinfo",
+        -4,
+        CLIENT_READ,
+        CLIENT_CATEGORIES,
+        "7.2.0",
+        "Sets information specific to the client or connection.",
+    ),
+];
+
+const COMMAND_READ: &[&str] = &["loading", "stale"];
+const COMMAND_CATEGORIES: &[&str] = &["slow", "connection"];
+
+static COMMAND_SUBCOMMANDS: &[CommandSpec] = &[
+    subcommand(
+        "command|count",
+        2,
+        COMMAND_READ,
+        COMMAND_CATEGORIES,
+        "2.8.13",
+        "Returns a count of commands.",
+    ),
+    subcommand(
+        "command|docs",
+        -2,
+        COMMAND_READ,
+        COMMAND_CATEGORIES,
+        "7.0.0",
+        "Returns documentary information about one, multiple or all commands.",
+    ),
+    subcommand(
+        "command|getkeys",
+        -3,
+        COMMAND_READ,
+        COMMAND_CATEGORIES,
+        "2.8.13",
+        "Extracts the key names from an arbitrary command.",
+    ),
+    subcommand(
+        "command|info",
+        -2,
+        COMMAND_READ,
+        COMMAND_CATEGORIES,
+        "2.8.13",
+        "Returns information about one, multiple or all commands.",
+    ),
+    subcommand(
+        "command|list",
+        -2,
+        COMMAND_READ,
+        COMMAND_CATEGORIES,
+        "7.0.0",
+        "Returns a list of command names.",
+    ),
+];
+
+impl CommandSpec {
+    /// 自定义命令的描述，没有flags、ACL分类和key，需要时直接修改对应的字段
+    pub const fn new(name: &'static str, arity: i64) -> Self {
+        CommandSpec {
+            name,
+            arity,
+            flags: &[],
+            categories: &[],
+            keys: NO_KEYS,
+            group: "module",
+            since: "",
+            summary: "",
+            subcommands: &[],
+            parse: None,
+            getkeys: None,
+        }
+    }
+
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.contains(&flag)
+    }
+
+    /// 写命令需要追加到AOF和复制流，只读的replica不能执行
+    pub fn is_write(&self) -> bool {
+        self.has_flag("write")
+    }
+
+    /// 子命令，不区分大小写
+    pub fn subcommand(&self, name: &[u8]) -> Option<&'static CommandSpec> {
+        self.subcommands.iter().find(|spec| {
+            spec.name
+                .split_once('|')
+                .is_some_and(|(_, sub)| sub.as_bytes().eq_ignore_ascii_case(name))
+        })
+    }
+
+    /// 参数个数是否符合arity
+    pub fn check_arity(&self, argc: usize) -> bool {
+        let argc = argc as i64;
+        if self.arity >= 0 {
+            argc == self.arity
+        } else {
+            argc >= -self.arity
+        }
+    }
+
+    /// 请求中包含的key
+    pub fn keys<'a>(&self, args: &'a RespArray) -> Vec<&'a [u8]> {
+        if let Some(getkeys) = self.getkeys {
+            return getkeys(args);
+        }
+        let KeySpec { first, last, step } = self.keys;
+        if first <= 0 {
+            return vec![];
+        }
+        let last = if last < 0 {
+            args.len() as i64 + last
+        } else {
+            last
+        };
+        (first..=last)
+            .step_by(step.max(1) as usize)
+            .filter_map(|i| match args.get(i as usize) {
+                Some(RespFrame::BulkString(key)) => Some(&key[..]),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// 命令是否有key参数
+    pub fn has_keys(&self) -> bool {
+        self.keys.first > 0 || self.getkeys.is_some()
+    }
+
+    /// 检查参数个数并解析命令，带子命令的命令同时检查子命令的参数个数
+    pub(super) fn parse(&self, args: RespArray) -> Result<Command, CommandError> {
+        if !self.check_arity(args.len()) {
+            return Err(CommandError::WrongArity(self.name.to_string()));
+        }
+        if let Some(RespFrame::BulkString(sub)) = args.get(1) {
+            if let Some(spec) = self.subcommand(sub) {
+                if !spec.check_arity(args.len()) {
+                    return Err(CommandError::WrongArity(spec.name.to_string()));
+                }
+            }
+        }
+        match self.parse {
+            Some(parse) => parse(args),
+            None => Err(CommandError::unknown_command(&args)),
+        }
+    }
+}
+
+/// 按名字查找内置命令，不区分大小写
+pub fn lookup_command(name: &[u8]) -> Option<&'static CommandSpec> {
+    COMMAND_TABLE
+        .iter()
+        .find(|spec| spec.name.as_bytes().eq_ignore_ascii_case(name))
+}
+
+/// 请求对应的命令
+pub fn command_spec(args: &RespArray) -> Option<&'static CommandSpec> {
+    match args.first() {
+        Some(RespFrame::BulkString(name)) => lookup_command(name),
+        _ => None,
+    }
+}
+
+/// 请求中包含的key，集群模式下用于检查key所在的slot，ACL用于检查key的权限
+pub fn command_keys(args: &RespArray) -> Vec<&[u8]> {
+    command_spec(args)
+        .map(|spec| spec.keys(args))
+        .unwrap_or_default()
+}
+
+/// MIGRATE host port key|"" db timeout [COPY] [REPLACE] [AUTH password] [AUTH2 user password] [KEYS key ...]
+/// key为空串时迁移KEYS之后的所有key
+fn migrate_keys(args: &RespArray) -> Vec<&[u8]> {
+    let arg = |i: usize| match args.get(i) {
+        Some(RespFrame::BulkString(s)) => Some(&s[..]),
+        _ => None,
+    };
+    match arg(3) {
+        Some(key) if !key.is_empty() => vec![key],
+        _ => {
+            let Some(pos) =
+                (6..args.len()).find(|i| arg(*i).is_some_and(|a| a.eq_ignore_ascii_case(b"keys")))
+            else {
+                return vec![];
+            };
+            (pos + 1..args.len()).filter_map(arg).collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BulkString;
+
+    fn args(args: &[&str]) -> RespArray {
+        RespArray::new(
+            args.iter()
+                .map(|arg| BulkString::new(*arg).into())
+                .collect::<Vec<RespFrame>>(),
+        )
+    }
+
+    #[test]
+    fn test_command_keys() {
+        let cases: &[(&[&str], &[&str])] = &[
+            (&["GET", "a"], &["a"]),
+            (&["del", "a", "b", "c"], &["a", "b", "c"]),
+            (&["hset", "h", "f", "v"], &["h"]),
+            (&["ping"], &[]),
+            (&["migrate", "h", "1", "k", "0", "0"], &["k"]),
+            (
+                &["migrate", "h", "1", "", "0", "0", "COPY", "KEYS", "a", "b"],
+                &["a", "b"],
+            ),
+            (&["unknown", "a"], &[]),
+        ];
+        for (request, expected) in cases {
+            let keys: Vec<&[u8]> = expected.iter().map(|k| k.as_bytes()).collect();
+            assert_eq!(command_keys(&args(request)), keys, "{:?}", request);
+        }
+    }
+
+    #[test]
+    fn test_arity() {
+        let cases: &[(&[&str], Option<&str>)] = &[
+            (&["get", "a"], None),
+            (&["get"], Some("get")),
+            (&["get", "a", "b"], Some("get")),
+            (&["del"], Some("del")),
+            (&["config", "get"], Some("config|get")),
+            (&["CONFIG", "SET", "a"], Some("config|set")),
+            (&["config"], Some("config")),
+        ];
+        for (request, expected) in cases {
+            let spec = command_spec(&args(request)).unwrap();
+            match (spec.parse(args(request)), expected) {
+                (Err(CommandError::WrongArity(name)), Some(expected)) => {
+                    assert_eq!(name, *expected)
+                }
+                (Ok(_), None) => {}
+                (ret, _) => panic!("{:?}: {:?}", request, ret),
+            }
+        }
+    }
+
+    #[test]
+    fn test_table_consistency() {
+        for spec in COMMAND_TABLE {
+            assert_eq!(spec.name, spec.name.to_ascii_lowercase());
+            assert!(spec.arity != 0, "{}", spec.name);
+            for sub in spec.subcommands {
+                assert!(sub.name.starts_with(&format!("{}|", spec.name)));
+                assert!(sub.arity.abs() >= 2, "{}", sub.name);
+            }
+        }
+    }
+}
@@ -0,0 +1,106 @@
+/// glob风格的模式匹配，支持 `*` `?` `[abc]` `[^a-z]` 和 `\` 转义
+pub fn glob_match(pattern: &[u8], s: &[u8], nocase: bool) -> bool {
+    let eq = |a: u8, b: u8| {
+        if nocase {
+            a.eq_ignore_ascii_case(&b)
+        } else {
+            a == b
+        }
+    };
+    let (mut p, mut i) = (0, 0);
+    // 上一个 `*` 的位置以及当时匹配到的字符串位置，失败时回溯
+    let mut star: Option<(usize, usize)> = None;
+    while i < s.len() {
+        let matched = match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, i));
+                p += 1;
+                continue;
+            }
+            Some(b'?') => Some(p + 1),
+            Some(b'[') => match_class(pattern, p, s[i], nocase),
+            Some(b'\\') if p + 1 < pattern.len() => eq(pattern[p + 1], s[i]).then_some(p + 2),
+            Some(&c) => eq(c, s[i]).then_some(p + 1),
+            None => None,
+        };
+        match (matched, star) {
+            (Some(next), _) => {
+                p = next;
+                i += 1;
+            }
+            (None, Some((sp, si))) => {
+                p = sp + 1;
+                i = si + 1;
+                star = Some((sp, si + 1));
+            }
+            (None, None) => return false,
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// 匹配 `[...]`，成功时返回之后的模式位置
+fn match_class(pattern: &[u8], start: usize, c: u8, nocase: bool) -> Option<usize> {
+    let lower = |c: u8| if nocase { c.to_ascii_lowercase() } else { c };
+    let c = lower(c);
+    let mut p = start + 1;
+    let negate = pattern.get(p) == Some(&b'^');
+    if negate {
+        p += 1;
+    }
+    let mut found = false;
+    loop {
+        match pattern.get(p) {
+            // 没有闭合的 `]` 当作到模式结尾
+            None => break,
+            Some(b']') => {
+                p += 1;
+                break;
+            }
+            Some(b'\\') if p + 1 < pattern.len() => {
+                found |= lower(pattern[p + 1]) == c;
+                p += 2;
+            }
+            Some(&from) if pattern.get(p + 1) == Some(&b'-') && p + 2 < pattern.len() => {
+                let (mut from, mut to) = (lower(from), lower(pattern[p + 2]));
+                if from > to {
+                    std::mem::swap(&mut from, &mut to);
+                }
+                found |= (from..=to).contains(&c);
+                p += 3;
+            }
+            Some(&other) => {
+                found |= lower(other) == c;
+                p += 1;
+            }
+        }
+    }
+    (found != negate).then_some(p)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        let m = |p: &str, s: &str| glob_match(p.as_bytes(), s.as_bytes(), false);
+        assert!(m("*", ""));
+        assert!(m("*", "maxmemory"));
+        assert!(m("max*", "maxmemory"));
+        assert!(m("*fsync", "appendfsync"));
+        assert!(m("*-*-*", "cluster-node-timeout"));
+        assert!(!m("max*", "appendonly"));
+        assert!(m("h?llo", "hello"));
+        assert!(!m("h?llo", "hllo"));
+        assert!(m("h[ae]llo", "hallo"));
+        assert!(!m("h[^e]llo", "hello"));
+        assert!(m("h[a-b]llo", "hbllo"));
+        assert!(m("h\\*llo", "h*llo"));
+        assert!(!m("h\\*llo", "hello"));
+        assert!(m("a*b*c", "axxbyyc"));
+        assert!(!m("a*b*c", "axxbyy"));
+        assert!(glob_match(b"PORT", b"port", true));
+        assert!(!glob_match(b"PORT", b"port", false));
+    }
+}
@@ -0,0 +1,505 @@
+//! 服务端配置
+//!
+//! 兼容redis.conf的格式，启动时依次应用配置文件、`SIMPLE_REDIS_<NAME>` 环境变量
+//! 和 `--name value` 命令行参数，后面的覆盖前面的。
+//! 参数的值直接读写各个模块的状态，CONFIG GET/SET 看到的就是正在运行的配置。
+
+mod glob;
+mod parser;
+mod registry;
+
+use std::{fs, path::PathBuf, sync::RwLock};
+
+use thiserror::Error;
+
+use crate::{acl::AclError, Backend};
+
+use self::{
+    parser::{parse_cli_args, parse_config, quote_arg, rewrite_config, Directive, RewriteOption},
+    registry::{find_entry, ConfigEntry, CONFIG_TABLE},
+};
+
+pub use self::glob::glob_match;
+
+/// 环境变量的前缀，例如 SIMPLE_REDIS_PORT 对应 port
+pub const ENV_PREFIX: &str = "SIMPLE_REDIS_";
+
+/// 配置相关的异常
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("IO Error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Bad directive at {location}: '{directive}' - {reason}")]
+    InvalidDirective {
+        location: String,
+        directive: String,
+        reason: String,
+    },
+    #[error("Unknown option or number of arguments for CONFIG SET - '{0}'")]
+    UnknownOption(String),
+    #[error("CONFIG SET failed (possibly related to argument '{name}') - {reason}")]
+    SetFailed { name: String, reason: String },
+    #[error("The server is running without a config file")]
+    NoConfigFile,
+    #[error("Error loading the ACL file: {0}")]
+    Acl(#[from] AclError),
+}
+
+/// 没有其他模块保存的配置
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerConfig {
+    /// 监听的地址，可以有多个
+    pub bind: Vec<String>,
+    pub port: u16,
+    /// 监听的Unix socket路径，为空时不监听
+    pub unixsocket: String,
+    /// Unix socket文件的权限，为0时使用默认权限
+    pub unixsocketperm: u32,
+    /// 同时连接的最大客户端数
+    pub maxclients: u64,
+    /// 客户端空闲多少秒之后断开，为0时不断开
+    pub timeout: u64,
+    /// TCP keepalive的时间(秒)，为0时不开启
+    pub tcp_keepalive: u64,
+    /// 请求中单个bulk string的最大长度
+    pub proto_max_bulk_len: u64,
+    pub cluster_enabled: bool,
+    pub cluster_config_file: String,
+    /// 关闭时等待replica的最长时间(秒)
+    pub shutdown_timeout: u64,
+    /// 默认用户的密码，为空时不需要密码
+    pub requirepass: String,
+    /// 保存用户的ACL文件
+    pub aclfile: String,
+    /// 连接master时使用的用户和密码
+    pub masteruser: String,
+    pub masterauth: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind: vec!["0.0.0.0".to_string()],
+            port: 6379,
+            unixsocket: String::new(),
+            unixsocketperm: 0,
+            maxclients: 10000,
+            timeout: 0,
+            tcp_keepalive: 300,
+            proto_max_bulk_len: 512 * 1024 * 1024,
+            cluster_enabled: false,
+            cluster_config_file: "nodes.conf".to_string(),
+            shutdown_timeout: 10,
+            requirepass: String::new(),
+            aclfile: String::new(),
+            masteruser: String::new(),
+            masterauth: String::new(),
+        }
+    }
+}
+
+/// 配置运行时状态，挂在Backend上共享
+#[derive(Debug, Default)]
+pub struct ConfigState {
+    config: RwLock<ServerConfig>,
+    /// 启动时使用的配置文件，CONFIG REWRITE 会重写这个文件
+    file: RwLock<Option<PathBuf>>,
+}
+
+impl ConfigState {
+    pub fn current(&self) -> ServerConfig {
+        self.config.read().unwrap().clone()
+    }
+
+    /// 只读取部分配置，避免每条命令都复制整个配置
+    pub fn read<T>(&self, f: impl FnOnce(&ServerConfig) -> T) -> T {
+        f(&self.config.read().unwrap())
+    }
+
+    pub fn update(&self, f: impl FnOnce(&mut ServerConfig)) {
+        f(&mut self.config.write().unwrap());
+    }
+
+    pub fn file(&self) -> Option<PathBuf> {
+        self.file.read().unwrap().clone()
+    }
+}
+
+/// 启动时加载配置：配置文件 < 环境变量 < 命令行参数
+pub fn load_config(
+    backend: &Backend,
+    args: impl IntoIterator<Item = String>,
+    env: impl IntoIterator<Item = (String, String)>,
+) -> Result<(), ConfigError> {
+    let (file, cli) = parse_cli_args(args)?;
+    if let Some(file) = file {
+        let content = fs::read_to_string(&file)?;
+        apply_directives(backend, parse_config(&content)?, |d| {
+            format!("line {}", d.line)
+        })?;
+        let path = fs::canonicalize(&file)?;
+        *backend.config().file.write().unwrap() = Some(path);
+    }
+
+    let mut from_env: Vec<(String, Directive)> = env
+        .into_iter()
+        .filter_map(|(key, value)| {
+            let name = key
+                .strip_prefix(ENV_PREFIX)?
+                .to_ascii_lowercase()
+                .replace('_', "-");
+            find_entry(&name)?;
+            Some((
+                key,
+                Directive {
+                    name,
+                    args: vec![value],
+                    line: 0,
+                },
+            ))
+        })
+        .collect();
+    // 环境变量的顺序不固定，按名字排序保证结果一致
+    from_env.sort_by(|a, b| a.0.cmp(&b.0));
+    for (key, directive) in from_env {
+        apply_directives(backend, vec![directive], |_| {
+            format!("environment variable {}", key)
+        })?;
+    }
+
+    apply_directives(backend, cli, |_| "command line".to_string())?;
+
+    // 配置了ACL文件时用文件中的用户代替requirepass
+    let aclfile = backend.config().current().aclfile;
+    if !aclfile.is_empty() {
+        backend.acl().load_file(&aclfile)?;
+    }
+    Ok(())
+}
+
+/// 应用一组指令，repeatable的参数多次出现时合并成一条
+fn apply_directives(
+    backend: &Backend,
+    directives: Vec<Directive>,
+    location: impl Fn(&Directive) -> String,
+) -> Result<(), ConfigError> {
+    let mut merged: Vec<(&'static ConfigEntry, Directive)> = Vec::new();
+    for directive in directives {
+        let invalid = |reason: &str| ConfigError::InvalidDirective {
+            location: location(&directive),
+            directive: directive.name.clone(),
+            reason: reason.to_string(),
+        };
+        let entry = find_entry(&directive.name).ok_or_else(|| invalid("Bad directive"))?;
+        if !entry.multi_arg && directive.args.len() != 1 {
+            return Err(invalid("wrong number of arguments"));
+        }
+        match merged
+            .iter_mut()
+            .find(|(e, _)| entry.repeatable && e.name == entry.name)
+        {
+            Some((_, first)) => first.args.extend(directive.args),
+            None => merged.push((entry, directive)),
+        }
+    }
+
+    for (entry, directive) in merged {
+        (entry.set)(backend, &directive.args.join(" ")).map_err(|reason| {
+            ConfigError::InvalidDirective {
+                location: location(&directive),
+                directive: format!("{} {}", directive.name, directive.args.join(" ")),
+                reason,
+            }
+        })?;
+    }
+    Ok(())
+}
+
+/// CONFIG GET，按照参数表的顺序返回名字匹配任意一个模式的参数，别名需要完整匹配
+pub fn config_get(backend: &Backend, patterns: &[String]) -> Vec<(String, String)> {
+    let mut ret = Vec::new();
+    for entry in CONFIG_TABLE {
+        if patterns
+            .iter()
+            .any(|p| glob_match(p.as_bytes(), entry.name.as_bytes(), true))
+        {
+            ret.push((entry.name.to_string(), (entry.get)(backend)));
+        }
+        if let Some(alias) = entry.alias {
+            if patterns.iter().any(|p| p.eq_ignore_ascii_case(alias)) {
+                ret.push((alias.to_string(), (entry.get)(backend)));
+            }
+        }
+    }
+    ret
+}
+
+/// CONFIG SET，所有参数要么都修改成功，要么都恢复原来的值
+pub fn config_set(backend: &Backend, pairs: Vec<(String, String)>) -> Result<(), ConfigError> {
+    let mut entries: Vec<(&'static ConfigEntry, String)> = Vec::new();
+    for (name, value) in pairs {
+        let entry = find_entry(&name.to_ascii_lowercase())
+            .ok_or_else(|| ConfigError::UnknownOption(name.clone()))?;
+        let failed = |reason: &str| ConfigError::SetFailed {
+            name: name.clone(),
+            reason: reason.to_string(),
+        };
+        if !entry.mutable {
+            return Err(failed("can't set immutable config"));
+        }
+        if entries.iter().any(|(e, _)| e.name == entry.name) {
+            return Err(failed("duplicate parameter"));
+        }
+        entries.push((entry, value));
+    }
+
+    let old: Vec<String> = entries.iter().map(|(e, _)| (e.get)(backend)).collect();
+    let restore = |count: usize| {
+        for ((entry, _), old) in entries.iter().zip(&old).take(count) {
+            let _ = (entry.set)(backend, old);
+        }
+    };
+    for (i, (entry, value)) in entries.iter().enumerate() {
+        if let Err(reason) = (entry.set)(backend, value) {
+            restore(i);
+            return Err(ConfigError::SetFailed {
+                name: entry.name.to_string(),
+                reason,
+            });
+        }
+    }
+
+    // 值发生变化的参数需要执行apply才能生效
+    let changed: Vec<&ConfigEntry> = entries
+        .iter()
+        .zip(&old)
+        .filter(|((entry, _), old)| (entry.get)(backend) != **old)
+        .map(|((entry, _), _)| *entry)
+        .collect();
+    for entry in &changed {
+        let Some(apply) = entry.apply else {
+            continue;
+        };
+        if let Err(reason) = apply(backend) {
+            restore(entries.len());
+            for entry in &changed {
+                if let Some(apply) = entry.apply {
+                    let _ = apply(backend);
+                }
+            }
+            return Err(ConfigError::SetFailed {
+                name: entry.name.to_string(),
+                reason,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// 写入配置文件的一行，多个参数的值原样拆开
+fn config_line(entry: &ConfigEntry, value: &str) -> String {
+    let value = if entry.multi_arg && !value.is_empty() {
+        value
+            .split_whitespace()
+            .map(quote_arg)
+            .collect::<Vec<_>>()
+            .join(" ")
+    } else {
+        quote_arg(value)
+    };
+    format!("{} {}", entry.name, value)
+}
+
+/// CONFIG REWRITE，保留原配置文件中的注释和顺序
+pub fn config_rewrite(backend: &Backend) -> Result<(), ConfigError> {
+    let path = backend.config().file().ok_or(ConfigError::NoConfigFile)?;
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let options: Vec<RewriteOption> = CONFIG_TABLE
+        .iter()
+        .map(|entry| {
+            let value = (entry.get)(backend);
+            let is_default = value == entry.default;
+            RewriteOption {
+                name: entry.name,
+                alias: entry.alias,
+                // 空的默认值(例如没有replicaof)直接删除
+                line: (!(is_default && value.is_empty())).then(|| config_line(entry, &value)),
+                force: !is_default,
+            }
+        })
+        .collect();
+
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, rewrite_config(&content, &options))?;
+    fs::rename(tmp, &path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replication::ReplicationRole;
+    use anyhow::Result;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn get(backend: &Backend, name: &str) -> String {
+        config_get(backend, &[name.to_string()])[0].1.clone()
+    }
+
+    #[test]
+    fn test_load_config() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("redis.conf");
+        let conf = format!(
+            "# 测试配置\nport 7000\ndir \"{}\"\nsave 60 1\nsave 300 10\nslaveof 127.0.0.1 6380\nappendfsync always\n",
+            dir.path().display()
+        );
+        fs::write(&path, conf)?;
+
+        let backend = Backend::new();
+        let env = vec![
+            ("SIMPLE_REDIS_PORT".to_string(), "7001".to_string()),
+            (
+                "SIMPLE_REDIS_REPL_BACKLOG_SIZE".to_string(),
+                "1mb".to_string(),
+            ),
+            ("HOME".to_string(), "/root".to_string()),
+        ];
+        let cli = args(&[
+            path.to_str().unwrap(),
+            "--port",
+            "7002",
+            "--bind",
+            "127.0.0.1",
+            "::1",
+        ]);
+        load_config(&backend, cli, env)?;
+
+        // 命令行参数覆盖环境变量，环境变量覆盖配置文件
+        assert_eq!(backend.config().current().port, 7002);
+        assert_eq!(backend.replication().listening_port(), 7002);
+        assert_eq!(backend.config().current().bind, vec!["127.0.0.1", "::1"]);
+        assert_eq!(backend.replication().backlog_size(), 1024 * 1024);
+        assert_eq!(backend.snapshot().config().dir, dir.path());
+        assert_eq!(backend.aof().config().dir, dir.path());
+        assert_eq!(get(&backend, "save"), "60 1 300 10");
+        assert_eq!(get(&backend, "appendfsync"), "always");
+        assert!(
+            matches!(backend.replication().role(), ReplicationRole::Replica(link) if link.port == 6380)
+        );
+        assert_eq!(backend.config().file(), Some(fs::canonicalize(&path)?));
+
+        let err = load_config(&Backend::new(), args(&["--no-such-option", "1"]), vec![]);
+        assert!(matches!(err, Err(ConfigError::InvalidDirective { .. })));
+        let err = load_config(&Backend::new(), args(&["--port", "abc"]), vec![]);
+        assert!(matches!(err, Err(ConfigError::InvalidDirective { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_get_set() {
+        let backend = Backend::new();
+        let names: Vec<String> = config_get(&backend, &["*fsync".to_string()])
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, vec!["appendfsync"]);
+        assert_eq!(
+            config_get(&backend, &["SLAVE-READ-ONLY".to_string()]),
+            vec![("slave-read-only".to_string(), "yes".to_string())]
+        );
+        assert!(config_get(&backend, &["*"].map(String::from))
+            .iter()
+            .all(|(name, _)| name != "slaveof"));
+
+        let set = |pairs: &[(&str, &str)]| {
+            config_set(
+                &backend,
+                pairs
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            )
+        };
+        set(&[("save", "100 10"), ("cluster-node-timeout", "500")]).unwrap();
+        assert_eq!(get(&backend, "save"), "100 10");
+        assert_eq!(backend.cluster().node_timeout(), 500);
+
+        assert!(matches!(
+            set(&[("port", "7000")]),
+            Err(ConfigError::SetFailed { .. })
+        ));
+        assert!(matches!(
+            set(&[("maxmemory-foo", "1")]),
+            Err(ConfigError::UnknownOption(_))
+        ));
+        // 任意一个参数失败时全部恢复
+        assert!(set(&[("save", ""), ("appendfsync", "sometimes")]).is_err());
+        assert_eq!(get(&backend, "save"), "100 10");
+        assert_eq!(get(&backend, "appendfsync"), "everysec");
+    }
+
+    #[test]
+    fn test_config_set_appendonly() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let backend = Backend::new();
+        backend.set("key".to_string(), crate::BulkString::new("value").into());
+        let set = |name: &str, value: &str| {
+            config_set(&backend, vec![(name.to_string(), value.to_string())])
+        };
+        set("dir", dir.path().to_str().unwrap())?;
+        set("appendonly", "yes")?;
+        // 开启时用当前数据生成AOF
+        let aof = backend.aof().config().path();
+        assert!(fs::read(&aof)?.windows(3).any(|w| w == b"key"));
+        set("appendonly", "no")?;
+        assert!(!backend.aof().is_enabled());
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_rewrite() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("redis.conf");
+        fs::write(
+            &path,
+            "# 注释会保留\nport 7000\n\n# 快照\nsave 60 1\nsave 300 10\nslave-read-only yes\n",
+        )?;
+        let backend = Backend::new();
+        assert!(matches!(
+            config_rewrite(&backend),
+            Err(ConfigError::NoConfigFile)
+        ));
+        load_config(&backend, args(&[path.to_str().unwrap()]), vec![])?;
+        config_set(
+            &backend,
+            vec![
+                ("save".to_string(), "".to_string()),
+                ("replica-read-only".to_string(), "no".to_string()),
+                ("dbfilename".to_string(), "my dump.srdb".to_string()),
+            ],
+        )?;
+        config_rewrite(&backend)?;
+        let content = fs::read_to_string(&path)?;
+        assert_eq!(
+            content,
+            "# 注释会保留\nport 7000\n\n# 快照\nsave \"\"\nreplica-read-only no\n\n# Generated by CONFIG REWRITE\ndbfilename \"my dump.srdb\"\n"
+        );
+
+        // 重写之后的文件可以重新加载得到相同的配置
+        let reloaded = Backend::new();
+        load_config(&reloaded, args(&[path.to_str().unwrap()]), vec![])?;
+        assert_eq!(
+            config_get(&reloaded, &["*".to_string()]),
+            config_get(&backend, &["*".to_string()])
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,284 @@
+use super::ConfigError;
+
+/// 配置文件中的一行指令
+#[derive(Debug, Clone, PartialEq)]
+pub struct Directive {
+    /// 小写的指令名
+    pub name: String,
+    pub args: Vec<String>,
+    /// 从1开始的行号
+    pub line: usize,
+}
+
+/// 按照redis.conf的规则拆分一行，支持双引号(可以转义)和单引号
+pub fn split_args(line: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(&first) = chars.peek() else {
+            return Ok(args);
+        };
+        let mut arg = String::new();
+        if first == '"' || first == '\'' {
+            chars.next();
+            loop {
+                match chars.next() {
+                    None => return Err("unbalanced quotes in configuration line".to_string()),
+                    Some(c) if c == first => break,
+                    Some('\\') if first == '"' => match chars.next() {
+                        Some('n') => arg.push('\n'),
+                        Some('r') => arg.push('\r'),
+                        Some('t') => arg.push('\t'),
+                        Some('b') => arg.push('\u{8}'),
+                        Some('a') => arg.push('\u{7}'),
+                        Some('x') => {
+                            let hex: String = (0..2).filter_map(|_| chars.next()).collect();
+                            let byte = u8::from_str_radix(&hex, 16)
+                                .map_err(|_| format!("invalid escape \\x{}", hex))?;
+                            arg.push(byte as char);
+                        }
+                        Some(c) => arg.push(c),
+                        None => return Err("unbalanced quotes in configuration line".to_string()),
+                    },
+                    Some('\\') if chars.peek() == Some(&'\'') => {
+                        chars.next();
+                        arg.push('\'');
+                    }
+                    Some(c) => arg.push(c),
+                }
+            }
+            // 闭合的引号之后必须是空白
+            if chars.peek().is_some_and(|c| !c.is_whitespace()) {
+                return Err("closing quote must be followed by a space".to_string());
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                arg.push(c);
+            }
+        }
+        args.push(arg);
+    }
+}
+
+/// 解析配置文件的内容，跳过空行和注释
+pub fn parse_config(content: &str) -> Result<Vec<Directive>, ConfigError> {
+    let mut directives = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let args = split_args(trimmed).map_err(|reason| ConfigError::InvalidDirective {
+            location: format!("line {}", i + 1),
+            directive: trimmed.to_string(),
+            reason,
+        })?;
+        let mut args = args.into_iter();
+        let Some(name) = args.next() else {
+            continue;
+        };
+        directives.push(Directive {
+            name: name.to_ascii_lowercase(),
+            args: args.collect(),
+            line: i + 1,
+        });
+    }
+    Ok(directives)
+}
+
+/// 解析命令行参数 `[config-file] [--name value ...]`，返回配置文件路径和其中的指令
+pub fn parse_cli_args(
+    args: impl IntoIterator<Item = String>,
+) -> Result<(Option<String>, Vec<Directive>), ConfigError> {
+    let mut args = args.into_iter().peekable();
+    let file = args.next_if(|arg| !arg.starts_with("--"));
+    let mut directives: Vec<Directive> = Vec::new();
+    for arg in args {
+        match (arg.strip_prefix("--"), directives.last_mut()) {
+            (Some(name), _) if !name.is_empty() => directives.push(Directive {
+                name: name.to_ascii_lowercase(),
+                args: Vec::new(),
+                line: 0,
+            }),
+            (_, Some(directive)) => directive.args.push(arg),
+            (_, None) => {
+                return Err(ConfigError::InvalidDirective {
+                    location: "command line".to_string(),
+                    directive: arg,
+                    reason: "option name must start with --".to_string(),
+                })
+            }
+        }
+    }
+    Ok((file, directives))
+}
+
+/// 必要时给参数加上引号，保证重新解析之后得到相同的值
+pub fn quote_arg(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_graphic() && c != '"' && c != '\'' && c != '\\');
+    if plain {
+        return arg.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for c in arg.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\x{:02x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// 重写配置文件时每个参数的状态
+#[derive(Debug)]
+pub struct RewriteOption {
+    pub name: &'static str,
+    pub alias: Option<&'static str>,
+    /// 写入配置文件的行，None表示删除已有的行
+    pub line: Option<String>,
+    /// 当前值和默认值不同，文件中没有时需要追加
+    pub force: bool,
+}
+
+/// 标记追加内容的注释
+const REWRITE_SIGNATURE: &str = "# Generated by CONFIG REWRITE";
+
+/// 在原有内容的基础上重写配置：保留注释和不认识的指令，
+/// 已有的参数替换为当前值，重复出现的只保留第一行，其余和默认值不同的参数追加到末尾
+pub fn rewrite_config(content: &str, options: &[RewriteOption]) -> String {
+    let find = |name: &str| {
+        options
+            .iter()
+            .position(|o| o.name == name || o.alias == Some(name))
+    };
+    let mut written = vec![false; options.len()];
+    let mut lines = Vec::new();
+    for line in content.lines() {
+        let trimmed = line.trim();
+        let name = match split_args(trimmed) {
+            Ok(args) if !trimmed.starts_with('#') => args.into_iter().next(),
+            _ => None,
+        };
+        match name.and_then(|name| find(&name.to_ascii_lowercase())) {
+            Some(i) => {
+                if !std::mem::replace(&mut written[i], true) {
+                    lines.extend(options[i].line.clone());
+                }
+            }
+            None => lines.push(line.to_string()),
+        }
+    }
+    // 去掉末尾的空行，避免每次重写都增加
+    while lines.last().is_some_and(|l| l.trim().is_empty()) {
+        lines.pop();
+    }
+
+    let appended: Vec<String> = options
+        .iter()
+        .zip(&written)
+        .filter(|(o, written)| o.force && !**written)
+        .filter_map(|(o, _)| o.line.clone())
+        .collect();
+    if !appended.is_empty() {
+        if !lines.is_empty() {
+            lines.push(String::new());
+        }
+        lines.push(REWRITE_SIGNATURE.to_string());
+        lines.extend(appended);
+    }
+    let mut ret = lines.join("\n");
+    ret.push('\n');
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_split_args() {
+        assert_eq!(split_args("port 6379").unwrap(), vec!["port", "6379"]);
+        assert_eq!(split_args("  save  \"\"  ").unwrap(), vec!["save", ""]);
+        assert_eq!(
+            split_args(r#"dir "/tmp/a b" 'c\'d' "e\"\x41""#).unwrap(),
+            vec!["dir", "/tmp/a b", "c'd", "e\"A"]
+        );
+        assert!(split_args("dir \"/tmp").is_err());
+        assert!(split_args("dir \"/tmp\"x").is_err());
+        for arg in ["", "a b", "x\"y", "\\", "line\n", "plain"] {
+            assert_eq!(split_args(&quote_arg(arg)).unwrap(), vec![arg]);
+        }
+    }
+
+    #[test]
+    fn test_parse_cli_args() -> Result<()> {
+        let args = [
+            "redis.conf",
+            "--port",
+            "7000",
+            "--save",
+            "60",
+            "1",
+            "--appendonly",
+        ];
+        let (file, directives) = parse_cli_args(args.map(String::from))?;
+        assert_eq!(file.as_deref(), Some("redis.conf"));
+        let directives: Vec<_> = directives
+            .into_iter()
+            .map(|d| (d.name, d.args.join(" ")))
+            .collect();
+        assert_eq!(
+            directives,
+            vec![
+                ("port".to_string(), "7000".to_string()),
+                ("save".to_string(), "60 1".to_string()),
+                ("appendonly".to_string(), "".to_string()),
+            ]
+        );
+        assert!(parse_cli_args(["a", "b"].map(String::from)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rewrite_config() {
+        let content = "# 端口\nport 6379\n\nsave 60 1\nsave 300 10\nunknown-directive yes\n";
+        let options = [
+            RewriteOption {
+                name: "port",
+                alias: None,
+                line: Some("port 7000".to_string()),
+                force: true,
+            },
+            RewriteOption {
+                name: "save",
+                alias: None,
+                line: Some("save 3600 1".to_string()),
+                force: false,
+            },
+            RewriteOption {
+                name: "replicaof",
+                alias: Some("slaveof"),
+                line: Some("replicaof 127.0.0.1 6380".to_string()),
+                force: true,
+            },
+        ];
+        let rewritten = rewrite_config(content, &options);
+        assert_eq!(
+            rewritten,
+            "# 端口\nport 7000\n\nsave 3600 1\nunknown-directive yes\n\n# Generated by CONFIG REWRITE\nreplicaof 127.0.0.1 6380\n"
+        );
+        // 再次重写内容不变
+        assert_eq!(rewrite_config(&rewritten, &options), rewritten);
+    }
+}
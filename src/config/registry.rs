@@ -0,0 +1,782 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    persistence::{self, AppendFsync, SaveRule, SnapshotFormat},
+    replication::ReplicationRole,
+    tls::TlsAuthClients,
+    Backend,
+};
+
+/// 让修改生效的回调
+type ApplyFn = fn(&Backend) -> Result<(), String>;
+
+/// 一个配置参数，值统一以字符串的形式读写，由各自的set负责解析和校验
+#[derive(Debug)]
+pub struct ConfigEntry {
+    pub name: &'static str,
+    /// 兼容旧名字，例如 slaveof
+    pub alias: Option<&'static str>,
+    /// 运行时可以通过CONFIG SET修改
+    pub mutable: bool,
+    /// 值由多个参数组成，例如 save 3600 1 300 100
+    pub multi_arg: bool,
+    /// 配置文件中多次出现时累加而不是覆盖
+    pub repeatable: bool,
+    pub default: &'static str,
+    pub get: fn(&Backend) -> String,
+    pub set: fn(&Backend, &str) -> Result<(), String>,
+    /// CONFIG SET修改了值之后执行，启动时不会执行
+    pub apply: Option<ApplyFn>,
+}
+
+/// 根据名字或者别名查找参数，名字需要是小写
+pub fn find_entry(name: &str) -> Option<&'static ConfigEntry> {
+    CONFIG_TABLE
+        .iter()
+        .find(|e| e.name == name || e.alias == Some(name))
+}
+
+pub fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => Err("argument must be 'yes' or 'no'".to_string()),
+    }
+}
+
+pub fn parse_int(value: &str, min: i64, max: i64) -> Result<i64, String> {
+    let n: i64 = value
+        .parse()
+        .map_err(|_| "argument couldn't be parsed into an integer".to_string())?;
+    if n < min || n > max {
+        return Err(format!(
+            "argument must be between {} and {} inclusive",
+            min, max
+        ));
+    }
+    Ok(n)
+}
+
+/// 解析内存大小，支持 k/kb/m/mb/g/gb 单位，k=1000，kb=1024
+pub fn parse_memory(value: &str) -> Result<u64, String> {
+    let lower = value.to_ascii_lowercase();
+    let units: [(&str, u64); 6] = [
+        ("kb", 1024),
+        ("mb", 1024 * 1024),
+        ("gb", 1024 * 1024 * 1024),
+        ("k", 1000),
+        ("m", 1000 * 1000),
+        ("g", 1000 * 1000 * 1000),
+    ];
+    let (num, unit) = units
+        .iter()
+        .find_map(|(suffix, unit)| lower.strip_suffix(suffix).map(|num| (num, *unit)))
+        .unwrap_or((lower.as_str(), 1));
+    num.parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit))
+        .ok_or_else(|| "argument must be a memory value".to_string())
+}
+
+/// 修改了TLS配置之后重新加载证书，新的连接生效
+fn reload_tls(backend: &Backend) -> Result<(), String> {
+    backend.tls().reload_if_enabled().map_err(|e| e.to_string())
+}
+
+fn yes_no(value: bool) -> String {
+    if value { "yes" } else { "no" }.to_string()
+}
+
+fn parse_save_rules(value: &str) -> Result<Vec<SaveRule>, String> {
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    if !parts.len().is_multiple_of(2) {
+        return Err("Invalid save parameters".to_string());
+    }
+    parts
+        .chunks(2)
+        .map(|pair| match (pair[0].parse(), pair[1].parse()) {
+            (Ok(seconds), Ok(changes)) => Ok(SaveRule { seconds, changes }),
+            _ => Err("Invalid save parameters".to_string()),
+        })
+        .collect()
+}
+
+fn set_dir(backend: &Backend, value: &str) -> Result<(), String> {
+    let dir = PathBuf::from(value);
+    if !dir.is_dir() {
+        return Err("No such file or directory".to_string());
+    }
+    let mut snapshot = backend.snapshot().config();
+    snapshot.dir = dir.clone();
+    backend.snapshot().set_config(snapshot);
+    let mut aof = backend.aof().config();
+    aof.dir = dir;
+    backend.aof().set_config(aof);
+    Ok(())
+}
+
+/// 文件名不能包含路径
+fn check_filename(value: &str, name: &str) -> Result<(), String> {
+    if value.is_empty() || Path::new(value).file_name() != Some(value.as_ref()) {
+        return Err(format!("{} can't be a path, just a filename", name));
+    }
+    Ok(())
+}
+
+/// 运行时开启AOF需要先用当前数据生成AOF文件，关闭时停止追加
+fn apply_appendonly(backend: &Backend) -> Result<(), String> {
+    let aof = backend.aof();
+    if aof.is_enabled() {
+        persistence::rewrite_aof(backend).map_err(|e| e.to_string())?;
+        aof.open().map_err(|e| e.to_string())
+    } else {
+        aof.close();
+        Ok(())
+    }
+}
+
+fn set_replicaof(backend: &Backend, value: &str) -> Result<(), String> {
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    let state = backend.replication();
+    match parts.as_slice() {
+        [] => state.promote(),
+        [no, one] if no.eq_ignore_ascii_case("no") && one.eq_ignore_ascii_case("one") => {
+            state.promote()
+        }
+        [host, port] => {
+            let port = parse_int(port, 1, u16::MAX as i64)?;
+            state.set_master(host, port as u16);
+        }
+        _ => return Err("wrong number of arguments".to_string()),
+    }
+    Ok(())
+}
+
+/// 所有支持的配置参数
+pub static CONFIG_TABLE: &[ConfigEntry] = &[
+    ConfigEntry {
+        name: "bind",
+        alias: None,
+        mutable: false,
+        multi_arg: true,
+        repeatable: false,
+        default: "0.0.0.0",
+        get: |backend| backend.config().current().bind.join(" "),
+        set: |backend, value| {
+            let addrs: Vec<String> = value.split_whitespace().map(String::from).collect();
+            if addrs.is_empty() {
+                return Err("bind requires at least one address".to_string());
+            }
+            backend.config().update(|c| c.bind = addrs);
+            Ok(())
+        },
+        apply: None,
+    },
+    ConfigEntry {
+        name: "port",
+        alias: None,
+        mutable: false,
+        multi_arg: false,
+        repeatable: false,
+        default: "6379",
+        get: |backend| backend.config().current().port.to_string(),
+        set: |backend, value| {
+            let port = parse_int(value, 0, u16::MAX as i64)? as u16;
+            backend.config().update(|c| c.port = port);
+            // 握手时告知master的端口
+            backend.replication().set_listening_port(port);
+            Ok(())
+        },
+        apply: None,
+    },
+    ConfigEntry {
+        name: "unixsocket",
+        alias: None,
+        mutable: false,
+        multi_arg: false,
+        repeatable: false,
+        default: "",
+        get: |backend| backend.config().current().unixsocket,
+        set: |backend, value| {
+            let value = value.to_string();
+            backend.config().update(|c| c.unixsocket = value);
+            Ok(())
+        },
+        apply: None,
+    },
+    ConfigEntry {
+        name: "unixsocketperm",
+        alias: None,
+        mutable: false,
+        multi_arg: false,
+        repeatable: false,
+        default: "0",
+        get: |backend| format!("{:o}", backend.config().current().unixsocketperm),
+        set: |backend, value| {
+            // 和chmod一样使用八进制
+            let perm = u32::from_str_radix(value, 8)
+                .ok()
+                .filter(|perm| *perm <= 0o777)
+                .ok_or_else(|| "argument must be an octal number between 0 and 777".to_string())?;
+            backend.config().update(|c| c.unixsocketperm = perm);
+            Ok(())
+        },
+        apply: None,
+    },
+    ConfigEntry {
+        name: "maxclients",
+        alias: None,
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: "10000",
+        get: |backend| backend.config().read(|c| c.maxclients).to_string(),
+        set: |backend, value| {
+            let maxclients = parse_int(value, 1, i64::MAX)? as u64;
+            backend.config().update(|c| c.maxclients = maxclients);
+            Ok(())
+        },
+        apply: None,
+    },
+    ConfigEntry {
+        name: "timeout",
+        alias: None,
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: "0",
+        get: |backend| backend.config().read(|c| c.timeout).to_string(),
+        set: |backend, value| {
+            let timeout = parse_int(value, 0, i32::MAX as i64)? as u64;
+            backend.config().update(|c| c.timeout = timeout);
+            Ok(())
+        },
+        apply: None,
+    },
+    ConfigEntry {
+        name: "tcp-keepalive",
+        alias: None,
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: "300",
+        get: |backend| backend.config().read(|c| c.tcp_keepalive).to_string(),
+        set: |backend, value| {
+            let tcp_keepalive = parse_int(value, 0, i32::MAX as i64)? as u64;
+            backend.config().update(|c| c.tcp_keepalive = tcp_keepalive);
+            Ok(())
+        },
+        apply: None,
+    },
+    ConfigEntry {
+        name: "proto-max-bulk-len",
+        alias: None,
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: "536870912",
+        get: |backend| backend.config().read(|c| c.proto_max_bulk_len).to_string(),
+        set: |backend, value| {
+            let len = parse_memory(value)?;
+            if len < 1024 * 1024 {
+                return Err("argument must be a memory value greater than 1mb".to_string());
+            }
+            backend.config().update(|c| c.proto_max_bulk_len = len);
+            Ok(())
+        },
+        apply: None,
+    },
+    ConfigEntry {
+        name: "dir",
+        alias: None,
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: ".",
+        get: |backend| backend.snapshot().config().dir.display().to_string(),
+        set: set_dir,
+        apply: None,
+    },
+    ConfigEntry {
+        name: "dbfilename",
+        alias: None,
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: "dump.srdb",
+        get: |backend| backend.snapshot().config().dbfilename,
+        set: |backend, value| {
+            check_filename(value, "dbfilename")?;
+            let mut config = backend.snapshot().config();
+            config.dbfilename = value.to_string();
+            backend.snapshot().set_config(config);
+            Ok(())
+        },
+        apply: None,
+    },
+    ConfigEntry {
+        name: "save",
+        alias: None,
+        mutable: true,
+        multi_arg: true,
+        repeatable: true,
+        default: "3600 1 300 100 60 10000",
+        get: |backend| {
+            backend
+                .snapshot()
+                .config()
+                .save_rules
+                .iter()
+                .map(|r| format!("{} {}", r.seconds, r.changes))
+                .collect::<Vec<_>>()
+                .join(" ")
+        },
+        set: |backend, value| {
+            let mut config = backend.snapshot().config();
+            config.save_rules = parse_save_rules(value)?;
+            backend.snapshot().set_config(config);
+            Ok(())
+        },
+        apply: None,
+    },
+    ConfigEntry {
+        name: "snapshot-format",
+        alias: None,
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: "native",
+        get: |backend| {
+            match backend.snapshot().config().format {
+                SnapshotFormat::Native => "native",
+                SnapshotFormat::Rdb => "rdb",
+            }
+            .to_string()
+        },
+        set: |backend, value| {
+            let mut config = backend.snapshot().config();
+            config.format = match value.to_ascii_lowercase().as_str() {
+                "native" => SnapshotFormat::Native,
+                "rdb" => SnapshotFormat::Rdb,
+                _ => return Err("argument(s) must be one of the following: native, rdb".into()),
+            };
+            backend.snapshot().set_config(config);
+            Ok(())
+        },
+        apply: None,
+    },
+    ConfigEntry {
+        name: "appendonly",
+        alias: None,
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: "no",
+        get: |backend| yes_no(backend.aof().is_enabled()),
+        set: |backend, value| {
+            let mut config = backend.aof().config();
+            config.enabled = parse_bool(value)?;
+            backend.aof().set_config(config);
+            Ok(())
+        },
+        apply: Some(apply_appendonly),
+    },
+    ConfigEntry {
+        name: "appendfilename",
+        alias: None,
+        mutable: false,
+        multi_arg: false,
+        repeatable: false,
+        default: "appendonly.aof",
+        get: |backend| backend.aof().config().filename,
+        set: |backend, value| {
+            check_filename(value, "appendfilename")?;
+            let mut config = backend.aof().config();
+            config.filename = value.to_string();
+            backend.aof().set_config(config);
+            Ok(())
+        },
+        apply: None,
+    },
+    ConfigEntry {
+        name: "appendfsync",
+        alias: None,
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: "everysec",
+        get: |backend| backend.aof().config().fsync.to_string(),
+        set: |backend, value| {
+            let mut config = backend.aof().config();
+            config.fsync = value.parse::<AppendFsync>().map_err(|_| {
+                "argument(s) must be one of the following: always, everysec, no".to_string()
+            })?;
+            backend.aof().set_config(config);
+            Ok(())
+        },
+        apply: None,
+    },
+    ConfigEntry {
+        name: "replicaof",
+        alias: Some("slaveof"),
+        mutable: false,
+        multi_arg: true,
+        repeatable: false,
+        default: "",
+        get: |backend| match backend.replication().role() {
+            ReplicationRole::Master => String::new(),
+            ReplicationRole::Replica(link) => format!("{} {}", link.host, link.port),
+        },
+        set: set_replicaof,
+        apply: None,
+    },
+    ConfigEntry {
+        name: "replica-read-only",
+        alias: Some("slave-read-only"),
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: "yes",
+        get: |backend| yes_no(backend.replication().read_only_config()),
+        set: |backend, value| {
+            backend.replication().set_read_only(parse_bool(value)?);
+            Ok(())
+        },
+        apply: None,
+    },
+    ConfigEntry {
+        name: "repl-backlog-size",
+        alias: None,
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: "1048576",
+        get: |backend| backend.replication().backlog_size().to_string(),
+        set: |backend, value| {
+            let size = parse_memory(value)?.max(1);
+            backend.replication().set_backlog_size(size as usize);
+            Ok(())
+        },
+        apply: None,
+    },
+    ConfigEntry {
+        name: "cluster-enabled",
+        alias: None,
+        mutable: false,
+        multi_arg: false,
+        repeatable: false,
+        default: "no",
+        get: |backend| yes_no(backend.config().current().cluster_enabled),
+        set: |backend, value| {
+            let enabled = parse_bool(value)?;
+            backend.config().update(|c| c.cluster_enabled = enabled);
+            Ok(())
+        },
+        apply: None,
+    },
+    ConfigEntry {
+        name: "cluster-config-file",
+        alias: None,
+        mutable: false,
+        multi_arg: false,
+        repeatable: false,
+        default: "nodes.conf",
+        get: |backend| backend.config().current().cluster_config_file,
+        set: |backend, value| {
+            let value = value.to_string();
+            backend.config().update(|c| c.cluster_config_file = value);
+            Ok(())
+        },
+        apply: None,
+    },
+    ConfigEntry {
+        name: "cluster-node-timeout",
+        alias: None,
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: "15000",
+        get: |backend| backend.cluster().node_timeout().to_string(),
+        set: |backend, value| {
+            let timeout = parse_int(value, 1, i64::MAX)?;
+            backend.cluster().set_node_timeout(timeout as u64);
+            Ok(())
+        },
+        apply: None,
+    },
+    ConfigEntry {
+        name: "shutdown-timeout",
+        alias: None,
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: "10",
+        get: |backend| backend.config().current().shutdown_timeout.to_string(),
+        set: |backend, value| {
+            let timeout = parse_int(value, 0, i32::MAX as i64)? as u64;
+            backend.config().update(|c| c.shutdown_timeout = timeout);
+            Ok(())
+        },
+        apply: None,
+    },
+    ConfigEntry {
+        name: "requirepass",
+        alias: None,
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: "",
+        get: |backend| backend.config().current().requirepass,
+        set: |backend, value| {
+            let value = value.to_string();
+            backend.acl().set_requirepass(&value);
+            backend.config().update(|c| c.requirepass = value);
+            Ok(())
+        },
+        apply: None,
+    },
+    ConfigEntry {
+        name: "aclfile",
+        alias: None,
+        mutable: false,
+        multi_arg: false,
+        repeatable: false,
+        default: "",
+        get: |backend| backend.config().current().aclfile,
+        set: |backend, value| {
+            let value = value.to_string();
+            backend.config().update(|c| c.aclfile = value);
+            Ok(())
+        },
+        apply: None,
+    },
+    ConfigEntry {
+        name: "acllog-max-len",
+        alias: None,
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: "128",
+        get: |backend| backend.acl().log_max_len().to_string(),
+        set: |backend, value| {
+            let max_len = parse_int(value, 0, i32::MAX as i64)? as usize;
+            backend.acl().set_log_max_len(max_len);
+            Ok(())
+        },
+        apply: None,
+    },
+    ConfigEntry {
+        name: "masteruser",
+        alias: None,
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: "",
+        get: |backend| backend.config().current().masteruser,
+        set: |backend, value| {
+            let value = value.to_string();
+            backend.config().update(|c| c.masteruser = value);
+            Ok(())
+        },
+        apply: None,
+    },
+    ConfigEntry {
+        name: "masterauth",
+        alias: None,
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: "",
+        get: |backend| backend.config().current().masterauth,
+        set: |backend, value| {
+            let value = value.to_string();
+            backend.config().update(|c| c.masterauth = value);
+            Ok(())
+        },
+        apply: None,
+    },
+    ConfigEntry {
+        name: "tls-port",
+        alias: None,
+        mutable: false,
+        multi_arg: false,
+        repeatable: false,
+        default: "0",
+        get: |backend| backend.tls().config().port.to_string(),
+        set: |backend, value| {
+            let mut config = backend.tls().config();
+            config.port = parse_int(value, 0, u16::MAX as i64)? as u16;
+            backend.tls().set_config(config);
+            Ok(())
+        },
+        apply: None,
+    },
+    ConfigEntry {
+        name: "tls-cert-file",
+        alias: None,
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: "",
+        get: |backend| backend.tls().config().cert_file,
+        set: |backend, value| {
+            let mut config = backend.tls().config();
+            config.cert_file = value.to_string();
+            backend.tls().set_config(config);
+            Ok(())
+        },
+        apply: Some(reload_tls),
+    },
+    ConfigEntry {
+        name: "tls-key-file",
+        alias: None,
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: "",
+        get: |backend| backend.tls().config().key_file,
+        set: |backend, value| {
+            let mut config = backend.tls().config();
+            config.key_file = value.to_string();
+            backend.tls().set_config(config);
+            Ok(())
+        },
+        apply: Some(reload_tls),
+    },
+    ConfigEntry {
+        name: "tls-ca-cert-file",
+        alias: None,
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: "",
+        get: |backend| backend.tls().config().ca_cert_file,
+        set: |backend, value| {
+            let mut config = backend.tls().config();
+            config.ca_cert_file = value.to_string();
+            backend.tls().set_config(config);
+            Ok(())
+        },
+        apply: Some(reload_tls),
+    },
+    ConfigEntry {
+        name: "tls-ca-cert-dir",
+        alias: None,
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: "",
+        get: |backend| backend.tls().config().ca_cert_dir,
+        set: |backend, value| {
+            let mut config = backend.tls().config();
+            config.ca_cert_dir = value.to_string();
+            backend.tls().set_config(config);
+            Ok(())
+        },
+        apply: Some(reload_tls),
+    },
+    ConfigEntry {
+        name: "tls-auth-clients",
+        alias: None,
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: "yes",
+        get: |backend| backend.tls().config().auth_clients.to_string(),
+        set: |backend, value| {
+            let mut config = backend.tls().config();
+            config.auth_clients = value.parse::<TlsAuthClients>()?;
+            backend.tls().set_config(config);
+            Ok(())
+        },
+        apply: Some(reload_tls),
+    },
+    ConfigEntry {
+        name: "tls-protocols",
+        alias: None,
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: "",
+        get: |backend| backend.tls().config().protocols,
+        set: |backend, value| {
+            let mut config = backend.tls().config();
+            config.protocols = value.to_string();
+            backend.tls().set_config(config);
+            Ok(())
+        },
+        apply: Some(reload_tls),
+    },
+    ConfigEntry {
+        name: "tls-ciphers",
+        alias: None,
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: "",
+        get: |backend| backend.tls().config().ciphers,
+        set: |backend, value| {
+            let mut config = backend.tls().config();
+            config.ciphers = value.to_string();
+            backend.tls().set_config(config);
+            Ok(())
+        },
+        apply: Some(reload_tls),
+    },
+    ConfigEntry {
+        name: "tls-ciphersuites",
+        alias: None,
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: "",
+        get: |backend| backend.tls().config().ciphersuites,
+        set: |backend, value| {
+            let mut config = backend.tls().config();
+            config.ciphersuites = value.to_string();
+            backend.tls().set_config(config);
+            Ok(())
+        },
+        apply: Some(reload_tls),
+    },
+    ConfigEntry {
+        name: "tls-prefer-server-ciphers",
+        alias: None,
+        mutable: true,
+        multi_arg: false,
+        repeatable: false,
+        default: "no",
+        get: |backend| yes_no(backend.tls().config().prefer_server_ciphers),
+        set: |backend, value| {
+            let mut config = backend.tls().config();
+            config.prefer_server_ciphers = parse_bool(value)?;
+            backend.tls().set_config(config);
+            Ok(())
+        },
+        apply: Some(reload_tls),
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults() {
+        // 默认值和新建的Backend一致，CONFIG REWRITE依赖默认值判断是否需要写入
+        let backend = Backend::new();
+        for entry in CONFIG_TABLE {
+            assert_eq!((entry.get)(&backend), entry.default, "{}", entry.name);
+        }
+    }
+
+    #[test]
+    fn test_parse_memory() {
+        assert_eq!(parse_memory("1024"), Ok(1024));
+        assert_eq!(parse_memory("1k"), Ok(1000));
+        assert_eq!(parse_memory("2KB"), Ok(2048));
+        assert_eq!(parse_memory("1mb"), Ok(1024 * 1024));
+        assert!(parse_memory("1xb").is_err());
+        assert!(parse_memory("-1").is_err());
+    }
+}
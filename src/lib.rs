@@ -1,6 +1,15 @@
+pub mod acl;
 mod backend;
+pub mod clients;
+pub mod cluster;
 pub mod cmd;
+pub mod config;
 pub mod network;
+pub mod persistence;
+pub mod replication;
 mod resp;
+pub mod sentinel;
+pub mod shutdown;
+pub mod tls;
 pub use backend::*;
 pub use resp::*;
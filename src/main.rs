@@ -1,21 +1,148 @@
-use anyhow::Result;
-use simple_redis::{network, Backend};
-use tokio::net::TcpListener;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use simple_redis::{cluster, config, network, persistence, replication, shutdown, tls, Backend};
+use tokio::{net::TcpListener, task::JoinSet};
+
+/// 关闭时等待连接处理完当前命令的最长时间
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[tokio::main]
 async fn main() -> Result<()> {
     tracing_subscriber::fmt::init();
 
-    let addr = "0.0.0.0:6379";
-    let listener = TcpListener::bind(addr).await?;
-    tracing::info!("Simple-Redis-Server listening on: {}", addr);
-
+    // simple-redis [redis.conf] [--name value ...]
     let backend = Backend::new();
+    config::load_config(&backend, std::env::args().skip(1), std::env::vars())?;
+    let server_config = backend.config().current();
+
+    let mut listeners = Vec::new();
+    for addr in &server_config.bind {
+        let listener = TcpListener::bind((addr.as_str(), server_config.port)).await?;
+        tracing::info!(
+            "Simple-Redis-Server listening on: {}",
+            listener.local_addr()?
+        );
+        listeners.push(listener);
+    }
+    // 开启TLS时加载证书并监听TLS端口
+    let mut tls_listeners = Vec::new();
+    let tls_config = backend.tls().config();
+    if tls_config.port != 0 {
+        backend.tls().reload()?;
+        for addr in &server_config.bind {
+            let listener = TcpListener::bind((addr.as_str(), tls_config.port)).await?;
+            tracing::info!(
+                "Simple-Redis-Server listening for TLS on: {}",
+                listener.local_addr()?
+            );
+            tls_listeners.push(listener);
+        }
+    }
+    // 本机的客户端可以通过Unix socket连接
+    #[cfg(unix)]
+    let unix_listener = if server_config.unixsocket.is_empty() {
+        None
+    } else {
+        let listener = network::bind_unix(&server_config.unixsocket, server_config.unixsocketperm)?;
+        tracing::info!(
+            "Simple-Redis-Server listening on unix socket: {}",
+            server_config.unixsocket
+        );
+        Some(listener)
+    };
+    // 端口为0时使用实际分配的端口
+    let port = listeners[0].local_addr()?.port();
+    backend.replication().set_listening_port(port);
+
+    // 集群模式下加载或者创建集群配置文件
+    if server_config.cluster_enabled {
+        if backend.replication().is_replica() {
+            bail!("replicaof directive not allowed in cluster mode");
+        }
+        let cport = port
+            .checked_add(10000)
+            .ok_or_else(|| anyhow!("port must be less than 55536 in cluster mode"))?;
+        let ip = match server_config.bind[0].as_str() {
+            "0.0.0.0" | "::" => "127.0.0.1",
+            ip => ip,
+        };
+        backend
+            .cluster()
+            .load_or_create(&server_config.cluster_config_file, ip, port, cport)?;
+    }
+
+    // 后台加载数据，加载完成前客户端会收到-LOADING
+    let loading = persistence::spawn_load(&backend);
+    let cloned_backend = backend.clone();
+    tokio::spawn(async move {
+        match loading.await {
+            Ok(Err(e)) => tracing::error!("Failed to load data: {}", e),
+            Err(e) => tracing::error!("Data loading task failed: {}", e),
+            _ => {}
+        }
+        // 加载完成之后再与配置的master同步
+        replication::start_replication(&cloned_backend);
+    });
+    // 按照save规则自动保存
+    tokio::spawn(persistence::run_save_cron(backend.clone()));
+    // everysec策略下定时fsync AOF
+    tokio::spawn(persistence::run_aof_cron(backend.clone()));
+    // 定期PING replica
+    tokio::spawn(replication::run_replication_cron(backend.clone()));
+    // 集群模式下监听集群总线端口
+    if let Some(myself) = backend.cluster().myself() {
+        let bus = TcpListener::bind((server_config.bind[0].as_str(), myself.cport)).await?;
+        tokio::spawn(cluster::run_cluster_bus(backend.clone(), bus));
+    }
+
+    // SIGTERM/SIGINT 触发和SHUTDOWN一样的关闭流程
+    tokio::spawn(shutdown::handle_signals(backend.clone()));
+
+    let mut servers = JoinSet::new();
+    for listener in listeners {
+        servers.spawn(serve(listener, backend.clone()));
+    }
+    if !tls_listeners.is_empty() {
+        tokio::spawn(tls::run_tls_cron(backend.clone()));
+    }
+    for listener in tls_listeners {
+        servers.spawn(tls::serve_tls(listener, backend.clone()));
+    }
+    #[cfg(unix)]
+    if let Some(listener) = unix_listener {
+        servers.spawn(network::serve_unix(listener, backend.clone()));
+    }
+    while let Some(ret) = servers.join_next().await {
+        ret??;
+    }
+
+    // 监听已经停止，等待连接回复完正在执行的命令
+    if !backend.shutdown().wait_connections(DRAIN_TIMEOUT).await {
+        tracing::warn!(
+            "{} connection(s) still active, exiting anyway",
+            backend.shutdown().connections()
+        );
+    }
+    #[cfg(unix)]
+    if !server_config.unixsocket.is_empty() {
+        let _ = std::fs::remove_file(&server_config.unixsocket);
+    }
+    Ok(())
+}
 
+async fn serve(listener: TcpListener, backend: Backend) -> Result<()> {
     loop {
         let cloned_backend = backend.clone();
-        let (stream, remote_addr) = listener.accept().await?;
+        let (stream, remote_addr) = tokio::select! {
+            _ = backend.shutdown().closed() => return Ok(()),
+            ret = listener.accept() => ret?,
+        };
         tracing::info!("Accepted connection from: {}", remote_addr);
+        let keepalive = backend.config().read(|c| c.tcp_keepalive);
+        if let Err(e) = network::set_tcp_keepalive(&stream, keepalive) {
+            tracing::warn!("Failed to set keepalive for {}: {}", remote_addr, e);
+        }
         tokio::spawn(async move {
             match network::stream_handler(stream, cloned_backend).await {
                 Ok(_) => {
@@ -0,0 +1,221 @@
+use crate::{
+    acl::AclDenied,
+    clients::validate_name,
+    cmd::{Auth, ErrorCode, Hello},
+    Backend, BulkString, RespArray, RespFrame, RespMap, SimpleString,
+};
+
+use super::ConnectionState;
+
+/// 检查连接是否已经认证以及用户是否有权限执行命令，拒绝时返回错误回复
+pub(super) fn authorize(
+    backend: &Backend,
+    state: &mut ConnectionState,
+    frame: &RespFrame,
+) -> Option<RespFrame> {
+    let RespFrame::Array(args) = frame else {
+        return None;
+    };
+    let Some(RespFrame::BulkString(cmd)) = args.first() else {
+        return None;
+    };
+    // 认证之前就可以执行的命令，任何用户都可以执行
+    if backend
+        .commands()
+        .lookup(cmd)
+        .is_some_and(|spec| spec.has_flag("no_auth"))
+    {
+        return None;
+    }
+    if !state.authenticated {
+        return Some(ErrorCode::NoAuth.reply("Authentication required."));
+    }
+    match backend.acl().check(&state.client.user(), args) {
+        Ok(()) => None,
+        // 用户已经被删除，断开连接
+        Err(AclDenied::NoUser(_)) => {
+            state.close = true;
+            None
+        }
+        Err(denied) => {
+            backend
+                .acl()
+                .log_denied(&denied, &state.client.user(), &state.client.info());
+            Some(denied.into())
+        }
+    }
+}
+
+/// 校验用户名和密码，成功之后连接切换到这个用户
+fn login(
+    backend: &Backend,
+    state: &mut ConnectionState,
+    username: &str,
+    password: &str,
+) -> Result<(), RespFrame> {
+    if !backend.acl().authenticate(username, password) {
+        backend
+            .acl()
+            .log_auth_failure(username, &state.client.info());
+        return Err(
+            ErrorCode::WrongPass.reply("invalid username-password pair or user is disabled.")
+        );
+    }
+    state.client.set_user(username);
+    state.authenticated = true;
+    Ok(())
+}
+
+/// AUTH [username] password
+pub(super) fn auth(backend: &Backend, state: &mut ConnectionState, auth: &Auth) -> RespFrame {
+    if auth.is_legacy() && backend.acl().default_nopass() {
+        return ErrorCode::Err.reply("AUTH <password> called without any password configured for the default user. Are you sure your configuration is correct?");
+    }
+    match login(backend, state, auth.username(), auth.password()) {
+        Ok(()) => SimpleString::new("OK").into(),
+        Err(frame) => frame,
+    }
+}
+
+/// HELLO [protover [AUTH username password] [SETNAME clientname]]
+pub(super) fn hello(backend: &Backend, state: &mut ConnectionState, hello: &Hello) -> RespFrame {
+    let protocol = hello.protover().unwrap_or(state.client.protocol());
+    if !(2..=3).contains(&protocol) {
+        return ErrorCode::NoProto.reply("unsupported protocol version");
+    }
+    match hello.auth() {
+        Some((username, password)) => {
+            if let Err(frame) = login(backend, state, username, password) {
+                return frame;
+            }
+        }
+        None if !state.authenticated => {
+            return ErrorCode::NoAuth.reply("HELLO must be called with the client already authenticated, otherwise the HELLO <proto> AUTH <user> <pass> option can be used to authenticate the client and select the RESP protocol version at the same time");
+        }
+        None => {}
+    }
+    if let Some(name) = hello.setname() {
+        if let Err(e) = validate_name(name) {
+            return ErrorCode::Err.reply(e);
+        }
+        state
+            .client
+            .set_name((!name.is_empty()).then(|| name.to_string()));
+    }
+    state.client.set_protocol(protocol);
+
+    let mode = if backend.cluster().is_enabled() {
+        "cluster"
+    } else {
+        "standalone"
+    };
+    let role = if backend.replication().is_replica() {
+        "replica"
+    } else {
+        "master"
+    };
+    let mut map = RespMap::default();
+    map.insert("server".to_string(), BulkString::new("redis").into());
+    map.insert(
+        "version".to_string(),
+        BulkString::new(env!("CARGO_PKG_VERSION")).into(),
+    );
+    map.insert("proto".to_string(), RespFrame::Integer(protocol));
+    map.insert(
+        "id".to_string(),
+        RespFrame::Integer(state.client.id() as i64),
+    );
+    map.insert("mode".to_string(), BulkString::new(mode).into());
+    map.insert("role".to_string(), BulkString::new(role).into());
+    map.insert("modules".to_string(), RespArray::new(vec![]).into());
+    map.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{network, Backend, BulkString, RespArray, RespEncode, RespFrame};
+    use anyhow::Result;
+    use futures::StreamExt;
+    use tokio::{
+        io::AsyncWriteExt,
+        net::{TcpListener, TcpStream},
+    };
+    use tokio_util::codec::Framed;
+
+    async fn request(
+        client: &mut Framed<TcpStream, network::RedisCodec>,
+        args: &[&str],
+    ) -> Result<RespFrame> {
+        let args = RespArray::new(
+            args.iter()
+                .map(|arg| BulkString::new(*arg).into())
+                .collect::<Vec<RespFrame>>(),
+        );
+        client.get_mut().write_all(&args.encode()).await?;
+        client.next().await.unwrap()
+    }
+
+    fn is_error(frame: &RespFrame, prefix: &str) -> bool {
+        matches!(frame, RespFrame::Error(e) if e.starts_with(prefix))
+    }
+
+    #[tokio::test]
+    async fn test_auth_acl() -> Result<()> {
+        let backend = Backend::new();
+        backend.acl().set_requirepass("foobared");
+        backend.acl().set_user(
+            "alice",
+            &["on", ">secret", "~cache:*", "+@read", "+acl|whoami"],
+        )?;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let cloned = backend.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(network::stream_handler(stream, cloned.clone()));
+            }
+        });
+
+        let mut client = Framed::new(
+            TcpStream::connect(addr).await?,
+            network::RedisCodec::default(),
+        );
+        let ret = request(&mut client, &["get", "cache:1"]).await?;
+        assert!(is_error(&ret, "NOAUTH"));
+        let ret = request(&mut client, &["auth", "wrong"]).await?;
+        assert!(is_error(&ret, "WRONGPASS"));
+        let ret = request(&mut client, &["auth", "foobared"]).await?;
+        assert_eq!(ret, RespFrame::SimpleString("OK".into()));
+        let ret = request(&mut client, &["acl", "whoami"]).await?;
+        assert_eq!(ret, BulkString::new("default").into());
+
+        // RESP2下HELLO返回key value交替的数组
+        let ret = request(&mut client, &["hello"]).await?;
+        assert!(matches!(ret, RespFrame::Array(arr) if arr[0] == BulkString::new("id").into()));
+        // HELLO AUTH 切换用户，同时切换到RESP3
+        let ret = request(&mut client, &["hello", "3", "auth", "alice", "secret"]).await?;
+        assert!(matches!(ret, RespFrame::Map(map) if map["proto"] == RespFrame::Integer(3)));
+        let ret = request(&mut client, &["acl", "whoami"]).await?;
+        assert_eq!(ret, BulkString::new("alice").into());
+        let ret = request(&mut client, &["get", "cache:1"]).await?;
+        assert_eq!(ret, crate::RespNull.into());
+        let ret = request(&mut client, &["get", "other"]).await?;
+        assert!(is_error(&ret, "NOPERM No permissions to access a key"));
+        let ret = request(&mut client, &["set", "cache:1", "v"]).await?;
+        assert!(is_error(
+            &ret,
+            "NOPERM User alice has no permissions to run the 'set'"
+        ));
+
+        let entries = backend.acl().log_entries(10);
+        let reasons: Vec<&str> = entries.iter().map(|e| e.reason.as_str()).collect();
+        assert_eq!(reasons, ["command", "key", "auth"]);
+
+        // 删除用户之后连接被关闭
+        backend.acl().del_users(&["alice"])?;
+        let args = RespArray::new(vec![BulkString::new("ping").into()]);
+        client.get_mut().write_all(&args.encode()).await?;
+        assert!(client.next().await.is_none());
+        Ok(())
+    }
+}
@@ -1,17 +1,121 @@
-use crate::{RespDecode, RespEncode, RespError, RespFrame};
+use crate::{
+    BulkString, RespEncode, RespFrame, RespMap, RespNull, RespNullBulkString, RespParser,
+    SimpleError, SimpleString,
+};
 use anyhow::Result;
+
+use super::inline::{decode_inline, is_inline};
+use bytes::BytesMut;
 use tokio_util::codec::{Decoder, Encoder};
 
 /// Redis的Codec四线
-#[derive(Debug)]
-pub struct RedisCodec;
+/// 默认使用RESP2，RESP3特有的类型在编码时降级为RESP2的类型，HELLO 3 之后原样编码
+#[derive(Debug, Default)]
+pub struct RedisCodec {
+    resp3: bool,
+    /// 保留不完整frame的解析进度
+    parser: RespParser,
+}
+
+impl RedisCodec {
+    /// 切换连接的协议版本
+    pub fn set_protocol(&mut self, protocol: i64) {
+        self.resp3 = protocol >= 3;
+    }
+
+    /// 设置bulk string的最大长度，对应proto-max-bulk-len
+    pub fn set_max_bulk_len(&mut self, len: usize) {
+        self.parser.set_max_bulk_len(len);
+    }
+}
+
+/// 按照连接的协议版本编码RespFrame
+///
+/// RESP3:
+/// - null bulk string/null array:统一转为null "_\r\n"
+///
+/// RESP2:
+/// - map:转为key value交替的数组
+/// - set:转为数组
+/// - null:转为null bulk string "$-1\r\n"
+/// - boolean:转为整数 1/0
+/// - double:转为bulk string
+/// - big number:转为bulk string
+/// - verbatim string:去掉编码格式，转为bulk string
+/// - blob error:转为simple error
+/// - push:转为数组
+/// - attribute:丢弃属性，只保留实际的回复
+fn encode_frame(frame: RespFrame, dst: &mut BytesMut, resp3: bool) {
+    match frame {
+        RespFrame::Null(_) | RespFrame::NullBulkString(_) | RespFrame::NullArray(_) if resp3 => {
+            dst.extend_from_slice(&RespNull.encode())
+        }
+        RespFrame::Null(_) => dst.extend_from_slice(&RespNullBulkString.encode()),
+        RespFrame::Array(array) => encode_aggregate(b'*', array.0, dst, resp3),
+        RespFrame::Set(set) if resp3 => encode_aggregate(b'~', set.0, dst, resp3),
+        RespFrame::Set(set) => encode_aggregate(b'*', set.0, dst, resp3),
+        RespFrame::Push(push) if resp3 => encode_aggregate(b'>', push.0, dst, resp3),
+        RespFrame::Push(push) => encode_aggregate(b'*', push.0, dst, resp3),
+        RespFrame::Map(map) if resp3 => {
+            dst.extend_from_slice(format!("%{}\r\n", map.len()).as_bytes());
+            encode_entries(map, dst, resp3);
+        }
+        RespFrame::Map(map) => {
+            dst.extend_from_slice(format!("*{}\r\n", map.len() * 2).as_bytes());
+            encode_entries(map, dst, resp3);
+        }
+        RespFrame::Attribute(attr) if resp3 => {
+            dst.extend_from_slice(format!("|{}\r\n", attr.attributes.len()).as_bytes());
+            encode_entries(attr.attributes, dst, resp3);
+            encode_frame(*attr.data, dst, resp3);
+        }
+        RespFrame::Attribute(attr) => encode_frame(attr.into_data(), dst, resp3),
+        RespFrame::Boolean(b) if !resp3 => {
+            dst.extend_from_slice(&RespFrame::Integer(b as i64).encode())
+        }
+        RespFrame::Double(d) if !resp3 => {
+            dst.extend_from_slice(&BulkString::new(d.value().to_string()).encode())
+        }
+        RespFrame::BigNumber(n) if !resp3 => dst.extend_from_slice(&BulkString::new(n.0).encode()),
+        RespFrame::Verbatim(v) if !resp3 => {
+            dst.extend_from_slice(&BulkString::new(v.data).encode())
+        }
+        RespFrame::BlobError(e) if !resp3 => {
+            // simple error不能包含换行
+            let msg = String::from_utf8_lossy(&e).replace(['\r', '\n'], " ");
+            dst.extend_from_slice(&SimpleError::new(msg).encode())
+        }
+        frame => dst.extend_from_slice(&frame.encode()),
+    }
+}
+
+/// 编码数组、集合和推送，元素递归编码
+fn encode_aggregate(prefix: u8, items: Vec<RespFrame>, dst: &mut BytesMut, resp3: bool) {
+    dst.extend_from_slice(&[prefix]);
+    dst.extend_from_slice(format!("{}\r\n", items.len()).as_bytes());
+    for item in items {
+        encode_frame(item, dst, resp3);
+    }
+}
+
+/// 编码map和属性的key value，RESP3的key为simple string，RESP2的key为bulk string
+fn encode_entries(map: RespMap, dst: &mut BytesMut, resp3: bool) {
+    for (key, value) in map.0 {
+        if resp3 {
+            dst.extend_from_slice(&SimpleString::new(key).encode());
+        } else {
+            dst.extend_from_slice(&BulkString::new(key).encode());
+        }
+        encode_frame(value, dst, resp3);
+    }
+}
+
 /// 将RespFrame Encode成bytes
 impl Encoder<RespFrame> for RedisCodec {
     type Error = anyhow::Error;
 
-    fn encode(&mut self, item: RespFrame, dst: &mut bytes::BytesMut) -> Result<()> {
-        let encode = item.encode();
-        dst.extend_from_slice(&encode);
+    fn encode(&mut self, item: RespFrame, dst: &mut BytesMut) -> Result<()> {
+        encode_frame(item, dst, self.resp3);
         Ok(())
     }
 }
@@ -21,11 +125,123 @@ impl Decoder for RedisCodec {
     type Item = RespFrame;
     type Error = anyhow::Error;
 
-    fn decode(&mut self, src: &mut bytes::BytesMut) -> Result<Option<Self::Item>> {
-        match RespFrame::decode(src) {
-            Ok(frame) => Ok(Some(frame)),
-            Err(RespError::NotComplete) => Ok(None),
-            Err(e) => Err(e.into()),
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>> {
+        // 不是以RESP类型前缀开头的数据按内联命令处理，例如telnet中输入的PING
+        if self.parser.is_idle() {
+            if let Some(frame) = decode_inline(src)? {
+                return Ok(Some(frame));
+            }
+            if is_inline(src) {
+                return Ok(None);
+            }
+        }
+        Ok(self.parser.parse(src)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        BlobError, RespArray, RespAttribute, RespBigNumber, RespDouble, RespNullArray, RespPush,
+        RespSet, VerbatimString,
+    };
+
+    fn encode(codec: &mut RedisCodec, frame: RespFrame) -> Result<BytesMut> {
+        let mut buf = BytesMut::new();
+        codec.encode(frame, &mut buf)?;
+        Ok(buf)
+    }
+
+    #[test]
+    fn test_resp2_downgrade() -> Result<()> {
+        let mut map = RespMap::default();
+        map.insert("proto".to_string(), RespFrame::Integer(2));
+        map.insert("score".to_string(), RespDouble::new(1.5).into());
+        let frame: RespFrame = RespArray::new(vec![
+            map.into(),
+            RespNull.into(),
+            RespFrame::Boolean(true),
+            RespSet::new(vec![RespFrame::Boolean(false)]).into(),
+        ])
+        .into();
+
+        let mut codec = RedisCodec::default();
+        let buf = encode(&mut codec, frame)?;
+        assert_eq!(
+            &buf[..],
+            b"*4\r\n*4\r\n$5\r\nproto\r\n:+2\r\n$5\r\nscore\r\n$3\r\n1.5\r\n$-1\r\n:+1\r\n*1\r\n:+0\r\n"
+        );
+
+        // RESP3原样编码
+        codec.set_protocol(3);
+        let buf = encode(&mut codec, RespNull.into())?;
+        assert_eq!(&buf[..], b"_\r\n");
+        let buf = encode(&mut codec, RespFrame::Boolean(true))?;
+        assert_eq!(&buf[..], b"#t\r\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_resp2_downgrade_resp3_only_types() -> Result<()> {
+        let mut codec = RedisCodec::default();
+        let frame: RespFrame = RespPush::new(vec![
+            RespBigNumber::new("12345678901234567890").into(),
+            VerbatimString::text("hello").into(),
+            BlobError::new("ERR a\r\nb").into(),
+            RespAttribute::new(RespMap::default(), RespFrame::Integer(1)).into(),
+        ])
+        .into();
+        let buf = encode(&mut codec, frame.clone())?;
+        assert_eq!(
+            &buf[..],
+            b"*4\r\n$20\r\n12345678901234567890\r\n$5\r\nhello\r\n-ERR a  b\r\n:+1\r\n"
+        );
+
+        codec.set_protocol(3);
+        let buf = encode(&mut codec, frame.clone())?;
+        assert_eq!(&buf[..], &frame.encode()[..]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_null_and_empty() -> Result<()> {
+        let frames: Vec<RespFrame> = vec![
+            BulkString::new("").into(),
+            RespNullBulkString.into(),
+            RespArray::new(vec![]).into(),
+            RespNullArray.into(),
+            RespNull.into(),
+        ];
+        let cases: [(i64, &[&[u8]]); 2] = [
+            (
+                2,
+                &[b"$0\r\n\r\n", b"$-1\r\n", b"*0\r\n", b"*-1\r\n", b"$-1\r\n"],
+            ),
+            (3, &[b"$0\r\n\r\n", b"_\r\n", b"*0\r\n", b"_\r\n", b"_\r\n"]),
+        ];
+        for (protocol, expected) in cases {
+            let mut codec = RedisCodec::default();
+            codec.set_protocol(protocol);
+            for (frame, expected) in frames.iter().zip(expected) {
+                let buf = encode(&mut codec, frame.clone())?;
+                assert_eq!(&buf[..], *expected, "RESP{} {:?}", protocol, frame);
+            }
         }
+
+        // 数组中的null和空串
+        let mut codec = RedisCodec::default();
+        let frame: RespFrame =
+            RespArray::new(vec![BulkString::new("").into(), RespNull.into()]).into();
+        let mut buf = encode(&mut codec, frame)?;
+        assert_eq!(&buf[..], b"*2\r\n$0\r\n\r\n$-1\r\n");
+        let frame = codec.decode(&mut buf)?;
+        assert_eq!(
+            frame,
+            Some(
+                RespArray::new(vec![BulkString::new("").into(), RespNullBulkString.into()]).into()
+            )
+        );
+        Ok(())
     }
 }
@@ -0,0 +1,211 @@
+use bytes::BytesMut;
+
+use crate::{BulkString, RespArray, RespError, RespFrame};
+
+/// 内联命令的最大长度，和Redis的PROTO_INLINE_MAX_SIZE一致
+pub(super) const INLINE_MAX_SIZE: usize = 64 * 1024;
+
+/// RESP类型的前缀，以其他字符开头的数据当作内联命令处理
+const RESP_PREFIXES: &[u8] = b"+-:$*_#,%~(=!>|";
+
+/// 是否是内联命令
+pub(super) fn is_inline(src: &[u8]) -> bool {
+    src.first().is_some_and(|b| !RESP_PREFIXES.contains(b))
+}
+
+/// 解析内联命令 "<arg1> <arg2> ...\r\n"
+/// - 空行直接跳过
+/// - 参数按空白分隔，支持单引号和双引号，双引号中支持转义
+/// - 解析为bulk string组成的数组
+pub(super) fn decode_inline(src: &mut BytesMut) -> Result<Option<RespFrame>, RespError> {
+    while is_inline(src) {
+        let Some(end) = src.iter().position(|b| *b == b'\n') else {
+            if src.len() > INLINE_MAX_SIZE {
+                return Err(RespError::Protocol("too big inline request".to_string()));
+            }
+            return Ok(None);
+        };
+        if end > INLINE_MAX_SIZE {
+            return Err(RespError::Protocol("too big inline request".to_string()));
+        }
+        let line = src.split_to(end + 1);
+        let line = line[..end].strip_suffix(b"\r").unwrap_or(&line[..end]);
+        let args = split_args(line)
+            .ok_or_else(|| RespError::Protocol("unbalanced quotes in request".to_string()))?;
+        if !args.is_empty() {
+            let args = args
+                .into_iter()
+                .map(|arg| BulkString::new(arg).into())
+                .collect::<Vec<RespFrame>>();
+            return Ok(Some(RespArray::new(args).into()));
+        }
+    }
+    // 跳过空行之后剩下的是RESP数据或者没有数据
+    Ok(None)
+}
+
+/// 按照Redis sdssplitargs的规则拆分参数，引号不匹配时返回None
+fn split_args(line: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut args = Vec::new();
+    let mut iter = line.iter().copied().peekable();
+    loop {
+        // 跳过空白
+        while iter.next_if(|b| b.is_ascii_whitespace()).is_some() {}
+        if iter.peek().is_none() {
+            return Some(args);
+        }
+
+        let mut arg = Vec::new();
+        let mut in_double = false;
+        let mut in_single = false;
+        loop {
+            let Some(b) = iter.next() else {
+                // 引号没有闭合
+                if in_double || in_single {
+                    return None;
+                }
+                break;
+            };
+            if in_double {
+                match b {
+                    b'\\' => match iter.next()? {
+                        b'x' => {
+                            let hex = [iter.peek().copied(), iter.clone().nth(1)];
+                            match hex {
+                                [Some(h), Some(l)]
+                                    if h.is_ascii_hexdigit() && l.is_ascii_hexdigit() =>
+                                {
+                                    iter.next();
+                                    iter.next();
+                                    arg.push((hex_value(h) << 4) | hex_value(l));
+                                }
+                                _ => arg.push(b'x'),
+                            }
+                        }
+                        b'n' => arg.push(b'\n'),
+                        b'r' => arg.push(b'\r'),
+                        b't' => arg.push(b'\t'),
+                        b'b' => arg.push(0x08),
+                        b'a' => arg.push(0x07),
+                        c => arg.push(c),
+                    },
+                    b'"' => {
+                        // 闭合的引号之后必须是空白或者结尾
+                        if iter.peek().is_some_and(|c| !c.is_ascii_whitespace()) {
+                            return None;
+                        }
+                        break;
+                    }
+                    c => arg.push(c),
+                }
+            } else if in_single {
+                match b {
+                    b'\\' if iter.peek() == Some(&b'\'') => {
+                        iter.next();
+                        arg.push(b'\'');
+                    }
+                    b'\'' => {
+                        if iter.peek().is_some_and(|c| !c.is_ascii_whitespace()) {
+                            return None;
+                        }
+                        break;
+                    }
+                    c => arg.push(c),
+                }
+            } else {
+                match b {
+                    b' ' | b'\n' | b'\r' | b'\t' | b'\0' => break,
+                    b'"' => in_double = true,
+                    b'\'' => in_single = true,
+                    c => arg.push(c),
+                }
+            }
+        }
+        args.push(arg);
+    }
+}
+
+fn hex_value(b: u8) -> u8 {
+    match b {
+        b'0'..=b'9' => b - b'0',
+        b'a'..=b'f' => b - b'a' + 10,
+        _ => b - b'A' + 10,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    fn args(frame: Option<RespFrame>) -> Vec<Vec<u8>> {
+        let Some(RespFrame::Array(arr)) = frame else {
+            panic!("expect array, got {:?}", frame);
+        };
+        arr.iter()
+            .map(|f| match f {
+                RespFrame::BulkString(s) => s.to_vec(),
+                f => panic!("expect bulk string, got {:?}", f),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_split_args() {
+        let cases: &[(&[u8], &[&[u8]])] = &[
+            (b"PING", &[b"PING"]),
+            (b"  set  key   value ", &[b"set", b"key", b"value"]),
+            (
+                b"set key \"hello world\"",
+                &[b"set", b"key", b"hello world"],
+            ),
+            (
+                b"set key \"a\\r\\n\\t\\\"b\\x41\\xzz\"",
+                &[b"set", b"key", b"a\r\n\t\"bAxzz"],
+            ),
+            (b"set key 'it\\'s \\n'", &[b"set", b"key", b"it's \\n"]),
+            (b"set key \"\"", &[b"set", b"key", b""]),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(split_args(line).unwrap(), *expected, "{:?}", line);
+        }
+
+        assert!(split_args(b"set key \"hello").is_none());
+        assert!(split_args(b"set key 'hello").is_none());
+        assert!(split_args(b"set key \"hello\"world").is_none());
+    }
+
+    #[test]
+    fn test_decode_inline() -> Result<()> {
+        // 空行被跳过，\n和\r\n都可以作为结尾
+        let mut buf = BytesMut::from("\r\n\nPING\r\nget key\n*1\r\n$4\r\nPING\r\n");
+        assert_eq!(args(decode_inline(&mut buf)?), [b"PING"]);
+        assert_eq!(
+            args(decode_inline(&mut buf)?),
+            [b"get".to_vec(), b"key".to_vec()]
+        );
+        // 剩下的是RESP数据
+        assert_eq!(decode_inline(&mut buf)?, None);
+        assert_eq!(&buf[..], b"*1\r\n$4\r\nPING\r\n");
+
+        // 不完整
+        let mut buf = BytesMut::from("PIN");
+        assert_eq!(decode_inline(&mut buf)?, None);
+        assert_eq!(&buf[..], b"PIN");
+
+        // 引号不匹配
+        let mut buf = BytesMut::from("set key \"value\r\n");
+        assert_eq!(
+            decode_inline(&mut buf).unwrap_err(),
+            RespError::Protocol("unbalanced quotes in request".to_string())
+        );
+
+        // 超过长度限制
+        let mut buf = BytesMut::from(&vec![b'a'; INLINE_MAX_SIZE + 1][..]);
+        assert_eq!(
+            decode_inline(&mut buf).unwrap_err(),
+            RespError::Protocol("too big inline request".to_string())
+        );
+        Ok(())
+    }
+}
@@ -1,16 +1,30 @@
+mod auth;
 mod codec;
+mod inline;
+mod stream;
+#[cfg(unix)]
+mod unix;
+
+use std::{io, time::Duration};
 
 use futures::{SinkExt, StreamExt};
-use tokio::net::TcpStream;
+use socket2::{SockRef, TcpKeepalive};
+use tokio::{net::TcpStream, time::Instant};
+use tokio_rustls::TlsAcceptor;
 
 use crate::{
-    cmd::{Command, CommandExecutor},
-    Backend, RespFrame,
+    clients::ClientHandle,
+    cmd::{Command, CommandExecutor, ErrorCode, ReplyMode},
+    replication::{self, SyncRequest},
+    shutdown, Backend, BulkString, RespError, RespFrame, RespNull, SimpleString,
 };
 use anyhow::Result;
 use tokio_util::codec::Framed;
 
-use self::codec::RedisCodec;
+pub use self::codec::RedisCodec;
+pub use self::stream::{ClientAddr, ClientStream};
+#[cfg(unix)]
+pub use self::unix::{bind_unix, serve_unix};
 
 /// 处理输入的Resp
 #[derive(Debug)]
@@ -24,37 +38,771 @@ struct RedisResponse {
     frame: RespFrame,
 }
 
-/// 处理客户端连接
-pub async fn stream_handler(stream: TcpStream, backend: Backend) -> Result<()> {
+/// TLS握手的超时时间
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 没有设置timeout时也定期醒来，CONFIG SET timeout 之后对已有连接生效
+const IDLE_CHECK_PERIOD: Duration = Duration::from_secs(1);
+
+/// 连接级别的状态
+#[derive(Debug)]
+struct ConnectionState {
+    /// 注册表中的客户端信息，包括用户、名字和协议版本
+    client: ClientHandle,
+    /// 是否已经通过认证
+    authenticated: bool,
+    /// 执行过ASKING，下一条命令可以访问正在迁入的slot
+    asking: bool,
+    /// 执行了SHUTDOWN，不回复直接关闭连接
+    close: bool,
+    /// CLIENT REPLY OFF
+    reply_off: bool,
+    /// CLIENT REPLY SKIP 之后还要跳过的回复数
+    skip_replies: u8,
+}
+
+impl ConnectionState {
+    /// 默认用户不需要密码时，新连接直接以默认用户认证
+    fn new(backend: &Backend, addr: String, laddr: String) -> Self {
+        ConnectionState {
+            client: backend.clients().register(addr, laddr),
+            authenticated: backend.acl().default_nopass(),
+            asking: false,
+            close: false,
+            reply_off: false,
+            skip_replies: 0,
+        }
+    }
+
+    /// 当前命令的回复是否需要发送
+    fn should_reply(&mut self) -> bool {
+        let skip = self.skip_replies > 0;
+        self.skip_replies = self.skip_replies.saturating_sub(1);
+        !self.reply_off && !skip
+    }
+}
+
+/// CLIENT LIST 中记录的命令名，包含子命令的命令记录为 命令|子命令
+fn command_name(backend: &Backend, frame: &RespFrame) -> Option<String> {
+    let RespFrame::Array(args) = frame else {
+        return None;
+    };
+    let name = |i: usize| match args.get(i) {
+        Some(RespFrame::BulkString(s)) => Some(String::from_utf8_lossy(s).to_ascii_lowercase()),
+        _ => None,
+    };
+    let cmd = name(0)?;
+    let container = backend
+        .commands()
+        .spec(args)
+        .is_some_and(|spec| !spec.subcommands.is_empty());
+    match name(1) {
+        Some(sub) if container => Some(format!("{}|{}", cmd, sub)),
+        _ => Some(cmd),
+    }
+}
+
+/// 处理TLS连接，握手完成之后和普通连接一样处理
+pub async fn tls_stream_handler(
+    stream: TcpStream,
+    acceptor: TlsAcceptor,
+    backend: Backend,
+) -> Result<()> {
+    let stream = tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream))
+        .await
+        .map_err(|_| anyhow::anyhow!("TLS handshake timed out"))??;
+    stream_handler(stream, backend).await
+}
+
+/// 按照tcp-keepalive配置接受的TCP连接，interval和Redis一样取time的三分之一
+pub fn set_tcp_keepalive(stream: &TcpStream, secs: u64) -> io::Result<()> {
+    if secs == 0 {
+        return Ok(());
+    }
+    let keepalive = TcpKeepalive::new()
+        .with_time(Duration::from_secs(secs))
+        .with_interval(Duration::from_secs((secs / 3).max(1)));
+    SockRef::from(stream).set_tcp_keepalive(&keepalive)
+}
+
+/// 处理客户端连接，TCP、TLS和Unix socket都由这里处理
+pub async fn stream_handler<S: ClientStream>(stream: S, backend: Backend) -> Result<()> {
+    let peer_addr = stream.client_addr()?;
+    let peer_ip = peer_addr.ip();
+    let mut state = ConnectionState::new(
+        &backend,
+        peer_addr.to_string(),
+        stream.local_addr()?.to_string(),
+    );
     //1. 从stream获取RespFrame
-    let mut framed = Framed::new(stream, RedisCodec);
+    let mut framed = Framed::new(stream, RedisCodec::default());
+    // 超过maxclients时回复错误之后关闭连接
+    if backend.clients().len() as u64 > backend.config().read(|c| c.maxclients) {
+        backend.stats().incr_rejected_connections();
+        framed
+            .send(ErrorCode::Err.reply("max number of clients reached"))
+            .await?;
+        return Ok(());
+    }
+    backend.stats().incr_connections_received();
+    let guard = backend.shutdown().connection_guard();
+    // replica通过REPLCONF listening-port告知的端口
+    let mut listening_port = 0;
     //2. 处理命令
     loop {
-        match framed.next().await {
+        // 只在等待下一条命令时检查空闲，正在执行命令的客户端不会被断开
+        // 成为replica之后离开这个循环，也不会因为空闲被断开
+        let timeout = Duration::from_secs(backend.config().read(|c| c.timeout));
+        let mut wake_at = Instant::now() + IDLE_CHECK_PERIOD;
+        if !timeout.is_zero() {
+            wake_at = wake_at.min(Instant::now() + timeout.saturating_sub(state.client.idle()));
+        }
+        let max_bulk_len = backend.config().read(|c| c.proto_max_bulk_len);
+        framed.codec_mut().set_max_bulk_len(max_bulk_len as usize);
+        let frame = tokio::select! {
+            biased;
+            // 服务器关闭时，处理完当前命令之后退出
+            _ = backend.shutdown().closed() => return Ok(()),
+            // CLIENT KILL
+            _ = state.client.killed() => return Ok(()),
+            frame = framed.next() => frame,
+            _ = tokio::time::sleep_until(wake_at) => {
+                if !timeout.is_zero() && state.client.idle() >= timeout {
+                    tracing::info!("Closing idle client {}", state.client.addr());
+                    return Ok(());
+                }
+                continue;
+            }
+        };
+        state.client.touch();
+        let read_buffer = framed.read_buffer();
+        state.client.set_buffers(
+            read_buffer.len(),
+            read_buffer.capacity() - read_buffer.len(),
+            framed.write_buffer().len(),
+        );
+        match frame {
             Some(Ok(req)) => {
+                if let Some(name) = command_name(&backend, &req) {
+                    state.client.set_last_cmd(name);
+                }
+                // 先检查认证和权限，包括复制相关的命令
+                if let Some(denied) = auth::authorize(&backend, &mut state, &req) {
+                    backend.stats().incr_error_replies();
+                    if state.should_reply() {
+                        framed.send(denied).await?;
+                    }
+                    continue;
+                }
+                if state.close {
+                    return Ok(());
+                }
+                // 复制相关的命令需要直接操作连接
+                match replication::parse_sync_request(&req) {
+                    Some(SyncRequest::ReplConf(args)) => {
+                        if let [option, port, ..] = args.as_slice() {
+                            if option.eq_ignore_ascii_case("listening-port") {
+                                listening_port = port.parse().unwrap_or_default();
+                            }
+                        }
+                        framed.send(SimpleString::new("OK").into()).await?;
+                        continue;
+                    }
+                    Some(SyncRequest::PSync { replid, offset }) => {
+                        // replica的连接不需要等待
+                        drop(guard);
+                        let client = state.client;
+                        client.set_replica();
+                        return replication::serve_replica(
+                            framed,
+                            backend,
+                            peer_ip,
+                            listening_port,
+                            replid,
+                            offset,
+                            async move { client.killed().await },
+                        )
+                        .await;
+                    }
+                    None => {}
+                }
+
                 // 创建RedisRequest
                 let req = RedisRequest {
                     frame: req,
                     backend: backend.clone(),
                 };
                 // 处理请求 等待结果
-                let resp = request_handler(req).await?;
+                let resp = request_handler(req, &mut state).await?;
+                if state.close {
+                    return Ok(());
+                }
 
                 //3. 返回结果 RespFrame
                 // 发送到stream里 ，由 RedisCodec解码
-                framed.send(resp.frame).await?;
+                if state.should_reply() {
+                    // HELLO 的回复已经使用新的协议版本
+                    framed.codec_mut().set_protocol(state.client.protocol());
+                    framed.send(resp.frame).await?;
+                }
+            }
+            Some(Err(e)) => {
+                // 协议错误之后数据已经不可信，回复错误之后关闭连接
+                let Some(e) = e.downcast_ref::<RespError>() else {
+                    return Err(e);
+                };
+                tracing::debug!("Protocol error from client {}: {}", state.client.addr(), e);
+                backend.stats().incr_error_replies();
+                framed.send(protocol_error(e)).await?;
+                return Ok(());
             }
-            Some(Err(e)) => return Err(e),
             None => return Ok(()),
         }
     }
 }
 
-async fn request_handler(req: RedisRequest) -> Result<RedisResponse> {
+/// 协议错误的回复 "-ERR Protocol error: ..."
+fn protocol_error(e: &RespError) -> RespFrame {
+    match e {
+        RespError::Protocol(_) => ErrorCode::Err.reply(e),
+        e => ErrorCode::Err.reply(format!("Protocol error: {}", e)),
+    }
+}
+
+/// 加载数据期间是否可以执行，包含子命令的命令看子命令的标记
+fn allowed_while_loading(backend: &Backend, frame: &RespFrame) -> bool {
+    let RespFrame::Array(args) = frame else {
+        return false;
+    };
+    let Some(spec) = backend.commands().spec(args) else {
+        return false;
+    };
+    match args.get(1) {
+        Some(RespFrame::BulkString(sub)) if !spec.subcommands.is_empty() => spec
+            .subcommand(sub)
+            .is_some_and(|sub| sub.has_flag("loading")),
+        _ => spec.has_flag("loading"),
+    }
+}
+
+async fn request_handler(req: RedisRequest, state: &mut ConnectionState) -> Result<RedisResponse> {
     let RedisRequest { frame, backend } = req;
-    // 尝试转换为命令
-    let cmd = Command::try_from(frame)?;
-    // 执行命令等结果
-    let ret_frame = cmd.execute(&backend);
+    // 加载数据期间只处理带loading标记的命令
+    if backend.snapshot().is_loading() && !allowed_while_loading(&backend, &frame) {
+        return Ok(RedisResponse {
+            frame: ErrorCode::Loading.reply("Redis is loading the dataset in memory"),
+        });
+    }
+    // 写命令需要传播，只读的replica不能执行
+    let is_write = matches!(&frame, RespFrame::Array(args) if backend.commands().spec(args).is_some_and(|spec| spec.is_write()));
+    // 开启AOF或者复制时保留原始请求，写命令执行后追加到AOF和复制流
+    let args = match &frame {
+        RespFrame::Array(args)
+            if backend.aof().is_enabled() || backend.replication().is_active() =>
+        {
+            Some(args.clone())
+        }
+        _ => None,
+    };
+    // ASKING只对下一条命令有效
+    let asking = std::mem::take(&mut state.asking);
+    // 集群模式下key所在的slot必须由当前节点负责
+    if let RespFrame::Array(args) = &frame {
+        let asking = asking
+            || matches!(args.first(), Some(RespFrame::BulkString(cmd)) if cmd.eq_ignore_ascii_case(b"restore-asking"));
+        let exists = |key: &[u8]| {
+            std::str::from_utf8(key)
+                .map(|key| backend.exists(key))
+                .unwrap_or(false)
+        };
+        if let Err(redirect) =
+            backend
+                .cluster()
+                .check_keys(&backend.commands().keys(args), asking, exists)
+        {
+            return Ok(RedisResponse {
+                frame: redirect.into(),
+            });
+        }
+    }
+    // 尝试转换为命令，解析失败时回复错误，连接继续处理之后的命令
+    let cmd = match backend.commands().parse(frame) {
+        Ok(cmd) => cmd,
+        Err(e) => {
+            backend.stats().incr_error_replies();
+            return Ok(RedisResponse { frame: e.into() });
+        }
+    };
+    let is_asking = matches!(cmd, Command::Asking(_));
+    // 需要修改连接状态的命令由连接执行
+    let frame = match &cmd {
+        // SHUTDOWN需要等待replica，在这里异步执行，成功之后不回复直接关闭连接
+        Command::Shutdown(shutdown) if !shutdown.is_abort() => {
+            match shutdown::shutdown(&backend, shutdown.flags()).await {
+                Ok(()) => {
+                    state.close = true;
+                    Some(RespNull.into())
+                }
+                Err(_) => Some(ErrorCode::Err.reply("Errors trying to SHUTDOWN. Check logs.")),
+            }
+        }
+        Command::Auth(cmd) => Some(auth::auth(&backend, state, cmd)),
+        Command::Hello(cmd) => Some(auth::hello(&backend, state, cmd)),
+        Command::Acl(cmd) if cmd.is_whoami() => Some(BulkString::new(state.client.user()).into()),
+        Command::Client(cmd) => {
+            match cmd.reply_mode() {
+                Some(ReplyMode::On) => {
+                    state.reply_off = false;
+                    state.skip_replies = 0;
+                }
+                Some(ReplyMode::Off) => state.reply_off = true,
+                // SKIP自己和下一条命令都不回复
+                Some(ReplyMode::Skip) => state.skip_replies = 2,
+                None => {}
+            }
+            Some(cmd.execute_for(&backend, Some(&state.client)))
+        }
+        _ => None,
+    };
+    if let Some(frame) = frame {
+        return Ok(RedisResponse { frame });
+    }
+    // 只读的replica不接受客户端的写命令
+    if is_write && backend.replication().is_read_only() {
+        return Ok(RedisResponse {
+            frame: ErrorCode::ReadOnly.reply("You can't write against a read only replica."),
+        });
+    }
+    // 手动故障转移以及关闭前等待replica期间暂停写入，等待replica追上复制偏移量
+    // CLIENT PAUSE 期间暂停写命令或者所有命令，CLIENT命令已经在上面执行，不会被暂停
+    while (is_write
+        && (backend.cluster().is_write_paused() || backend.shutdown().is_write_paused()))
+        || backend.clients().is_paused(is_write)
+    {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    // 暂停期间服务器已经关闭，写命令不再执行
+    if backend.shutdown().is_closing() {
+        state.close = true;
+        return Ok(RedisResponse {
+            frame: RespNull.into(),
+        });
+    }
+    // 执行命令等结果，自定义命令可以访问发送命令的客户端
+    let ret_frame = match cmd {
+        Command::Custom(cmd) => cmd.execute_for(&backend, Some(&state.client)),
+        cmd => cmd.execute(&backend),
+    };
+    let failed = matches!(ret_frame, RespFrame::Error(_));
+    backend.stats().incr_commands_processed();
+    if failed {
+        backend.stats().incr_error_replies();
+    }
+    state.asking = is_asking && !failed;
+    // 执行失败的写命令不需要传播，传播失败时命令已经执行，回复错误之后连接继续处理之后的命令
+    if let (true, false, Some(args)) = (is_write, failed, args) {
+        if let Err(e) = backend.propagate(&args) {
+            tracing::warn!("Failed to propagate write command: {}", e);
+            backend.stats().incr_error_replies();
+            return Ok(RedisResponse {
+                frame: ErrorCode::MisConf.reply(format!("Errors writing to the AOF file: {}", e)),
+            });
+        }
+    }
     Ok(RedisResponse { frame: ret_frame })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cmd::{CommandContext, CommandError, CommandSpec, CustomExecutor, KeySpec},
+        RespArray, RespEncode, SimpleError,
+    };
+    use std::net::SocketAddr;
+    use tokio::{io::AsyncWriteExt, net::TcpListener};
+
+    async fn serve(backend: &Backend) -> Result<SocketAddr> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let backend = backend.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(stream_handler(stream, backend.clone()));
+            }
+        });
+        Ok(addr)
+    }
+
+    async fn send(client: &mut Framed<TcpStream, RedisCodec>, args: &[&str]) -> Result<()> {
+        let args = RespArray::new(
+            args.iter()
+                .map(|arg| BulkString::new(*arg).into())
+                .collect::<Vec<RespFrame>>(),
+        );
+        client.get_mut().write_all(&args.encode()).await?;
+        Ok(())
+    }
+
+    async fn request(
+        client: &mut Framed<TcpStream, RedisCodec>,
+        args: &[&str],
+    ) -> Result<Option<RespFrame>> {
+        send(client, args).await?;
+        client.next().await.transpose()
+    }
+
+    #[tokio::test]
+    async fn test_maxclients_and_timeout() -> Result<()> {
+        let backend = Backend::new();
+        backend.config().update(|c| c.maxclients = 1);
+        let addr = serve(&backend).await?;
+
+        let mut first = Framed::new(TcpStream::connect(addr).await?, RedisCodec::default());
+        assert_eq!(
+            request(&mut first, &["ping"]).await?,
+            Some(SimpleString::new("PONG").into())
+        );
+        // 超过maxclients的连接收到错误之后被关闭
+        let mut second = Framed::new(TcpStream::connect(addr).await?, RedisCodec::default());
+        assert_eq!(
+            second.next().await.transpose()?,
+            Some(SimpleError::new("ERR max number of clients reached").into())
+        );
+        assert!(second.next().await.is_none());
+        assert_eq!(backend.clients().connected_clients(), 1);
+
+        // 空闲超过timeout的连接被断开
+        backend.config().update(|c| c.timeout = 1);
+        let closed = tokio::time::timeout(Duration::from_secs(3), first.next()).await?;
+        assert!(closed.is_none());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(backend.clients().connected_clients(), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_client_reply_and_kill() -> Result<()> {
+        let backend = Backend::new();
+        let addr = serve(&backend).await?;
+        let mut first = Framed::new(TcpStream::connect(addr).await?, RedisCodec::default());
+        let mut second = Framed::new(TcpStream::connect(addr).await?, RedisCodec::default());
+
+        let Some(RespFrame::Integer(id)) = request(&mut first, &["client", "id"]).await? else {
+            panic!("CLIENT ID should return an integer");
+        };
+        // SKIP自己和下一条命令都不回复
+        send(&mut first, &["client", "reply", "skip"]).await?;
+        send(&mut first, &["echo", "skipped"]).await?;
+        assert_eq!(
+            request(&mut first, &["echo", "hello"]).await?,
+            Some(BulkString::new("hello").into())
+        );
+        send(&mut first, &["client", "reply", "off"]).await?;
+        send(&mut first, &["echo", "off"]).await?;
+        assert_eq!(
+            request(&mut first, &["client", "reply", "on"]).await?,
+            Some(SimpleString::new("OK").into())
+        );
+
+        let Some(RespFrame::BulkString(info)) = request(&mut first, &["client", "info"]).await?
+        else {
+            panic!("CLIENT INFO should return a bulk string");
+        };
+        assert!(String::from_utf8(info.into_vec())?.contains(" cmd=client|info "));
+
+        let id = id.to_string();
+        assert_eq!(
+            request(&mut second, &["client", "kill", "id", &id]).await?,
+            Some(RespFrame::Integer(1))
+        );
+        assert!(first.next().await.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_error_replies() -> Result<()> {
+        let backend = Backend::new();
+        let addr = serve(&backend).await?;
+
+        let mut client = Framed::new(TcpStream::connect(addr).await?, RedisCodec::default());
+        let cases: [(&[&str], &str); 4] = [
+            (
+                &["foo", "a", "b"],
+                "ERR unknown command 'foo', with args beginning with: 'a' 'b' ",
+            ),
+            (&["get"], "ERR wrong number of arguments for 'get' command"),
+            (
+                &["config", "foo"],
+                "ERR unknown subcommand 'foo'. Try CONFIG HELP.",
+            ),
+            (&["hset", "k", "f", "v"], ""),
+        ];
+        for (args, expected) in cases {
+            let ret = request(&mut client, args).await?;
+            if !expected.is_empty() {
+                assert_eq!(ret, Some(SimpleError::new(expected).into()), "{:?}", args);
+            }
+        }
+        // 类型不对的key
+        assert_eq!(
+            request(&mut client, &["GET", "k"]).await?,
+            Some(
+                SimpleError::new(
+                    "WRONGTYPE Operation against a key holding the wrong kind of value"
+                )
+                .into()
+            )
+        );
+        // 出错之后连接仍然可用，命令不区分大小写
+        assert_eq!(
+            request(&mut client, &["SET", "k", "v"]).await?,
+            Some(SimpleString::new("OK").into())
+        );
+        assert_eq!(
+            request(&mut client, &["Get", "k"]).await?,
+            Some(BulkString::new("v").into())
+        );
+        assert!(backend.stats().info().contains("total_error_replies:4\r\n"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_loading() -> Result<()> {
+        let backend = Backend::new();
+        let addr = serve(&backend).await?;
+        backend.snapshot().set_loading(true);
+
+        let mut client = Framed::new(TcpStream::connect(addr).await?, RedisCodec::default());
+        let loading =
+            Some(SimpleError::new("LOADING Redis is loading the dataset in memory").into());
+        // 带loading标记的命令可以执行，子命令看子命令的标记
+        for args in [
+            &["ping"][..],
+            &["info", "persistence"],
+            &["config", "get", "port"],
+            &["command", "count"],
+        ] {
+            let ret = request(&mut client, args).await?;
+            assert_ne!(ret, loading, "{:?}", args);
+        }
+        for args in [&["get", "k"][..], &["set", "k", "v"], &["foo"]] {
+            assert_eq!(request(&mut client, args).await?, loading, "{:?}", args);
+        }
+
+        backend.snapshot().set_loading(false);
+        assert_eq!(
+            request(&mut client, &["set", "k", "v"]).await?,
+            Some(SimpleString::new("OK").into())
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_inline_commands() -> Result<()> {
+        let backend = Backend::new();
+        let addr = serve(&backend).await?;
+
+        let mut client = Framed::new(TcpStream::connect(addr).await?, RedisCodec::default());
+        client
+            .get_mut()
+            .write_all(b"ping\r\n\r\nset k \"hello world\"\nget k\r\n")
+            .await?;
+        assert_eq!(
+            client.next().await.transpose()?,
+            Some(SimpleString::new("PONG").into())
+        );
+        assert_eq!(
+            client.next().await.transpose()?,
+            Some(SimpleString::new("OK").into())
+        );
+        assert_eq!(
+            client.next().await.transpose()?,
+            Some(BulkString::new("hello world").into())
+        );
+
+        // 引号不匹配时回复协议错误之后关闭连接
+        client.get_mut().write_all(b"get \"k\r\n").await?;
+        assert_eq!(
+            client.next().await.transpose()?,
+            Some(SimpleError::new("ERR Protocol error: unbalanced quotes in request").into())
+        );
+        assert!(client.next().await.is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_protocol_errors() -> Result<()> {
+        let backend = Backend::new();
+        backend
+            .config()
+            .update(|c| c.proto_max_bulk_len = 1024 * 1024);
+        let addr = serve(&backend).await?;
+
+        let nested = "*1\r\n".repeat(1000);
+        let cases: [(&[u8], &str); 6] = [
+            (b"*99999999999\r\n", "invalid multibulk length"),
+            (b"*1\r\n\r\n", "unexpected empty line"),
+            (b"*2\r\n:1\r\n\r\n", "unexpected empty line"),
+            (b"*1\r\n$4000000000\r\n", "invalid bulk length"),
+            (b"*1\r\n$-5\r\n", "invalid bulk length"),
+            (nested.as_bytes(), "nesting too deep"),
+        ];
+        for (data, msg) in cases {
+            let mut client = Framed::new(TcpStream::connect(addr).await?, RedisCodec::default());
+            client.get_mut().write_all(data).await?;
+            assert_eq!(
+                client.next().await.transpose()?,
+                Some(SimpleError::new(format!("ERR Protocol error: {}", msg)).into())
+            );
+            assert!(client.next().await.is_none());
+        }
+
+        // 不超过proto-max-bulk-len的bulk string正常处理
+        let mut client = Framed::new(TcpStream::connect(addr).await?, RedisCodec::default());
+        let value = "v".repeat(1024 * 1024);
+        let set = format!(
+            "*3\r\n$3\r\nset\r\n$1\r\nk\r\n${}\r\n{}\r\n",
+            value.len(),
+            value
+        );
+        client.get_mut().write_all(set.as_bytes()).await?;
+        assert_eq!(
+            client.next().await.transpose()?,
+            Some(SimpleString::new("OK").into())
+        );
+        client
+            .get_mut()
+            .write_all(b"*3\r\n$3\r\nset\r\n$1\r\nk\r\n$1048577\r\n")
+            .await?;
+        assert_eq!(
+            client.next().await.transpose()?,
+            Some(SimpleError::new("ERR Protocol error: invalid bulk length").into())
+        );
+        Ok(())
+    }
+
+    /// 把key设置为发送命令的客户端的名字
+    #[derive(Debug)]
+    struct SetClientName {
+        key: String,
+    }
+
+    impl TryFrom<RespArray> for SetClientName {
+        type Error = CommandError;
+
+        fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+            match value.get(1) {
+                Some(RespFrame::BulkString(key)) => Ok(SetClientName {
+                    key: String::from_utf8_lossy(key).to_string(),
+                }),
+                _ => Err(CommandError::InvalidArgument("invalid key".to_string())),
+            }
+        }
+    }
+
+    impl CustomExecutor for SetClientName {
+        fn execute(self: Box<Self>, ctx: &CommandContext) -> RespFrame {
+            let name = ctx
+                .client
+                .and_then(|client| client.name())
+                .unwrap_or_default();
+            ctx.backend.set(self.key, BulkString::new(name).into());
+            SimpleString::new("OK").into()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_custom_command() -> Result<()> {
+        let mut spec = CommandSpec::new("test.setclientname", 2);
+        spec.flags = &["write"];
+        spec.categories = &["write"];
+        spec.keys = KeySpec {
+            first: 1,
+            last: 1,
+            step: 1,
+        };
+        let backend = Backend::new();
+        backend.commands().register::<SetClientName>(spec)?;
+        // 注册只对这个Backend有效
+        assert!(Backend::new()
+            .commands()
+            .lookup(b"test.setclientname")
+            .is_none());
+        let addr = serve(&backend).await?;
+        let mut client = Framed::new(TcpStream::connect(addr).await?, RedisCodec::default());
+        request(&mut client, &["client", "setname", "alice"]).await?;
+        assert_eq!(
+            request(&mut client, &["TEST.SETCLIENTNAME", "k"]).await?,
+            Some(SimpleString::new("OK").into())
+        );
+        assert_eq!(
+            request(&mut client, &["get", "k"]).await?,
+            Some(BulkString::new("alice").into())
+        );
+        assert_eq!(
+            request(&mut client, &["test.setclientname"]).await?,
+            Some(
+                SimpleError::new("ERR wrong number of arguments for 'test.setclientname' command")
+                    .into()
+            )
+        );
+        assert_eq!(
+            request(
+                &mut client,
+                &["command", "getkeys", "test.setclientname", "k"]
+            )
+            .await?,
+            Some(RespArray::new(vec![BulkString::new("k").into()]).into())
+        );
+        let Some(RespFrame::Array(info)) =
+            request(&mut client, &["command", "info", "test.setclientname"]).await?
+        else {
+            panic!("COMMAND INFO should return an array");
+        };
+        assert!(matches!(&info[0], RespFrame::Array(_)));
+        // ACL规则可以引用自定义命令
+        assert_eq!(
+            request(
+                &mut client,
+                &["acl", "setuser", "bob", "+test.setclientname"]
+            )
+            .await?,
+            Some(SimpleString::new("OK").into())
+        );
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_propagate_error() -> Result<()> {
+        use crate::persistence::AofConfig;
+
+        // 写入/dev/full总是失败
+        let backend = Backend::new();
+        backend.aof().set_config(AofConfig {
+            enabled: true,
+            dir: "/dev".into(),
+            filename: "full".to_string(),
+            ..Default::default()
+        });
+        backend.aof().open()?;
+        let addr = serve(&backend).await?;
+
+        let mut client = Framed::new(TcpStream::connect(addr).await?, RedisCodec::default());
+        let Some(RespFrame::Error(e)) = request(&mut client, &["set", "k", "v"]).await? else {
+            panic!("SET should fail when the AOF can't be written");
+        };
+        assert!(
+            e.starts_with("MISCONF Errors writing to the AOF file"),
+            "{:?}",
+            e
+        );
+        // 命令已经执行，连接仍然可用
+        assert_eq!(
+            request(&mut client, &["get", "k"]).await?,
+            Some(BulkString::new("v").into())
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+use std::{fmt, io, net::SocketAddr};
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpStream,
+};
+use tokio_rustls::server::TlsStream;
+
+/// 客户端的地址
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientAddr {
+    Tcp(SocketAddr),
+    /// Unix socket的客户端一般没有地址，使用监听的socket路径
+    Unix(String),
+}
+
+impl ClientAddr {
+    /// replica连接时记录的ip，Unix socket的replica只能是本机
+    pub fn ip(&self) -> String {
+        match self {
+            ClientAddr::Tcp(addr) => addr.ip().to_string(),
+            ClientAddr::Unix(_) => "127.0.0.1".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ClientAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientAddr::Tcp(addr) => write!(f, "{}", addr),
+            // 和Redis一样，Unix socket的地址是 path:0
+            ClientAddr::Unix(path) => write!(f, "{}:0", path),
+        }
+    }
+}
+
+/// 可以作为客户端连接的stream，TCP、TLS以及Unix socket共用同一套连接处理
+pub trait ClientStream: AsyncRead + AsyncWrite + Unpin {
+    fn client_addr(&self) -> io::Result<ClientAddr>;
+
+    /// 客户端连接的服务端地址
+    fn local_addr(&self) -> io::Result<ClientAddr>;
+}
+
+impl ClientStream for TcpStream {
+    fn client_addr(&self) -> io::Result<ClientAddr> {
+        self.peer_addr().map(ClientAddr::Tcp)
+    }
+
+    fn local_addr(&self) -> io::Result<ClientAddr> {
+        TcpStream::local_addr(self).map(ClientAddr::Tcp)
+    }
+}
+
+impl<S: ClientStream> ClientStream for TlsStream<S> {
+    fn client_addr(&self) -> io::Result<ClientAddr> {
+        self.get_ref().0.client_addr()
+    }
+
+    fn local_addr(&self) -> io::Result<ClientAddr> {
+        self.get_ref().0.local_addr()
+    }
+}
+
+#[cfg(unix)]
+impl ClientStream for tokio::net::UnixStream {
+    fn client_addr(&self) -> io::Result<ClientAddr> {
+        ClientStream::local_addr(self)
+    }
+
+    fn local_addr(&self) -> io::Result<ClientAddr> {
+        let addr = tokio::net::UnixStream::local_addr(self)?;
+        let path = addr
+            .as_pathname()
+            .map(|p| p.display().to_string())
+            .unwrap_or_default();
+        Ok(ClientAddr::Unix(path))
+    }
+}
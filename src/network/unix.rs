@@ -0,0 +1,79 @@
+use std::{fs, io, os::unix::fs::PermissionsExt, path::Path};
+
+use tokio::net::UnixListener;
+
+use crate::Backend;
+
+use super::stream_handler;
+
+/// 监听Unix socket，perm不为0时设置socket文件的权限
+pub fn bind_unix(path: impl AsRef<Path>, perm: u32) -> io::Result<UnixListener> {
+    let path = path.as_ref();
+    // 上次没有正常退出时会留下socket文件，不删除的话无法bind
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let listener = UnixListener::bind(path)?;
+    if perm != 0 {
+        fs::set_permissions(path, fs::Permissions::from_mode(perm))?;
+    }
+    Ok(listener)
+}
+
+/// 接受Unix socket连接，和TCP连接一样处理
+pub async fn serve_unix(listener: UnixListener, backend: Backend) -> anyhow::Result<()> {
+    loop {
+        let (stream, _) = tokio::select! {
+            _ = backend.shutdown().closed() => return Ok(()),
+            ret = listener.accept() => ret?,
+        };
+        tracing::info!("Accepted connection from unix socket");
+        let backend = backend.clone();
+        tokio::spawn(async move {
+            match stream_handler(stream, backend).await {
+                Ok(_) => tracing::info!("Unix socket connection exited"),
+                Err(e) => tracing::warn!("handle error for unix socket connection: {:?}", e),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BulkString, RespArray, RespEncode, RespFrame};
+    use anyhow::Result;
+    use futures::StreamExt;
+    use tokio::{io::AsyncWriteExt, net::UnixStream};
+    use tokio_util::codec::Framed;
+
+    #[tokio::test]
+    async fn test_unix_socket() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("redis.sock");
+        // 遗留的socket文件会被替换
+        fs::write(&path, "")?;
+        let listener = bind_unix(&path, 0o700)?;
+        assert_eq!(fs::metadata(&path)?.permissions().mode() & 0o777, 0o700);
+
+        let backend = Backend::new();
+        tokio::spawn(serve_unix(listener, backend.clone()));
+        let mut client = Framed::new(
+            UnixStream::connect(&path).await?,
+            crate::network::RedisCodec::default(),
+        );
+        let args = RespArray::new(vec![
+            BulkString::new("set").into(),
+            BulkString::new("key").into(),
+            BulkString::new("value").into(),
+        ]);
+        client.get_mut().write_all(&args.encode()).await?;
+        assert_eq!(
+            client.next().await.unwrap()?,
+            RespFrame::SimpleString("OK".into())
+        );
+        assert_eq!(backend.get("key"), Some(BulkString::new("value").into()));
+        Ok(())
+    }
+}
@@ -0,0 +1,473 @@
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, RwLock,
+    },
+    time::Duration,
+};
+
+use bytes::BytesMut;
+
+use crate::{
+    cmd::CommandExecutor, Backend, BackendValue, BulkString, RespArray, RespDecode, RespEncode,
+    RespError, RespFrame,
+};
+
+use super::{dump_value, PersistenceError};
+
+/// SADD重写时每条命令最多携带的成员数量
+const AOF_REWRITE_ITEMS_PER_CMD: usize = 64;
+
+/// AOF的fsync策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppendFsync {
+    /// 每次写入都fsync
+    Always,
+    /// 每秒fsync一次
+    EverySec,
+    /// 交给操作系统决定
+    No,
+}
+
+impl FromStr for AppendFsync {
+    type Err = PersistenceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "always" => Ok(AppendFsync::Always),
+            "everysec" => Ok(AppendFsync::EverySec),
+            "no" => Ok(AppendFsync::No),
+            _ => Err(PersistenceError::InvalidFormat(format!(
+                "invalid appendfsync: {}",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for AppendFsync {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            AppendFsync::Always => "always",
+            AppendFsync::EverySec => "everysec",
+            AppendFsync::No => "no",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// AOF相关的配置
+#[derive(Debug, Clone)]
+pub struct AofConfig {
+    /// 是否开启AOF
+    pub enabled: bool,
+    /// AOF文件所在目录
+    pub dir: PathBuf,
+    /// AOF文件名
+    pub filename: String,
+    /// fsync策略
+    pub fsync: AppendFsync,
+}
+
+impl Default for AofConfig {
+    fn default() -> Self {
+        AofConfig {
+            enabled: false,
+            dir: PathBuf::from("."),
+            filename: "appendonly.aof".to_string(),
+            fsync: AppendFsync::EverySec,
+        }
+    }
+}
+
+impl AofConfig {
+    /// AOF文件的完整路径
+    pub fn path(&self) -> PathBuf {
+        self.dir.join(&self.filename)
+    }
+}
+
+#[derive(Debug, Default)]
+struct AofInner {
+    /// 以追加方式打开的AOF文件
+    file: Option<File>,
+    /// 重写期间新写入的命令，重写完成后追加到新文件末尾
+    rewrite_buf: Option<Vec<u8>>,
+}
+
+/// AOF运行时状态，挂在Backend上共享
+#[derive(Debug, Default)]
+pub struct AofState {
+    config: RwLock<AofConfig>,
+    inner: Mutex<AofInner>,
+    rewrite_in_progress: AtomicBool,
+}
+
+impl AofState {
+    pub fn config(&self) -> AofConfig {
+        self.config.read().unwrap().clone()
+    }
+
+    pub fn set_config(&self, config: AofConfig) {
+        *self.config.write().unwrap() = config;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.read().unwrap().enabled
+    }
+
+    pub fn is_rewrite_in_progress(&self) -> bool {
+        self.rewrite_in_progress.load(Ordering::Relaxed)
+    }
+
+    /// 打开AOF文件准备追加
+    pub fn open(&self) -> Result<(), PersistenceError> {
+        let config = self.config();
+        fs::create_dir_all(&config.dir)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(config.path())?;
+        self.inner.lock().unwrap().file = Some(file);
+        Ok(())
+    }
+
+    /// 关闭AOF文件，之后的写命令不再追加
+    pub fn close(&self) {
+        self.inner.lock().unwrap().file = None;
+    }
+
+    /// 追加一条写命令
+    pub fn feed(&self, args: &RespArray) -> Result<(), PersistenceError> {
+        let data = args.clone().encode();
+        let fsync = self.config.read().unwrap().fsync;
+
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(buf) = inner.rewrite_buf.as_mut() {
+            buf.extend_from_slice(&data);
+        }
+        if let Some(file) = inner.file.as_mut() {
+            file.write_all(&data)?;
+            if fsync == AppendFsync::Always {
+                file.sync_data()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 将已写入的数据刷到磁盘
+    pub fn fsync(&self) -> Result<(), PersistenceError> {
+        if let Some(file) = self.inner.lock().unwrap().file.as_ref() {
+            file.sync_data()?;
+        }
+        Ok(())
+    }
+}
+
+/// 回放AOF文件，文件不存在时返回None，否则返回回放的命令数量
+/// 文件末尾不完整的命令会被截断丢弃
+pub fn load_aof(backend: &Backend) -> Result<Option<usize>, PersistenceError> {
+    let path = backend.aof().config().path();
+    let data = match fs::read(&path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let total = data.len();
+    let mut buf = BytesMut::from(&data[..]);
+    let mut count = 0;
+    while !buf.is_empty() {
+        let frame = match RespFrame::decode(&mut buf) {
+            Ok(frame) => frame,
+            Err(RespError::NotComplete) => {
+                let valid = total - buf.len();
+                tracing::warn!(
+                    "AOF {} is truncated, discarding the last {} bytes",
+                    path.display(),
+                    buf.len()
+                );
+                OpenOptions::new()
+                    .write(true)
+                    .open(&path)?
+                    .set_len(valid as u64)?;
+                break;
+            }
+            Err(e) => {
+                return Err(PersistenceError::InvalidFormat(format!(
+                    "bad AOF command at offset {}: {}",
+                    total - buf.len(),
+                    e
+                )))
+            }
+        };
+
+        let cmd = backend
+            .commands()
+            .parse(frame)
+            .map_err(|e| PersistenceError::InvalidFormat(format!("bad AOF command: {}", e)))?;
+        cmd.execute(backend);
+        count += 1;
+    }
+
+    // 回放出来的数据已经持久化过了
+    backend.decr_dirty(backend.dirty());
+    tracing::info!("DB loaded from append only file: {} commands", count);
+    Ok(Some(count))
+}
+
+/// 将当前数据集编码为最少的写命令
+/// 带过期时间的key使用 RESTORE ... ABSTTL 保留绝对过期时间
+/// 没有对应写命令的类型(list/zset)使用 RESTORE 写入
+fn rewrite_commands(backend: &Backend) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(4096);
+
+    for key in backend.keys() {
+        let Some(value) = backend.get_value(&key) else {
+            continue;
+        };
+        let key_frame: RespFrame = BulkString::from(key.as_str()).into();
+
+        let expire_at = backend.expire_at(&key);
+        if expire_at.is_some() || matches!(value, BackendValue::List(_) | BackendValue::ZSet(_)) {
+            let args = RespArray::new(vec![
+                BulkString::new("restore").into(),
+                key_frame,
+                BulkString::from(expire_at.unwrap_or(0).to_string()).into(),
+                BulkString::new(dump_value(&value)).into(),
+                BulkString::new("replace").into(),
+                BulkString::new("absttl").into(),
+            ]);
+            buf.extend_from_slice(&args.encode());
+            continue;
+        }
+
+        match value {
+            BackendValue::String(v) => {
+                let args = RespArray::new(vec![BulkString::new("set").into(), key_frame, v]);
+                buf.extend_from_slice(&args.encode());
+            }
+            BackendValue::Hash(fields) => {
+                for (field, v) in fields {
+                    let args = RespArray::new(vec![
+                        BulkString::new("hset").into(),
+                        key_frame.clone(),
+                        BulkString::from(field).into(),
+                        v,
+                    ]);
+                    buf.extend_from_slice(&args.encode());
+                }
+            }
+            BackendValue::Set(members) => {
+                for chunk in members.chunks(AOF_REWRITE_ITEMS_PER_CMD) {
+                    let mut args = vec![BulkString::new("sadd").into(), key_frame.clone()];
+                    args.extend_from_slice(chunk);
+                    buf.extend_from_slice(&RespArray::new(args).encode());
+                }
+            }
+            BackendValue::List(_) | BackendValue::ZSet(_) => unreachable!(),
+        }
+    }
+
+    buf
+}
+
+/// 同步重写AOF
+pub fn rewrite_aof(backend: &Backend) -> Result<(), PersistenceError> {
+    let state = backend.aof();
+    let config = state.config();
+
+    // 开始收集重写期间的新命令
+    state.inner.lock().unwrap().rewrite_buf = Some(Vec::new());
+
+    let ret = (|| -> Result<(), PersistenceError> {
+        let data = rewrite_commands(backend);
+        fs::create_dir_all(&config.dir)?;
+        let tmp = config
+            .dir
+            .join(format!("temp-rewriteaof-{}.aof", std::process::id()));
+        let mut file = File::create(&tmp)?;
+        file.write_all(&data)?;
+
+        // 持有锁期间把缓冲的命令写入新文件并替换旧文件，之后的命令直接写入新文件
+        let mut inner = state.inner.lock().unwrap();
+        if let Some(buf) = inner.rewrite_buf.take() {
+            file.write_all(&buf)?;
+        }
+        file.sync_all()?;
+        fs::rename(&tmp, config.path())?;
+        if inner.file.is_some() || config.enabled {
+            inner.file = Some(OpenOptions::new().append(true).open(config.path())?);
+        }
+        Ok(())
+    })();
+
+    state.inner.lock().unwrap().rewrite_buf = None;
+    if ret.is_ok() {
+        tracing::info!("Append only file rewritten: {}", config.path().display());
+    }
+    ret
+}
+
+/// 在后台线程重写AOF
+pub fn bgrewriteaof(backend: &Backend) -> Result<(), PersistenceError> {
+    let state = backend.aof();
+    if state
+        .rewrite_in_progress
+        .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+        .is_err()
+    {
+        return Err(PersistenceError::AofRewriteInProgress);
+    }
+
+    let backend = backend.clone();
+    std::thread::spawn(move || {
+        if let Err(e) = rewrite_aof(&backend) {
+            tracing::warn!("Background AOF rewrite error: {}", e);
+        }
+        backend
+            .aof()
+            .rewrite_in_progress
+            .store(false, Ordering::Release);
+    });
+
+    Ok(())
+}
+
+/// everysec策略下每秒fsync一次
+pub async fn run_aof_cron(backend: Backend) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+
+        let state = backend.aof();
+        if state.config.read().unwrap().fsync != AppendFsync::EverySec {
+            continue;
+        }
+        if let Err(e) = state.fsync() {
+            tracing::warn!("AOF fsync error: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    fn aof_backend(dir: &std::path::Path) -> Result<Backend> {
+        let backend = Backend::new();
+        backend.aof().set_config(AofConfig {
+            enabled: true,
+            dir: dir.to_path_buf(),
+            fsync: AppendFsync::Always,
+            ..Default::default()
+        });
+        backend.aof().open()?;
+        Ok(backend)
+    }
+
+    fn set_args(key: &str, value: &str) -> RespArray {
+        RespArray::new(vec![
+            BulkString::new("set").into(),
+            BulkString::new(key).into(),
+            BulkString::new(value).into(),
+        ])
+    }
+
+    #[test]
+    fn test_appendfsync_parse() -> Result<()> {
+        assert_eq!("always".parse::<AppendFsync>()?, AppendFsync::Always);
+        assert_eq!("EVERYSEC".parse::<AppendFsync>()?, AppendFsync::EverySec);
+        assert_eq!(AppendFsync::No.to_string(), "no");
+        assert!("sometimes".parse::<AppendFsync>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_feed_and_load() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let backend = aof_backend(dir.path())?;
+        backend.aof().feed(&set_args("hello", "world"))?;
+        backend.aof().feed(&set_args("hello", "rust"))?;
+
+        let restored = Backend::new();
+        restored.aof().set_config(backend.aof().config());
+        assert_eq!(load_aof(&restored)?, Some(2));
+        assert_eq!(restored.get("hello"), Some(BulkString::new("rust").into()));
+        assert_eq!(restored.dirty(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_truncated_aof() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let backend = aof_backend(dir.path())?;
+        backend.aof().feed(&set_args("hello", "world"))?;
+        let valid = fs::metadata(backend.aof().config().path())?.len();
+
+        // 模拟写到一半宕机
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(backend.aof().config().path())?;
+        file.write_all(b"*3\r\n$3\r\nset\r\n$3\r\nfoo")?;
+
+        let restored = Backend::new();
+        restored.aof().set_config(backend.aof().config());
+        assert_eq!(load_aof(&restored)?, Some(1));
+        assert_eq!(restored.get("foo"), None);
+        assert_eq!(fs::metadata(backend.aof().config().path())?.len(), valid);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rewrite_aof() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let backend = aof_backend(dir.path())?;
+        for i in 0..10 {
+            let value = format!("v{}", i);
+            backend.set("key".to_string(), BulkString::new(value.as_str()).into());
+            backend.aof().feed(&set_args("key", &value))?;
+        }
+        backend.hset(
+            "map".to_string(),
+            "field".to_string(),
+            BulkString::new("value").into(),
+        );
+        backend.sadd(
+            "set".to_string(),
+            vec![BulkString::new("a").into(), BulkString::new("b").into()],
+        );
+        let at = crate::now_ms() + 10_000;
+        backend.set("ttl".to_string(), BulkString::new("value").into());
+        backend.set_expire_at("ttl", at);
+
+        rewrite_aof(&backend)?;
+        // 重写后追加的命令依然写入新文件
+        backend.aof().feed(&set_args("after", "rewrite"))?;
+
+        let restored = Backend::new();
+        restored.aof().set_config(backend.aof().config());
+        assert_eq!(load_aof(&restored)?, Some(5));
+        assert_eq!(restored.expire_at("ttl"), Some(at));
+        assert_eq!(restored.get("key"), Some(BulkString::new("v9").into()));
+        assert_eq!(
+            restored.hget("map", "field"),
+            Some(BulkString::new("value").into())
+        );
+        assert_eq!(restored.sismembers("set").map(|s| s.len()), Some(2));
+        assert_eq!(
+            restored.get("after"),
+            Some(BulkString::new("rewrite").into())
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,47 @@
+/// CRC-64/Jones 的多项式(反射形式)，与Redis的crc64保持一致
+const POLY: u64 = 0x95ac_9329_ac4b_c9b5;
+
+/// 预先计算好的查找表
+const TABLE: [u64; 256] = build_table();
+
+const fn build_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u64;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ POLY
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// 在已有的crc基础上继续计算，方便分段计算校验和
+pub fn crc64(crc: u64, data: &[u8]) -> u64 {
+    data.iter().fold(crc, |crc, &b| {
+        TABLE[((crc ^ b as u64) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc64() {
+        // Redis源码中crc64的测试向量
+        assert_eq!(crc64(0, b"123456789"), 0xe9c6_d914_c4b8_d9ca);
+
+        // 分段计算结果应该一致
+        let crc = crc64(0, b"12345");
+        assert_eq!(crc64(crc, b"6789"), 0xe9c6_d914_c4b8_d9ca);
+    }
+}
@@ -0,0 +1,116 @@
+use crate::BackendValue;
+
+use super::{
+    crc64::crc64,
+    encoding::{value_type, write_u64, write_u8, write_value, Reader},
+    PersistenceError, SNAPSHOT_VERSION,
+};
+
+/// 序列化单个key的数据，格式与快照中的值一致
+/// 格式: TYPE | VALUE | VERSION(u16) | CRC64
+pub fn dump_value(value: &BackendValue) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(64);
+    write_u8(&mut buf, value_type(value));
+    write_value(&mut buf, value);
+    buf.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+    let checksum = crc64(0, &buf);
+    write_u64(&mut buf, checksum);
+    buf
+}
+
+/// 校验并反序列化DUMP的结果
+pub fn restore_value(data: &[u8]) -> Result<BackendValue, PersistenceError> {
+    // 最少包含 TYPE + VERSION + CRC64
+    if data.len() < 1 + 2 + 8 {
+        return Err(PersistenceError::InvalidFormat(
+            "payload too short".to_string(),
+        ));
+    }
+
+    let (body, trailer) = data.split_at(data.len() - 8);
+    let mut expected = [0u8; 8];
+    expected.copy_from_slice(trailer);
+    if crc64(0, body) != u64::from_le_bytes(expected) {
+        return Err(PersistenceError::ChecksumMismatch);
+    }
+
+    let (payload, version) = body.split_at(body.len() - 2);
+    let version = u16::from_le_bytes([version[0], version[1]]);
+    if version > SNAPSHOT_VERSION {
+        return Err(PersistenceError::UnsupportedVersion(version));
+    }
+
+    let mut reader = Reader::new(payload);
+    let value_type = reader.read_u8()?;
+    let value = reader.read_value(value_type)?;
+    if !reader.is_empty() {
+        return Err(PersistenceError::InvalidFormat(
+            "trailing data after value".to_string(),
+        ));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BulkString, RespFrame};
+    use anyhow::Result;
+
+    #[test]
+    fn test_dump_restore() -> Result<()> {
+        let values = vec![
+            BackendValue::String(BulkString::new("hello").into()),
+            BackendValue::Hash(vec![("field".to_string(), RespFrame::Integer(1))]),
+            BackendValue::Set(vec![BulkString::new("a").into()]),
+        ];
+
+        for value in values {
+            let payload = dump_value(&value);
+            assert_eq!(restore_value(&payload)?, value);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_bad_payload() {
+        let mut payload = dump_value(&BackendValue::String(BulkString::new("hello").into()));
+        payload[1] ^= 0xff;
+        assert!(matches!(
+            restore_value(&payload),
+            Err(PersistenceError::ChecksumMismatch)
+        ));
+
+        assert!(restore_value(b"short").is_err());
+    }
+
+    /// 字符串类型的值，内容是嵌套depth层的单元素数组
+    fn nested_payload(depth: usize) -> Vec<u8> {
+        let mut buf = vec![0];
+        for _ in 0..depth {
+            // FRAME_ARRAY + 长度1
+            buf.extend_from_slice(&[4, 1, 0, 0, 0]);
+        }
+        // FRAME_NULL
+        buf.push(5);
+        buf.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+        let checksum = crc64(0, &buf);
+        write_u64(&mut buf, checksum);
+        buf
+    }
+
+    #[test]
+    fn test_restore_nested_payload() -> Result<()> {
+        assert!(matches!(
+            restore_value(&nested_payload(100))?,
+            BackendValue::String(RespFrame::Array(_))
+        ));
+        // 嵌套过深的数据返回错误，不会栈溢出
+        assert!(matches!(
+            restore_value(&nested_payload(200_000)),
+            Err(PersistenceError::InvalidFormat(_))
+        ));
+        Ok(())
+    }
+}
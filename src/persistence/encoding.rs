@@ -0,0 +1,408 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    BackendValue, BlobError, BulkString, RespArray, RespAttribute, RespBigNumber, RespDouble,
+    RespFrame, RespMap, RespNull, RespNullArray, RespNullBulkString, RespPush, RespSet,
+    SimpleError, SimpleString, VerbatimString,
+};
+
+use super::PersistenceError;
+
+/// RespFrame在持久化文件中的类型标记
+const FRAME_SIMPLE_STRING: u8 = 0;
+const FRAME_ERROR: u8 = 1;
+const FRAME_INTEGER: u8 = 2;
+const FRAME_BULK_STRING: u8 = 3;
+const FRAME_ARRAY: u8 = 4;
+const FRAME_NULL: u8 = 5;
+const FRAME_BOOLEAN: u8 = 6;
+const FRAME_DOUBLE: u8 = 7;
+const FRAME_MAP: u8 = 8;
+const FRAME_SET: u8 = 9;
+const FRAME_BIG_NUMBER: u8 = 10;
+const FRAME_VERBATIM: u8 = 11;
+const FRAME_BLOB_ERROR: u8 = 12;
+const FRAME_PUSH: u8 = 13;
+const FRAME_ATTRIBUTE: u8 = 14;
+const FRAME_NULL_BULK_STRING: u8 = 15;
+const FRAME_NULL_ARRAY: u8 = 16;
+
+/// 嵌套的RespFrame的最大深度，避免恶意数据递归解析时栈溢出
+const MAX_FRAME_DEPTH: usize = 128;
+
+/// 值类型标记
+pub(crate) const TYPE_STRING: u8 = 0;
+pub(crate) const TYPE_HASH: u8 = 1;
+pub(crate) const TYPE_SET: u8 = 2;
+pub(crate) const TYPE_LIST: u8 = 3;
+pub(crate) const TYPE_ZSET: u8 = 4;
+
+pub(crate) fn write_u8(buf: &mut Vec<u8>, v: u8) {
+    buf.push(v);
+}
+
+pub(crate) fn write_u32(buf: &mut Vec<u8>, v: u32) {
+    buf.extend_from_slice(&v.to_le_bytes());
+}
+
+pub(crate) fn write_u64(buf: &mut Vec<u8>, v: u64) {
+    buf.extend_from_slice(&v.to_le_bytes());
+}
+
+/// 写入 长度(u32) + 数据
+pub(crate) fn write_bytes(buf: &mut Vec<u8>, data: &[u8]) {
+    write_u32(buf, data.len() as u32);
+    buf.extend_from_slice(data);
+}
+
+/// 将RespFrame编码为 类型标记 + 数据
+pub(crate) fn write_frame(buf: &mut Vec<u8>, frame: &RespFrame) {
+    match frame {
+        RespFrame::SimpleString(s) => {
+            write_u8(buf, FRAME_SIMPLE_STRING);
+            write_bytes(buf, s.as_bytes());
+        }
+        RespFrame::Error(e) => {
+            write_u8(buf, FRAME_ERROR);
+            write_bytes(buf, e.as_bytes());
+        }
+        RespFrame::Integer(i) => {
+            write_u8(buf, FRAME_INTEGER);
+            write_u64(buf, *i as u64);
+        }
+        RespFrame::BulkString(s) => {
+            write_u8(buf, FRAME_BULK_STRING);
+            write_bytes(buf, s);
+        }
+        RespFrame::Array(arr) => {
+            write_u8(buf, FRAME_ARRAY);
+            write_u32(buf, arr.len() as u32);
+            for item in arr.iter() {
+                write_frame(buf, item);
+            }
+        }
+        RespFrame::NullBulkString(_) => write_u8(buf, FRAME_NULL_BULK_STRING),
+        RespFrame::NullArray(_) => write_u8(buf, FRAME_NULL_ARRAY),
+        RespFrame::Null(_) => write_u8(buf, FRAME_NULL),
+        RespFrame::Boolean(b) => {
+            write_u8(buf, FRAME_BOOLEAN);
+            write_u8(buf, *b as u8);
+        }
+        RespFrame::Double(d) => {
+            write_u8(buf, FRAME_DOUBLE);
+            write_u64(buf, d.value().to_bits());
+        }
+        RespFrame::Map(map) => {
+            write_u8(buf, FRAME_MAP);
+            write_map(buf, map);
+        }
+        RespFrame::Set(set) => {
+            write_u8(buf, FRAME_SET);
+            write_u32(buf, set.len() as u32);
+            for item in set.iter() {
+                write_frame(buf, item);
+            }
+        }
+        RespFrame::BigNumber(n) => {
+            write_u8(buf, FRAME_BIG_NUMBER);
+            write_bytes(buf, n.as_bytes());
+        }
+        RespFrame::Verbatim(v) => {
+            write_u8(buf, FRAME_VERBATIM);
+            buf.extend_from_slice(v.format());
+            write_bytes(buf, v.data());
+        }
+        RespFrame::BlobError(e) => {
+            write_u8(buf, FRAME_BLOB_ERROR);
+            write_bytes(buf, e);
+        }
+        RespFrame::Push(push) => {
+            write_u8(buf, FRAME_PUSH);
+            write_u32(buf, push.len() as u32);
+            for item in push.iter() {
+                write_frame(buf, item);
+            }
+        }
+        RespFrame::Attribute(attr) => {
+            write_u8(buf, FRAME_ATTRIBUTE);
+            write_map(buf, attr.attributes());
+            write_frame(buf, attr.data());
+        }
+    }
+}
+
+/// 写入 长度(u32) + key value
+fn write_map(buf: &mut Vec<u8>, map: &RespMap) {
+    write_u32(buf, map.len() as u32);
+    for (k, v) in map.iter() {
+        write_bytes(buf, k.as_bytes());
+        write_frame(buf, v);
+    }
+}
+
+/// 值的类型标记
+pub(crate) fn value_type(value: &BackendValue) -> u8 {
+    match value {
+        BackendValue::String(_) => TYPE_STRING,
+        BackendValue::Hash(_) => TYPE_HASH,
+        BackendValue::Set(_) => TYPE_SET,
+        BackendValue::List(_) => TYPE_LIST,
+        BackendValue::ZSet(_) => TYPE_ZSET,
+    }
+}
+
+/// 写入值的数据部分，类型标记由调用者写入
+pub(crate) fn write_value(buf: &mut Vec<u8>, value: &BackendValue) {
+    match value {
+        BackendValue::String(v) => write_frame(buf, v),
+        BackendValue::Hash(fields) => {
+            write_u32(buf, fields.len() as u32);
+            for (field, value) in fields {
+                write_bytes(buf, field.as_bytes());
+                write_frame(buf, value);
+            }
+        }
+        BackendValue::Set(members) | BackendValue::List(members) => {
+            write_u32(buf, members.len() as u32);
+            for member in members {
+                write_frame(buf, member);
+            }
+        }
+        BackendValue::ZSet(members) => {
+            write_u32(buf, members.len() as u32);
+            for (member, score) in members {
+                write_bytes(buf, member.as_bytes());
+                write_u64(buf, score.to_bits());
+            }
+        }
+    }
+}
+
+/// 顺序读取持久化数据的游标
+pub(crate) struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    /// 正在解析的RespFrame的嵌套深度
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(buf: &'a [u8]) -> Self {
+        Reader {
+            buf,
+            pos: 0,
+            depth: 0,
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pos >= self.buf.len()
+    }
+
+    /// 剩余未读取的字节数
+    pub(crate) fn remaining(&self) -> usize {
+        self.buf.len().saturating_sub(self.pos)
+    }
+
+    pub(crate) fn read_exact(&mut self, len: usize) -> Result<&'a [u8], PersistenceError> {
+        if self.buf.len() - self.pos < len {
+            return Err(PersistenceError::InvalidFormat(
+                "unexpected end of data".to_string(),
+            ));
+        }
+        let data = &self.buf[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(data)
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8, PersistenceError> {
+        Ok(self.read_exact(1)?[0])
+    }
+
+    pub(crate) fn read_u16(&mut self) -> Result<u16, PersistenceError> {
+        let data = self.read_exact(2)?;
+        Ok(u16::from_le_bytes([data[0], data[1]]))
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32, PersistenceError> {
+        let mut data = [0u8; 4];
+        data.copy_from_slice(self.read_exact(4)?);
+        Ok(u32::from_le_bytes(data))
+    }
+
+    pub(crate) fn read_u64(&mut self) -> Result<u64, PersistenceError> {
+        let mut data = [0u8; 8];
+        data.copy_from_slice(self.read_exact(8)?);
+        Ok(u64::from_le_bytes(data))
+    }
+
+    pub(crate) fn read_bytes(&mut self) -> Result<&'a [u8], PersistenceError> {
+        let len = self.read_u32()? as usize;
+        self.read_exact(len)
+    }
+
+    pub(crate) fn read_string(&mut self) -> Result<String, PersistenceError> {
+        let data = self.read_bytes()?;
+        String::from_utf8(data.to_vec())
+            .map_err(|e| PersistenceError::InvalidFormat(format!("invalid utf8: {}", e)))
+    }
+
+    /// 根据类型标记读取值的数据部分
+    pub(crate) fn read_value(&mut self, value_type: u8) -> Result<BackendValue, PersistenceError> {
+        let value = match value_type {
+            TYPE_STRING => BackendValue::String(self.read_frame()?),
+            TYPE_HASH => {
+                let len = self.read_u32()? as usize;
+                let mut fields = Vec::with_capacity(len.min(1024));
+                for _ in 0..len {
+                    let field = self.read_string()?;
+                    let value = self.read_frame()?;
+                    fields.push((field, value));
+                }
+                BackendValue::Hash(fields)
+            }
+            TYPE_SET | TYPE_LIST => {
+                let len = self.read_u32()? as usize;
+                let mut members = Vec::with_capacity(len.min(1024));
+                for _ in 0..len {
+                    members.push(self.read_frame()?);
+                }
+                if value_type == TYPE_SET {
+                    BackendValue::Set(members)
+                } else {
+                    BackendValue::List(members)
+                }
+            }
+            TYPE_ZSET => {
+                let len = self.read_u32()? as usize;
+                let mut members = Vec::with_capacity(len.min(1024));
+                for _ in 0..len {
+                    let member = self.read_string()?;
+                    let score = f64::from_bits(self.read_u64()?);
+                    members.push((member, score));
+                }
+                BackendValue::ZSet(members)
+            }
+            t => {
+                return Err(PersistenceError::InvalidFormat(format!(
+                    "unknown value type: {}",
+                    t
+                )))
+            }
+        };
+        Ok(value)
+    }
+
+    pub(crate) fn read_frame(&mut self) -> Result<RespFrame, PersistenceError> {
+        if self.depth >= MAX_FRAME_DEPTH {
+            return Err(PersistenceError::InvalidFormat(
+                "frame nesting too deep".to_string(),
+            ));
+        }
+        self.depth += 1;
+        let frame = self.read_frame_inner();
+        self.depth -= 1;
+        frame
+    }
+
+    fn read_frame_inner(&mut self) -> Result<RespFrame, PersistenceError> {
+        let frame = match self.read_u8()? {
+            FRAME_SIMPLE_STRING => SimpleString::new(self.read_string()?).into(),
+            FRAME_ERROR => SimpleError::new(self.read_string()?).into(),
+            FRAME_INTEGER => RespFrame::Integer(self.read_u64()? as i64),
+            FRAME_BULK_STRING => BulkString::new(self.read_bytes()?).into(),
+            FRAME_ARRAY => RespArray::new(self.read_frames()?).into(),
+            FRAME_NULL_BULK_STRING => RespNullBulkString.into(),
+            FRAME_NULL_ARRAY => RespNullArray.into(),
+            FRAME_NULL => RespNull.into(),
+            FRAME_BOOLEAN => RespFrame::Boolean(self.read_u8()? != 0),
+            FRAME_DOUBLE => RespDouble::new(f64::from_bits(self.read_u64()?)).into(),
+            FRAME_MAP => self.read_map()?.into(),
+            FRAME_SET => RespSet::new(self.read_frames()?).into(),
+            FRAME_BIG_NUMBER => RespBigNumber::new(self.read_string()?).into(),
+            FRAME_VERBATIM => {
+                let format = [self.read_u8()?, self.read_u8()?, self.read_u8()?];
+                VerbatimString::new(&format, self.read_bytes()?).into()
+            }
+            FRAME_BLOB_ERROR => BlobError::new(self.read_bytes()?).into(),
+            FRAME_PUSH => RespPush::new(self.read_frames()?).into(),
+            FRAME_ATTRIBUTE => {
+                let attributes = self.read_map()?;
+                RespAttribute::new(attributes, self.read_frame()?).into()
+            }
+            t => {
+                return Err(PersistenceError::InvalidFormat(format!(
+                    "unknown frame type: {}",
+                    t
+                )))
+            }
+        };
+        Ok(frame)
+    }
+
+    /// 读取 长度(u32) + 多个RespFrame
+    fn read_frames(&mut self) -> Result<Vec<RespFrame>, PersistenceError> {
+        let len = self.read_u32()? as usize;
+        let mut items = Vec::with_capacity(len.min(1024));
+        for _ in 0..len {
+            items.push(self.read_frame()?);
+        }
+        Ok(items)
+    }
+
+    /// 读取 长度(u32) + key value
+    fn read_map(&mut self) -> Result<RespMap, PersistenceError> {
+        let len = self.read_u32()? as usize;
+        let mut map = BTreeMap::new();
+        for _ in 0..len {
+            let key = self.read_string()?;
+            let value = self.read_frame()?;
+            map.insert(key, value);
+        }
+        Ok(RespMap(map))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_frame_roundtrip() -> Result<()> {
+        let mut map = RespMap::default();
+        map.insert("key".to_string(), BulkString::new("value").into());
+        let frames: Vec<RespFrame> = vec![
+            SimpleString::new("OK").into(),
+            SimpleError::new("ERR oops").into(),
+            RespFrame::Integer(-42),
+            BulkString::new("").into(),
+            RespArray::new(vec![BulkString::new("a").into(), RespNull.into()]).into(),
+            true.into(),
+            RespDouble::new(1.5).into(),
+            map.clone().into(),
+            RespSet::new(vec![RespFrame::Integer(1)]).into(),
+            RespBigNumber::new("-12345678901234567890").into(),
+            VerbatimString::new(b"mkd", "# title").into(),
+            BlobError::new("ERR blob").into(),
+            RespPush::new(vec![BulkString::new("message").into()]).into(),
+            RespAttribute::new(map, RespFrame::Integer(1)).into(),
+            RespNullBulkString.into(),
+            RespNullArray.into(),
+            RespArray::new(vec![]).into(),
+        ];
+
+        let mut buf = Vec::new();
+        for frame in &frames {
+            write_frame(&mut buf, frame);
+        }
+
+        let mut reader = Reader::new(&buf);
+        for frame in frames {
+            assert_eq!(reader.read_frame()?, frame);
+        }
+        assert!(reader.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+mod aof;
+mod crc64;
+mod dump;
+pub(crate) mod encoding;
+pub mod rdb;
+mod snapshot;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use thiserror::Error;
+
+use crate::Backend;
+
+pub use self::{
+    aof::{bgrewriteaof, load_aof, rewrite_aof, run_aof_cron, AofConfig, AofState, AppendFsync},
+    crc64::crc64,
+    dump::{dump_value, restore_value},
+    snapshot::{
+        bgsave, decode_snapshot, encode_snapshot, load, load_bytes, run_save_cron, save, SaveRule,
+        SnapshotConfig, SnapshotFormat, SnapshotState, SNAPSHOT_VERSION,
+    },
+};
+
+/// 持久化过程中的异常
+#[derive(Error, Debug)]
+pub enum PersistenceError {
+    #[error("IO Error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid Format: {0}")]
+    InvalidFormat(String),
+    #[error("Unsupported Version: {0}")]
+    UnsupportedVersion(u16),
+    #[error("Checksum Mismatch")]
+    ChecksumMismatch,
+    #[error("Background save already in progress")]
+    BgsaveInProgress,
+    #[error("Background append only file rewriting already in progress")]
+    AofRewriteInProgress,
+}
+
+/// 启动时加载数据：开启AOF时优先回放AOF，否则加载快照
+pub fn load_data(backend: &Backend) -> Result<Option<usize>, PersistenceError> {
+    if !backend.aof().is_enabled() {
+        return load(backend);
+    }
+
+    match load_aof(backend)? {
+        Some(count) => {
+            backend.aof().open()?;
+            Ok(Some(count))
+        }
+        None => {
+            // 还没有AOF文件时先加载快照，再用当前数据生成AOF
+            let ret = load(backend)?;
+            rewrite_aof(backend)?;
+            backend.aof().open()?;
+            Ok(ret)
+        }
+    }
+}
+
+/// 启动时在后台加载数据，加载期间客户端会收到-LOADING
+pub fn spawn_load(
+    backend: &Backend,
+) -> tokio::task::JoinHandle<Result<Option<usize>, PersistenceError>> {
+    backend.snapshot().set_loading(true);
+    let backend = backend.clone();
+    tokio::task::spawn_blocking(move || {
+        let ret = load_data(&backend);
+        backend.snapshot().set_loading(false);
+        ret
+    })
+}
+
+/// 当前的unix时间(秒)
+pub(crate) fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,347 @@
+//! Redis内部紧凑编码: ziplist, listpack, intset, zipmap
+
+use crate::persistence::{encoding::Reader, PersistenceError};
+
+/// 紧凑编码中的一个元素，可能是字符串或整数
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Entry {
+    Str(Vec<u8>),
+    Int(i64),
+}
+
+impl Entry {
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        match self {
+            Entry::Str(s) => s,
+            Entry::Int(i) => i.to_string().into_bytes(),
+        }
+    }
+}
+
+fn invalid(what: &str) -> PersistenceError {
+    PersistenceError::InvalidFormat(format!("invalid {} encoding", what))
+}
+
+fn read_int_le(reader: &mut Reader, len: usize) -> Result<i64, PersistenceError> {
+    let data = reader.read_exact(len)?;
+    let mut buf = [0u8; 8];
+    buf[..len].copy_from_slice(data);
+    // 符号扩展
+    let shift = 64 - len * 8;
+    Ok((i64::from_le_bytes(buf) << shift) >> shift)
+}
+
+/// 解析ziplist
+/// zlbytes(u32) | zltail(u32) | zllen(u16) | [prevlen encoding data]... | 0xff
+pub(crate) fn parse_ziplist(data: &[u8]) -> Result<Vec<Entry>, PersistenceError> {
+    let mut reader = Reader::new(data);
+    let zlbytes = reader.read_u32()? as usize;
+    if zlbytes != data.len() {
+        return Err(invalid("ziplist"));
+    }
+    reader.read_u32()?;
+    reader.read_u16()?;
+
+    let mut entries = Vec::new();
+    loop {
+        let prevlen = reader.read_u8()?;
+        if prevlen == 0xff {
+            break;
+        }
+        if prevlen == 0xfe {
+            reader.read_u32()?;
+        }
+
+        let enc = reader.read_u8()?;
+        let entry = match enc >> 6 {
+            0 => Entry::Str(reader.read_exact((enc & 0x3f) as usize)?.to_vec()),
+            1 => {
+                let len = ((enc & 0x3f) as usize) << 8 | reader.read_u8()? as usize;
+                Entry::Str(reader.read_exact(len)?.to_vec())
+            }
+            2 => {
+                let len = u32::from_be_bytes(reader.read_u32()?.to_le_bytes()) as usize;
+                Entry::Str(reader.read_exact(len)?.to_vec())
+            }
+            _ => match enc {
+                0xc0 => Entry::Int(read_int_le(&mut reader, 2)?),
+                0xd0 => Entry::Int(read_int_le(&mut reader, 4)?),
+                0xe0 => Entry::Int(read_int_le(&mut reader, 8)?),
+                0xf0 => Entry::Int(read_int_le(&mut reader, 3)?),
+                0xfe => Entry::Int(read_int_le(&mut reader, 1)?),
+                0xf1..=0xfd => Entry::Int((enc & 0x0f) as i64 - 1),
+                _ => return Err(invalid("ziplist")),
+            },
+        };
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// 解析listpack
+/// total(u32) | num(u16) | [encoding data backlen]... | 0xff
+pub(crate) fn parse_listpack(data: &[u8]) -> Result<Vec<Entry>, PersistenceError> {
+    let mut reader = Reader::new(data);
+    let total = reader.read_u32()? as usize;
+    if total != data.len() {
+        return Err(invalid("listpack"));
+    }
+    reader.read_u16()?;
+
+    let mut entries = Vec::new();
+    loop {
+        let enc = reader.read_u8()?;
+        if enc == 0xff {
+            break;
+        }
+
+        let (entry, len) = if enc & 0x80 == 0 {
+            (Entry::Int(enc as i64), 1)
+        } else if enc & 0xc0 == 0x80 {
+            let len = (enc & 0x3f) as usize;
+            (Entry::Str(reader.read_exact(len)?.to_vec()), 1 + len)
+        } else if enc & 0xe0 == 0xc0 {
+            let v = ((enc & 0x1f) as i64) << 8 | reader.read_u8()? as i64;
+            let v = if v >= 1 << 12 { v - (1 << 13) } else { v };
+            (Entry::Int(v), 2)
+        } else if enc & 0xf0 == 0xe0 {
+            let len = ((enc & 0x0f) as usize) << 8 | reader.read_u8()? as usize;
+            (Entry::Str(reader.read_exact(len)?.to_vec()), 2 + len)
+        } else {
+            match enc {
+                0xf0 => {
+                    let len = reader.read_u32()? as usize;
+                    (Entry::Str(reader.read_exact(len)?.to_vec()), 5 + len)
+                }
+                0xf1 => (Entry::Int(read_int_le(&mut reader, 2)?), 3),
+                0xf2 => (Entry::Int(read_int_le(&mut reader, 3)?), 4),
+                0xf3 => (Entry::Int(read_int_le(&mut reader, 4)?), 5),
+                0xf4 => (Entry::Int(read_int_le(&mut reader, 8)?), 9),
+                _ => return Err(invalid("listpack")),
+            }
+        };
+
+        reader.read_exact(backlen_size(len))?;
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+/// 解析intset
+/// encoding(u32) | length(u32) | [int]...
+pub(crate) fn parse_intset(data: &[u8]) -> Result<Vec<i64>, PersistenceError> {
+    let mut reader = Reader::new(data);
+    let width = reader.read_u32()? as usize;
+    if !matches!(width, 2 | 4 | 8) {
+        return Err(invalid("intset"));
+    }
+    let len = reader.read_u32()? as usize;
+
+    let mut items = Vec::with_capacity(len.min(1024));
+    for _ in 0..len {
+        items.push(read_int_le(&mut reader, width)?);
+    }
+    if !reader.is_empty() {
+        return Err(invalid("intset"));
+    }
+    Ok(items)
+}
+
+/// 解析zipmap(RDB 2.6之前的hash编码)
+/// zmlen(u8) | [len key len free value]... | 0xff
+/// 返回 key value 交替排列的元素
+pub(crate) fn parse_zipmap(data: &[u8]) -> Result<Vec<Entry>, PersistenceError> {
+    fn read_len(reader: &mut Reader) -> Result<Option<usize>, PersistenceError> {
+        match reader.read_u8()? {
+            0xff => Ok(None),
+            0xfe => Ok(Some(reader.read_u32()? as usize)),
+            len => Ok(Some(len as usize)),
+        }
+    }
+
+    let mut reader = Reader::new(data);
+    reader.read_u8()?;
+
+    let mut fields = Vec::new();
+    while let Some(len) = read_len(&mut reader)? {
+        let key = reader.read_exact(len)?.to_vec();
+        let len = read_len(&mut reader)?.ok_or_else(|| invalid("zipmap"))?;
+        let free = reader.read_u8()? as usize;
+        let value = reader.read_exact(len)?.to_vec();
+        reader.read_exact(free)?;
+        fields.push(Entry::Str(key));
+        fields.push(Entry::Str(value));
+    }
+
+    Ok(fields)
+}
+
+/// listpack中记录元素长度的backlen占用的字节数
+fn backlen_size(len: usize) -> usize {
+    match len {
+        0..=127 => 1,
+        128..=16382 => 2,
+        16383..=2097150 => 3,
+        2097151..=268435454 => 4,
+        _ => 5,
+    }
+}
+
+/// 字符串是否是规范的整数表示(没有前导0和+号)，是则返回对应的整数
+pub(crate) fn canonical_int(data: &[u8]) -> Option<i64> {
+    if data.is_empty() || data.len() > 20 {
+        return None;
+    }
+    let s = std::str::from_utf8(data).ok()?;
+    let v: i64 = s.parse().ok()?;
+    (v.to_string() == s).then_some(v)
+}
+
+/// 编码listpack，能表示为整数的元素使用整数编码
+pub(crate) fn encode_listpack<T: AsRef<[u8]>>(items: &[T]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for item in items {
+        let item = item.as_ref();
+        let start = body.len();
+        match canonical_int(item) {
+            Some(v @ 0..=127) => body.push(v as u8),
+            Some(v @ -4096..=4095) => {
+                let u = (v as u16) & 0x1fff;
+                body.push(0xc0 | (u >> 8) as u8);
+                body.push(u as u8);
+            }
+            Some(v @ -32768..=32767) => {
+                body.push(0xf1);
+                body.extend_from_slice(&(v as i16).to_le_bytes());
+            }
+            Some(v @ -8388608..=8388607) => {
+                body.push(0xf2);
+                body.extend_from_slice(&(v as i32).to_le_bytes()[..3]);
+            }
+            Some(v) if i32::try_from(v).is_ok() => {
+                body.push(0xf3);
+                body.extend_from_slice(&(v as i32).to_le_bytes());
+            }
+            Some(v) => {
+                body.push(0xf4);
+                body.extend_from_slice(&v.to_le_bytes());
+            }
+            None => {
+                let len = item.len();
+                if len < 64 {
+                    body.push(0x80 | len as u8);
+                } else if len < 4096 {
+                    body.push(0xe0 | (len >> 8) as u8);
+                    body.push(len as u8);
+                } else {
+                    body.push(0xf0);
+                    body.extend_from_slice(&(len as u32).to_le_bytes());
+                }
+                body.extend_from_slice(item);
+            }
+        }
+        let len = body.len() - start;
+        encode_backlen(&mut body, len);
+    }
+
+    let total = 4 + 2 + body.len() + 1;
+    let mut buf = Vec::with_capacity(total);
+    buf.extend_from_slice(&(total as u32).to_le_bytes());
+    // 元素个数超过u16时记为65535，读取时需要遍历
+    buf.extend_from_slice(&(items.len().min(u16::MAX as usize) as u16).to_le_bytes());
+    buf.extend_from_slice(&body);
+    buf.push(0xff);
+    buf
+}
+
+/// 编码backlen，从后向前读取，每个字节的最高位表示前面还有字节
+fn encode_backlen(buf: &mut Vec<u8>, len: usize) {
+    let size = backlen_size(len);
+    for i in (0..size).rev() {
+        let b = ((len >> (7 * i)) & 0x7f) as u8;
+        buf.push(if i == size - 1 { b } else { b | 0x80 });
+    }
+}
+
+/// 编码intset，按从小到大排序，使用能容纳所有元素的最小宽度
+pub(crate) fn encode_intset(items: &[i64]) -> Vec<u8> {
+    let mut items = items.to_vec();
+    items.sort_unstable();
+    let width: usize = if items.iter().all(|v| i16::try_from(*v).is_ok()) {
+        2
+    } else if items.iter().all(|v| i32::try_from(*v).is_ok()) {
+        4
+    } else {
+        8
+    };
+
+    let mut buf = Vec::with_capacity(8 + items.len() * width);
+    buf.extend_from_slice(&(width as u32).to_le_bytes());
+    buf.extend_from_slice(&(items.len() as u32).to_le_bytes());
+    for v in items {
+        buf.extend_from_slice(&v.to_le_bytes()[..width]);
+    }
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_listpack_roundtrip() -> Result<()> {
+        let long = "x".repeat(5000);
+        let items: Vec<&[u8]> = vec![
+            b"hello",
+            b"0",
+            b"127",
+            b"-1",
+            b"4095",
+            b"-4096",
+            b"30000",
+            b"-8000000",
+            b"2147483647",
+            b"9223372036854775807",
+            b"007",
+            &[b'a'; 100],
+            long.as_bytes(),
+        ];
+
+        let data = encode_listpack(&items);
+        let entries = parse_listpack(&data)?;
+        let decoded: Vec<Vec<u8>> = entries.into_iter().map(Entry::into_bytes).collect();
+        let expected: Vec<Vec<u8>> = items.iter().map(|v| v.to_vec()).collect();
+        assert_eq!(decoded, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_intset_roundtrip() -> Result<()> {
+        assert_eq!(parse_intset(&encode_intset(&[3, 1, 2]))?, vec![1, 2, 3]);
+        let wide = vec![-1, i32::MAX as i64 + 1];
+        assert_eq!(parse_intset(&encode_intset(&wide))?, wide);
+        assert!(parse_intset(&[3, 0, 0, 0, 0, 0, 0, 0]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_backlen() {
+        for len in [1, 127, 128, 16382, 16383, 2097151] {
+            let mut buf = Vec::new();
+            encode_backlen(&mut buf, len);
+            assert_eq!(buf.len(), backlen_size(len));
+
+            // 从后向前解码
+            let mut v = 0;
+            for (i, b) in buf.iter().rev().enumerate() {
+                v |= ((b & 0x7f) as usize) << (7 * i);
+            }
+            assert_eq!(v, len);
+        }
+    }
+}
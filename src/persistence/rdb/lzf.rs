@@ -0,0 +1,148 @@
+use crate::persistence::PersistenceError;
+
+/// 哈希表大小 2^HLOG
+const HLOG: u32 = 14;
+/// 最大回溯距离
+const MAX_OFF: usize = 1 << 13;
+/// 最长匹配长度
+const MAX_REF: usize = (1 << 8) + (1 << 3);
+/// 最长字面量长度
+const MAX_LIT: usize = 1 << 5;
+
+/// LZF解压，expect为解压后的长度
+pub(crate) fn decompress(input: &[u8], expect: usize) -> Result<Vec<u8>, PersistenceError> {
+    let err = || PersistenceError::InvalidFormat("invalid LZF data".to_string());
+    // expect来自RDB文件，不能直接按它分配内存
+    let mut out = Vec::with_capacity(expect.min(input.len().saturating_mul(8)));
+    let mut i = 0;
+
+    while i < input.len() {
+        let ctrl = input[i] as usize;
+        i += 1;
+
+        if ctrl < 32 {
+            // 字面量 ctrl + 1 个字节
+            let len = ctrl + 1;
+            if out.len() + len > expect {
+                return Err(err());
+            }
+            let lit = input.get(i..i + len).ok_or_else(err)?;
+            out.extend_from_slice(lit);
+            i += len;
+        } else {
+            // 回溯引用
+            let mut len = ctrl >> 5;
+            if len == 7 {
+                len += *input.get(i).ok_or_else(err)? as usize;
+                i += 1;
+            }
+            let off = ((ctrl & 0x1f) << 8) + *input.get(i).ok_or_else(err)? as usize + 1;
+            i += 1;
+            if off > out.len() || out.len() + len + 2 > expect {
+                return Err(err());
+            }
+            // 引用区域可能和输出重叠，需要逐字节复制
+            let start = out.len() - off;
+            for k in 0..len + 2 {
+                out.push(out[start + k]);
+            }
+        }
+    }
+
+    if out.len() != expect {
+        return Err(err());
+    }
+    Ok(out)
+}
+
+/// LZF压缩
+pub(crate) fn compress(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len());
+    let mut table = vec![usize::MAX; 1 << HLOG];
+    let mut lit = Vec::with_capacity(MAX_LIT);
+    let mut ip = 0;
+
+    fn flush(out: &mut Vec<u8>, lit: &mut Vec<u8>) {
+        if !lit.is_empty() {
+            out.push((lit.len() - 1) as u8);
+            out.extend_from_slice(lit);
+            lit.clear();
+        }
+    }
+
+    while ip < input.len() {
+        if ip + 2 < input.len() {
+            let v = (input[ip] as u32) << 16 | (input[ip + 1] as u32) << 8 | input[ip + 2] as u32;
+            let h = (v.wrapping_mul(2_654_435_761) >> (32 - HLOG)) as usize;
+            let r = table[h];
+            table[h] = ip;
+
+            if r != usize::MAX && ip - r <= MAX_OFF && input[r..r + 3] == input[ip..ip + 3] {
+                let max = MAX_REF.min(input.len() - ip);
+                let mut len = 3;
+                while len < max && input[r + len] == input[ip + len] {
+                    len += 1;
+                }
+
+                flush(&mut out, &mut lit);
+                let off = ip - r - 1;
+                let l = len - 2;
+                if l < 7 {
+                    out.push(((l as u8) << 5) | (off >> 8) as u8);
+                } else {
+                    out.push((7 << 5) | (off >> 8) as u8);
+                    out.push((l - 7) as u8);
+                }
+                out.push((off & 0xff) as u8);
+                ip += len;
+                continue;
+            }
+        }
+
+        lit.push(input[ip]);
+        ip += 1;
+        if lit.len() == MAX_LIT {
+            flush(&mut out, &mut lit);
+        }
+    }
+    flush(&mut out, &mut lit);
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_lzf_roundtrip() -> Result<()> {
+        let inputs: Vec<Vec<u8>> = vec![
+            b"hello world".to_vec(),
+            b"a".repeat(1000),
+            b"abcabcabcabcabcabcabcabcabcabc-hello-hello-hello".to_vec(),
+            (0..5000u32).map(|i| (i % 251) as u8).collect(),
+        ];
+
+        for input in inputs {
+            let compressed = compress(&input);
+            assert_eq!(decompress(&compressed, input.len())?, input);
+        }
+
+        // 重复数据应该被压缩
+        assert!(compress(&b"a".repeat(1000)).len() < 100);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lzf_invalid() {
+        assert!(decompress(&[0x20, 0x00], 3).is_err());
+        assert!(decompress(&[0x05, b'a'], 6).is_err());
+        // 解压后的长度和expect不一致
+        assert!(decompress(&[0x00, b'a'], 2).is_err());
+        assert!(decompress(&[0x01, b'a', b'b'], 1).is_err());
+        // 伪造的超大长度不会导致按expect分配内存
+        assert!(decompress(&[0x00, b'a'], usize::MAX).is_err());
+    }
+}
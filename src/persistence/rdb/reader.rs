@@ -0,0 +1,368 @@
+use crate::{
+    now_ms,
+    persistence::{crc64, encoding::Reader, PersistenceError},
+    Backend, BackendValue, BulkString, RespFrame,
+};
+
+use super::{
+    encodings::{parse_intset, parse_listpack, parse_ziplist, parse_zipmap, Entry},
+    lzf, *,
+};
+
+/// 解析后的RDB文件
+#[derive(Debug, Default)]
+pub struct Rdb {
+    pub version: u16,
+    /// AUX字段，如 redis-ver, ctime 等
+    pub aux: Vec<(String, String)>,
+    pub entries: Vec<RdbEntry>,
+}
+
+/// RDB中的一个key
+#[derive(Debug, Clone, PartialEq)]
+pub struct RdbEntry {
+    pub db: u64,
+    pub key: String,
+    pub value: BackendValue,
+    /// 过期时间(unix毫秒)
+    pub expire_at: Option<i64>,
+}
+
+struct RdbReader<'a> {
+    reader: Reader<'a>,
+}
+
+fn invalid(msg: impl Into<String>) -> PersistenceError {
+    PersistenceError::InvalidFormat(msg.into())
+}
+
+fn lossy(data: Vec<u8>) -> String {
+    String::from_utf8(data).unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())
+}
+
+fn parse_score(data: &[u8]) -> Result<f64, PersistenceError> {
+    let s = std::str::from_utf8(data).map_err(|_| invalid("invalid zset score"))?;
+    match s {
+        "inf" | "+inf" => Ok(f64::INFINITY),
+        "-inf" => Ok(f64::NEG_INFINITY),
+        s => s.parse().map_err(|_| invalid("invalid zset score")),
+    }
+}
+
+fn entry_score(entry: Entry) -> Result<f64, PersistenceError> {
+    match entry {
+        Entry::Int(i) => Ok(i as f64),
+        Entry::Str(s) => parse_score(&s),
+    }
+}
+
+fn pairs(entries: Vec<Entry>) -> Result<Vec<(Entry, Entry)>, PersistenceError> {
+    if !entries.len().is_multiple_of(2) {
+        return Err(invalid("odd number of entries in pair encoding"));
+    }
+    let mut iter = entries.into_iter();
+    let mut ret = Vec::new();
+    while let (Some(a), Some(b)) = (iter.next(), iter.next()) {
+        ret.push((a, b));
+    }
+    Ok(ret)
+}
+
+fn bulk(data: Vec<u8>) -> RespFrame {
+    BulkString::new(data).into()
+}
+
+fn sort_zset(members: &mut [(String, f64)]) {
+    members.sort_by(|a, b| {
+        a.1.partial_cmp(&b.1)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.0.cmp(&b.0))
+    });
+}
+
+impl<'a> RdbReader<'a> {
+    /// 读取长度编码，第二个返回值表示是否是特殊编码的字符串
+    fn read_length_with_encoding(&mut self) -> Result<(u64, bool), PersistenceError> {
+        let b = self.reader.read_u8()?;
+        match b >> 6 {
+            0 => Ok(((b & 0x3f) as u64, false)),
+            1 => Ok((
+                ((b & 0x3f) as u64) << 8 | self.reader.read_u8()? as u64,
+                false,
+            )),
+            2 => match b {
+                RDB_32BIT_LEN => {
+                    let mut buf = [0u8; 4];
+                    buf.copy_from_slice(self.reader.read_exact(4)?);
+                    Ok((u32::from_be_bytes(buf) as u64, false))
+                }
+                RDB_64BIT_LEN => {
+                    let mut buf = [0u8; 8];
+                    buf.copy_from_slice(self.reader.read_exact(8)?);
+                    Ok((u64::from_be_bytes(buf), false))
+                }
+                _ => Err(invalid(format!("unknown length encoding: {:#x}", b))),
+            },
+            _ => Ok(((b & 0x3f) as u64, true)),
+        }
+    }
+
+    fn read_length(&mut self) -> Result<usize, PersistenceError> {
+        match self.read_length_with_encoding()? {
+            (len, false) => usize::try_from(len).map_err(|_| invalid("length too large")),
+            _ => Err(invalid("unexpected encoded length")),
+        }
+    }
+
+    fn read_string(&mut self) -> Result<Vec<u8>, PersistenceError> {
+        let (len, encoded) = self.read_length_with_encoding()?;
+        if !encoded {
+            return Ok(self.reader.read_exact(len as usize)?.to_vec());
+        }
+
+        let v = match len {
+            RDB_ENC_INT8 => self.reader.read_u8()? as i8 as i64,
+            RDB_ENC_INT16 => self.reader.read_u16()? as i16 as i64,
+            RDB_ENC_INT32 => self.reader.read_u32()? as i32 as i64,
+            RDB_ENC_LZF => {
+                let clen = self.read_length()?;
+                let len = self.read_length()?;
+                let data = self.reader.read_exact(clen)?;
+                return lzf::decompress(data, len);
+            }
+            _ => return Err(invalid(format!("unknown string encoding: {}", len))),
+        };
+        Ok(v.to_string().into_bytes())
+    }
+
+    /// RDB_TYPE_ZSET中以字符串保存的score
+    fn read_double_string(&mut self) -> Result<f64, PersistenceError> {
+        match self.reader.read_u8()? {
+            253 => Ok(f64::NAN),
+            254 => Ok(f64::INFINITY),
+            255 => Ok(f64::NEG_INFINITY),
+            len => parse_score(self.reader.read_exact(len as usize)?),
+        }
+    }
+
+    fn read_binary_double(&mut self) -> Result<f64, PersistenceError> {
+        Ok(f64::from_bits(self.reader.read_u64()?))
+    }
+
+    fn read_list<T>(
+        &mut self,
+        mut f: impl FnMut(&mut Self) -> Result<T, PersistenceError>,
+    ) -> Result<Vec<T>, PersistenceError> {
+        let len = self.read_length()?;
+        let mut items = Vec::with_capacity(len.min(1024));
+        for _ in 0..len {
+            items.push(f(self)?);
+        }
+        Ok(items)
+    }
+
+    fn read_object(&mut self, rdb_type: u8) -> Result<BackendValue, PersistenceError> {
+        let value = match rdb_type {
+            RDB_TYPE_STRING => BackendValue::String(bulk(self.read_string()?)),
+            RDB_TYPE_LIST => BackendValue::List(self.read_list(|r| Ok(bulk(r.read_string()?)))?),
+            RDB_TYPE_SET => BackendValue::Set(self.read_list(|r| Ok(bulk(r.read_string()?)))?),
+            RDB_TYPE_ZSET | RDB_TYPE_ZSET_2 => {
+                let mut members = self.read_list(|r| {
+                    let member = lossy(r.read_string()?);
+                    let score = if rdb_type == RDB_TYPE_ZSET {
+                        r.read_double_string()?
+                    } else {
+                        r.read_binary_double()?
+                    };
+                    Ok((member, score))
+                })?;
+                sort_zset(&mut members);
+                BackendValue::ZSet(members)
+            }
+            RDB_TYPE_HASH => BackendValue::Hash(
+                self.read_list(|r| Ok((lossy(r.read_string()?), bulk(r.read_string()?))))?,
+            ),
+            RDB_TYPE_HASH_ZIPMAP => BackendValue::Hash(
+                pairs(parse_zipmap(&self.read_string()?)?)?
+                    .into_iter()
+                    .map(|(k, v)| (lossy(k.into_bytes()), bulk(v.into_bytes())))
+                    .collect(),
+            ),
+            RDB_TYPE_LIST_ZIPLIST => BackendValue::List(
+                parse_ziplist(&self.read_string()?)?
+                    .into_iter()
+                    .map(|e| bulk(e.into_bytes()))
+                    .collect(),
+            ),
+            RDB_TYPE_SET_INTSET => BackendValue::Set(
+                parse_intset(&self.read_string()?)?
+                    .into_iter()
+                    .map(|v| bulk(v.to_string().into_bytes()))
+                    .collect(),
+            ),
+            RDB_TYPE_SET_LISTPACK => BackendValue::Set(
+                parse_listpack(&self.read_string()?)?
+                    .into_iter()
+                    .map(|e| bulk(e.into_bytes()))
+                    .collect(),
+            ),
+            RDB_TYPE_ZSET_ZIPLIST | RDB_TYPE_ZSET_LISTPACK => {
+                let data = self.read_string()?;
+                let entries = if rdb_type == RDB_TYPE_ZSET_ZIPLIST {
+                    parse_ziplist(&data)?
+                } else {
+                    parse_listpack(&data)?
+                };
+                let mut members = pairs(entries)?
+                    .into_iter()
+                    .map(|(m, s)| Ok((lossy(m.into_bytes()), entry_score(s)?)))
+                    .collect::<Result<Vec<_>, PersistenceError>>()?;
+                sort_zset(&mut members);
+                BackendValue::ZSet(members)
+            }
+            RDB_TYPE_HASH_ZIPLIST | RDB_TYPE_HASH_LISTPACK => {
+                let data = self.read_string()?;
+                let entries = if rdb_type == RDB_TYPE_HASH_ZIPLIST {
+                    parse_ziplist(&data)?
+                } else {
+                    parse_listpack(&data)?
+                };
+                BackendValue::Hash(
+                    pairs(entries)?
+                        .into_iter()
+                        .map(|(k, v)| (lossy(k.into_bytes()), bulk(v.into_bytes())))
+                        .collect(),
+                )
+            }
+            RDB_TYPE_LIST_QUICKLIST => {
+                let nodes = self.read_list(|r| parse_ziplist(&r.read_string()?))?;
+                BackendValue::List(
+                    nodes
+                        .into_iter()
+                        .flatten()
+                        .map(|e| bulk(e.into_bytes()))
+                        .collect(),
+                )
+            }
+            RDB_TYPE_LIST_QUICKLIST_2 => {
+                let nodes = self.read_list(|r| match r.read_length()? {
+                    QUICKLIST_NODE_PLAIN => Ok(vec![Entry::Str(r.read_string()?)]),
+                    QUICKLIST_NODE_PACKED => parse_listpack(&r.read_string()?),
+                    c => Err(invalid(format!("unknown quicklist container: {}", c))),
+                })?;
+                BackendValue::List(
+                    nodes
+                        .into_iter()
+                        .flatten()
+                        .map(|e| bulk(e.into_bytes()))
+                        .collect(),
+                )
+            }
+            t => return Err(invalid(format!("unsupported RDB value type: {}", t))),
+        };
+        Ok(value)
+    }
+}
+
+/// 解析RDB文件
+pub fn parse_rdb(data: &[u8]) -> Result<Rdb, PersistenceError> {
+    if data.len() < RDB_MAGIC.len() + 4 || !data.starts_with(RDB_MAGIC) {
+        return Err(invalid("not a RDB file"));
+    }
+    let version = std::str::from_utf8(&data[RDB_MAGIC.len()..RDB_MAGIC.len() + 4])
+        .ok()
+        .and_then(|v| v.parse::<u16>().ok())
+        .ok_or_else(|| invalid("invalid RDB version"))?;
+    if !(1..=RDB_VERSION).contains(&version) {
+        return Err(PersistenceError::UnsupportedVersion(version));
+    }
+
+    let mut rdb = Rdb {
+        version,
+        ..Default::default()
+    };
+    let mut reader = RdbReader {
+        reader: Reader::new(&data[RDB_MAGIC.len() + 4..]),
+    };
+    let mut db = 0;
+    let mut expire_at = None;
+    loop {
+        match reader.reader.read_u8()? {
+            RDB_OPCODE_AUX => {
+                let key = lossy(reader.read_string()?);
+                let value = lossy(reader.read_string()?);
+                rdb.aux.push((key, value));
+            }
+            RDB_OPCODE_SELECTDB => db = reader.read_length()? as u64,
+            RDB_OPCODE_RESIZEDB => {
+                reader.read_length()?;
+                reader.read_length()?;
+            }
+            RDB_OPCODE_EXPIRETIME => {
+                expire_at = Some(reader.reader.read_u32()? as i64 * 1000);
+            }
+            RDB_OPCODE_EXPIRETIME_MS => {
+                expire_at = Some(reader.reader.read_u64()? as i64);
+            }
+            // 没有LRU/LFU淘汰策略，直接跳过
+            RDB_OPCODE_FREQ => {
+                reader.reader.read_u8()?;
+            }
+            RDB_OPCODE_IDLE => {
+                reader.read_length()?;
+            }
+            RDB_OPCODE_FUNCTION2 => {
+                reader.read_string()?;
+                tracing::warn!("Ignoring function library in RDB file");
+            }
+            RDB_OPCODE_EOF => break,
+            t @ (RDB_OPCODE_MODULE_AUX | RDB_OPCODE_FUNCTION_PRE_GA) => {
+                return Err(invalid(format!("unsupported RDB opcode: {:#x}", t)));
+            }
+            t => {
+                let key = lossy(reader.read_string()?);
+                let value = reader.read_object(t)?;
+                rdb.entries.push(RdbEntry {
+                    db,
+                    key,
+                    value,
+                    expire_at: expire_at.take(),
+                });
+            }
+        }
+    }
+
+    // 版本5开始有CRC64，值为0表示保存时关闭了校验
+    if version >= 5 {
+        let body_len = data.len() - reader.reader.remaining();
+        let checksum = reader.reader.read_u64()?;
+        if checksum != 0 && crc64(0, &data[..body_len]) != checksum {
+            return Err(PersistenceError::ChecksumMismatch);
+        }
+    }
+    if !reader.reader.is_empty() {
+        return Err(invalid("trailing data after EOF"));
+    }
+
+    Ok(rdb)
+}
+
+/// 解析RDB文件并写入Backend，返回加载的key数量
+/// 只支持单个数据库，其他数据库的key和已经过期的key会被丢弃
+pub fn load_rdb(data: &[u8], backend: &Backend) -> Result<usize, PersistenceError> {
+    let rdb = parse_rdb(data)?;
+    let now = now_ms();
+    let mut count = 0;
+    for entry in rdb.entries {
+        if entry.db != 0 {
+            tracing::warn!("Skipping key {} in db {}", entry.key, entry.db);
+            continue;
+        }
+        if matches!(entry.expire_at, Some(at) if at <= now) {
+            continue;
+        }
+        backend.load_value(entry.key, entry.value, entry.expire_at);
+        count += 1;
+    }
+    Ok(count)
+}
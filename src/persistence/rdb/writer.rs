@@ -0,0 +1,273 @@
+use crate::{
+    persistence::{crc64, now_secs, PersistenceError},
+    Backend, BackendValue, RespFrame,
+};
+
+use super::{
+    encodings::{canonical_int, encode_intset, encode_listpack},
+    lzf, *,
+};
+
+/// 小于这个长度的字符串不尝试压缩
+const LZF_MIN_LEN: usize = 20;
+/// 使用listpack/intset编码的最大元素个数和元素长度，与Redis默认配置一致
+const MAX_LISTPACK_ENTRIES: usize = 128;
+const MAX_LISTPACK_VALUE: usize = 64;
+const MAX_INTSET_ENTRIES: usize = 512;
+/// quicklist每个节点的最大元素个数
+const QUICKLIST_NODE_SIZE: usize = 128;
+
+/// 将RespFrame转换为RDB中的字符串，无法表示为字符串的返回None
+fn frame_bytes(frame: &RespFrame) -> Option<Vec<u8>> {
+    match frame {
+        RespFrame::BulkString(s) => Some(s.to_vec()),
+        RespFrame::SimpleString(s) => Some(s.as_bytes().to_vec()),
+        RespFrame::Integer(i) => Some(i.to_string().into_bytes()),
+        RespFrame::Double(d) => Some(d.value().to_string().into_bytes()),
+        RespFrame::Boolean(b) => Some(if *b { b"1".to_vec() } else { b"0".to_vec() }),
+        _ => None,
+    }
+}
+
+fn frames_bytes(frames: &[RespFrame]) -> Option<Vec<Vec<u8>>> {
+    frames.iter().map(frame_bytes).collect()
+}
+
+fn score_bytes(score: f64) -> Vec<u8> {
+    if score.is_infinite() {
+        return if score > 0.0 {
+            b"inf".to_vec()
+        } else {
+            b"-inf".to_vec()
+        };
+    }
+    score.to_string().into_bytes()
+}
+
+fn is_small<T: AsRef<[u8]>>(items: &[T]) -> bool {
+    items.len() <= MAX_LISTPACK_ENTRIES
+        && items.iter().all(|v| v.as_ref().len() <= MAX_LISTPACK_VALUE)
+}
+
+struct RdbWriter {
+    buf: Vec<u8>,
+    version: u16,
+}
+
+impl RdbWriter {
+    fn write_length(&mut self, len: usize) {
+        if len < 1 << 6 {
+            self.buf.push(len as u8);
+        } else if len < 1 << 14 {
+            self.buf.push(0x40 | (len >> 8) as u8);
+            self.buf.push(len as u8);
+        } else if u32::try_from(len).is_ok() {
+            self.buf.push(RDB_32BIT_LEN);
+            self.buf.extend_from_slice(&(len as u32).to_be_bytes());
+        } else {
+            self.buf.push(RDB_64BIT_LEN);
+            self.buf.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+
+    /// 写入字符串，能表示为32位整数的使用整数编码，较长的字符串尝试LZF压缩
+    fn write_string(&mut self, data: &[u8]) {
+        if data.len() <= 11 {
+            if let Some(v) = canonical_int(data) {
+                if let Ok(v) = i8::try_from(v) {
+                    self.buf.push(0xc0 | RDB_ENC_INT8 as u8);
+                    self.buf.push(v as u8);
+                    return;
+                }
+                if let Ok(v) = i16::try_from(v) {
+                    self.buf.push(0xc0 | RDB_ENC_INT16 as u8);
+                    self.buf.extend_from_slice(&v.to_le_bytes());
+                    return;
+                }
+                if let Ok(v) = i32::try_from(v) {
+                    self.buf.push(0xc0 | RDB_ENC_INT32 as u8);
+                    self.buf.extend_from_slice(&v.to_le_bytes());
+                    return;
+                }
+            }
+        }
+
+        if data.len() > LZF_MIN_LEN {
+            let compressed = lzf::compress(data);
+            if compressed.len() < data.len() {
+                self.buf.push(0xc0 | RDB_ENC_LZF as u8);
+                self.write_length(compressed.len());
+                self.write_length(data.len());
+                self.buf.extend_from_slice(&compressed);
+                return;
+            }
+        }
+
+        self.write_length(data.len());
+        self.buf.extend_from_slice(data);
+    }
+
+    fn write_aux(&mut self, key: &str, value: &str) {
+        self.buf.push(RDB_OPCODE_AUX);
+        self.write_string(key.as_bytes());
+        self.write_string(value.as_bytes());
+    }
+
+    /// 写入类型标记和值，值中有无法表示的frame时返回false，此时不写入任何数据
+    fn write_object(&mut self, key: &str, value: &BackendValue) -> bool {
+        match value {
+            BackendValue::String(frame) => {
+                let Some(data) = frame_bytes(frame) else {
+                    return false;
+                };
+                self.buf.push(RDB_TYPE_STRING);
+                self.write_string(key.as_bytes());
+                self.write_string(&data);
+            }
+            BackendValue::List(items) => {
+                let Some(items) = frames_bytes(items) else {
+                    return false;
+                };
+                if self.version >= 10 {
+                    self.buf.push(RDB_TYPE_LIST_QUICKLIST_2);
+                    self.write_string(key.as_bytes());
+                    let nodes: Vec<_> = items.chunks(QUICKLIST_NODE_SIZE).collect();
+                    self.write_length(nodes.len());
+                    for node in nodes {
+                        self.write_length(QUICKLIST_NODE_PACKED);
+                        self.write_string(&encode_listpack(node));
+                    }
+                } else {
+                    self.buf.push(RDB_TYPE_LIST);
+                    self.write_string(key.as_bytes());
+                    self.write_length(items.len());
+                    for item in items {
+                        self.write_string(&item);
+                    }
+                }
+            }
+            BackendValue::Set(members) => {
+                let Some(members) = frames_bytes(members) else {
+                    return false;
+                };
+                let ints: Option<Vec<i64>> = members.iter().map(|m| canonical_int(m)).collect();
+                match ints {
+                    Some(ints) if ints.len() <= MAX_INTSET_ENTRIES => {
+                        self.buf.push(RDB_TYPE_SET_INTSET);
+                        self.write_string(key.as_bytes());
+                        self.write_string(&encode_intset(&ints));
+                    }
+                    _ if self.version >= 11 && is_small(&members) => {
+                        self.buf.push(RDB_TYPE_SET_LISTPACK);
+                        self.write_string(key.as_bytes());
+                        self.write_string(&encode_listpack(&members));
+                    }
+                    _ => {
+                        self.buf.push(RDB_TYPE_SET);
+                        self.write_string(key.as_bytes());
+                        self.write_length(members.len());
+                        for member in members {
+                            self.write_string(&member);
+                        }
+                    }
+                }
+            }
+            BackendValue::Hash(fields) => {
+                let mut items = Vec::with_capacity(fields.len() * 2);
+                for (field, value) in fields {
+                    let Some(value) = frame_bytes(value) else {
+                        return false;
+                    };
+                    items.push(field.as_bytes().to_vec());
+                    items.push(value);
+                }
+                if self.version >= 10 && is_small(&items) {
+                    self.buf.push(RDB_TYPE_HASH_LISTPACK);
+                    self.write_string(key.as_bytes());
+                    self.write_string(&encode_listpack(&items));
+                } else {
+                    self.buf.push(RDB_TYPE_HASH);
+                    self.write_string(key.as_bytes());
+                    self.write_length(fields.len());
+                    for item in items {
+                        self.write_string(&item);
+                    }
+                }
+            }
+            BackendValue::ZSet(members) => {
+                let items: Vec<Vec<u8>> = members
+                    .iter()
+                    .flat_map(|(m, s)| [m.as_bytes().to_vec(), score_bytes(*s)])
+                    .collect();
+                if self.version >= 10 && is_small(&items) {
+                    self.buf.push(RDB_TYPE_ZSET_LISTPACK);
+                    self.write_string(key.as_bytes());
+                    self.write_string(&encode_listpack(&items));
+                } else {
+                    self.buf.push(RDB_TYPE_ZSET_2);
+                    self.write_string(key.as_bytes());
+                    self.write_length(members.len());
+                    for (member, score) in members {
+                        self.write_string(member.as_bytes());
+                        self.buf.extend_from_slice(&score.to_bits().to_le_bytes());
+                    }
+                }
+            }
+        }
+        true
+    }
+}
+
+/// 将Backend中的数据编码成指定版本(9-11)的RDB文件
+/// 数组等无法用RDB表示的值会被跳过
+pub fn dump_rdb(backend: &Backend, version: u16) -> Result<Vec<u8>, PersistenceError> {
+    if !(RDB_MIN_WRITE_VERSION..=RDB_VERSION).contains(&version) {
+        return Err(PersistenceError::UnsupportedVersion(version));
+    }
+
+    let mut writer = RdbWriter {
+        buf: Vec::with_capacity(4096),
+        version,
+    };
+    writer.buf.extend_from_slice(RDB_MAGIC);
+    writer
+        .buf
+        .extend_from_slice(format!("{:04}", version).as_bytes());
+
+    writer.write_aux("redis-ver", RDB_REDIS_VER);
+    writer.write_aux("redis-bits", &(usize::BITS).to_string());
+    writer.write_aux("ctime", &now_secs().to_string());
+    writer.write_aux("used-mem", "0");
+    writer.write_aux("aof-base", "0");
+
+    let keys = backend.keys();
+    let expires = keys
+        .iter()
+        .filter(|k| backend.expire_at(k).is_some())
+        .count();
+    writer.buf.push(RDB_OPCODE_SELECTDB);
+    writer.write_length(0);
+    writer.buf.push(RDB_OPCODE_RESIZEDB);
+    writer.write_length(keys.len());
+    writer.write_length(expires);
+
+    for key in keys {
+        let Some(value) = backend.get_value(&key) else {
+            continue;
+        };
+        let mark = writer.buf.len();
+        if let Some(at) = backend.expire_at(&key) {
+            writer.buf.push(RDB_OPCODE_EXPIRETIME_MS);
+            writer.buf.extend_from_slice(&(at as u64).to_le_bytes());
+        }
+        if !writer.write_object(&key, &value) {
+            writer.buf.truncate(mark);
+            tracing::warn!("Skipping key {}: value can not be saved as RDB", key);
+        }
+    }
+
+    writer.buf.push(RDB_OPCODE_EOF);
+    let checksum = crc64(0, &writer.buf);
+    writer.buf.extend_from_slice(&checksum.to_le_bytes());
+    Ok(writer.buf)
+}
@@ -0,0 +1,470 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicI64, Ordering},
+        Mutex, RwLock,
+    },
+    time::Duration,
+};
+
+use crate::{now_ms, Backend};
+
+use super::{
+    crc64::crc64,
+    encoding::{value_type, write_bytes, write_u64, write_u8, write_value, Reader},
+    now_secs,
+    rdb::{dump_rdb, load_rdb, RDB_MAGIC, RDB_VERSION},
+    PersistenceError,
+};
+
+/// 快照文件的魔数
+const MAGIC: &[u8] = b"SREDIS";
+/// 当前快照格式的版本
+/// - 1: 初始版本
+/// - 2: 增加过期时间
+/// - 3: 增加list和zset
+pub const SNAPSHOT_VERSION: u16 = 3;
+
+/// 过期时间标记，之后是8字节的unix毫秒时间，作用于紧随其后的key
+const OPCODE_EXPIRE_MS: u8 = 0xfc;
+/// 数据结束标记，之后是8字节的CRC64
+const OPCODE_EOF: u8 = 0xff;
+
+/// 自动保存规则：seconds秒内至少有changes次修改就触发BGSAVE
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SaveRule {
+    pub seconds: u64,
+    pub changes: u64,
+}
+
+/// 快照文件的格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SnapshotFormat {
+    /// simple-redis自己的格式，可以保存任意RespFrame
+    #[default]
+    Native,
+    /// Redis的RDB格式，可以和Redis互相交换数据
+    Rdb,
+}
+
+/// 快照相关的配置
+#[derive(Debug, Clone)]
+pub struct SnapshotConfig {
+    /// 快照文件所在目录
+    pub dir: PathBuf,
+    /// 快照文件名
+    pub dbfilename: String,
+    /// 自动保存规则，为空则不自动保存
+    pub save_rules: Vec<SaveRule>,
+    /// 保存时使用的格式，加载时根据文件内容自动识别
+    pub format: SnapshotFormat,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        // 与Redis默认的 save 3600 1 300 100 60 10000 保持一致
+        SnapshotConfig {
+            dir: PathBuf::from("."),
+            dbfilename: "dump.srdb".to_string(),
+            save_rules: vec![
+                SaveRule {
+                    seconds: 3600,
+                    changes: 1,
+                },
+                SaveRule {
+                    seconds: 300,
+                    changes: 100,
+                },
+                SaveRule {
+                    seconds: 60,
+                    changes: 10000,
+                },
+            ],
+            format: SnapshotFormat::Native,
+        }
+    }
+}
+
+impl SnapshotConfig {
+    /// 快照文件的完整路径
+    pub fn path(&self) -> PathBuf {
+        self.dir.join(&self.dbfilename)
+    }
+}
+
+/// 快照运行时状态，挂在Backend上共享
+#[derive(Debug)]
+pub struct SnapshotState {
+    config: RwLock<SnapshotConfig>,
+    /// 上一次成功保存的时间(unix秒)
+    last_save: AtomicI64,
+    bgsave_in_progress: AtomicBool,
+    last_bgsave_ok: AtomicBool,
+    /// 启动时是否正在加载数据
+    loading: AtomicBool,
+    /// 保证同一时间只有一个保存在写文件
+    save_lock: Mutex<()>,
+}
+
+impl Default for SnapshotState {
+    fn default() -> Self {
+        SnapshotState {
+            config: RwLock::new(SnapshotConfig::default()),
+            last_save: AtomicI64::new(now_secs()),
+            bgsave_in_progress: AtomicBool::new(false),
+            last_bgsave_ok: AtomicBool::new(true),
+            loading: AtomicBool::new(false),
+            save_lock: Mutex::new(()),
+        }
+    }
+}
+
+impl SnapshotState {
+    pub fn config(&self) -> SnapshotConfig {
+        self.config.read().unwrap().clone()
+    }
+
+    pub fn set_config(&self, config: SnapshotConfig) {
+        *self.config.write().unwrap() = config;
+    }
+
+    pub fn last_save(&self) -> i64 {
+        self.last_save.load(Ordering::Relaxed)
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.loading.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_loading(&self, loading: bool) {
+        self.loading.store(loading, Ordering::Release);
+    }
+
+    pub fn is_bgsave_in_progress(&self) -> bool {
+        self.bgsave_in_progress.load(Ordering::Relaxed)
+    }
+
+    pub fn last_bgsave_ok(&self) -> bool {
+        self.last_bgsave_ok.load(Ordering::Relaxed)
+    }
+}
+
+/// 将Backend中的数据编码成快照
+/// 格式: MAGIC | VERSION(u16) | [[EXPIRE_MS AT] TYPE KEY VALUE]... | EOF | CRC64
+pub fn encode_snapshot(backend: &Backend) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(4096);
+    buf.extend_from_slice(MAGIC);
+    buf.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+
+    for key in backend.keys() {
+        // key可能在收集之后被删除
+        let Some(value) = backend.get_value(&key) else {
+            continue;
+        };
+        if let Some(at) = backend.expire_at(&key) {
+            write_u8(&mut buf, OPCODE_EXPIRE_MS);
+            write_u64(&mut buf, at as u64);
+        }
+        write_u8(&mut buf, value_type(&value));
+        write_bytes(&mut buf, key.as_bytes());
+        write_value(&mut buf, &value);
+    }
+
+    write_u8(&mut buf, OPCODE_EOF);
+    let checksum = crc64(0, &buf);
+    write_u64(&mut buf, checksum);
+    buf
+}
+
+/// 校验并解析快照，将数据写入Backend，返回加载的key数量
+/// 已经过期的key会被丢弃
+pub fn decode_snapshot(data: &[u8], backend: &Backend) -> Result<usize, PersistenceError> {
+    if data.len() < MAGIC.len() + 2 + 1 + 8 || !data.starts_with(MAGIC) {
+        return Err(PersistenceError::InvalidFormat(
+            "not a snapshot file".to_string(),
+        ));
+    }
+
+    // 先校验CRC64
+    let (body, trailer) = data.split_at(data.len() - 8);
+    let mut expected = [0u8; 8];
+    expected.copy_from_slice(trailer);
+    if crc64(0, body) != u64::from_le_bytes(expected) {
+        return Err(PersistenceError::ChecksumMismatch);
+    }
+
+    let mut reader = Reader::new(&body[MAGIC.len()..]);
+    let version = reader.read_u16()?;
+    if version > SNAPSHOT_VERSION {
+        return Err(PersistenceError::UnsupportedVersion(version));
+    }
+
+    let now = now_ms();
+    let mut count = 0;
+    let mut expire_at = None;
+    loop {
+        match reader.read_u8()? {
+            OPCODE_EXPIRE_MS => {
+                expire_at = Some(reader.read_u64()? as i64);
+            }
+            OPCODE_EOF => break,
+            t => {
+                let key = reader.read_string()?;
+                let value = reader.read_value(t)?;
+                match expire_at.take() {
+                    Some(at) if at <= now => {}
+                    at => {
+                        backend.load_value(key, value, at);
+                        count += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    if !reader.is_empty() {
+        return Err(PersistenceError::InvalidFormat(
+            "trailing data after EOF".to_string(),
+        ));
+    }
+
+    Ok(count)
+}
+
+/// 同步保存快照，会阻塞调用者直到文件写入完成
+pub fn save(backend: &Backend) -> Result<(), PersistenceError> {
+    let state = &backend.snapshot;
+    let _guard = state.save_lock.lock().unwrap();
+    let config = state.config();
+
+    let dirty_before = backend.dirty();
+    let data = match config.format {
+        SnapshotFormat::Native => encode_snapshot(backend),
+        SnapshotFormat::Rdb => dump_rdb(backend, RDB_VERSION)?,
+    };
+
+    // 先写临时文件再rename，保证快照文件总是完整的
+    fs::create_dir_all(&config.dir)?;
+    let tmp = config.dir.join(format!("temp-{}.srdb", std::process::id()));
+    fs::write(&tmp, &data)?;
+    fs::rename(&tmp, config.path())?;
+
+    backend.decr_dirty(dirty_before);
+    state.last_save.store(now_secs(), Ordering::Relaxed);
+    tracing::info!("DB saved on disk: {}", config.path().display());
+    Ok(())
+}
+
+/// 在后台线程保存快照，不阻塞命令处理
+pub fn bgsave(backend: &Backend) -> Result<(), PersistenceError> {
+    let state = &backend.snapshot;
+    if state
+        .bgsave_in_progress
+        .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+        .is_err()
+    {
+        return Err(PersistenceError::BgsaveInProgress);
+    }
+
+    let backend = backend.clone();
+    std::thread::spawn(move || {
+        let ret = save(&backend);
+        if let Err(e) = &ret {
+            tracing::warn!("Background saving error: {}", e);
+        }
+        let state = &backend.snapshot;
+        state.last_bgsave_ok.store(ret.is_ok(), Ordering::Relaxed);
+        state.bgsave_in_progress.store(false, Ordering::Release);
+    });
+
+    Ok(())
+}
+
+/// 从快照文件加载数据，支持自己的格式和RDB格式，文件不存在时返回None
+pub fn load(backend: &Backend) -> Result<Option<usize>, PersistenceError> {
+    let path = backend.snapshot.config().path();
+    let data = match fs::read(&path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let count = load_bytes(&data, backend)?;
+    tracing::info!("DB loaded from disk: {} keys", count);
+    Ok(Some(count))
+}
+
+/// 加载快照数据，根据魔数自动识别自己的格式和RDB格式，返回加载的key数量
+pub fn load_bytes(data: &[u8], backend: &Backend) -> Result<usize, PersistenceError> {
+    if data.starts_with(RDB_MAGIC) {
+        load_rdb(data, backend)
+    } else {
+        decode_snapshot(data, backend)
+    }
+}
+
+/// 每秒检查一次自动保存规则
+pub async fn run_save_cron(backend: Backend) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+
+        let state = &backend.snapshot;
+        if state.is_loading() || state.is_bgsave_in_progress() {
+            continue;
+        }
+
+        let dirty = backend.dirty();
+        let elapsed = now_secs() - state.last_save();
+        let rule = state
+            .config()
+            .save_rules
+            .into_iter()
+            .find(|r| dirty >= r.changes && elapsed >= r.seconds as i64);
+
+        if let Some(rule) = rule {
+            tracing::info!(
+                "{} changes in {} seconds. Saving...",
+                rule.changes,
+                rule.seconds
+            );
+            if let Err(e) = bgsave(&backend) {
+                tracing::warn!("Background saving failed to start: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BulkString, RespFrame};
+    use anyhow::Result;
+
+    fn backend_with_data() -> Backend {
+        let backend = Backend::new();
+        backend.set("hello".to_string(), BulkString::new("world").into());
+        backend.hset(
+            "map".to_string(),
+            "field".to_string(),
+            BulkString::new("value").into(),
+        );
+        backend.sadd(
+            "set".to_string(),
+            vec![BulkString::new("a").into(), RespFrame::Integer(1)],
+        );
+        backend
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() -> Result<()> {
+        let backend = backend_with_data();
+        let data = encode_snapshot(&backend);
+
+        let restored = Backend::new();
+        let count = decode_snapshot(&data, &restored)?;
+        assert_eq!(count, 3);
+        assert_eq!(restored.get("hello"), Some(BulkString::new("world").into()));
+        assert_eq!(
+            restored.hget("map", "field"),
+            Some(BulkString::new("value").into())
+        );
+        let set = restored.sismembers("set").unwrap();
+        assert!(set.contains(&RespFrame::Integer(1)));
+        assert!(set.contains(&BulkString::new("a").into()));
+        assert_eq!(restored.dirty(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_expire() -> Result<()> {
+        let backend = backend_with_data();
+        let at = now_ms() + 10_000;
+        backend.set_expire_at("hello", at);
+        // 已经过期的key不会写入快照
+        backend.set_expire_at("map", now_ms() - 1);
+        // 保存之后才过期的key在加载时丢弃
+        backend.set_expire_at("set", now_ms() + 50);
+        let data = encode_snapshot(&backend);
+        std::thread::sleep(Duration::from_millis(100));
+
+        let restored = Backend::new();
+        assert_eq!(decode_snapshot(&data, &restored)?, 1);
+        assert_eq!(restored.expire_at("hello"), Some(at));
+        assert!(!restored.exists("set"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_checksum_mismatch() {
+        let backend = backend_with_data();
+        let mut data = encode_snapshot(&backend);
+        let pos = data.len() / 2;
+        data[pos] ^= 0xff;
+
+        let ret = decode_snapshot(&data, &Backend::new());
+        assert!(matches!(ret, Err(PersistenceError::ChecksumMismatch)));
+    }
+
+    #[test]
+    fn test_save_and_load() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let backend = backend_with_data();
+        let config = SnapshotConfig {
+            dir: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        backend.snapshot.set_config(config.clone());
+        assert_eq!(backend.dirty(), 4);
+
+        save(&backend)?;
+        assert_eq!(backend.dirty(), 0);
+        assert!(config.path().exists());
+
+        let restored = Backend::new();
+        restored.snapshot.set_config(config);
+        assert_eq!(load(&restored)?, Some(3));
+        assert_eq!(restored.get("hello"), Some(BulkString::new("world").into()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_and_load_rdb() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let backend = backend_with_data();
+        let config = SnapshotConfig {
+            dir: dir.path().to_path_buf(),
+            dbfilename: "dump.rdb".to_string(),
+            format: SnapshotFormat::Rdb,
+            ..Default::default()
+        };
+        backend.snapshot.set_config(config.clone());
+        save(&backend)?;
+        assert!(fs::read(config.path())?.starts_with(RDB_MAGIC));
+
+        let restored = Backend::new();
+        restored.snapshot.set_config(config);
+        assert_eq!(load(&restored)?, Some(3));
+        assert_eq!(restored.get("hello"), Some(BulkString::new("world").into()));
+        // RDB中只有字符串，整数成员会转换为字符串
+        let set = restored.sismembers("set").unwrap();
+        assert!(set.contains(&BulkString::new("1").into()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_missing_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let backend = Backend::new();
+        backend.snapshot.set_config(SnapshotConfig {
+            dir: dir.path().to_path_buf(),
+            ..Default::default()
+        });
+        assert_eq!(load(&backend)?, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::VecDeque;
+
+/// 复制积压缓冲区，保存最近写入复制流的数据，用于断线后的部分重同步
+/// 复制流中每个字节都有一个从1开始的偏移量
+#[derive(Debug)]
+pub(crate) struct ReplBacklog {
+    buf: VecDeque<u8>,
+    capacity: usize,
+    /// 缓冲区中第一个字节的偏移量
+    first_offset: u64,
+}
+
+impl ReplBacklog {
+    /// 创建缓冲区，offset为当前复制偏移量，之后写入的第一个字节偏移量为offset + 1
+    pub(crate) fn new(capacity: usize, offset: u64) -> Self {
+        ReplBacklog {
+            buf: VecDeque::with_capacity(capacity.min(64 * 1024)),
+            capacity,
+            first_offset: offset + 1,
+        }
+    }
+
+    /// 写入数据，超出容量时丢弃最旧的数据
+    pub(crate) fn feed(&mut self, data: &[u8]) {
+        self.buf.extend(data);
+        if self.buf.len() > self.capacity {
+            let excess = self.buf.len() - self.capacity;
+            self.buf.drain(..excess);
+            self.first_offset += excess as u64;
+        }
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub(crate) fn first_offset(&self) -> u64 {
+        self.first_offset
+    }
+
+    /// 缓冲区中保存的数据长度
+    pub(crate) fn histlen(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// 读取从offset开始到末尾的数据，offset不在缓冲区范围内时返回None
+    /// offset正好是下一个要写入的字节时返回空数据
+    pub(crate) fn read_from(&self, offset: u64) -> Option<Vec<u8>> {
+        let end = self.first_offset + self.buf.len() as u64;
+        if offset < self.first_offset || offset > end {
+            return None;
+        }
+        let start = (offset - self.first_offset) as usize;
+        Some(self.buf.range(start..).copied().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backlog() {
+        let mut backlog = ReplBacklog::new(8, 100);
+        assert_eq!(backlog.read_from(101), Some(vec![]));
+
+        backlog.feed(b"hello");
+        assert_eq!(backlog.read_from(101), Some(b"hello".to_vec()));
+        assert_eq!(backlog.read_from(103), Some(b"llo".to_vec()));
+        assert_eq!(backlog.read_from(106), Some(vec![]));
+        assert_eq!(backlog.read_from(107), None);
+        assert_eq!(backlog.read_from(100), None);
+
+        // 超出容量后丢弃最旧的数据
+        backlog.feed(b"world");
+        assert_eq!(backlog.histlen(), 8);
+        assert_eq!(backlog.first_offset(), 103);
+        assert_eq!(backlog.read_from(102), None);
+        assert_eq!(backlog.read_from(103), Some(b"lloworld".to_vec()));
+    }
+}
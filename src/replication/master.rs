@@ -0,0 +1,182 @@
+use std::{future::Future, time::Duration};
+
+use anyhow::Result;
+use futures::{SinkExt, StreamExt};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio_util::codec::Framed;
+
+use crate::{
+    network::RedisCodec,
+    now_ms,
+    persistence::rdb::{dump_rdb, RDB_VERSION},
+    Backend, RespFrame, SimpleString,
+};
+
+use super::SyncPlan;
+
+/// master向replica发送PING的间隔
+const PING_REPLICA_PERIOD: Duration = Duration::from_secs(10);
+
+/// 需要由连接处理的复制命令
+#[derive(Debug, PartialEq, Eq)]
+pub enum SyncRequest {
+    /// REPLCONF <option> <value> ...
+    ReplConf(Vec<String>),
+    /// PSYNC <replid> <offset>
+    PSync { replid: String, offset: i64 },
+}
+
+/// 判断请求是否是REPLCONF或PSYNC
+pub fn parse_sync_request(frame: &RespFrame) -> Option<SyncRequest> {
+    let RespFrame::Array(args) = frame else {
+        return None;
+    };
+    let mut args = args.iter().map(|arg| match arg {
+        RespFrame::BulkString(s) => String::from_utf8_lossy(s).into_owned(),
+        _ => String::new(),
+    });
+    let name = args.next()?.to_ascii_lowercase();
+    match name.as_str() {
+        "replconf" => Some(SyncRequest::ReplConf(args.collect())),
+        "psync" => {
+            let replid = args.next()?;
+            let offset = args.next()?.parse().ok()?;
+            Some(SyncRequest::PSync { replid, offset })
+        }
+        _ => None,
+    }
+}
+
+/// 接管replica的连接：完成全量或部分同步，然后持续发送复制流并接收ACK
+/// killed完成时(CLIENT KILL)断开连接
+pub async fn serve_replica<S>(
+    mut framed: Framed<S, RedisCodec>,
+    backend: Backend,
+    ip: String,
+    port: u16,
+    replid: String,
+    offset: i64,
+    killed: impl Future<Output = ()>,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    tokio::pin!(killed);
+    let state = backend.replication();
+    let (id, mut rx, plan) = state.add_replica(ip, port, &replid, offset);
+    let ret: Result<()> = async {
+        match plan {
+            SyncPlan::Full { replid, offset } => {
+                tracing::info!("Starting full resync with replica {} at {}", id, offset);
+                framed
+                    .send(SimpleString::new(format!("FULLRESYNC {} {}", replid, offset)).into())
+                    .await?;
+
+                // 注册replica之后才生成快照，期间的写命令会在快照之后再发送一次
+                // 目前的写命令重复执行不影响最终结果
+                let cloned = backend.clone();
+                let payload =
+                    tokio::task::spawn_blocking(move || dump_rdb(&cloned, RDB_VERSION)).await??;
+                // 与Redis一致，快照数据之后没有\r\n
+                let stream = framed.get_mut();
+                stream
+                    .write_all(format!("${}\r\n", payload.len()).as_bytes())
+                    .await?;
+                stream.write_all(&payload).await?;
+            }
+            SyncPlan::Continue { replid, data } => {
+                tracing::info!("Partial resync with replica {}: {} bytes", id, data.len());
+                framed
+                    .send(SimpleString::new(format!("CONTINUE {}", replid)).into())
+                    .await?;
+                framed.get_mut().write_all(&data).await?;
+            }
+        }
+        state.update_replica(id, |r| r.online = true);
+
+        loop {
+            tokio::select! {
+                _ = &mut killed => return Ok(()),
+                data = rx.recv() => match data {
+                    Some(data) => framed.get_mut().write_all(&data).await?,
+                    None => return Ok(()),
+                },
+                frame = framed.next() => match frame {
+                    Some(Ok(frame)) => {
+                        if let Some(SyncRequest::ReplConf(args)) = parse_sync_request(&frame) {
+                            if let [option, offset, ..] = args.as_slice() {
+                                if option.eq_ignore_ascii_case("ack") {
+                                    let offset = offset.parse().unwrap_or_default();
+                                    state.update_replica(id, |r| {
+                                        r.ack_offset = offset;
+                                        r.last_ack_ms = now_ms();
+                                    });
+                                }
+                            }
+                        }
+                    }
+                    Some(Err(e)) => return Err(e),
+                    None => return Ok(()),
+                },
+            }
+        }
+    }
+    .await;
+
+    state.remove_replica(id);
+    tracing::info!("Connection with replica {} lost", id);
+    ret
+}
+
+/// 定期向replica发送PING，写入复制流中让replica知道连接正常
+pub async fn run_replication_cron(backend: Backend) {
+    let mut interval = tokio::time::interval(PING_REPLICA_PERIOD);
+    loop {
+        interval.tick().await;
+
+        let state = backend.replication();
+        if !state.is_replica() && !state.replicas().is_empty() {
+            state.feed(b"*1\r\n$4\r\nPING\r\n");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BulkString, RespArray};
+
+    #[test]
+    fn test_parse_sync_request() {
+        let frame: RespFrame = RespArray::new(vec![
+            BulkString::new("PSYNC").into(),
+            BulkString::new("?").into(),
+            BulkString::new("-1").into(),
+        ])
+        .into();
+        assert_eq!(
+            parse_sync_request(&frame),
+            Some(SyncRequest::PSync {
+                replid: "?".to_string(),
+                offset: -1
+            })
+        );
+
+        let frame: RespFrame = RespArray::new(vec![
+            BulkString::new("replconf").into(),
+            BulkString::new("listening-port").into(),
+            BulkString::new("6380").into(),
+        ])
+        .into();
+        assert_eq!(
+            parse_sync_request(&frame),
+            Some(SyncRequest::ReplConf(vec![
+                "listening-port".to_string(),
+                "6380".to_string()
+            ]))
+        );
+
+        let frame: RespFrame = RespArray::new(vec![BulkString::new("get").into()]).into();
+        assert_eq!(parse_sync_request(&frame), None);
+    }
+}
@@ -0,0 +1,555 @@
+//! 主从复制
+//!
+//! master维护复制ID、复制偏移量和积压缓冲区，每个写命令都会编码后追加到复制流中，
+//! replica断线重连时如果请求的偏移量还在积压缓冲区内，就只发送缺失的部分，
+//! 否则发送完整的RDB快照后再继续发送复制流。
+
+mod backlog;
+mod master;
+mod replica;
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::BuildHasher,
+    sync::{
+        atomic::{AtomicBool, AtomicU16, AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use bytes::Bytes;
+use tokio::sync::mpsc;
+
+use crate::{now_ms, RespArray, RespEncode};
+
+use self::backlog::ReplBacklog;
+
+pub use self::{
+    master::{parse_sync_request, run_replication_cron, serve_replica, SyncRequest},
+    replica::start_replication,
+};
+
+/// 默认的积压缓冲区大小
+pub const DEFAULT_BACKLOG_SIZE: usize = 1024 * 1024;
+/// 复制ID的长度
+const REPLID_LEN: usize = 40;
+
+/// replica与master之间连接的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkState {
+    /// 等待连接
+    Connect,
+    /// 正在连接和握手
+    Connecting,
+    /// 正在接收快照
+    Sync,
+    /// 已经同步，正在接收复制流
+    Connected,
+}
+
+impl LinkState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkState::Connect => "connect",
+            LinkState::Connecting => "connecting",
+            LinkState::Sync => "sync",
+            LinkState::Connected => "connected",
+        }
+    }
+}
+
+/// replica到master的连接信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MasterLink {
+    pub host: String,
+    pub port: u16,
+    pub state: LinkState,
+    /// 上次收到master数据的时间(unix毫秒)，0表示从未收到
+    pub last_io_ms: i64,
+    /// 连接断开的时间(unix毫秒)
+    pub down_since_ms: i64,
+}
+
+/// 当前实例的角色
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplicationRole {
+    Master,
+    Replica(MasterLink),
+}
+
+/// master上连接的一个replica
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplicaInfo {
+    pub id: u64,
+    pub ip: String,
+    /// replica通过REPLCONF listening-port告知的端口
+    pub port: u16,
+    /// 完成同步之后为true
+    pub online: bool,
+    /// replica通过REPLCONF ACK确认的偏移量
+    pub ack_offset: u64,
+    /// 上次收到ACK的时间(unix毫秒)
+    pub last_ack_ms: i64,
+}
+
+/// PSYNC的处理方式
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum SyncPlan {
+    /// 全量同步，replica需要从offset开始接收复制流
+    Full { replid: String, offset: u64 },
+    /// 部分重同步，先发送积压缓冲区中缺失的数据
+    Continue { replid: String, data: Vec<u8> },
+}
+
+#[derive(Debug)]
+struct ReplicaHandle {
+    info: ReplicaInfo,
+    tx: mpsc::UnboundedSender<Bytes>,
+}
+
+#[derive(Debug)]
+struct ReplInner {
+    role: ReplicationRole,
+    replid: String,
+    /// 切换master之前的复制ID，用于故障转移之后的部分重同步
+    replid2: String,
+    /// replid2有效的最大偏移量(不含)，-1表示无效
+    second_replid_offset: i64,
+    /// 复制偏移量，即写入复制流的总字节数
+    offset: u64,
+    backlog: Option<ReplBacklog>,
+    backlog_size: usize,
+    replicas: Vec<ReplicaHandle>,
+}
+
+/// 复制运行时状态，挂在Backend上共享
+#[derive(Debug)]
+pub struct ReplicationState {
+    inner: Mutex<ReplInner>,
+    /// 每次REPLICAOF都会增加，旧的复制任务发现不一致时退出
+    generation: AtomicU64,
+    /// replica是否拒绝客户端的写命令
+    read_only: AtomicBool,
+    /// 本实例监听的端口，握手时告知master
+    listening_port: AtomicU16,
+    next_replica_id: AtomicU64,
+}
+
+impl Default for ReplicationState {
+    fn default() -> Self {
+        ReplicationState {
+            inner: Mutex::new(ReplInner {
+                role: ReplicationRole::Master,
+                replid: gen_replid(),
+                replid2: "0".repeat(REPLID_LEN),
+                second_replid_offset: -1,
+                offset: 0,
+                backlog: None,
+                backlog_size: DEFAULT_BACKLOG_SIZE,
+                replicas: Vec::new(),
+            }),
+            generation: AtomicU64::new(0),
+            read_only: AtomicBool::new(true),
+            listening_port: AtomicU16::new(6379),
+            next_replica_id: AtomicU64::new(1),
+        }
+    }
+}
+
+/// 生成随机的40位十六进制ID，用作复制ID和集群节点ID
+pub(crate) fn gen_replid() -> String {
+    let seed = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let mut id: String = (0..3)
+        .map(|i| format!("{:016x}", RandomState::new().hash_one((seed, i))))
+        .collect();
+    id.truncate(REPLID_LEN);
+    id
+}
+
+impl ReplicationState {
+    pub fn role(&self) -> ReplicationRole {
+        self.inner.lock().unwrap().role.clone()
+    }
+
+    pub fn is_replica(&self) -> bool {
+        matches!(self.inner.lock().unwrap().role, ReplicationRole::Replica(_))
+    }
+
+    /// 是否是只读的replica
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::Relaxed) && self.is_replica()
+    }
+
+    /// replica-read-only 配置的值，和当前角色无关
+    pub fn read_only_config(&self) -> bool {
+        self.read_only.load(Ordering::Relaxed)
+    }
+
+    pub fn set_read_only(&self, read_only: bool) {
+        self.read_only.store(read_only, Ordering::Relaxed);
+    }
+
+    pub fn replid(&self) -> String {
+        self.inner.lock().unwrap().replid.clone()
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.inner.lock().unwrap().offset
+    }
+
+    pub fn listening_port(&self) -> u16 {
+        self.listening_port.load(Ordering::Relaxed)
+    }
+
+    pub fn set_listening_port(&self, port: u16) {
+        self.listening_port.store(port, Ordering::Relaxed);
+    }
+
+    pub fn backlog_size(&self) -> usize {
+        self.inner.lock().unwrap().backlog_size
+    }
+
+    /// 设置积压缓冲区大小，已经创建的缓冲区会重新创建
+    pub fn set_backlog_size(&self, size: usize) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.backlog_size = size;
+        if inner.backlog.is_some() {
+            inner.backlog = Some(ReplBacklog::new(size, inner.offset));
+        }
+    }
+
+    /// 是否需要写入复制流，只有创建了积压缓冲区之后才需要
+    pub fn is_active(&self) -> bool {
+        self.inner.lock().unwrap().backlog.is_some()
+    }
+
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// 追加数据到复制流，并发送给所有replica
+    pub fn feed(&self, data: &[u8]) {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(backlog) = inner.backlog.as_mut() else {
+            return;
+        };
+        backlog.feed(data);
+        inner.offset += data.len() as u64;
+
+        // 在锁内发送，保证每个replica收到的数据顺序和偏移量一致
+        let data = Bytes::copy_from_slice(data);
+        inner
+            .replicas
+            .retain(|replica| replica.tx.send(data.clone()).is_ok());
+    }
+
+    /// 将写命令追加到复制流
+    pub fn feed_command(&self, args: &RespArray) {
+        if self.is_active() {
+            self.feed(&args.clone().encode());
+        }
+    }
+
+    /// 注册一个replica，根据PSYNC的参数决定全量同步还是部分重同步
+    /// 注册和确定偏移量在同一个锁内完成，之后写入的数据都会发送到返回的channel中
+    pub(crate) fn add_replica(
+        &self,
+        ip: String,
+        port: u16,
+        replid: &str,
+        offset: i64,
+    ) -> (u64, mpsc::UnboundedReceiver<Bytes>, SyncPlan) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.backlog.is_none() {
+            inner.backlog = Some(ReplBacklog::new(inner.backlog_size, inner.offset));
+        }
+
+        let id_matched = replid == inner.replid
+            || (replid == inner.replid2 && offset <= inner.second_replid_offset);
+        let data = match (id_matched, u64::try_from(offset)) {
+            (true, Ok(offset)) => inner.backlog.as_ref().and_then(|b| b.read_from(offset)),
+            _ => None,
+        };
+        let plan = match data {
+            Some(data) => SyncPlan::Continue {
+                replid: inner.replid.clone(),
+                data,
+            },
+            None => SyncPlan::Full {
+                replid: inner.replid.clone(),
+                offset: inner.offset,
+            },
+        };
+
+        let id = self.next_replica_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = mpsc::unbounded_channel();
+        inner.replicas.push(ReplicaHandle {
+            info: ReplicaInfo {
+                id,
+                ip,
+                port,
+                online: false,
+                ack_offset: 0,
+                last_ack_ms: now_ms(),
+            },
+            tx,
+        });
+        (id, rx, plan)
+    }
+
+    pub(crate) fn remove_replica(&self, id: u64) {
+        self.inner
+            .lock()
+            .unwrap()
+            .replicas
+            .retain(|r| r.info.id != id);
+    }
+
+    fn update_replica(&self, id: u64, f: impl FnOnce(&mut ReplicaInfo)) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(replica) = inner.replicas.iter_mut().find(|r| r.info.id == id) {
+            f(&mut replica.info);
+        }
+    }
+
+    /// 当前连接的replica
+    pub fn replicas(&self) -> Vec<ReplicaInfo> {
+        self.inner
+            .lock()
+            .unwrap()
+            .replicas
+            .iter()
+            .map(|r| r.info.clone())
+            .collect()
+    }
+
+    /// 成为host:port的replica，返回false表示已经是它的replica
+    pub(crate) fn set_master(&self, host: &str, port: u16) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        if let ReplicationRole::Replica(link) = &inner.role {
+            if link.host == host && link.port == port {
+                return false;
+            }
+        }
+
+        inner.role = ReplicationRole::Replica(MasterLink {
+            host: host.to_string(),
+            port,
+            state: LinkState::Connect,
+            last_io_ms: 0,
+            down_since_ms: now_ms(),
+        });
+        // replica也需要积压缓冲区，用于转发给下级replica和提升为master之后的部分重同步
+        if inner.backlog.is_none() {
+            inner.backlog = Some(ReplBacklog::new(inner.backlog_size, inner.offset));
+        }
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        true
+    }
+
+    /// 提升为master，保留旧的复制ID，其他replica可以继续部分重同步
+    pub(crate) fn promote(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.role == ReplicationRole::Master {
+            return;
+        }
+        inner.role = ReplicationRole::Master;
+        inner.replid2 = std::mem::replace(&mut inner.replid, gen_replid());
+        inner.second_replid_offset = inner.offset as i64 + 1;
+        self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// 更新到master的连接状态，generation不一致说明已经切换了master
+    fn update_link(&self, generation: u64, f: impl FnOnce(&mut MasterLink)) {
+        let mut inner = self.inner.lock().unwrap();
+        if self.generation() != generation {
+            return;
+        }
+        if let ReplicationRole::Replica(link) = &mut inner.role {
+            f(link);
+        }
+    }
+
+    fn set_link_state(&self, generation: u64, state: LinkState) {
+        self.update_link(generation, |link| {
+            if link.state == LinkState::Connected && state != LinkState::Connected {
+                link.down_since_ms = now_ms();
+            }
+            link.state = state;
+        });
+    }
+
+    /// 全量同步之后使用master的复制ID和偏移量
+    fn reset_replid(&self, replid: String, offset: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.replid = replid;
+        inner.replid2 = "0".repeat(REPLID_LEN);
+        inner.second_replid_offset = -1;
+        inner.offset = offset;
+        inner.backlog = Some(ReplBacklog::new(inner.backlog_size, offset));
+    }
+
+    /// 部分重同步时master的复制ID发生了变化(master经过了故障转移)
+    fn switch_replid(&self, replid: String) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.replid != replid {
+            inner.replid2 = std::mem::replace(&mut inner.replid, replid);
+            inner.second_replid_offset = inner.offset as i64 + 1;
+        }
+    }
+
+    /// INFO replication 的内容
+    pub fn info(&self) -> String {
+        let inner = self.inner.lock().unwrap();
+        let now = now_ms();
+        let mut lines = vec!["# Replication".to_string()];
+
+        match &inner.role {
+            ReplicationRole::Master => lines.push("role:master".to_string()),
+            ReplicationRole::Replica(link) => {
+                lines.push("role:slave".to_string());
+                lines.push(format!("master_host:{}", link.host));
+                lines.push(format!("master_port:{}", link.port));
+                let up = link.state == LinkState::Connected;
+                lines.push(format!(
+                    "master_link_status:{}",
+                    if up { "up" } else { "down" }
+                ));
+                let last_io = if link.last_io_ms == 0 {
+                    -1
+                } else {
+                    (now - link.last_io_ms) / 1000
+                };
+                lines.push(format!("master_last_io_seconds_ago:{}", last_io));
+                lines.push(format!(
+                    "master_sync_in_progress:{}",
+                    (link.state == LinkState::Sync) as u8
+                ));
+                lines.push(format!("slave_read_repl_offset:{}", inner.offset));
+                lines.push(format!("slave_repl_offset:{}", inner.offset));
+                if !up {
+                    lines.push(format!(
+                        "master_link_down_since_seconds:{}",
+                        (now - link.down_since_ms) / 1000
+                    ));
+                }
+                lines.push("slave_priority:100".to_string());
+                lines.push(format!(
+                    "slave_read_only:{}",
+                    self.read_only.load(Ordering::Relaxed) as u8
+                ));
+            }
+        }
+
+        lines.push(format!("connected_slaves:{}", inner.replicas.len()));
+        for (i, replica) in inner.replicas.iter().map(|r| &r.info).enumerate() {
+            lines.push(format!(
+                "slave{}:ip={},port={},state={},offset={},lag={}",
+                i,
+                replica.ip,
+                replica.port,
+                if replica.online {
+                    "online"
+                } else {
+                    "wait_bgsave"
+                },
+                replica.ack_offset,
+                (now - replica.last_ack_ms) / 1000
+            ));
+        }
+
+        lines.push(format!("master_replid:{}", inner.replid));
+        lines.push(format!("master_replid2:{}", inner.replid2));
+        lines.push(format!("master_repl_offset:{}", inner.offset));
+        lines.push(format!("second_repl_offset:{}", inner.second_replid_offset));
+        match &inner.backlog {
+            Some(backlog) => {
+                lines.push("repl_backlog_active:1".to_string());
+                lines.push(format!("repl_backlog_size:{}", backlog.capacity()));
+                lines.push(format!(
+                    "repl_backlog_first_byte_offset:{}",
+                    backlog.first_offset()
+                ));
+                lines.push(format!("repl_backlog_histlen:{}", backlog.histlen()));
+            }
+            None => {
+                lines.push("repl_backlog_active:0".to_string());
+                lines.push(format!("repl_backlog_size:{}", inner.backlog_size));
+                lines.push("repl_backlog_first_byte_offset:0".to_string());
+                lines.push("repl_backlog_histlen:0".to_string());
+            }
+        }
+
+        lines.join("\r\n") + "\r\n"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_psync_plan() {
+        let state = ReplicationState::default();
+        let replid = state.replid();
+        assert_eq!(replid.len(), REPLID_LEN);
+        assert!(!state.is_active());
+
+        // 第一次同步总是全量同步
+        let (id, _rx, plan) = state.add_replica("127.0.0.1".to_string(), 6380, "?", -1);
+        assert_eq!(
+            plan,
+            SyncPlan::Full {
+                replid: replid.clone(),
+                offset: 0
+            }
+        );
+        assert!(state.is_active());
+
+        state.feed(b"hello");
+        state.feed(b"world");
+        assert_eq!(state.offset(), 10);
+        state.remove_replica(id);
+
+        // 断线后从第6个字节继续
+        let (_, mut rx, plan) = state.add_replica("127.0.0.1".to_string(), 6380, &replid, 6);
+        assert_eq!(
+            plan,
+            SyncPlan::Continue {
+                replid: replid.clone(),
+                data: b"world".to_vec()
+            }
+        );
+        state.feed(b"!");
+        assert_eq!(rx.try_recv().unwrap(), Bytes::from_static(b"!"));
+
+        // 复制ID不一致或者偏移量超出范围
+        let (_, _, plan) = state.add_replica("127.0.0.1".to_string(), 6380, "other", 6);
+        assert!(matches!(plan, SyncPlan::Full { offset: 11, .. }));
+        let (_, _, plan) = state.add_replica("127.0.0.1".to_string(), 6380, &replid, 100);
+        assert!(matches!(plan, SyncPlan::Full { .. }));
+    }
+
+    #[test]
+    fn test_promote_keeps_history() {
+        let state = ReplicationState::default();
+        assert!(state.set_master("127.0.0.1", 6379));
+        assert!(!state.set_master("127.0.0.1", 6379));
+        assert!(state.is_read_only());
+        state.reset_replid("a".repeat(REPLID_LEN), 100);
+        state.feed(b"hello");
+
+        state.promote();
+        assert_eq!(state.role(), ReplicationRole::Master);
+        assert!(!state.is_read_only());
+        assert_ne!(state.replid(), "a".repeat(REPLID_LEN));
+
+        // 其他replica可以用旧的复制ID部分重同步
+        let (_, _, plan) =
+            state.add_replica("127.0.0.1".to_string(), 6380, &"a".repeat(REPLID_LEN), 103);
+        assert!(matches!(plan, SyncPlan::Continue { data, .. } if data == b"llo"));
+    }
+}
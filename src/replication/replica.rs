@@ -0,0 +1,255 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use bytes::{Buf, BytesMut};
+use futures::StreamExt;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+use tokio_util::codec::Framed;
+
+use crate::{
+    cmd::CommandExecutor, network::RedisCodec, now_ms, persistence, Backend, BulkString, RespArray,
+    RespEncode, RespFrame,
+};
+
+use super::{LinkState, ReplicationRole};
+
+/// 连接master的超时时间
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// 超过这个时间没有收到master的数据就认为连接已经断开
+const REPL_TIMEOUT_MS: i64 = 60_000;
+/// 重连间隔
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 在后台开始与当前master同步，没有tokio运行时的时候只修改状态
+pub fn start_replication(backend: &Backend) {
+    let ReplicationRole::Replica(link) = backend.replication().role() else {
+        return;
+    };
+    let generation = backend.replication().generation();
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => {
+            handle.spawn(run_replica(
+                backend.clone(),
+                link.host,
+                link.port,
+                generation,
+            ));
+        }
+        Err(_) => tracing::warn!("No runtime available, replication is not started"),
+    }
+}
+
+/// 与master保持同步，断线后自动重连，切换master之后退出
+async fn run_replica(backend: Backend, host: String, port: u16, generation: u64) {
+    let state = backend.replication();
+    while state.generation() == generation {
+        state.set_link_state(generation, LinkState::Connecting);
+        tracing::info!("Connecting to MASTER {}:{}", host, port);
+        if let Err(e) = sync_with_master(&backend, &host, port, generation).await {
+            tracing::warn!("Replication with MASTER {}:{} failed: {}", host, port, e);
+        }
+        state.set_link_state(generation, LinkState::Connect);
+        tokio::time::sleep(RECONNECT_INTERVAL).await;
+    }
+}
+
+/// 向master发送一条命令
+async fn send_command(framed: &mut Framed<TcpStream, RedisCodec>, args: &[&str]) -> Result<()> {
+    let args = RespArray::new(
+        args.iter()
+            .map(|arg| BulkString::new(*arg).into())
+            .collect::<Vec<RespFrame>>(),
+    );
+    framed.get_mut().write_all(&args.encode()).await?;
+    Ok(())
+}
+
+async fn read_reply(framed: &mut Framed<TcpStream, RedisCodec>) -> Result<RespFrame> {
+    match framed.next().await {
+        Some(Ok(RespFrame::Error(e))) => Err(anyhow!("master replied error: {}", e.as_str())),
+        Some(frame) => frame,
+        None => bail!("connection closed by master"),
+    }
+}
+
+/// 读取全量同步的快照数据: $<len>\r\n<data>，数据之后没有\r\n
+/// 生成快照期间master可能会发送\n保持连接
+async fn read_payload(framed: &mut Framed<TcpStream, RedisCodec>) -> Result<Vec<u8>> {
+    let mut buf: BytesMut = std::mem::take(framed.read_buffer_mut());
+    let stream = framed.get_mut();
+
+    let len = loop {
+        while buf.first() == Some(&b'\n') {
+            buf.advance(1);
+        }
+        if let Some(pos) = buf.windows(2).position(|w| w == b"\r\n") {
+            if buf[0] != b'$' {
+                bail!("invalid sync payload from master");
+            }
+            let len: usize = std::str::from_utf8(&buf[1..pos])?.parse()?;
+            buf.advance(pos + 2);
+            break len;
+        }
+        if stream.read_buf(&mut buf).await? == 0 {
+            bail!("connection closed during sync");
+        }
+    };
+
+    while buf.len() < len {
+        if stream.read_buf(&mut buf).await? == 0 {
+            bail!("connection closed during sync");
+        }
+    }
+    let payload = buf.split_to(len).to_vec();
+    // 剩余的数据已经是复制流的一部分
+    framed.read_buffer_mut().extend_from_slice(&buf);
+    Ok(payload)
+}
+
+/// 清空当前数据并加载master发送的快照
+async fn load_payload(backend: &Backend, payload: Vec<u8>) -> Result<()> {
+    let backend = backend.clone();
+    tokio::task::spawn_blocking(move || {
+        backend.snapshot().set_loading(true);
+        backend.flush_all();
+        let ret = persistence::load_bytes(&payload, &backend);
+        backend.snapshot().set_loading(false);
+        let count = ret?;
+        tracing::info!("MASTER <-> REPLICA sync: loaded {} keys", count);
+        // 数据已经整体替换，AOF也需要重新生成
+        if backend.aof().is_enabled() {
+            persistence::rewrite_aof(&backend)?;
+        }
+        Ok(())
+    })
+    .await?
+}
+
+async fn sync_with_master(backend: &Backend, host: &str, port: u16, generation: u64) -> Result<()> {
+    let state = backend.replication();
+    let stream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect((host, port))).await??;
+    let mut framed = Framed::new(stream, RedisCodec::default());
+
+    // 握手，master配置了密码时先认证
+    let config = backend.config().current();
+    if !config.masterauth.is_empty() {
+        let mut args = vec!["auth"];
+        if !config.masteruser.is_empty() {
+            args.push(&config.masteruser);
+        }
+        args.push(&config.masterauth);
+        send_command(&mut framed, &args).await?;
+        read_reply(&mut framed).await?;
+    }
+    send_command(&mut framed, &["PING"]).await?;
+    read_reply(&mut framed).await?;
+    let listening_port = state.listening_port().to_string();
+    send_command(
+        &mut framed,
+        &["REPLCONF", "listening-port", &listening_port],
+    )
+    .await?;
+    read_reply(&mut framed).await?;
+    send_command(&mut framed, &["REPLCONF", "capa", "psync2"]).await?;
+    read_reply(&mut framed).await?;
+
+    let replid = state.replid();
+    let offset = (state.offset() + 1).to_string();
+    send_command(&mut framed, &["PSYNC", &replid, &offset]).await?;
+    let reply = match read_reply(&mut framed).await? {
+        RespFrame::SimpleString(s) => s.0,
+        frame => bail!("unexpected PSYNC reply: {:?}", frame),
+    };
+    let parts: Vec<&str> = reply.split_whitespace().collect();
+    match parts.as_slice() {
+        ["FULLRESYNC", replid, offset] => {
+            let offset: u64 = offset.parse()?;
+            tracing::info!("Full resync from master: {}:{}", replid, offset);
+            state.set_link_state(generation, LinkState::Sync);
+            let payload = read_payload(&mut framed).await?;
+            load_payload(backend, payload).await?;
+            state.reset_replid(replid.to_string(), offset);
+        }
+        ["CONTINUE", replid] => {
+            tracing::info!("Partial resync accepted by master");
+            state.switch_replid(replid.to_string());
+        }
+        ["CONTINUE"] => tracing::info!("Partial resync accepted by master"),
+        _ => bail!("unexpected PSYNC reply: {}", reply),
+    }
+
+    state.set_link_state(generation, LinkState::Connected);
+    state.update_link(generation, |link| link.last_io_ms = now_ms());
+    tracing::info!("MASTER <-> REPLICA sync succeeded");
+
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        if state.generation() != generation {
+            return Ok(());
+        }
+        tokio::select! {
+            frame = framed.next() => match frame {
+                Some(Ok(frame)) => {
+                    state.update_link(generation, |link| link.last_io_ms = now_ms());
+                    apply_from_master(backend, frame, &mut framed).await?;
+                }
+                Some(Err(e)) => return Err(e),
+                None => bail!("connection closed by master"),
+            },
+            _ = interval.tick() => {
+                let offset = state.offset().to_string();
+                send_command(&mut framed, &["REPLCONF", "ACK", &offset]).await?;
+                let last_io = match state.role() {
+                    ReplicationRole::Replica(link) => link.last_io_ms,
+                    ReplicationRole::Master => return Ok(()),
+                };
+                if now_ms() - last_io > REPL_TIMEOUT_MS {
+                    bail!("timeout connecting to the MASTER");
+                }
+            }
+        }
+    }
+}
+
+/// 执行master发送的命令，并原样转发给下级replica
+async fn apply_from_master(
+    backend: &Backend,
+    frame: RespFrame,
+    framed: &mut Framed<TcpStream, RedisCodec>,
+) -> Result<()> {
+    let RespFrame::Array(args) = frame else {
+        bail!("unexpected frame from master: {:?}", frame);
+    };
+    let raw = args.clone().encode();
+    let state = backend.replication();
+
+    let is_getack = matches!(
+        (args.first(), args.get(1)),
+        (Some(RespFrame::BulkString(cmd)), Some(RespFrame::BulkString(sub)))
+            if cmd.eq_ignore_ascii_case(b"replconf") && sub.eq_ignore_ascii_case(b"getack")
+    );
+    if is_getack {
+        let offset = state.offset().to_string();
+        send_command(framed, &["REPLCONF", "ACK", &offset]).await?;
+    } else {
+        match backend.commands().parse(args.clone()) {
+            Ok(cmd) => {
+                let is_write = backend
+                    .commands()
+                    .spec(&args)
+                    .is_some_and(|spec| spec.is_write());
+                cmd.execute(backend);
+                if is_write {
+                    backend.aof().feed(&args)?;
+                }
+            }
+            Err(e) => tracing::warn!("Invalid command from master: {}", e),
+        }
+    }
+
+    state.feed(&raw);
+    Ok(())
+}
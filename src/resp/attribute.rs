@@ -0,0 +1,108 @@
+use bytes::BytesMut;
+
+use crate::{RespDecode, RespEncode, RespError, RespFrame, RespMap, SimpleString};
+
+use super::{decode_as, RESP_ARRAY_CAP};
+
+/// RespAttribute，属性以及紧跟在属性之后的实际回复
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Hash)]
+pub struct RespAttribute {
+    pub(crate) attributes: RespMap,
+    pub(crate) data: Box<RespFrame>,
+}
+
+impl RespAttribute {
+    pub fn new(attributes: RespMap, data: impl Into<RespFrame>) -> Self {
+        RespAttribute {
+            attributes,
+            data: Box::new(data.into()),
+        }
+    }
+
+    /// 属性
+    pub fn attributes(&self) -> &RespMap {
+        &self.attributes
+    }
+
+    /// 实际的回复
+    pub fn data(&self) -> &RespFrame {
+        &self.data
+    }
+
+    /// 丢弃属性，只保留实际的回复
+    pub fn into_data(self) -> RespFrame {
+        *self.data
+    }
+}
+
+/// - attribute:"|<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n><reply>"
+impl RespEncode for RespAttribute {
+    fn encode(self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(RESP_ARRAY_CAP);
+        buf.extend_from_slice(&format!("|{}\r\n", self.attributes.len()).into_bytes());
+        for (key, value) in self.attributes.0 {
+            buf.extend_from_slice(&SimpleString::new(key).encode());
+            buf.extend_from_slice(&value.encode());
+        }
+        buf.extend_from_slice(&self.data.encode());
+        buf
+    }
+}
+
+/// - attribute:"|<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n><reply>"
+impl RespDecode for RespAttribute {
+    const PREFIX: &'static str = "|";
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        decode_as(buf, Self::PREFIX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BulkString, RespArray, RespDouble};
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    use super::*;
+
+    #[test]
+    fn test_attribute() -> Result<()> {
+        // 协议文档中的例子，属性的值是bulk string为Key的Map
+        let capture = b"|1\r\n+key-popularity\r\n%2\r\n$1\r\na\r\n,0.1923\r\n$1\r\nb\r\n,0.0012\r\n*2\r\n:2039123\r\n:9543892\r\n";
+        let mut buf = BytesMut::from(&capture[..]);
+        let RespFrame::Attribute(frame) = RespFrame::decode(&mut buf)? else {
+            panic!("expect attribute");
+        };
+        assert!(buf.is_empty());
+
+        let mut popularity = RespMap::default();
+        popularity.insert("a".to_string(), RespDouble::new(0.1923).into());
+        popularity.insert("b".to_string(), RespDouble::new(0.0012).into());
+        let mut attributes = RespMap::default();
+        attributes.insert("key-popularity".to_string(), popularity.into());
+        assert_eq!(frame.attributes(), &attributes);
+        let reply: RespFrame = RespArray::new(vec![
+            RespFrame::Integer(2039123),
+            RespFrame::Integer(9543892),
+        ])
+        .into();
+        assert_eq!(frame.data(), &reply);
+
+        // 编码之后可以再解码回来
+        let mut attributes = RespMap::default();
+        attributes.insert("ttl".to_string(), RespFrame::Integer(3600));
+        let frame: RespFrame = RespAttribute::new(attributes, BulkString::new("value")).into();
+        assert_eq!(
+            frame.clone().encode(),
+            b"|1\r\n+ttl\r\n:+3600\r\n$5\r\nvalue\r\n"
+        );
+        let mut buf = BytesMut::from(&frame.clone().encode()[..]);
+        assert_eq!(RespFrame::decode(&mut buf)?, frame);
+
+        // 缺少实际的回复
+        let mut buf = BytesMut::from("|1\r\n+ttl\r\n:3600\r\n");
+        let frame = RespAttribute::decode(&mut buf);
+        assert_eq!(frame.unwrap_err(), RespError::NotComplete);
+        Ok(())
+    }
+}
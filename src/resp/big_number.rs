@@ -0,0 +1,69 @@
+use std::ops::Deref;
+
+use bytes::BytesMut;
+
+use crate::{RespDecode, RespEncode, RespError};
+
+use super::decode_as;
+
+/// RespBigNumber，超出i64范围的整数，以十进制字符串保存
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Hash)]
+pub struct RespBigNumber(pub(crate) String);
+
+impl RespBigNumber {
+    pub fn new(s: impl Into<String>) -> Self {
+        RespBigNumber(s.into())
+    }
+}
+
+/// - big number:"([+|-]<number>\r\n"
+impl RespEncode for RespBigNumber {
+    fn encode(self) -> Vec<u8> {
+        format!("({}\r\n", self.0).into_bytes()
+    }
+}
+
+/// - big number:"([+|-]<number>\r\n"
+impl RespDecode for RespBigNumber {
+    const PREFIX: &'static str = "(";
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        decode_as(buf, Self::PREFIX)
+    }
+}
+
+impl Deref for RespBigNumber {
+    type Target = String;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RespFrame;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    use super::*;
+
+    #[test]
+    fn test_big_number() -> Result<()> {
+        let capture = b"(3492890328409238509324850943850943825024385\r\n";
+        let mut buf = BytesMut::from(&capture[..]);
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(
+            frame,
+            RespBigNumber::new("3492890328409238509324850943850943825024385").into()
+        );
+        assert_eq!(frame.encode(), capture);
+
+        let mut buf = BytesMut::from("(-12a\r\n");
+        let frame = RespBigNumber::decode(&mut buf);
+        assert_eq!(
+            frame.unwrap_err(),
+            RespError::InvalidFrame("Invalid Big Number:-12a".to_string())
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+use std::ops::Deref;
+
+use bytes::BytesMut;
+
+use crate::{RespDecode, RespEncode, RespError};
+
+use super::decode_as;
+
+/// BlobError，可以包含任意二进制数据的错误
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Hash)]
+pub struct BlobError(pub(crate) Vec<u8>);
+
+impl BlobError {
+    pub fn new(s: impl Into<Vec<u8>>) -> Self {
+        BlobError(s.into())
+    }
+}
+
+/// - blob error:"!<length>\r\n<error>\r\n"
+impl RespEncode for BlobError {
+    fn encode(self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.0.len() + 16);
+        buf.extend_from_slice(&format!("!{}\r\n", self.len()).into_bytes());
+        buf.extend_from_slice(&self);
+        buf.extend_from_slice(b"\r\n");
+        buf
+    }
+}
+
+/// - blob error:"!<length>\r\n<error>\r\n"
+impl RespDecode for BlobError {
+    const PREFIX: &'static str = "!";
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        decode_as(buf, Self::PREFIX)
+    }
+}
+
+impl Deref for BlobError {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RespFrame;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    use super::*;
+
+    #[test]
+    fn test_blob_error() -> Result<()> {
+        let capture = b"!21\r\nSYNTAX invalid syntax\r\n";
+        let mut buf = BytesMut::from(&capture[..]);
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(frame, BlobError::new("SYNTAX invalid syntax").into());
+        assert_eq!(frame.encode(), capture);
+
+        // 数据不完整
+        let mut buf = BytesMut::from("!21\r\nSYNTAX invalid\r\n");
+        let frame = BlobError::decode(&mut buf);
+        assert_eq!(frame.unwrap_err(), RespError::NotComplete);
+        Ok(())
+    }
+}
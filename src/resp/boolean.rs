@@ -1,6 +1,8 @@
 use bytes::BytesMut;
 
-use crate::{extract_fixed_data, RespDecode, RespEncode, RespError};
+use crate::{RespDecode, RespEncode, RespError};
+
+use super::decode_as;
 
 // - boolean:"#<tf>\r\n"
 impl RespEncode for bool {
@@ -14,18 +16,7 @@ impl RespEncode for bool {
 impl RespDecode for bool {
     const PREFIX: &'static str = "#";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        match extract_fixed_data(buf, "#t\r\n", Self::PREFIX) {
-            Ok(_) => Ok(true),
-            Err(RespError::NotComplete) => Err(RespError::NotComplete),
-            Err(_) => match extract_fixed_data(buf, "#f\r\n", Self::PREFIX) {
-                Ok(_) => Ok(false),
-                Err(RespError::NotComplete) => Err(RespError::NotComplete),
-                Err(e) => Err(e),
-            },
-        }
-    }
-    fn expect_length(_buf: &[u8]) -> Result<usize, RespError> {
-        Ok(4)
+        decode_as(buf, Self::PREFIX)
     }
 }
 
@@ -2,9 +2,9 @@ use std::hash::Hash;
 
 use bytes::BytesMut;
 
-use crate::{extract_simple_frame_data, RespDecode, RespEncode, RespError};
+use crate::{RespDecode, RespEncode, RespError};
 
-use super::CRLF_LEN;
+use super::decode_as;
 
 // 使用RespDouble 包装F64 以实现Eq
 #[derive(Debug, Clone, PartialOrd)]
@@ -43,25 +43,12 @@ impl RespEncode for RespDouble {
 /// - double:",[+>]<integral>[.<fractional>][<Ee>[sign]<exponent>]\r\n"
 impl RespDecode for RespDouble {
     const PREFIX: &'static str = ",";
-
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        // 获取第一个CRLF
-        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
-
-        let data = buf.split_to(end + CRLF_LEN);
-
-        let s = String::from_utf8_lossy(&data[Self::PREFIX.len()..end]);
-        // 转换为i64
-        let num = s.parse::<f64>()?.into();
-        Ok(num)
-    }
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
-        Ok(end + CRLF_LEN)
+        decode_as(buf, Self::PREFIX)
     }
 }
 
-/// 下面这俩转换貌似不行
+// 下面这俩转换貌似不行
 
 /// 实现From f64
 impl From<f64> for RespDouble {
@@ -80,6 +67,11 @@ impl RespDouble {
     pub fn new(f: f64) -> Self {
         RespDouble(f)
     }
+
+    /// 获取内部的f64
+    pub fn value(&self) -> f64 {
+        self.0
+    }
 }
 
 #[cfg(test)]
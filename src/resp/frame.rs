@@ -1,8 +1,9 @@
 use bytes::BytesMut;
 
 use crate::{
-    BulkString, RespArray, RespDecode, RespError, RespMap, RespNull, RespSet, SimpleError,
-    SimpleString,
+    BlobError, BulkString, RespArray, RespAttribute, RespBigNumber, RespDecode, RespError, RespMap,
+    RespNull, RespNullArray, RespNullBulkString, RespParser, RespPush, RespSet, SimpleError,
+    SimpleString, VerbatimString,
 };
 
 use super::double::RespDouble;
@@ -22,10 +23,13 @@ pub enum RespFrame {
     //    -"*2\r\n$3\r\nget\r\n$5\r\nhellolr\n"
     Array(RespArray),
 
+    // - null bulk string:"$-1\r\n"
+    NullBulkString(RespNullBulkString),
+    // - null array:"*-1\r\n"
+    NullArray(RespNullArray),
+
     // - null:"_\r\n"
     Null(RespNull),
-    // // - null array:"*-1\r\n"
-    // NullArray(RespNullArray),
     // - boolean:"#<tf>\r\n"
     Boolean(bool),
     // - double:",[+>]<integral>[.<fractional>][<Ee>[sign]<exponent>]\r\n"
@@ -34,76 +38,26 @@ pub enum RespFrame {
     Map(RespMap),
     // - set:"~<number-of-elements>\r\n<element-1>..<element-n>"
     Set(RespSet),
+    // - big number:"([+|-]<number>\r\n"
+    BigNumber(RespBigNumber),
+    // - verbatim string:"=<length>\r\n<format>:<data>\r\n"
+    Verbatim(VerbatimString),
+    // - blob error:"!<length>\r\n<error>\r\n"
+    BlobError(BlobError),
+    // - push:"><number-of-elements>\r\n<element-1>...<element-n>"
+    Push(RespPush),
+    // - attribute:"|<number-of-entries>\r\n<key-1><value-1>...<key-n><value-n><reply>"
+    Attribute(RespAttribute),
 }
 
 /// 为RespFrame实现解码
+/// 数据不完整时返回NotComplete并且不消费buf，需要多次喂数据的场景使用RespParser保留进度
 impl RespDecode for RespFrame {
     const PREFIX: &'static str = "";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        let mut iter = buf.iter().peekable();
-        match iter.peek() {
-            Some(b'+') => {
-                let frame = SimpleString::decode(buf)?;
-                Ok(frame.into())
-            }
-            Some(b'-') => {
-                let frame = SimpleError::decode(buf)?;
-                Ok(frame.into())
-            }
-            Some(b':') => {
-                let frame = i64::decode(buf)?;
-                Ok(frame.into())
-            }
-            Some(b'$') => {
-                let frame = BulkString::decode(buf)?;
-                Ok(frame.into())
-            }
-            Some(b'*') => {
-                let frame = RespArray::decode(buf)?;
-                Ok(frame.into())
-            }
-            Some(b'_') => {
-                let frame = RespNull::decode(buf)?;
-                Ok(frame.into())
-            }
-            Some(b'#') => {
-                let frame = bool::decode(buf)?;
-                Ok(frame.into())
-            }
-            Some(b',') => {
-                let frame = RespDouble::decode(buf)?;
-                Ok(frame.into())
-            }
-            Some(b'%') => {
-                let frame = RespMap::decode(buf)?;
-                Ok(frame.into())
-            }
-            Some(b'~') => {
-                let frame = RespSet::decode(buf)?;
-                Ok(frame.into())
-            }
-            None => Err(RespError::NotComplete),
-            _ => Err(RespError::InvalidFrameType(format!(
-                "expect_length: unknown frame type: {:?}",
-                buf
-            ))),
-        }
-    }
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let mut iter = buf.iter().peekable();
-        match iter.peek() {
-            Some(b'*') => RespArray::expect_length(buf),
-            Some(b'~') => RespSet::expect_length(buf),
-            Some(b'%') => RespMap::expect_length(buf),
-            Some(b'$') => BulkString::expect_length(buf),
-            Some(b':') => i64::expect_length(buf),
-            Some(b'+') => SimpleString::expect_length(buf),
-            Some(b'-') => SimpleError::expect_length(buf),
-            Some(b'#') => bool::expect_length(buf),
-            Some(b',') => RespDouble::expect_length(buf),
-            Some(b'_') => RespNull::expect_length(buf),
-            _ => Err(RespError::NotComplete),
-        }
+        RespParser::default()
+            .parse(buf)?
+            .ok_or(RespError::NotComplete)
     }
 }
 
@@ -115,13 +69,13 @@ impl From<&str> for RespFrame {
 
 impl From<&[u8]> for RespFrame {
     fn from(s: &[u8]) -> Self {
-        BulkString(s.to_vec()).into()
+        BulkString::new(s).into()
     }
 }
 
 impl<const N: usize> From<&[u8; N]> for RespFrame {
     fn from(s: &[u8; N]) -> Self {
-        BulkString(s.to_vec()).into()
+        BulkString::new(s).into()
     }
 }
 
@@ -1,8 +1,8 @@
 use bytes::BytesMut;
 
-use crate::{extract_simple_frame_data, RespDecode, RespEncode, RespError};
+use crate::{RespDecode, RespEncode, RespError};
 
-use super::CRLF_LEN;
+use super::decode_as;
 
 /// - integer:":[<+|->]<value>\r\n"
 impl RespEncode for i64 {
@@ -16,19 +16,7 @@ impl RespEncode for i64 {
 impl RespDecode for i64 {
     const PREFIX: &'static str = ":";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        // 获取第一个CRLF
-        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
-
-        let data = buf.split_to(end + CRLF_LEN);
-
-        let s = String::from_utf8_lossy(&data[Self::PREFIX.len()..end]);
-        // 转换为i64
-        let num = s.parse()?;
-        Ok(num)
-    }
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
-        Ok(end + CRLF_LEN)
+        decode_as(buf, Self::PREFIX)
     }
 }
 
@@ -3,11 +3,11 @@ use std::{
     ops::{Deref, DerefMut},
 };
 
-use bytes::{Buf, BytesMut};
+use bytes::BytesMut;
 
 use crate::{RespDecode, RespEncode, RespError, RespFrame, SimpleString};
 
-use super::{calc_total_length, parse_length, CRLF_LEN, RESP_ARRAY_CAP};
+use super::{decode_as, RESP_ARRAY_CAP};
 
 /// RespMap
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Hash, Default)]
@@ -17,26 +17,7 @@ pub struct RespMap(pub(crate) BTreeMap<String, RespFrame>);
 impl RespDecode for RespMap {
     const PREFIX: &'static str = "%";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        // 获取长度
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
-        let total_len = calc_total_length(buf, end, len, Self::PREFIX)?;
-        if buf.len() < total_len {
-            return Err(RespError::NotComplete);
-        }
-        buf.advance(end + CRLF_LEN);
-
-        let mut map = RespMap::default();
-        for _ in 0..len {
-            let key = SimpleString::decode(buf)?;
-            let value = RespFrame::decode(buf)?;
-            map.insert(key.0, value);
-        }
-        Ok(map)
-    }
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
-        let total = calc_total_length(buf, end, len, Self::PREFIX)?;
-        Ok(total)
+        decode_as(buf, Self::PREFIX)
     }
 }
 
@@ -110,4 +91,15 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_decode_map_bulk_key() -> Result<()> {
+        // 真实Redis HELLO 3返回的Key是bulk string
+        let mut buf = BytesMut::from("%2\r\n$6\r\nserver\r\n$5\r\nredis\r\n$5\r\nproto\r\n:3\r\n");
+        let frame = RespMap::decode(&mut buf)?;
+        assert_eq!(frame["server"], BulkString::new("redis").into());
+        assert_eq!(frame["proto"], RespFrame::Integer(3));
+        assert!(buf.is_empty());
+        Ok(())
+    }
 }
@@ -5,6 +5,9 @@
 */
 
 mod array;
+mod attribute;
+mod big_number;
+mod blob_error;
 mod boolean;
 mod bulk_string;
 mod double;
@@ -12,17 +15,32 @@ mod frame;
 mod intenger;
 mod map;
 mod null;
+mod parser;
+mod push;
 mod set;
 mod simpe_string;
 mod simple_error;
+mod verbatim_string;
 
-use bytes::{Buf, BytesMut};
+use bytes::BytesMut;
 use thiserror::Error;
 
 pub use {
-    self::array::RespArray, bulk_string::BulkString, double::RespDouble, frame::RespFrame,
-    map::RespMap, null::RespNull, set::RespSet, simpe_string::SimpleString,
+    self::array::{RespArray, RespNullArray},
+    attribute::RespAttribute,
+    big_number::RespBigNumber,
+    blob_error::BlobError,
+    bulk_string::{BulkString, RespNullBulkString},
+    double::RespDouble,
+    frame::RespFrame,
+    map::RespMap,
+    null::RespNull,
+    parser::RespParser,
+    push::RespPush,
+    set::RespSet,
+    simpe_string::SimpleString,
     simple_error::SimpleError,
+    verbatim_string::VerbatimString,
 };
 
 /// 预分配的缓冲区大小
@@ -46,9 +64,6 @@ pub trait RespDecode: Sized {
     const PREFIX: &'static str;
     /// 将BytesMut解码为自己
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError>;
-
-    /// 期望的长度
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError>;
 }
 
 /// Resp解码异常
@@ -62,158 +77,30 @@ pub enum RespError {
     InvalidFrameLength(usize),
     #[error("Frame Not Complete")]
     NotComplete,
+    #[error("Protocol error: {0}")]
+    Protocol(String),
     #[error("Invalid Parse: {0}")]
     InvalidIntParse(#[from] std::num::ParseIntError),
     #[error("Invalid Parse: {0}")]
     InvalidFloatParse(#[from] std::num::ParseFloatError),
 }
 
-/// 查找结束符号
-pub(crate) fn find_crlf(buf: &[u8], nth: usize) -> Option<usize> {
-    // 计数
-    let mut count = 0;
-    // 查找结束字符"\r\n"
-    for i in 1..buf.len() - 1 {
-        if buf[i] == b'\r' && buf[i + 1] == b'\n' {
-            count += 1;
-            if count == nth {
-                return Some(i);
-            }
-        }
-    }
-
-    None
-}
-/// 处理固定长度的数据
-pub(crate) fn extract_fixed_data(
-    buf: &mut BytesMut,
-    expect: &str,
-    expect_type: &str,
-) -> Result<(), RespError> {
-    // 检查长度
-    if buf.len() < expect.len() {
+/// 解码一个完整的frame并转换为具体的类型
+fn decode_as<T>(buf: &mut BytesMut, prefix: &str) -> Result<T, RespError>
+where
+    RespFrame: TryInto<T, Error = &'static str>,
+{
+    if buf.len() < prefix.len() {
         return Err(RespError::NotComplete);
     }
-
     // 检查类型标识
-    if !buf.starts_with(expect.as_bytes()) {
-        return Err(RespError::InvalidFrameType(format!(
-            "except ({:}) got:{:?}",
-            expect_type, buf
-        )));
-    }
-    // 移动Buf的位置 到验证的固定组合末尾
-    buf.advance(expect.len());
-
-    Ok(())
-}
-
-/// 处理简单类型数据
-pub(crate) fn extract_simple_frame_data(buf: &[u8], prefix: &str) -> Result<usize, RespError> {
-    // 简单数据必定为 prefix + data + \r\n
-    // 所以验证最少要大于3
-    if buf.len() < 3 {
-        return Err(RespError::NotComplete);
-    }
-    // 验证前缀
-
     if !buf.starts_with(prefix.as_bytes()) {
         return Err(RespError::InvalidFrameType(format!(
             "except ({:}) got:{:?}",
             prefix, buf
         )));
     }
-
-    // 找到第一个结束符号就返回
-    let end = find_crlf(buf, 1).ok_or(RespError::NotComplete)?;
-    Ok(end)
-}
-
-/// 拿到标头长度
-fn parse_length(buf: &[u8], prefix: &str) -> Result<(usize, usize), RespError> {
-    let end = extract_simple_frame_data(buf, prefix)?;
-    let s = String::from_utf8_lossy(&buf[prefix.len()..end]);
-
-    // 根据前缀判断是否是负数
-    if s.starts_with('-') {
-        if s.starts_with("-1") {
-            Ok((end, 0))
-        } else {
-            Err(RespError::InvalidFrame(format!("Invalid Length:{}", s)))
-        }
-    } else {
-        Ok((end, s.parse()?))
-    }
-}
-
-/// 计算总长度
-fn calc_total_length(buf: &[u8], end: usize, len: usize, prefix: &str) -> Result<usize, RespError> {
-    // 计算总长度
-    let mut total = end + CRLF_LEN;
-    let mut data = &buf[total..];
-
-    match prefix {
-        "*" | "~" => {
-            // 数组和集合只处理元素的长度
-            for _ in 0..len {
-                let len = RespFrame::expect_length(data)?;
-                data = &data[len..];
-                total += len;
-            }
-            Ok(total)
-        }
-        "%" => {
-            // Map需要处理Key的长度和Value的长度
-            for _ in 0..len {
-                let len = SimpleString::expect_length(data)?;
-
-                data = &data[len..];
-                total += len;
-
-                let len = RespFrame::expect_length(data)?;
-
-                data = &data[len..];
-                total += len;
-            }
-            Ok(total)
-        }
-        _ => Ok(len + CRLF_LEN),
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use bytes::BytesMut;
-
-    #[test]
-    fn test_find_crlf() {
-        let buf = b"hello\r\nworld\r\n";
-        let pos = find_crlf(buf, 1).unwrap();
-        assert_eq!(pos, 5);
-
-        let pos = find_crlf(buf, 2).unwrap();
-        assert_eq!(pos, 12);
-    }
-    #[test]
-    fn test_extract_simple_frame_data() {
-        let buf = b"+hello\r\nworld\r\n";
-        let pos = extract_simple_frame_data(buf, "+").unwrap();
-        assert_eq!(pos, 6);
-
-        let buf = b"+hello\r\nworld\r\n";
-        let pos = extract_simple_frame_data(buf, "-");
-        assert!(pos.is_err());
-    }
-
-    #[test]
-    fn test_extract_fixed_data() {
-        let mut buf = BytesMut::from("+hello\r\nworld\r\n");
-        let pos = extract_fixed_data(&mut buf, "+hello\r\n", "SimpleString");
-        assert!(pos.is_ok());
-
-        let mut buf = BytesMut::from("+hello\r\nworld\r\n");
-        let pos = extract_fixed_data(&mut buf, "-hello\r\n", "SimpleError");
-        assert!(pos.is_err());
-    }
+    RespFrame::decode(buf)?
+        .try_into()
+        .map_err(|e: &str| RespError::InvalidFrameType(e.to_string()))
 }
@@ -1,6 +1,8 @@
 use bytes::BytesMut;
 
-use crate::{extract_fixed_data, RespDecode, RespEncode, RespError};
+use crate::{RespDecode, RespEncode, RespError};
+
+use super::decode_as;
 
 /// RespNull
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Hash)]
@@ -17,11 +19,7 @@ impl RespEncode for RespNull {
 impl RespDecode for RespNull {
     const PREFIX: &'static str = "_";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        extract_fixed_data(buf, "_\r\n", Self::PREFIX)?;
-        Ok(RespNull)
-    }
-    fn expect_length(_buf: &[u8]) -> Result<usize, RespError> {
-        Ok(3)
+        decode_as(buf, Self::PREFIX)
     }
 }
 
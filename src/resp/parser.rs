@@ -0,0 +1,521 @@
+use std::ops::Range;
+
+use bytes::{Bytes, BytesMut};
+
+use crate::{
+    BlobError, BulkString, RespArray, RespAttribute, RespBigNumber, RespDouble, RespError,
+    RespFrame, RespMap, RespNull, RespNullArray, RespNullBulkString, RespPush, RespSet,
+    SimpleError, SimpleString, VerbatimString,
+};
+
+use super::CRLF_LEN;
+
+/// bulk string的默认最大长度，和Redis proto-max-bulk-len的默认值一致
+pub const DEFAULT_MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+/// 聚合类型的默认最大元素个数
+pub const DEFAULT_MAX_MULTIBULK_LEN: usize = 1024 * 1024;
+/// 聚合类型的默认最大嵌套深度
+pub const DEFAULT_MAX_DEPTH: usize = 64;
+/// 一行的最大长度，和内联命令的限制一样，避免没有CRLF的数据一直缓存
+const MAX_LINE_LEN: usize = 64 * 1024;
+
+/// 可恢复的RESP解析器
+///
+/// 数据不完整时记录已经解析的进度，下次调用从上次停下的位置继续，不会重复扫描已经解析过的数据。
+/// 一个完整的frame解析完成之前不会消费buf，完成之后整体从buf中切出，
+/// bulk string的数据直接引用切出的Bytes，不再复制。
+/// 长度和嵌套深度超过限制时返回协议错误，不会相信对端发来的长度。
+#[derive(Debug)]
+pub struct RespParser {
+    /// 当前frame已经解析到的位置
+    pos: usize,
+    /// 查找CRLF时已经扫描过的位置
+    scanned: usize,
+    /// 已经读到长度、等待数据的bulk类型
+    blob: Option<Blob>,
+    /// 还没有收齐元素的聚合类型
+    stack: Vec<Pending>,
+    /// bulk类型的最大长度
+    max_bulk_len: usize,
+    /// 聚合类型的最大元素个数
+    max_multibulk_len: usize,
+    /// 聚合类型的最大嵌套深度
+    max_depth: usize,
+}
+
+impl Default for RespParser {
+    fn default() -> Self {
+        Self {
+            pos: 0,
+            scanned: 0,
+            blob: None,
+            stack: Vec::new(),
+            max_bulk_len: DEFAULT_MAX_BULK_LEN,
+            max_multibulk_len: DEFAULT_MAX_MULTIBULK_LEN,
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+}
+
+/// 带长度的二进制类型
+#[derive(Debug, Clone, Copy)]
+struct Blob {
+    prefix: u8,
+    start: usize,
+    len: usize,
+}
+
+/// 还没有收齐元素的聚合类型
+#[derive(Debug)]
+struct Pending {
+    kind: Aggregate,
+    remaining: usize,
+    items: Vec<Node>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Aggregate {
+    Array,
+    Set,
+    Push,
+    Map,
+    Attribute,
+}
+
+/// 解析过程中的节点，bulk string在整个frame完成之前只记录数据的位置
+#[derive(Debug)]
+enum Node {
+    Frame(RespFrame),
+    Bulk(Range<usize>),
+    Aggregate(Aggregate, Vec<Node>),
+}
+
+impl RespParser {
+    /// 设置bulk类型的最大长度，下一个长度头开始生效
+    pub fn set_max_bulk_len(&mut self, len: usize) {
+        self.max_bulk_len = len;
+    }
+
+    /// 设置聚合类型的最大元素个数
+    pub fn set_max_multibulk_len(&mut self, len: usize) {
+        self.max_multibulk_len = len;
+    }
+
+    /// 设置聚合类型的最大嵌套深度
+    pub fn set_max_depth(&mut self, depth: usize) {
+        self.max_depth = depth;
+    }
+
+    /// 是否正在解析一个frame
+    pub fn is_idle(&self) -> bool {
+        self.pos == 0 && self.blob.is_none() && self.stack.is_empty()
+    }
+
+    /// 从buf中解析一个frame，数据不完整时返回None并保留进度
+    /// 解析失败之后进度被重置，buf中的数据已经不可信，调用者应该关闭连接
+    pub fn parse(&mut self, buf: &mut BytesMut) -> Result<Option<RespFrame>, RespError> {
+        match self.parse_node(buf) {
+            Ok(Some(node)) => {
+                let data = buf.split_to(self.pos).freeze();
+                self.reset();
+                node.into_frame(&data).map(Some)
+            }
+            Ok(None) => Ok(None),
+            Err(e) => {
+                self.reset();
+                Err(e)
+            }
+        }
+    }
+
+    /// 清空解析进度，保留限制
+    fn reset(&mut self) {
+        self.pos = 0;
+        self.scanned = 0;
+        self.blob = None;
+        self.stack.clear();
+    }
+
+    fn parse_node(&mut self, buf: &[u8]) -> Result<Option<Node>, RespError> {
+        loop {
+            let node = match self.blob {
+                Some(blob) => match self.parse_blob(buf, blob)? {
+                    Some(node) => node,
+                    None => return Ok(None),
+                },
+                None => match self.parse_line(buf)? {
+                    Some(Some(node)) => node,
+                    // 聚合类型的头部，继续解析元素
+                    Some(None) => continue,
+                    None => return Ok(None),
+                },
+            };
+            if let Some(node) = self.complete(node) {
+                return Ok(Some(node));
+            }
+        }
+    }
+
+    /// 解析一行，返回None表示数据不完整，Some(None)表示开始了一个聚合类型或者bulk类型
+    fn parse_line(&mut self, buf: &[u8]) -> Result<Option<Option<Node>>, RespError> {
+        let end = self.find_crlf(buf);
+        if end.unwrap_or(buf.len()) - self.pos > MAX_LINE_LEN {
+            let msg = match buf[self.pos] {
+                b'*' | b'~' | b'>' | b'%' | b'|' => "too big mbulk count string",
+                b'$' | b'=' | b'!' => "too big bulk count string",
+                _ => "too big line",
+            };
+            return Err(RespError::Protocol(msg.to_string()));
+        }
+        let Some(end) = end else {
+            return Ok(None);
+        };
+        // 空行没有类型前缀
+        if end == self.pos {
+            return Err(RespError::Protocol("unexpected empty line".to_string()));
+        }
+        let prefix = buf[self.pos];
+        let line = &buf[self.pos + 1..end];
+        self.pos = end + CRLF_LEN;
+        self.scanned = self.pos;
+
+        let frame: RespFrame = match prefix {
+            b'+' => SimpleString::new(String::from_utf8_lossy(line)).into(),
+            b'-' => SimpleError::new(String::from_utf8_lossy(line)).into(),
+            b':' => RespFrame::Integer(parse_str(line)?.parse()?),
+            b',' => RespDouble::new(parse_str(line)?.parse()?).into(),
+            b'#' => match line {
+                b"t" => RespFrame::Boolean(true),
+                b"f" => RespFrame::Boolean(false),
+                _ => return Err(invalid_line(prefix, line)),
+            },
+            b'_' if line.is_empty() => RespNull.into(),
+            b'(' => {
+                let s = parse_str(line)?;
+                let digits = s.strip_prefix(['+', '-']).unwrap_or(s);
+                if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(RespError::InvalidFrame(format!("Invalid Big Number:{}", s)));
+                }
+                RespBigNumber::new(s).into()
+            }
+            b'$' | b'=' | b'!' => match parse_len(line, "bulk")? {
+                None if prefix == b'$' => RespNullBulkString.into(),
+                None => return Err(invalid_line(prefix, line)),
+                Some(len) if len > self.max_bulk_len => {
+                    return Err(RespError::Protocol("invalid bulk length".to_string()))
+                }
+                Some(len) => {
+                    self.blob = Some(Blob {
+                        prefix,
+                        start: self.pos,
+                        len,
+                    });
+                    return Ok(Some(None));
+                }
+            },
+            b'*' | b'~' | b'>' | b'%' | b'|' => {
+                let (kind, per_entry) = match prefix {
+                    b'*' => (Aggregate::Array, 1),
+                    b'~' => (Aggregate::Set, 1),
+                    b'>' => (Aggregate::Push, 1),
+                    b'%' => (Aggregate::Map, 2),
+                    _ => (Aggregate::Attribute, 2),
+                };
+                let len = match parse_len(line, "multibulk")? {
+                    Some(len) => len,
+                    None if prefix == b'*' => {
+                        return Ok(Some(Some(Node::Frame(RespNullArray.into()))))
+                    }
+                    None => return Err(invalid_line(prefix, line)),
+                };
+                if len > self.max_multibulk_len {
+                    return Err(RespError::Protocol("invalid multibulk length".to_string()));
+                }
+                // 属性之后还有实际的回复
+                let remaining = len * per_entry + matches!(kind, Aggregate::Attribute) as usize;
+                if remaining == 0 {
+                    return Ok(Some(Some(Node::Aggregate(kind, Vec::new()))));
+                }
+                if self.stack.len() >= self.max_depth {
+                    return Err(RespError::Protocol("nesting too deep".to_string()));
+                }
+                self.stack.push(Pending {
+                    kind,
+                    remaining,
+                    items: Vec::with_capacity(remaining.min(1024)),
+                });
+                return Ok(Some(None));
+            }
+            _ => return Err(invalid_line(prefix, line)),
+        };
+        Ok(Some(Some(Node::Frame(frame))))
+    }
+
+    /// 解析bulk类型的数据部分
+    fn parse_blob(&mut self, buf: &[u8], blob: Blob) -> Result<Option<Node>, RespError> {
+        let end = blob.start + blob.len;
+        if buf.len() < end + CRLF_LEN {
+            return Ok(None);
+        }
+        if &buf[end..end + CRLF_LEN] != b"\r\n" {
+            return Err(RespError::InvalidFrame(format!(
+                "expect CRLF after {} bytes of data",
+                blob.len
+            )));
+        }
+        self.blob = None;
+        self.pos = end + CRLF_LEN;
+        self.scanned = self.pos;
+
+        let data = &buf[blob.start..end];
+        let node = match blob.prefix {
+            b'$' => Node::Bulk(blob.start..end),
+            b'!' => Node::Frame(BlobError::new(data).into()),
+            _ => {
+                // 前三个字节是编码格式，之后是冒号
+                let format = data
+                    .get(..4)
+                    .filter(|f| f[3] == b':')
+                    .map(|f| [f[0], f[1], f[2]])
+                    .ok_or_else(|| {
+                        RespError::InvalidFrame(format!(
+                            "Invalid Verbatim String:{}",
+                            String::from_utf8_lossy(data)
+                        ))
+                    })?;
+                Node::Frame(VerbatimString::new(&format, &data[4..]).into())
+            }
+        };
+        Ok(Some(node))
+    }
+
+    /// 把完成的节点放进上一层的聚合类型中，最外层完成时返回
+    fn complete(&mut self, mut node: Node) -> Option<Node> {
+        loop {
+            let Some(pending) = self.stack.last_mut() else {
+                return Some(node);
+            };
+            pending.items.push(node);
+            pending.remaining -= 1;
+            if pending.remaining > 0 {
+                return None;
+            }
+            let pending = self.stack.pop()?;
+            node = Node::Aggregate(pending.kind, pending.items);
+        }
+    }
+
+    /// 从上次扫描的位置开始查找CRLF
+    fn find_crlf(&mut self, buf: &[u8]) -> Option<usize> {
+        let start = self.scanned.max(self.pos);
+        match buf[start..].windows(CRLF_LEN).position(|w| w == b"\r\n") {
+            Some(i) => Some(start + i),
+            None => {
+                // 最后一个字节可能是\r，下次从它开始找
+                self.scanned = buf.len().saturating_sub(1).max(self.pos);
+                None
+            }
+        }
+    }
+}
+
+impl Node {
+    fn into_frame(self, data: &Bytes) -> Result<RespFrame, RespError> {
+        let frame = match self {
+            Node::Frame(frame) => frame,
+            Node::Bulk(range) => BulkString(data.slice(range)).into(),
+            Node::Aggregate(kind, items) => {
+                let mut items = items
+                    .into_iter()
+                    .map(|node| node.into_frame(data))
+                    .collect::<Result<Vec<_>, _>>()?;
+                match kind {
+                    Aggregate::Array => RespArray::new(items).into(),
+                    Aggregate::Set => RespSet::new(items).into(),
+                    Aggregate::Push => RespPush::new(items).into(),
+                    Aggregate::Map => into_map(items)?.into(),
+                    Aggregate::Attribute => {
+                        let reply = items.pop().unwrap_or(RespNull.into());
+                        RespAttribute::new(into_map(items)?, reply).into()
+                    }
+                }
+            }
+        };
+        Ok(frame)
+    }
+}
+
+/// key value交替的元素转换为Map，Redis返回的Key可能是simple string也可能是bulk string
+fn into_map(items: Vec<RespFrame>) -> Result<RespMap, RespError> {
+    let mut map = RespMap::default();
+    let mut iter = items.into_iter();
+    while let (Some(key), Some(value)) = (iter.next(), iter.next()) {
+        let key = match key {
+            RespFrame::SimpleString(s) => s.0,
+            RespFrame::BulkString(s) => String::from_utf8_lossy(&s).into_owned(),
+            key => {
+                return Err(RespError::InvalidFrame(format!(
+                    "Invalid Map Key:{:?}",
+                    key
+                )))
+            }
+        };
+        map.insert(key, value);
+    }
+    Ok(map)
+}
+
+fn parse_str(line: &[u8]) -> Result<&str, RespError> {
+    std::str::from_utf8(line).map_err(|_| {
+        RespError::InvalidFrame(format!("Invalid Line:{}", String::from_utf8_lossy(line)))
+    })
+}
+
+/// 解析长度，-1表示null，kind用于错误信息
+fn parse_len(line: &[u8], kind: &str) -> Result<Option<usize>, RespError> {
+    match line {
+        b"-1" => Ok(None),
+        _ => std::str::from_utf8(line)
+            .ok()
+            .filter(|s| !s.starts_with('+'))
+            .and_then(|s| s.parse().ok())
+            .map(Some)
+            .ok_or_else(|| RespError::Protocol(format!("invalid {} length", kind))),
+    }
+}
+
+fn invalid_line(prefix: u8, line: &[u8]) -> RespError {
+    RespError::InvalidFrameType(format!(
+        "unknown frame: {}{}",
+        prefix as char,
+        String::from_utf8_lossy(line)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_parse_resumable() -> Result<()> {
+        let data = b"*3\r\n$3\r\nset\r\n$3\r\nkey\r\n%1\r\n+a\r\n$5\r\nhello\r\n+OK\r\n";
+        let (command, rest) = data.split_at(data.len() - 5);
+        let mut parser = RespParser::default();
+        let mut buf = BytesMut::new();
+        // 一个字节一个字节的喂给解析器
+        let mut frame = None;
+        for b in command {
+            assert!(frame.is_none());
+            buf.extend_from_slice(&[*b]);
+            frame = parser.parse(&mut buf)?;
+        }
+
+        let mut map = RespMap::default();
+        map.insert("a".to_string(), BulkString::new("hello").into());
+        let expected: RespFrame = RespArray::new(vec![
+            BulkString::new("set").into(),
+            BulkString::new("key").into(),
+            map.into(),
+        ])
+        .into();
+        assert_eq!(frame, Some(expected));
+        assert!(parser.is_idle());
+        assert!(buf.is_empty());
+
+        buf.extend_from_slice(&rest[..4]);
+        assert_eq!(parser.parse(&mut buf)?, None);
+        buf.extend_from_slice(&rest[4..]);
+        assert_eq!(
+            parser.parse(&mut buf)?,
+            Some(SimpleString::new("OK").into())
+        );
+        assert!(buf.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_zero_copy() -> Result<()> {
+        let mut buf = BytesMut::from("*2\r\n$3\r\nget\r\n$5\r\nhello\r\n");
+        let start = buf.as_ptr() as usize;
+        let Some(RespFrame::Array(arr)) = RespParser::default().parse(&mut buf)? else {
+            panic!("expect array");
+        };
+        // bulk string直接引用原来的内存
+        let RespFrame::BulkString(key) = &arr[1] else {
+            panic!("expect bulk string");
+        };
+        assert_eq!(key.0.as_ptr() as usize, start + 17);
+        assert_eq!(&key[..], b"hello");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let cases: &[&[u8]] = &[
+            b"?\r\n",
+            b"#x\r\n",
+            b"$-2\r\n",
+            b"$3\r\nabcd\r\n",
+            b":abc\r\n",
+            b"%1\r\n:1\r\n:2\r\n",
+            b"\r\n",
+            b"*1\r\n\r\nfoo",
+            b"*2\r\n:1\r\n\r\n",
+        ];
+        for case in cases {
+            let mut parser = RespParser::default();
+            let mut buf = BytesMut::from(&case[..]);
+            assert!(parser.parse(&mut buf).is_err(), "{:?}", case);
+            assert!(parser.is_idle());
+        }
+    }
+
+    #[test]
+    fn test_parse_limits() -> Result<()> {
+        let mut parser = RespParser::default();
+        parser.set_max_bulk_len(5);
+        parser.set_max_multibulk_len(2);
+        parser.set_max_depth(2);
+
+        let cases: [(&[u8], &str); 5] = [
+            (b"$6\r\n", "invalid bulk length"),
+            (b"$99999999999999999999999\r\n", "invalid bulk length"),
+            (b"*3\r\n", "invalid multibulk length"),
+            (b"%3\r\n", "invalid multibulk length"),
+            (b"*1\r\n*1\r\n*1\r\n", "nesting too deep"),
+        ];
+        for (data, msg) in cases {
+            let mut buf = BytesMut::from(data);
+            assert_eq!(
+                parser.parse(&mut buf).unwrap_err(),
+                RespError::Protocol(msg.to_string())
+            );
+            assert!(parser.is_idle());
+        }
+
+        // 没有CRLF的行分多次到达，超过长度限制之后返回错误
+        let chunk = vec![b'1'; 16 * 1024];
+        for (prefix, msg) in [
+            (b'*', "too big mbulk count string"),
+            (b'$', "too big bulk count string"),
+            (b'+', "too big line"),
+        ] {
+            let mut buf = BytesMut::from(&[prefix][..]);
+            let mut ret = parser.parse(&mut buf);
+            while let Ok(None) = ret {
+                assert!(buf.len() <= MAX_LINE_LEN + chunk.len());
+                buf.extend_from_slice(&chunk);
+                ret = parser.parse(&mut buf);
+            }
+            assert_eq!(ret.unwrap_err(), RespError::Protocol(msg.to_string()));
+            assert!(parser.is_idle());
+        }
+
+        // 出错之后限制仍然生效
+        let mut buf = BytesMut::from("*2\r\n*1\r\n$5\r\nhello\r\n$0\r\n\r\n");
+        assert!(parser.parse(&mut buf)?.is_some());
+        assert!(parser.parse(&mut BytesMut::from("$6\r\n")).is_err());
+        Ok(())
+    }
+}
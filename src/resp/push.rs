@@ -0,0 +1,78 @@
+use std::ops::Deref;
+
+use bytes::BytesMut;
+
+use crate::{RespDecode, RespEncode, RespError, RespFrame};
+
+use super::{decode_as, RESP_ARRAY_CAP};
+
+/// RespPush，服务端主动推送给客户端的数据
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Hash)]
+pub struct RespPush(pub(crate) Vec<RespFrame>);
+
+impl RespPush {
+    pub fn new(s: impl Into<Vec<RespFrame>>) -> Self {
+        RespPush(s.into())
+    }
+}
+
+/// - push:"><number-of-elements>\r\n<element-1>...<element-n>"
+impl RespEncode for RespPush {
+    fn encode(self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(RESP_ARRAY_CAP);
+        buf.extend_from_slice(&format!(">{}\r\n", self.len()).into_bytes());
+        for item in self.0 {
+            buf.extend_from_slice(&item.encode());
+        }
+        buf
+    }
+}
+
+/// - push:"><number-of-elements>\r\n<element-1>...<element-n>"
+impl RespDecode for RespPush {
+    const PREFIX: &'static str = ">";
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        decode_as(buf, Self::PREFIX)
+    }
+}
+
+impl Deref for RespPush {
+    type Target = Vec<RespFrame>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::BulkString;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    use super::*;
+
+    #[test]
+    fn test_push() -> Result<()> {
+        // SUBSCRIBE之后收到的消息
+        let capture = b">3\r\n$7\r\nmessage\r\n$7\r\nchannel\r\n$5\r\nhello\r\n";
+        let mut buf = BytesMut::from(&capture[..]);
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(
+            frame,
+            RespPush::new(vec![
+                BulkString::new("message").into(),
+                BulkString::new("channel").into(),
+                BulkString::new("hello").into(),
+            ])
+            .into()
+        );
+        assert_eq!(frame.encode(), capture);
+
+        // 数据不完整
+        let mut buf = BytesMut::from(">3\r\n$7\r\nmessage\r\n$7\r\nchannel\r\n");
+        let frame = RespPush::decode(&mut buf);
+        assert_eq!(frame.unwrap_err(), RespError::NotComplete);
+        Ok(())
+    }
+}
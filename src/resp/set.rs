@@ -1,10 +1,10 @@
 use std::ops::{Deref, DerefMut};
 
-use bytes::{Buf, BytesMut};
+use bytes::BytesMut;
 
 use crate::{RespDecode, RespEncode, RespError, RespFrame};
 
-use super::{calc_total_length, parse_length, CRLF_LEN, RESP_ARRAY_CAP};
+use super::{decode_as, RESP_ARRAY_CAP};
 
 /// RespSet
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Default, Hash)]
@@ -42,25 +42,7 @@ impl RespEncode for RespSet {
 impl RespDecode for RespSet {
     const PREFIX: &'static str = "~";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        // 获取长度
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
-        let total_len = calc_total_length(buf, end, len, Self::PREFIX)?;
-        if buf.len() < total_len {
-            return Err(RespError::NotComplete);
-        }
-        buf.advance(end + CRLF_LEN);
-
-        let mut set = RespSet::default();
-        for _ in 0..len {
-            let frame = RespFrame::decode(buf)?;
-            set.push(frame);
-        }
-        Ok(set)
-    }
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let (end, len) = parse_length(buf, Self::PREFIX)?;
-        let total = calc_total_length(buf, end, len, Self::PREFIX)?;
-        Ok(total)
+        decode_as(buf, Self::PREFIX)
     }
 }
 
@@ -2,9 +2,9 @@ use std::ops::Deref;
 
 use bytes::BytesMut;
 
-use crate::{extract_simple_frame_data, RespDecode, RespEncode, RespError};
+use crate::{RespDecode, RespEncode, RespError};
 
-use super::CRLF_LEN;
+use super::decode_as;
 
 /// SimpleString
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Hash)]
@@ -26,24 +26,8 @@ impl RespEncode for SimpleString {
 // - simple string:"+OK\r\n"
 impl RespDecode for SimpleString {
     const PREFIX: &'static str = "+";
-
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        // 获取第一个CRLF
-        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
-
-        // 获取数据
-        let data = buf.split_to(end + CRLF_LEN);
-
-        let s = String::from_utf8_lossy(&data[Self::PREFIX.len()..end]);
-
-        Ok(SimpleString::new(s))
-
-        // 获取第一个\r\n的位置
-    }
-
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
-        Ok(end + CRLF_LEN)
+        decode_as(buf, Self::PREFIX)
     }
 }
 
@@ -2,9 +2,9 @@ use std::ops::Deref;
 
 use bytes::BytesMut;
 
-use crate::{extract_simple_frame_data, RespDecode, RespEncode, RespError};
+use crate::{RespDecode, RespEncode, RespError};
 
-use super::CRLF_LEN;
+use super::decode_as;
 
 /// SimpleError
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Hash)]
@@ -27,18 +27,7 @@ impl RespEncode for SimpleError {
 impl RespDecode for SimpleError {
     const PREFIX: &'static str = "-";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        // 获取第一个CRLF
-        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
-
-        let data = buf.split_to(end + CRLF_LEN);
-
-        let s = String::from_utf8_lossy(&data[Self::PREFIX.len()..end]);
-
-        Ok(SimpleError::new(s))
-    }
-    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let end = extract_simple_frame_data(buf, Self::PREFIX)?;
-        Ok(end + CRLF_LEN)
+        decode_as(buf, Self::PREFIX)
     }
 }
 
@@ -0,0 +1,88 @@
+use bytes::BytesMut;
+
+use crate::{RespDecode, RespEncode, RespError};
+
+use super::decode_as;
+
+/// 编码格式的长度，例如 txt / mkd
+const FORMAT_LEN: usize = 3;
+
+/// VerbatimString，带有编码格式的字符串
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Hash)]
+pub struct VerbatimString {
+    pub(crate) format: [u8; FORMAT_LEN],
+    pub(crate) data: Vec<u8>,
+}
+
+impl VerbatimString {
+    pub fn new(format: &[u8; FORMAT_LEN], data: impl Into<Vec<u8>>) -> Self {
+        VerbatimString {
+            format: *format,
+            data: data.into(),
+        }
+    }
+
+    /// 纯文本
+    pub fn text(data: impl Into<Vec<u8>>) -> Self {
+        Self::new(b"txt", data)
+    }
+
+    /// 编码格式
+    pub fn format(&self) -> &[u8] {
+        &self.format
+    }
+
+    /// 字符串内容
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// - verbatim string:"=<length>\r\n<format>:<data>\r\n"
+impl RespEncode for VerbatimString {
+    fn encode(self) -> Vec<u8> {
+        let len = FORMAT_LEN + 1 + self.data.len();
+        let mut buf = Vec::with_capacity(len + 16);
+        buf.extend_from_slice(&format!("={}\r\n", len).into_bytes());
+        buf.extend_from_slice(&self.format);
+        buf.push(b':');
+        buf.extend_from_slice(&self.data);
+        buf.extend_from_slice(b"\r\n");
+        buf
+    }
+}
+
+/// - verbatim string:"=<length>\r\n<format>:<data>\r\n"
+impl RespDecode for VerbatimString {
+    const PREFIX: &'static str = "=";
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        decode_as(buf, Self::PREFIX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RespFrame;
+    use anyhow::Result;
+    use bytes::BytesMut;
+
+    use super::*;
+
+    #[test]
+    fn test_verbatim_string() -> Result<()> {
+        let capture = b"=15\r\ntxt:Some string\r\n";
+        let mut buf = BytesMut::from(&capture[..]);
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(frame, VerbatimString::text("Some string").into());
+        assert_eq!(frame.encode(), capture);
+
+        // 缺少编码格式
+        let mut buf = BytesMut::from("=4\r\ntext\r\n");
+        let frame = VerbatimString::decode(&mut buf);
+        assert_eq!(
+            frame.unwrap_err(),
+            RespError::InvalidFrame("Invalid Verbatim String:text".to_string())
+        );
+        Ok(())
+    }
+}
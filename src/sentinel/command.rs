@@ -0,0 +1,309 @@
+use crate::{
+    cmd::{CommandError, ErrorCode},
+    now_ms, BulkString, RespArray, RespFrame, RespNull, SimpleString,
+};
+
+use super::{
+    instance::{Hello, Instance, MonitoredMaster},
+    Sentinel,
+};
+
+/// sentinel支持的命令
+#[derive(Debug, PartialEq)]
+pub enum SentinelCommand {
+    Ping,
+    Info,
+    Role,
+    /// SENTINEL get-master-addr-by-name <name>
+    GetMasterAddrByName(String),
+    /// SENTINEL masters
+    Masters,
+    /// SENTINEL master <name>
+    Master(String),
+    /// SENTINEL replicas <name>，SENTINEL slaves 是同样的命令
+    Replicas(String),
+    /// SENTINEL sentinels <name>
+    Sentinels(String),
+    /// SENTINEL is-master-down-by-addr <ip> <port> <current-epoch> <runid>
+    IsMasterDownByAddr {
+        ip: String,
+        port: u16,
+        epoch: u64,
+        runid: String,
+    },
+    /// SENTINEL hello <ip> <port> <runid> <current-epoch> <master-name> <master-ip> <master-port> <config-epoch>
+    Hello(Vec<String>),
+    /// SENTINEL failover <name>
+    Failover(String),
+    /// SENTINEL myid
+    MyId,
+}
+
+/// 实例的状态，SENTINEL MASTERS/REPLICAS 中使用
+fn instance_fields(inst: &Instance, flags: String, now: i64) -> Vec<(&'static str, String)> {
+    vec![
+        ("name", inst.addr()),
+        ("ip", inst.ip.clone()),
+        ("port", inst.port.to_string()),
+        ("flags", flags),
+        ("last-ok-ping-reply", (now - inst.last_ok_ping).to_string()),
+        ("info-refresh", (now - inst.info_time).to_string()),
+        (
+            "role-reported",
+            match &inst.info {
+                Some(info) if !info.is_master => "slave",
+                _ => "master",
+            }
+            .to_string(),
+        ),
+    ]
+}
+
+fn master_fields(master: &MonitoredMaster, now: i64) -> Vec<(&'static str, String)> {
+    let mut fields = instance_fields(&master.master, master.flags(), now);
+    fields[0].1 = master.name.clone();
+    fields.extend([
+        ("down-after-milliseconds", master.down_after_ms.to_string()),
+        ("config-epoch", master.config_epoch.to_string()),
+        ("num-slaves", master.replicas.len().to_string()),
+        ("num-other-sentinels", master.sentinels.len().to_string()),
+        ("quorum", master.quorum.to_string()),
+        ("failover-timeout", master.failover_timeout_ms.to_string()),
+    ]);
+    fields
+}
+
+fn replica_fields(replica: &Instance, now: i64) -> Vec<(&'static str, String)> {
+    let mut flags = "slave".to_string();
+    if replica.sdown_since.is_some() {
+        flags.push_str(",s_down");
+    }
+    let mut fields = instance_fields(replica, flags, now);
+    if let Some(info) = &replica.info {
+        fields.extend([
+            (
+                "master-link-status",
+                if info.link_up { "ok" } else { "err" }.to_string(),
+            ),
+            ("master-host", info.master_host.clone().unwrap_or_default()),
+            (
+                "master-port",
+                info.master_port.unwrap_or_default().to_string(),
+            ),
+            ("slave-priority", info.priority.to_string()),
+            ("slave-repl-offset", info.offset.to_string()),
+        ]);
+    }
+    fields
+}
+
+/// 以 key1 value1 key2 value2 ... 的形式返回
+fn fields_frame(fields: Vec<(&'static str, String)>) -> RespFrame {
+    RespArray::new(
+        fields
+            .into_iter()
+            .flat_map(|(k, v)| [BulkString::new(k).into(), BulkString::new(v).into()])
+            .collect::<Vec<RespFrame>>(),
+    )
+    .into()
+}
+
+fn no_such_master() -> RespFrame {
+    ErrorCode::Err.reply("No such master with that name")
+}
+
+impl SentinelCommand {
+    pub fn execute(self, sentinel: &Sentinel) -> RespFrame {
+        let now = now_ms();
+        let mut inner = sentinel.0.inner.lock().unwrap();
+        match self {
+            SentinelCommand::Ping => SimpleString::new("PONG").into(),
+            SentinelCommand::Info => {
+                let mut lines = vec![
+                    "# Sentinel".to_string(),
+                    format!("sentinel_masters:{}", inner.masters.len()),
+                    "sentinel_tilt:0".to_string(),
+                    "sentinel_running_scripts:0".to_string(),
+                ];
+                for (i, master) in inner.masters.values().enumerate() {
+                    let status = if master.odown_since.is_some() {
+                        "odown"
+                    } else if master.master.sdown_since.is_some() {
+                        "sdown"
+                    } else {
+                        "ok"
+                    };
+                    lines.push(format!(
+                        "master{}:name={},status={},address={},slaves={},sentinels={}",
+                        i,
+                        master.name,
+                        status,
+                        master.master.addr(),
+                        master.replicas.len(),
+                        master.sentinels.len() + 1
+                    ));
+                }
+                BulkString::new(lines.join("\r\n") + "\r\n").into()
+            }
+            SentinelCommand::Role => RespArray::new(vec![
+                BulkString::new("sentinel").into(),
+                RespArray::new(
+                    inner
+                        .masters
+                        .keys()
+                        .map(|name| BulkString::new(name.as_str()).into())
+                        .collect::<Vec<RespFrame>>(),
+                )
+                .into(),
+            ])
+            .into(),
+            SentinelCommand::GetMasterAddrByName(name) => match inner.masters.get(&name) {
+                Some(m) => RespArray::new(vec![
+                    BulkString::new(m.master.ip.as_str()).into(),
+                    BulkString::new(m.master.port.to_string()).into(),
+                ])
+                .into(),
+                None => RespNull.into(),
+            },
+            SentinelCommand::Masters => RespArray::new(
+                inner
+                    .masters
+                    .values()
+                    .map(|m| fields_frame(master_fields(m, now)))
+                    .collect::<Vec<RespFrame>>(),
+            )
+            .into(),
+            SentinelCommand::Master(name) => match inner.masters.get(&name) {
+                Some(m) => fields_frame(master_fields(m, now)),
+                None => no_such_master(),
+            },
+            SentinelCommand::Replicas(name) => match inner.masters.get(&name) {
+                Some(m) => RespArray::new(
+                    m.replicas
+                        .values()
+                        .map(|r| fields_frame(replica_fields(r, now)))
+                        .collect::<Vec<RespFrame>>(),
+                )
+                .into(),
+                None => no_such_master(),
+            },
+            SentinelCommand::Sentinels(name) => match inner.masters.get(&name) {
+                Some(m) => RespArray::new(
+                    m.sentinels
+                        .values()
+                        .map(|s| {
+                            fields_frame(vec![
+                                ("name", s.runid.clone()),
+                                ("ip", s.ip.clone()),
+                                ("port", s.port.to_string()),
+                                ("runid", s.runid.clone()),
+                                ("flags", "sentinel".to_string()),
+                                ("last-hello-message", (now - s.last_hello_ok).to_string()),
+                            ])
+                        })
+                        .collect::<Vec<RespFrame>>(),
+                )
+                .into(),
+                None => no_such_master(),
+            },
+            SentinelCommand::IsMasterDownByAddr {
+                ip,
+                port,
+                epoch,
+                runid,
+            } => {
+                let (down, leader, leader_epoch) =
+                    inner.is_master_down_by_addr(&ip, port, epoch, &runid, now);
+                RespArray::new(vec![
+                    RespFrame::Integer(down as i64),
+                    BulkString::new(leader.unwrap_or_else(|| "*".to_string())).into(),
+                    RespFrame::Integer(leader_epoch as i64),
+                ])
+                .into()
+            }
+            SentinelCommand::Hello(args) => match Hello::from_args(&args) {
+                Some(hello) => {
+                    if inner.handle_hello(hello, now) {
+                        SimpleString::new("OK").into()
+                    } else {
+                        no_such_master()
+                    }
+                }
+                None => ErrorCode::Err.reply("Invalid hello message"),
+            },
+            SentinelCommand::Failover(name) => match inner.force_failover(&name, now) {
+                Ok(()) => SimpleString::new("OK").into(),
+                Err(e) => e,
+            },
+            SentinelCommand::MyId => BulkString::new(inner.myid.as_str()).into(),
+        }
+    }
+}
+
+impl TryFrom<RespArray> for SentinelCommand {
+    type Error = CommandError;
+
+    fn try_from(value: RespArray) -> Result<Self, Self::Error> {
+        let args = value
+            .0
+            .into_iter()
+            .map(|frame| match frame {
+                RespFrame::BulkString(s) => Ok(String::from_utf8(s.into_vec())?),
+                _ => Err(CommandError::InvalidArgument(
+                    "argument must be a bulk string".to_string(),
+                )),
+            })
+            .collect::<Result<Vec<String>, CommandError>>()?;
+        let Some(cmd) = args.first() else {
+            return Err(CommandError::InvalidCommand("empty command".to_string()));
+        };
+        let cmd = cmd.to_ascii_lowercase();
+        let wrong_args =
+            || CommandError::WrongArity(args.iter().take(2).cloned().collect::<Vec<_>>().join("|"));
+        match (cmd.as_str(), args.len()) {
+            ("ping", _) => return Ok(SentinelCommand::Ping),
+            ("info", _) => return Ok(SentinelCommand::Info),
+            ("role", 1) => return Ok(SentinelCommand::Role),
+            ("sentinel", n) if n >= 2 => {}
+            ("role" | "sentinel", _) => return Err(wrong_args()),
+            _ => {
+                return Err(CommandError::UnknownCommand {
+                    name: args[0].clone(),
+                    args: args[1..].iter().map(|arg| format!("'{}' ", arg)).collect(),
+                })
+            }
+        }
+
+        let name = || args.get(2).cloned().ok_or_else(wrong_args);
+        let cmd = match args[1].to_ascii_lowercase().as_str() {
+            "get-master-addr-by-name" => SentinelCommand::GetMasterAddrByName(name()?),
+            "masters" => SentinelCommand::Masters,
+            "master" => SentinelCommand::Master(name()?),
+            "replicas" | "slaves" => SentinelCommand::Replicas(name()?),
+            "sentinels" => SentinelCommand::Sentinels(name()?),
+            "failover" => SentinelCommand::Failover(name()?),
+            "myid" => SentinelCommand::MyId,
+            "hello" => SentinelCommand::Hello(args[2..].to_vec()),
+            "is-master-down-by-addr" => {
+                let [_, _, ip, port, epoch, runid] = args.as_slice() else {
+                    return Err(wrong_args());
+                };
+                let invalid =
+                    |_| CommandError::InvalidArgument("invalid port or epoch".to_string());
+                SentinelCommand::IsMasterDownByAddr {
+                    ip: ip.clone(),
+                    port: port.parse().map_err(invalid)?,
+                    epoch: epoch.parse().map_err(invalid)?,
+                    runid: runid.clone(),
+                }
+            }
+            sub => {
+                return Err(CommandError::UnknownSubcommand {
+                    cmd: "SENTINEL".to_string(),
+                    sub: sub.to_string(),
+                })
+            }
+        };
+        Ok(cmd)
+    }
+}
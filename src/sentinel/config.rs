@@ -0,0 +1,227 @@
+use std::fmt;
+
+use super::SentinelError;
+
+/// 默认的sentinel端口
+pub const DEFAULT_SENTINEL_PORT: u16 = 26379;
+/// 默认的主观下线时间(毫秒)
+const DEFAULT_DOWN_AFTER_MS: u64 = 30_000;
+/// 默认的故障转移超时时间(毫秒)
+const DEFAULT_FAILOVER_TIMEOUT_MS: u64 = 180_000;
+
+/// sentinel.conf 的内容
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentinelConfig {
+    pub bind: String,
+    pub port: u16,
+    /// 40位十六进制的sentinel ID，没有配置时自动生成
+    pub myid: String,
+    /// 告诉其他sentinel的地址，默认为监听地址
+    pub announce_ip: Option<String>,
+    pub announce_port: Option<u16>,
+    pub current_epoch: u64,
+    pub masters: Vec<MasterConfig>,
+}
+
+/// 一个被监控的master
+#[derive(Debug, Clone, PartialEq)]
+pub struct MasterConfig {
+    pub name: String,
+    pub ip: String,
+    pub port: u16,
+    /// 认为master客观下线需要同意的sentinel数量
+    pub quorum: usize,
+    pub down_after_ms: u64,
+    pub failover_timeout_ms: u64,
+    pub config_epoch: u64,
+    pub known_replicas: Vec<(String, u16)>,
+    /// (ip, port, runid)
+    pub known_sentinels: Vec<(String, u16, String)>,
+}
+
+impl Default for SentinelConfig {
+    fn default() -> Self {
+        SentinelConfig {
+            bind: "0.0.0.0".to_string(),
+            port: DEFAULT_SENTINEL_PORT,
+            myid: crate::replication::gen_replid(),
+            announce_ip: None,
+            announce_port: None,
+            current_epoch: 0,
+            masters: Vec::new(),
+        }
+    }
+}
+
+impl MasterConfig {
+    pub fn new(name: impl Into<String>, ip: impl Into<String>, port: u16, quorum: usize) -> Self {
+        MasterConfig {
+            name: name.into(),
+            ip: ip.into(),
+            port,
+            quorum,
+            down_after_ms: DEFAULT_DOWN_AFTER_MS,
+            failover_timeout_ms: DEFAULT_FAILOVER_TIMEOUT_MS,
+            config_epoch: 0,
+            known_replicas: Vec::new(),
+            known_sentinels: Vec::new(),
+        }
+    }
+}
+
+/// 解析sentinel.conf，`sentinel xxx <master>` 必须出现在对应的 `sentinel monitor` 之后
+pub fn parse_sentinel_conf(content: &str) -> Result<SentinelConfig, SentinelError> {
+    let mut config = SentinelConfig::default();
+    for (lineno, line) in content.lines().enumerate() {
+        let invalid =
+            |msg: &str| SentinelError::InvalidConfig(format!("line {}: {}", lineno + 1, msg));
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let num = |i: usize| -> Result<u64, SentinelError> {
+            parts
+                .get(i)
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(|| invalid("invalid number"))
+        };
+        let arg = |i: usize| -> Result<String, SentinelError> {
+            parts
+                .get(i)
+                .map(|s| s.to_string())
+                .ok_or_else(|| invalid("wrong number of arguments"))
+        };
+
+        match parts[0].to_ascii_lowercase().as_str() {
+            "bind" => config.bind = arg(1)?,
+            "port" => config.port = num(1)? as u16,
+            "sentinel" => match arg(1)?.to_ascii_lowercase().as_str() {
+                "myid" => config.myid = arg(2)?,
+                "announce-ip" => config.announce_ip = Some(arg(2)?),
+                "announce-port" => config.announce_port = Some(num(2)? as u16),
+                "current-epoch" => config.current_epoch = num(2)?,
+                "monitor" => {
+                    let name = arg(2)?;
+                    if config.masters.iter().any(|m| m.name == name) {
+                        return Err(invalid("duplicated master name"));
+                    }
+                    let master = MasterConfig::new(name, arg(3)?, num(4)? as u16, num(5)? as usize);
+                    if master.quorum == 0 {
+                        return Err(invalid("quorum must be 1 or greater"));
+                    }
+                    config.masters.push(master);
+                }
+                option => {
+                    let name = arg(2)?;
+                    let master = config
+                        .masters
+                        .iter_mut()
+                        .find(|m| m.name == name)
+                        .ok_or_else(|| invalid("no such master with specified name"))?;
+                    match option {
+                        "down-after-milliseconds" => master.down_after_ms = num(3)?,
+                        "failover-timeout" => master.failover_timeout_ms = num(3)?,
+                        "config-epoch" => master.config_epoch = num(3)?,
+                        "known-replica" | "known-slave" => {
+                            master.known_replicas.push((arg(3)?, num(4)? as u16))
+                        }
+                        "known-sentinel" => {
+                            master
+                                .known_sentinels
+                                .push((arg(3)?, num(4)? as u16, arg(5)?))
+                        }
+                        _ => return Err(invalid("unknown sentinel option")),
+                    }
+                }
+            },
+            _ => return Err(invalid("unknown directive")),
+        }
+    }
+    Ok(config)
+}
+
+/// 生成配置文件的内容，故障转移之后重写配置文件时使用
+impl fmt::Display for SentinelConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "bind {}", self.bind)?;
+        writeln!(f, "port {}", self.port)?;
+        writeln!(f, "sentinel myid {}", self.myid)?;
+        if let Some(ip) = &self.announce_ip {
+            writeln!(f, "sentinel announce-ip {}", ip)?;
+        }
+        if let Some(port) = self.announce_port {
+            writeln!(f, "sentinel announce-port {}", port)?;
+        }
+        writeln!(f, "sentinel current-epoch {}", self.current_epoch)?;
+        for m in &self.masters {
+            writeln!(
+                f,
+                "sentinel monitor {} {} {} {}",
+                m.name, m.ip, m.port, m.quorum
+            )?;
+            writeln!(
+                f,
+                "sentinel down-after-milliseconds {} {}",
+                m.name, m.down_after_ms
+            )?;
+            writeln!(
+                f,
+                "sentinel failover-timeout {} {}",
+                m.name, m.failover_timeout_ms
+            )?;
+            writeln!(f, "sentinel config-epoch {} {}", m.name, m.config_epoch)?;
+            for (ip, port) in &m.known_replicas {
+                writeln!(f, "sentinel known-replica {} {} {}", m.name, ip, port)?;
+            }
+            for (ip, port, runid) in &m.known_sentinels {
+                writeln!(
+                    f,
+                    "sentinel known-sentinel {} {} {} {}",
+                    m.name, ip, port, runid
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    #[test]
+    fn test_parse_sentinel_conf() -> Result<()> {
+        let content = r#"
+# sentinel配置
+port 26380
+sentinel myid 0000000000000000000000000000000000000001
+sentinel monitor mymaster 127.0.0.1 6379 2
+sentinel down-after-milliseconds mymaster 5000
+sentinel known-replica mymaster 127.0.0.1 6380
+sentinel known-sentinel mymaster 127.0.0.1 26381 0000000000000000000000000000000000000002
+"#;
+        let config = parse_sentinel_conf(content)?;
+        assert_eq!(config.port, 26380);
+        assert_eq!(config.myid, format!("{:040x}", 1));
+        let master = &config.masters[0];
+        assert_eq!(master.name, "mymaster");
+        assert_eq!(
+            (master.ip.as_str(), master.port, master.quorum),
+            ("127.0.0.1", 6379, 2)
+        );
+        assert_eq!(master.down_after_ms, 5000);
+        assert_eq!(master.failover_timeout_ms, DEFAULT_FAILOVER_TIMEOUT_MS);
+        assert_eq!(master.known_replicas, vec![("127.0.0.1".to_string(), 6380)]);
+        assert_eq!(master.known_sentinels[0].2, format!("{:040x}", 2));
+
+        // 重写之后的配置文件内容不变
+        assert_eq!(parse_sentinel_conf(&config.to_string())?, config);
+
+        assert!(parse_sentinel_conf("sentinel down-after-milliseconds mymaster 10").is_err());
+        assert!(parse_sentinel_conf("sentinel monitor mymaster 127.0.0.1 6379 0").is_err());
+        assert!(parse_sentinel_conf("maxmemory 10mb").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,1329 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::{BuildHasher, RandomState},
+};
+
+use crate::{cmd::ErrorCode, now_ms, RespFrame};
+
+use super::config::{MasterConfig, SentinelConfig};
+
+/// PING被监控实例的间隔(毫秒)
+const PING_PERIOD_MS: i64 = 1000;
+/// INFO的间隔，master主观下线或者故障转移期间使用更短的间隔
+const INFO_PERIOD_MS: i64 = 10_000;
+const FAST_INFO_PERIOD_MS: i64 = 1000;
+/// 向其他sentinel发送HELLO的间隔
+const HELLO_PERIOD_MS: i64 = 2000;
+/// 询问其他sentinel是否认为master下线的间隔
+const ASK_PERIOD_MS: i64 = 1000;
+/// 其他sentinel回复的下线状态的有效时间
+const REPLY_VALIDITY_MS: i64 = 5000;
+/// 随机延迟的上限，避免多个sentinel同时发起选举
+const MAX_DESYNC_MS: u64 = 1000;
+/// 选举的最长时间
+const ELECTION_TIMEOUT_MS: i64 = 10_000;
+/// 实例报告的角色和配置不一致时，等待这么长时间之后再纠正
+const RECONF_WAIT_MS: i64 = HELLO_PERIOD_MS * 4;
+
+/// 0到MAX_DESYNC_MS之间的随机延迟
+fn desync() -> i64 {
+    (RandomState::new().hash_one(now_ms()) % MAX_DESYNC_MS) as i64
+}
+
+/// 实例通过INFO报告的复制信息
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(super) struct InstanceInfo {
+    pub is_master: bool,
+    pub master_host: Option<String>,
+    pub master_port: Option<u16>,
+    pub link_up: bool,
+    pub offset: u64,
+    pub priority: u32,
+    /// master报告的replica地址
+    pub replicas: Vec<(String, u16)>,
+}
+
+impl InstanceInfo {
+    /// 解析INFO replication的内容
+    pub fn parse(text: &str) -> Self {
+        let mut info = InstanceInfo {
+            priority: 100,
+            ..Default::default()
+        };
+        for line in text.lines() {
+            let Some((key, value)) = line.trim().split_once(':') else {
+                continue;
+            };
+            match key {
+                "role" => info.is_master = value == "master",
+                "master_host" => info.master_host = Some(value.to_string()),
+                "master_port" => info.master_port = value.parse().ok(),
+                "master_link_status" => info.link_up = value == "up",
+                "slave_repl_offset" => info.offset = value.parse().unwrap_or_default(),
+                "slave_priority" | "replica_priority" => {
+                    info.priority = value.parse().unwrap_or(100)
+                }
+                key if key.starts_with("slave") && key[5..].parse::<usize>().is_ok() => {
+                    // slave0:ip=127.0.0.1,port=6380,state=online,offset=0,lag=0
+                    let fields: HashMap<&str, &str> =
+                        value.split(',').filter_map(|f| f.split_once('=')).collect();
+                    if let (Some(ip), Some(port)) = (
+                        fields.get("ip"),
+                        fields.get("port").and_then(|p| p.parse().ok()),
+                    ) {
+                        info.replicas.push((ip.to_string(), port));
+                    }
+                }
+                _ => {}
+            }
+        }
+        info
+    }
+}
+
+/// 被监控的master或replica
+#[derive(Debug, Clone)]
+pub(super) struct Instance {
+    pub ip: String,
+    pub port: u16,
+    /// 上次收到有效PING回复的时间
+    pub last_ok_ping: i64,
+    last_ping: i64,
+    ping_pending: bool,
+    /// 最早的一个还没有收到有效回复的PING的发送时间
+    act_ping: Option<i64>,
+    last_info: i64,
+    info_pending: bool,
+    /// 上次收到INFO回复的时间
+    pub info_time: i64,
+    pub info: Option<InstanceInfo>,
+    /// 报告的角色或者master地址上次变化的时间
+    info_changed: i64,
+    /// 上次发送REPLICAOF纠正配置的时间
+    reconf_sent: i64,
+    /// 主观下线的时间
+    pub sdown_since: Option<i64>,
+}
+
+impl Instance {
+    fn new(ip: impl Into<String>, port: u16, now: i64) -> Self {
+        Instance {
+            ip: ip.into(),
+            port,
+            last_ok_ping: now,
+            last_ping: 0,
+            ping_pending: false,
+            act_ping: None,
+            last_info: 0,
+            info_pending: false,
+            info_time: 0,
+            info: None,
+            info_changed: now,
+            reconf_sent: 0,
+            sdown_since: None,
+        }
+    }
+
+    pub fn addr(&self) -> String {
+        format!("{}:{}", self.ip, self.port)
+    }
+}
+
+/// 其他监控同一个master的sentinel
+#[derive(Debug, Clone)]
+pub(super) struct Peer {
+    pub ip: String,
+    pub port: u16,
+    pub runid: String,
+    /// 上次收到HELLO回复的时间
+    pub last_hello_ok: i64,
+    last_hello: i64,
+    hello_pending: bool,
+    last_ask: i64,
+    ask_pending: bool,
+    /// 对方是否认为master已经下线
+    master_down: bool,
+    reply_time: i64,
+    /// 对方在leader_epoch投票给的leader
+    leader: Option<String>,
+    leader_epoch: u64,
+}
+
+impl Peer {
+    fn new(ip: impl Into<String>, port: u16, runid: impl Into<String>) -> Self {
+        Peer {
+            ip: ip.into(),
+            port,
+            runid: runid.into(),
+            last_hello_ok: 0,
+            last_hello: 0,
+            hello_pending: false,
+            last_ask: 0,
+            ask_pending: false,
+            master_down: false,
+            reply_time: 0,
+            leader: None,
+            leader_epoch: 0,
+        }
+    }
+}
+
+/// 故障转移的阶段
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum FailoverState {
+    /// 等待选举出leader
+    WaitStart,
+    /// 已经向选中的replica发送REPLICAOF NO ONE，等待它成为master
+    WaitPromotion(String),
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Failover {
+    pub epoch: u64,
+    pub state: FailoverState,
+    state_time: i64,
+    /// SENTINEL FAILOVER 发起的故障转移，不需要其他sentinel同意
+    forced: bool,
+}
+
+/// 一个被监控的master以及它的replica和其他sentinel
+#[derive(Debug, Clone)]
+pub(super) struct MonitoredMaster {
+    pub name: String,
+    pub quorum: usize,
+    pub down_after_ms: u64,
+    pub failover_timeout_ms: u64,
+    pub config_epoch: u64,
+    pub master: Instance,
+    /// 地址 -> replica
+    pub replicas: BTreeMap<String, Instance>,
+    /// runid -> sentinel
+    pub sentinels: BTreeMap<String, Peer>,
+    /// 客观下线的时间
+    pub odown_since: Option<i64>,
+    /// 当前节点在leader_epoch投票给的leader
+    leader: Option<String>,
+    leader_epoch: u64,
+    pub failover: Option<Failover>,
+    /// 上次尝试故障转移的时间，包含随机延迟
+    failover_start_time: i64,
+}
+
+impl MonitoredMaster {
+    fn new(config: &MasterConfig, now: i64) -> Self {
+        let replicas = config
+            .known_replicas
+            .iter()
+            .map(|(ip, port)| {
+                let replica = Instance::new(ip.clone(), *port, now);
+                (replica.addr(), replica)
+            })
+            .collect();
+        let sentinels = config
+            .known_sentinels
+            .iter()
+            .map(|(ip, port, runid)| (runid.clone(), Peer::new(ip.clone(), *port, runid.clone())))
+            .collect();
+        MonitoredMaster {
+            name: config.name.clone(),
+            quorum: config.quorum,
+            down_after_ms: config.down_after_ms,
+            failover_timeout_ms: config.failover_timeout_ms,
+            config_epoch: config.config_epoch,
+            master: Instance::new(config.ip.clone(), config.port, now),
+            replicas,
+            sentinels,
+            odown_since: None,
+            leader: None,
+            leader_epoch: 0,
+            failover: None,
+            failover_start_time: 0,
+        }
+    }
+
+    fn to_config(&self) -> MasterConfig {
+        MasterConfig {
+            name: self.name.clone(),
+            ip: self.master.ip.clone(),
+            port: self.master.port,
+            quorum: self.quorum,
+            down_after_ms: self.down_after_ms,
+            failover_timeout_ms: self.failover_timeout_ms,
+            config_epoch: self.config_epoch,
+            known_replicas: self
+                .replicas
+                .values()
+                .map(|r| (r.ip.clone(), r.port))
+                .collect(),
+            known_sentinels: self
+                .sentinels
+                .values()
+                .map(|s| (s.ip.clone(), s.port, s.runid.clone()))
+                .collect(),
+        }
+    }
+
+    fn instance_mut(&mut self, addr: &str) -> Option<&mut Instance> {
+        if self.master.addr() == addr {
+            Some(&mut self.master)
+        } else {
+            self.replicas.get_mut(addr)
+        }
+    }
+
+    /// 状态标记，SENTINEL MASTERS 和 INFO 中使用
+    pub fn flags(&self) -> String {
+        let mut flags = vec!["master"];
+        if self.master.sdown_since.is_some() {
+            flags.push("s_down");
+        }
+        if self.odown_since.is_some() {
+            flags.push("o_down");
+        }
+        if self.failover.is_some() {
+            flags.push("failover_in_progress");
+        }
+        flags.join(",")
+    }
+
+    /// 切换到新的master，原来的master成为replica
+    fn switch_master(&mut self, ip: &str, port: u16, now: i64) {
+        let old = std::mem::replace(&mut self.master, Instance::new(ip, port, now));
+        let new_addr = self.master.addr();
+        if let Some(promoted) = self.replicas.remove(&new_addr) {
+            self.master.last_ok_ping = promoted.last_ok_ping.max(now);
+            self.master.info = promoted.info;
+            self.master.info_time = promoted.info_time;
+        }
+        let old = Instance::new(old.ip, old.port, now);
+        self.replicas.insert(old.addr(), old);
+        self.odown_since = None;
+        self.failover = None;
+        for peer in self.sentinels.values_mut() {
+            peer.master_down = false;
+        }
+    }
+
+    /// 投票给runid，每个纪元只投一次，返回当前的投票结果
+    fn vote_leader(
+        &mut self,
+        current_epoch: &mut u64,
+        req_epoch: u64,
+        runid: &str,
+        myid: &str,
+        now: i64,
+    ) -> (Option<String>, u64) {
+        if req_epoch > *current_epoch {
+            *current_epoch = req_epoch;
+        }
+        if self.leader_epoch < req_epoch && *current_epoch <= req_epoch {
+            tracing::info!(
+                "+vote-for-leader {} {} for master {}",
+                runid,
+                req_epoch,
+                self.name
+            );
+            self.leader = Some(runid.to_string());
+            self.leader_epoch = *current_epoch;
+            // 投票给了其他sentinel，推迟自己的故障转移
+            if runid != myid {
+                self.failover_start_time = now + desync();
+            }
+        }
+        (self.leader.clone(), self.leader_epoch)
+    }
+}
+
+/// 需要发送给实例或者其他sentinel的请求
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Request {
+    pub master: String,
+    pub ip: String,
+    pub port: u16,
+    pub kind: RequestKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum RequestKind {
+    Ping,
+    Info,
+    /// 询问其他sentinel是否认为master下线，runid为*表示只询问不请求投票
+    IsMasterDown {
+        master_ip: String,
+        master_port: u16,
+        runid: String,
+        epoch: u64,
+    },
+    /// 向其他sentinel发送当前节点和master配置
+    Hello(Vec<String>),
+    /// None表示REPLICAOF NO ONE
+    ReplicaOf(Option<(String, u16)>),
+}
+
+impl Request {
+    pub fn addr(&self) -> String {
+        format!("{}:{}", self.ip, self.port)
+    }
+
+    /// 请求对应的命令，命令名使用小写，目前的命令分发区分大小写
+    pub fn command(&self) -> Vec<String> {
+        match &self.kind {
+            RequestKind::Ping => vec!["ping".to_string()],
+            RequestKind::Info => vec!["info".to_string(), "replication".to_string()],
+            RequestKind::IsMasterDown {
+                master_ip,
+                master_port,
+                runid,
+                epoch,
+            } => {
+                vec![
+                    "sentinel".to_string(),
+                    "is-master-down-by-addr".to_string(),
+                    master_ip.clone(),
+                    master_port.to_string(),
+                    epoch.to_string(),
+                    runid.clone(),
+                ]
+            }
+            RequestKind::Hello(payload) => {
+                let mut args = vec!["sentinel".to_string(), "hello".to_string()];
+                args.extend(payload.iter().cloned());
+                args
+            }
+            RequestKind::ReplicaOf(None) => {
+                vec!["replicaof".to_string(), "no".to_string(), "one".to_string()]
+            }
+            RequestKind::ReplicaOf(Some((ip, port))) => {
+                vec!["replicaof".to_string(), ip.clone(), port.to_string()]
+            }
+        }
+    }
+}
+
+/// HELLO消息的内容
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Hello {
+    pub ip: String,
+    pub port: u16,
+    pub runid: String,
+    pub current_epoch: u64,
+    pub master_name: String,
+    pub master_ip: String,
+    pub master_port: u16,
+    pub config_epoch: u64,
+}
+
+impl Hello {
+    pub fn to_args(&self) -> Vec<String> {
+        vec![
+            self.ip.clone(),
+            self.port.to_string(),
+            self.runid.clone(),
+            self.current_epoch.to_string(),
+            self.master_name.clone(),
+            self.master_ip.clone(),
+            self.master_port.to_string(),
+            self.config_epoch.to_string(),
+        ]
+    }
+
+    pub fn from_args(args: &[String]) -> Option<Self> {
+        let [ip, port, runid, current_epoch, master_name, master_ip, master_port, config_epoch] =
+            args
+        else {
+            return None;
+        };
+        Some(Hello {
+            ip: ip.clone(),
+            port: port.parse().ok()?,
+            runid: runid.clone(),
+            current_epoch: current_epoch.parse().ok()?,
+            master_name: master_name.clone(),
+            master_ip: master_ip.clone(),
+            master_port: master_port.parse().ok()?,
+            config_epoch: config_epoch.parse().ok()?,
+        })
+    }
+}
+
+/// sentinel的全部状态，所有操作都传入当前时间，方便测试
+#[derive(Debug)]
+pub(super) struct SentinelInner {
+    pub bind: String,
+    pub port: u16,
+    pub myid: String,
+    pub announce_ip: String,
+    pub announce_port: u16,
+    pub current_epoch: u64,
+    pub masters: BTreeMap<String, MonitoredMaster>,
+    /// 配置发生了变化，需要重写配置文件
+    pub todo_save: bool,
+}
+
+impl SentinelInner {
+    pub fn new(config: &SentinelConfig, now: i64) -> Self {
+        SentinelInner {
+            bind: config.bind.clone(),
+            port: config.port,
+            myid: config.myid.clone(),
+            announce_ip: config
+                .announce_ip
+                .clone()
+                .unwrap_or_else(|| match config.bind.as_str() {
+                    "0.0.0.0" | "::" => "127.0.0.1".to_string(),
+                    bind => bind.to_string(),
+                }),
+            announce_port: config.announce_port.unwrap_or(config.port),
+            current_epoch: config.current_epoch,
+            masters: config
+                .masters
+                .iter()
+                .map(|m| (m.name.clone(), MonitoredMaster::new(m, now)))
+                .collect(),
+            todo_save: false,
+        }
+    }
+
+    pub fn to_config(&self) -> SentinelConfig {
+        SentinelConfig {
+            bind: self.bind.clone(),
+            port: self.port,
+            myid: self.myid.clone(),
+            announce_ip: Some(self.announce_ip.clone()),
+            announce_port: Some(self.announce_port),
+            current_epoch: self.current_epoch,
+            masters: self.masters.values().map(|m| m.to_config()).collect(),
+        }
+    }
+
+    /// 定时任务，返回需要发送的请求
+    pub fn tick(&mut self, now: i64) -> Vec<Request> {
+        let mut requests = Vec::new();
+        let names: Vec<String> = self.masters.keys().cloned().collect();
+        for name in names {
+            self.tick_master(&name, now, &mut requests);
+        }
+        requests
+    }
+
+    fn tick_master(&mut self, name: &str, now: i64, requests: &mut Vec<Request>) {
+        let myid = self.myid.clone();
+        let hello = self.hello(name);
+        let Some(master) = self.masters.get_mut(name) else {
+            return;
+        };
+        let request = |ip: &str, port: u16, kind: RequestKind| Request {
+            master: name.to_string(),
+            ip: ip.to_string(),
+            port,
+            kind,
+        };
+
+        // PING和INFO所有实例，检测主观下线
+        let info_period = if master.master.sdown_since.is_some() || master.failover.is_some() {
+            FAST_INFO_PERIOD_MS
+        } else {
+            INFO_PERIOD_MS
+        };
+        let down_after = master.down_after_ms as i64;
+        let ping_period = PING_PERIOD_MS.min(down_after);
+        let is_master_addr = master.master.addr();
+        for inst in std::iter::once(&mut master.master).chain(master.replicas.values_mut()) {
+            if !inst.ping_pending && now - inst.last_ping >= ping_period {
+                inst.ping_pending = true;
+                inst.last_ping = now;
+                inst.act_ping.get_or_insert(now);
+                requests.push(request(&inst.ip, inst.port, RequestKind::Ping));
+            }
+            if !inst.info_pending && now - inst.last_info >= info_period {
+                inst.info_pending = true;
+                inst.last_info = now;
+                requests.push(request(&inst.ip, inst.port, RequestKind::Info));
+            }
+            let role = if inst.addr() == is_master_addr {
+                "master"
+            } else {
+                "slave"
+            };
+            // PING超过down-after-milliseconds没有有效回复则主观下线
+            let down = inst.act_ping.is_some_and(|at| now - at > down_after);
+            match (down, inst.sdown_since) {
+                (true, None) => {
+                    tracing::warn!("+sdown {} {} @ {}", role, inst.addr(), name);
+                    inst.sdown_since = Some(now);
+                }
+                (false, Some(_)) => {
+                    tracing::info!("-sdown {} {} @ {}", role, inst.addr(), name);
+                    inst.sdown_since = None;
+                }
+                _ => {}
+            }
+        }
+
+        // 与其他sentinel交换配置
+        for peer in master.sentinels.values_mut() {
+            if let Some(hello) = &hello {
+                if !peer.hello_pending && now - peer.last_hello >= HELLO_PERIOD_MS {
+                    peer.hello_pending = true;
+                    peer.last_hello = now;
+                    requests.push(request(
+                        &peer.ip,
+                        peer.port,
+                        RequestKind::Hello(hello.to_args()),
+                    ));
+                }
+            }
+        }
+
+        // 主观下线之后询问其他sentinel，足够多的sentinel同意则客观下线
+        if master.master.sdown_since.is_some() {
+            let in_election = master.failover.is_some();
+            for peer in master.sentinels.values_mut() {
+                if !peer.ask_pending && now - peer.last_ask >= ASK_PERIOD_MS {
+                    peer.ask_pending = true;
+                    peer.last_ask = now;
+                    let runid = if in_election {
+                        myid.clone()
+                    } else {
+                        "*".to_string()
+                    };
+                    requests.push(request(
+                        &peer.ip,
+                        peer.port,
+                        RequestKind::IsMasterDown {
+                            master_ip: master.master.ip.clone(),
+                            master_port: master.master.port,
+                            runid,
+                            epoch: self.current_epoch,
+                        },
+                    ));
+                }
+            }
+            let agree = 1 + master
+                .sentinels
+                .values()
+                .filter(|p| p.master_down && now - p.reply_time < REPLY_VALIDITY_MS)
+                .count();
+            match (agree >= master.quorum, master.odown_since) {
+                (true, None) => {
+                    tracing::warn!(
+                        "+odown master {} {} #quorum {}/{}",
+                        name,
+                        master.master.addr(),
+                        agree,
+                        master.quorum
+                    );
+                    master.odown_since = Some(now);
+                    // 第一次故障转移之前也加入随机延迟，避免所有sentinel同时发起选举
+                    let timeout = master.failover_timeout_ms as i64;
+                    master.failover_start_time =
+                        master.failover_start_time.max(now - timeout * 2 + desync());
+                }
+                (false, Some(_)) => {
+                    tracing::info!("-odown master {} {}", name, master.master.addr());
+                    master.odown_since = None;
+                }
+                _ => {}
+            }
+        } else {
+            if master.odown_since.take().is_some() {
+                tracing::info!("-odown master {} {}", name, master.master.addr());
+            }
+            for peer in master.sentinels.values_mut() {
+                peer.master_down = false;
+            }
+        }
+
+        // 客观下线之后开始故障转移，失败之后等待2倍的超时时间再重试
+        let timeout = master.failover_timeout_ms as i64;
+        if master.odown_since.is_some()
+            && master.failover.is_none()
+            && now - master.failover_start_time >= timeout * 2
+        {
+            self.current_epoch += 1;
+            self.todo_save = true;
+            tracing::warn!(
+                "+try-failover master {} {} epoch {}",
+                name,
+                master.master.addr(),
+                self.current_epoch
+            );
+            master.failover = Some(Failover {
+                epoch: self.current_epoch,
+                state: FailoverState::WaitStart,
+                state_time: now,
+                forced: false,
+            });
+            master.failover_start_time = now + desync();
+        }
+        self.failover_state_machine(name, now, requests);
+        self.reconfigure_replicas(name, now, requests);
+    }
+
+    /// 推进故障转移
+    fn failover_state_machine(&mut self, name: &str, now: i64, requests: &mut Vec<Request>) {
+        let myid = self.myid.clone();
+        let Some(master) = self.masters.get_mut(name) else {
+            return;
+        };
+        let Some(failover) = master.failover.clone() else {
+            return;
+        };
+        let timeout = master.failover_timeout_ms as i64;
+
+        match failover.state {
+            FailoverState::WaitStart => {
+                if !failover.forced {
+                    let leader = Self::get_leader(
+                        master,
+                        &mut self.current_epoch,
+                        failover.epoch,
+                        &myid,
+                        now,
+                    );
+                    if leader.as_deref() != Some(myid.as_str()) {
+                        if now - failover.state_time > timeout.min(ELECTION_TIMEOUT_MS) {
+                            tracing::warn!("-failover-abort-not-elected master {}", name);
+                            master.failover = None;
+                        }
+                        return;
+                    }
+                    tracing::warn!("+elected-leader master {} epoch {}", name, failover.epoch);
+                }
+
+                // 选择最合适的replica: 在线、优先级最高、复制偏移量最大
+                let mut candidates: Vec<&Instance> = master
+                    .replicas
+                    .values()
+                    .filter(|r| {
+                        r.sdown_since.is_none()
+                            && now - r.last_ok_ping <= PING_PERIOD_MS * 5
+                            && now - r.info_time <= PING_PERIOD_MS * 5
+                            && r.info.as_ref().is_some_and(|i| i.priority != 0)
+                    })
+                    .collect();
+                candidates.sort_by_key(|r| {
+                    let info = r.info.as_ref().expect("filtered above");
+                    (info.priority, std::cmp::Reverse(info.offset), r.addr())
+                });
+                let Some(selected) = candidates.first() else {
+                    tracing::warn!("-failover-abort-no-good-slave master {}", name);
+                    master.failover = None;
+                    return;
+                };
+                tracing::warn!("+selected-slave {} @ {}", selected.addr(), name);
+                requests.push(Request {
+                    master: name.to_string(),
+                    ip: selected.ip.clone(),
+                    port: selected.port,
+                    kind: RequestKind::ReplicaOf(None),
+                });
+                master.failover = Some(Failover {
+                    state: FailoverState::WaitPromotion(selected.addr()),
+                    state_time: now,
+                    ..failover
+                });
+            }
+            FailoverState::WaitPromotion(addr) => {
+                if now - failover.state_time > timeout {
+                    tracing::warn!("-failover-abort-slave-timeout {} @ {}", addr, name);
+                    master.failover = None;
+                }
+            }
+        }
+    }
+
+    /// 统计投票，得票超过半数并且不少于quorum的sentinel成为leader
+    fn get_leader(
+        master: &mut MonitoredMaster,
+        current_epoch: &mut u64,
+        epoch: u64,
+        myid: &str,
+        now: i64,
+    ) -> Option<String> {
+        let voters = master.sentinels.len() + 1;
+        let mut votes: HashMap<String, usize> = HashMap::new();
+        for peer in master.sentinels.values() {
+            if let Some(leader) = peer.leader.as_ref().filter(|_| peer.leader_epoch == epoch) {
+                *votes.entry(leader.clone()).or_default() += 1;
+            }
+        }
+        let winner = Self::winner(&votes);
+        // 当前节点投票给得票最多的sentinel，没有的话投给自己
+        let candidate = winner.unwrap_or_else(|| myid.to_string());
+        let (my_vote, vote_epoch) = master.vote_leader(current_epoch, epoch, &candidate, myid, now);
+        if let Some(my_vote) = my_vote.filter(|_| vote_epoch == epoch) {
+            *votes.entry(my_vote).or_default() += 1;
+        }
+        let winner = Self::winner(&votes)?;
+        let needed = (voters / 2 + 1).max(master.quorum);
+        (votes[&winner] >= needed).then_some(winner)
+    }
+
+    /// 得票最多的sentinel，票数相同时选择runid较小的
+    fn winner(votes: &HashMap<String, usize>) -> Option<String> {
+        votes
+            .iter()
+            .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(id, _)| id.clone())
+    }
+
+    /// 纠正角色或者master地址不正确的replica，例如恢复之后的旧master
+    fn reconfigure_replicas(&mut self, name: &str, now: i64, requests: &mut Vec<Request>) {
+        let Some(master) = self.masters.get_mut(name) else {
+            return;
+        };
+        if master.failover.is_some() || master.master.sdown_since.is_some() {
+            return;
+        }
+        let (ip, port) = (master.master.ip.clone(), master.master.port);
+        for replica in master.replicas.values_mut() {
+            let Some(info) = &replica.info else {
+                continue;
+            };
+            let wrong = info.is_master
+                || info.master_host.as_deref() != Some(ip.as_str())
+                || info.master_port != Some(port);
+            if wrong
+                && replica.sdown_since.is_none()
+                && now - replica.info_changed > RECONF_WAIT_MS
+                && now - replica.reconf_sent > RECONF_WAIT_MS
+            {
+                tracing::info!("+fix-slave-config {} @ {}", replica.addr(), name);
+                replica.reconf_sent = now;
+                requests.push(Request {
+                    master: name.to_string(),
+                    ip: replica.ip.clone(),
+                    port: replica.port,
+                    kind: RequestKind::ReplicaOf(Some((ip.clone(), port))),
+                });
+            }
+        }
+    }
+
+    /// 当前节点发送给其他sentinel的HELLO
+    fn hello(&self, name: &str) -> Option<Hello> {
+        let master = self.masters.get(name)?;
+        Some(Hello {
+            ip: self.announce_ip.clone(),
+            port: self.announce_port,
+            runid: self.myid.clone(),
+            current_epoch: self.current_epoch,
+            master_name: name.to_string(),
+            master_ip: master.master.ip.clone(),
+            master_port: master.master.port,
+            config_epoch: master.config_epoch,
+        })
+    }
+
+    /// 处理请求的回复，None表示请求失败
+    pub fn handle_reply(
+        &mut self,
+        request: &Request,
+        reply: Option<RespFrame>,
+        now: i64,
+    ) -> Vec<Request> {
+        let mut requests = Vec::new();
+        let Some(master) = self.masters.get_mut(&request.master) else {
+            return requests;
+        };
+        let addr = request.addr();
+        match &request.kind {
+            RequestKind::Ping => {
+                if let Some(inst) = master.instance_mut(&addr) {
+                    inst.ping_pending = false;
+                    // PONG以及LOADING、MASTERDOWN都是有效的回复
+                    let valid = match &reply {
+                        Some(RespFrame::SimpleString(s)) => s.as_str() == "PONG",
+                        Some(RespFrame::Error(e)) => {
+                            e.starts_with("LOADING") || e.starts_with("MASTERDOWN")
+                        }
+                        _ => false,
+                    };
+                    if valid {
+                        inst.last_ok_ping = now;
+                        inst.act_ping = None;
+                    }
+                }
+            }
+            RequestKind::Info => {
+                let text = match reply {
+                    Some(RespFrame::BulkString(s)) => Some(String::from_utf8_lossy(&s).to_string()),
+                    _ => None,
+                };
+                let Some(inst) = master.instance_mut(&addr) else {
+                    return requests;
+                };
+                inst.info_pending = false;
+                let Some(text) = text else {
+                    return requests;
+                };
+                let info = InstanceInfo::parse(&text);
+                let changed = inst.info.as_ref().is_none_or(|old| {
+                    old.is_master != info.is_master
+                        || old.master_host != info.master_host
+                        || old.master_port != info.master_port
+                });
+                if changed {
+                    inst.info_changed = now;
+                }
+                inst.info_time = now;
+                inst.info = Some(info.clone());
+
+                if addr == master.master.addr() {
+                    // 从master的INFO中发现新的replica
+                    for (ip, port) in info.replicas.iter().filter(|_| info.is_master) {
+                        let replica = Instance::new(ip.clone(), *port, now);
+                        if !master.replicas.contains_key(&replica.addr()) {
+                            tracing::info!("+slave {} @ {}", replica.addr(), master.name);
+                            master.replicas.insert(replica.addr(), replica);
+                            self.todo_save = true;
+                        }
+                    }
+                    return requests;
+                }
+
+                // 选中的replica已经成为master，让其他replica复制它
+                let promoted = matches!(
+                    master.failover.as_ref().map(|f| &f.state),
+                    Some(FailoverState::WaitPromotion(promoted)) if *promoted == addr
+                );
+                if promoted && info.is_master {
+                    let epoch = master
+                        .failover
+                        .as_ref()
+                        .map(|f| f.epoch)
+                        .unwrap_or_default();
+                    tracing::warn!("+promoted-slave {} @ {}", addr, master.name);
+                    let (ip, port) = (request.ip.clone(), request.port);
+                    for replica in master.replicas.values().filter(|r| r.addr() != addr) {
+                        tracing::info!("+slave-reconf-sent {} @ {}", replica.addr(), master.name);
+                        requests.push(Request {
+                            master: master.name.clone(),
+                            ip: replica.ip.clone(),
+                            port: replica.port,
+                            kind: RequestKind::ReplicaOf(Some((ip.clone(), port))),
+                        });
+                    }
+                    tracing::warn!(
+                        "+switch-master {} {} {}",
+                        master.name,
+                        master.master.addr(),
+                        addr
+                    );
+                    master.config_epoch = epoch;
+                    master.switch_master(&ip, port, now);
+                    self.todo_save = true;
+                }
+            }
+            RequestKind::IsMasterDown { .. } => {
+                let Some(peer) = master
+                    .sentinels
+                    .values_mut()
+                    .find(|p| p.ip == request.ip && p.port == request.port)
+                else {
+                    return requests;
+                };
+                peer.ask_pending = false;
+                // 回复为 [下线状态, leader, leader纪元]
+                if let Some(RespFrame::Array(arr)) = reply {
+                    if let [RespFrame::Integer(down), RespFrame::BulkString(leader), RespFrame::Integer(epoch)] =
+                        arr.as_slice()
+                    {
+                        peer.master_down = *down == 1;
+                        peer.reply_time = now;
+                        if leader.as_ref() != b"*" {
+                            peer.leader = Some(String::from_utf8_lossy(leader).to_string());
+                            peer.leader_epoch = *epoch as u64;
+                        }
+                    }
+                }
+            }
+            RequestKind::Hello(_) => {
+                if let Some(peer) = master
+                    .sentinels
+                    .values_mut()
+                    .find(|p| p.ip == request.ip && p.port == request.port)
+                {
+                    peer.hello_pending = false;
+                    if matches!(reply, Some(RespFrame::SimpleString(_))) {
+                        peer.last_hello_ok = now;
+                    }
+                }
+            }
+            RequestKind::ReplicaOf(None) => {
+                let failed = !matches!(reply, Some(RespFrame::SimpleString(_)));
+                let selected = matches!(
+                    master.failover.as_ref().map(|f| &f.state),
+                    Some(FailoverState::WaitPromotion(promoted)) if *promoted == addr
+                );
+                if failed && selected {
+                    tracing::warn!(
+                        "-failover-abort-slaveof-noone-failed {} @ {}",
+                        addr,
+                        master.name
+                    );
+                    master.failover = None;
+                }
+            }
+            RequestKind::ReplicaOf(Some(_)) => {}
+        }
+        requests
+    }
+
+    /// 处理其他sentinel发来的HELLO，发现新的sentinel，接受更新的master配置
+    pub fn handle_hello(&mut self, hello: Hello, now: i64) -> bool {
+        if hello.current_epoch > self.current_epoch {
+            self.current_epoch = hello.current_epoch;
+            self.todo_save = true;
+        }
+        let myid = self.myid.clone();
+        let Some(master) = self.masters.get_mut(&hello.master_name) else {
+            return false;
+        };
+        if hello.runid == myid {
+            return true;
+        }
+        // 同一个地址的sentinel重启之后会有新的runid
+        let before = master.sentinels.len();
+        master.sentinels.retain(|id, p| {
+            *id == hello.runid || (p.ip.as_str(), p.port) != (hello.ip.as_str(), hello.port)
+        });
+        let peer = master
+            .sentinels
+            .entry(hello.runid.clone())
+            .or_insert_with(|| {
+                tracing::info!(
+                    "+sentinel {}:{} {} @ {}",
+                    hello.ip,
+                    hello.port,
+                    hello.runid,
+                    hello.master_name
+                );
+                Peer::new(hello.ip.clone(), hello.port, hello.runid.clone())
+            });
+        if (peer.ip.as_str(), peer.port) != (hello.ip.as_str(), hello.port) {
+            peer.ip = hello.ip.clone();
+            peer.port = hello.port;
+        }
+        if master.sentinels.len() != before {
+            self.todo_save = true;
+        }
+
+        // 其他sentinel完成了故障转移
+        if hello.config_epoch > master.config_epoch
+            && (master.master.ip.as_str(), master.master.port)
+                != (hello.master_ip.as_str(), hello.master_port)
+        {
+            tracing::warn!(
+                "+config-update-from sentinel {} @ {}: switch master to {}:{}",
+                hello.runid,
+                hello.master_name,
+                hello.master_ip,
+                hello.master_port
+            );
+            master.config_epoch = hello.config_epoch;
+            master.switch_master(&hello.master_ip, hello.master_port, now);
+            self.todo_save = true;
+        }
+        true
+    }
+
+    /// SENTINEL is-master-down-by-addr，返回 (是否下线, leader, leader纪元)
+    pub fn is_master_down_by_addr(
+        &mut self,
+        ip: &str,
+        port: u16,
+        req_epoch: u64,
+        runid: &str,
+        now: i64,
+    ) -> (bool, Option<String>, u64) {
+        let myid = self.myid.clone();
+        let Some(master) = self
+            .masters
+            .values_mut()
+            .find(|m| m.master.ip == ip && m.master.port == port)
+        else {
+            return (false, None, 0);
+        };
+        let down = master.master.sdown_since.is_some();
+        if runid == "*" {
+            return (down, None, 0);
+        }
+        let before = self.current_epoch;
+        let (leader, epoch) =
+            master.vote_leader(&mut self.current_epoch, req_epoch, runid, &myid, now);
+        if self.current_epoch != before {
+            self.todo_save = true;
+        }
+        (down, leader, epoch)
+    }
+
+    /// SENTINEL FAILOVER: 不需要其他sentinel同意，直接开始故障转移
+    pub fn force_failover(&mut self, name: &str, now: i64) -> Result<(), RespFrame> {
+        let Some(master) = self.masters.get_mut(name) else {
+            return Err(ErrorCode::Err.reply("No such master with that name"));
+        };
+        if master.failover.is_some() {
+            return Err(ErrorCode::InProg.reply("Failover already in progress"));
+        }
+        let has_replica = master
+            .replicas
+            .values()
+            .any(|r| r.sdown_since.is_none() && r.info.as_ref().is_some_and(|i| i.priority != 0));
+        if !has_replica {
+            return Err(ErrorCode::NoGoodSlave.reply("No suitable replica to promote"));
+        }
+        self.current_epoch += 1;
+        self.todo_save = true;
+        tracing::warn!(
+            "+new-epoch {}, forced failover of {}",
+            self.current_epoch,
+            name
+        );
+        master.failover = Some(Failover {
+            epoch: self.current_epoch,
+            state: FailoverState::WaitStart,
+            state_time: now,
+            forced: true,
+        });
+        master.failover_start_time = now;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BulkString, RespArray, SimpleString};
+
+    fn runid(id: u16) -> String {
+        format!("{:040x}", id)
+    }
+
+    /// 监控6379，replica为6380和6381，sentinel的端口为 26379 + id
+    fn sentinel(id: u16, peers: &[u16]) -> SentinelInner {
+        let mut master = MasterConfig::new("mymaster", "127.0.0.1", 6379, 2);
+        master.down_after_ms = 1000;
+        master.failover_timeout_ms = 3000;
+        master.known_replicas = vec![
+            ("127.0.0.1".to_string(), 6380),
+            ("127.0.0.1".to_string(), 6381),
+        ];
+        master.known_sentinels = peers
+            .iter()
+            .map(|p| ("127.0.0.1".to_string(), 26379 + p, runid(*p)))
+            .collect();
+        let config = SentinelConfig {
+            port: 26379 + id,
+            myid: runid(id),
+            masters: vec![master],
+            ..Default::default()
+        };
+        SentinelInner::new(&config, 0)
+    }
+
+    /// 模拟的master和replica: master已经下线，6381的复制偏移量更大
+    #[derive(Default)]
+    struct Env {
+        promoted: Option<u16>,
+        replicaof: Vec<(u16, Option<u16>)>,
+    }
+
+    impl Env {
+        fn reply(&mut self, request: &Request) -> Option<RespFrame> {
+            if request.port == 6379 {
+                return None;
+            }
+            match &request.kind {
+                RequestKind::Ping => Some(SimpleString::new("PONG").into()),
+                RequestKind::Info => {
+                    let info = if self.promoted == Some(request.port) {
+                        "# Replication\r\nrole:master\r\nconnected_slaves:0\r\n".to_string()
+                    } else {
+                        format!(
+                            "# Replication\r\nrole:slave\r\nmaster_host:127.0.0.1\r\nmaster_port:6379\r\nmaster_link_status:down\r\nslave_repl_offset:{}\r\nslave_priority:100\r\n",
+                            request.port
+                        )
+                    };
+                    Some(BulkString::new(info).into())
+                }
+                RequestKind::ReplicaOf(master) => {
+                    self.replicaof
+                        .push((request.port, master.as_ref().map(|m| m.1)));
+                    if master.is_none() {
+                        self.promoted = Some(request.port);
+                    }
+                    Some(SimpleString::new("OK").into())
+                }
+                _ => None,
+            }
+        }
+    }
+
+    /// 所有sentinel执行一次定时任务，并处理所有的请求
+    fn step(sentinels: &mut [SentinelInner], env: &mut Env, now: i64) {
+        let mut pending: Vec<(usize, Request)> = Vec::new();
+        for (i, s) in sentinels.iter_mut().enumerate() {
+            pending.extend(s.tick(now).into_iter().map(|r| (i, r)));
+        }
+        while let Some((from, request)) = pending.pop() {
+            let to = sentinels.iter().position(|s| s.port == request.port);
+            let reply = match (&request.kind, to) {
+                (
+                    RequestKind::IsMasterDown {
+                        master_ip,
+                        master_port,
+                        runid,
+                        epoch,
+                    },
+                    Some(to),
+                ) => {
+                    let (down, leader, leader_epoch) = sentinels[to].is_master_down_by_addr(
+                        master_ip,
+                        *master_port,
+                        *epoch,
+                        runid,
+                        now,
+                    );
+                    Some(
+                        RespArray::new(vec![
+                            RespFrame::Integer(down as i64),
+                            BulkString::new(leader.unwrap_or_else(|| "*".to_string())).into(),
+                            RespFrame::Integer(leader_epoch as i64),
+                        ])
+                        .into(),
+                    )
+                }
+                (RequestKind::Hello(args), Some(to)) => {
+                    let hello = Hello::from_args(args).unwrap();
+                    sentinels[to].handle_hello(hello, now);
+                    Some(SimpleString::new("OK").into())
+                }
+                _ => env.reply(&request),
+            };
+            let more = sentinels[from].handle_reply(&request, reply, now);
+            pending.extend(more.into_iter().map(|r| (from, r)));
+        }
+    }
+
+    #[test]
+    fn test_parse_info() {
+        let info = InstanceInfo::parse(
+            "# Replication\r\nrole:master\r\nconnected_slaves:2\r\nslave0:ip=127.0.0.1,port=6380,state=online,offset=10,lag=0\r\nslave1:ip=127.0.0.1,port=6381,state=online,offset=10,lag=1\r\n",
+        );
+        assert!(info.is_master);
+        assert_eq!(
+            info.replicas,
+            vec![
+                ("127.0.0.1".to_string(), 6380),
+                ("127.0.0.1".to_string(), 6381)
+            ]
+        );
+
+        let info = InstanceInfo::parse("role:slave\r\nmaster_host:127.0.0.1\r\nmaster_port:6379\r\nmaster_link_status:up\r\nslave_repl_offset:42\r\nslave_priority:10\r\n");
+        assert!(!info.is_master);
+        assert_eq!(info.master_port, Some(6379));
+        assert!(info.link_up);
+        assert_eq!((info.offset, info.priority), (42, 10));
+    }
+
+    #[test]
+    fn test_vote_leader() {
+        let mut s = sentinel(1, &[]);
+        let vote = |s: &mut SentinelInner, epoch, id| {
+            s.is_master_down_by_addr("127.0.0.1", 6379, epoch, &runid(id), 0)
+        };
+        assert_eq!(vote(&mut s, 1, 2), (false, Some(runid(2)), 1));
+        // 同一个纪元只投一次票
+        assert_eq!(vote(&mut s, 1, 3), (false, Some(runid(2)), 1));
+        assert_eq!(vote(&mut s, 2, 3), (false, Some(runid(3)), 2));
+        assert_eq!(s.current_epoch, 2);
+        // 只询问状态不投票
+        assert_eq!(
+            s.is_master_down_by_addr("127.0.0.1", 6379, 3, "*", 0),
+            (false, None, 0)
+        );
+        assert_eq!(s.current_epoch, 2);
+    }
+
+    #[test]
+    fn test_failover() {
+        let mut sentinels = vec![
+            sentinel(1, &[2, 3]),
+            sentinel(2, &[1, 3]),
+            sentinel(3, &[1, 2]),
+        ];
+        let mut env = Env::default();
+        let mut now = 0;
+        let switched = |sentinels: &[SentinelInner]| {
+            sentinels
+                .iter()
+                .all(|s| s.masters["mymaster"].master.port == 6381)
+        };
+        while !switched(&sentinels) && now < 120_000 {
+            step(&mut sentinels, &mut env, now);
+            if now == 2500 {
+                // master下线，所有sentinel都已经主观下线并且客观下线
+                for s in &sentinels {
+                    let master = &s.masters["mymaster"];
+                    assert!(master.master.sdown_since.is_some());
+                    assert!(master.odown_since.is_some());
+                }
+            }
+            now += 100;
+        }
+        assert!(switched(&sentinels), "failover not finished");
+
+        // 复制偏移量最大的replica被提升，另一个replica复制新的master
+        assert_eq!(env.replicaof[0], (6381, None));
+        assert!(env.replicaof.contains(&(6380, Some(6381))));
+        for s in &sentinels {
+            let master = &s.masters["mymaster"];
+            assert!(master.failover.is_none());
+            assert_eq!(
+                master.config_epoch,
+                sentinels[0].masters["mymaster"].config_epoch
+            );
+            assert!(master.replicas.contains_key("127.0.0.1:6379"));
+            assert!(master.replicas.contains_key("127.0.0.1:6380"));
+            assert!(s.todo_save);
+        }
+    }
+
+    #[test]
+    fn test_hello_discovery() {
+        let mut s1 = sentinel(1, &[]);
+        let mut hello = Hello {
+            ip: "127.0.0.1".to_string(),
+            port: 26381,
+            runid: runid(2),
+            current_epoch: 5,
+            master_name: "mymaster".to_string(),
+            master_ip: "127.0.0.1".to_string(),
+            master_port: 6379,
+            config_epoch: 0,
+        };
+        assert!(s1.handle_hello(hello.clone(), 0));
+        assert_eq!(s1.current_epoch, 5);
+        assert!(s1.masters["mymaster"].sentinels.contains_key(&runid(2)));
+
+        // 同一个地址重启之后使用新的runid
+        hello.runid = runid(3);
+        assert!(s1.handle_hello(hello.clone(), 0));
+        let sentinels = &s1.masters["mymaster"].sentinels;
+        assert_eq!(sentinels.keys().collect::<Vec<_>>(), vec![&runid(3)]);
+
+        // 更大的配置纪元
+        hello.master_port = 6380;
+        hello.config_epoch = 5;
+        assert!(s1.handle_hello(hello.clone(), 0));
+        let master = &s1.masters["mymaster"];
+        assert_eq!(master.master.port, 6380);
+        assert!(master.replicas.contains_key("127.0.0.1:6379"));
+        assert!(!master.replicas.contains_key("127.0.0.1:6380"));
+
+        hello.master_name = "other".to_string();
+        assert!(!s1.handle_hello(hello, 0));
+    }
+}
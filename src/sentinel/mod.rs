@@ -0,0 +1,122 @@
+//! Sentinel
+//!
+//! 非集群模式下监控master和replica，master客观下线之后选举出leader sentinel，
+//! 由leader提升最合适的replica，并让其他replica复制新的master。
+//!
+//! 没有发布订阅，sentinel之间通过 `SENTINEL HELLO` 直接交换Redis中hello频道的消息内容，
+//! 用来发现其他sentinel以及传播故障转移之后的新配置。
+
+mod command;
+mod config;
+mod instance;
+mod server;
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use thiserror::Error;
+
+use crate::{now_ms, RespFrame};
+
+use self::instance::{Request, SentinelInner};
+
+pub use self::{
+    command::SentinelCommand,
+    config::{parse_sentinel_conf, MasterConfig, SentinelConfig, DEFAULT_SENTINEL_PORT},
+    server::run_sentinel,
+};
+
+/// Sentinel相关的异常
+#[derive(Error, Debug)]
+pub enum SentinelError {
+    #[error("IO Error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid sentinel config: {0}")]
+    InvalidConfig(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Sentinel(Arc<SentinelState>);
+
+#[derive(Debug)]
+pub struct SentinelState {
+    inner: Mutex<SentinelInner>,
+    /// 配置发生变化时重写的配置文件
+    config_file: Option<PathBuf>,
+}
+
+impl Sentinel {
+    pub fn new(config: &SentinelConfig) -> Self {
+        Sentinel(Arc::new(SentinelState {
+            inner: Mutex::new(SentinelInner::new(config, now_ms())),
+            config_file: None,
+        }))
+    }
+
+    /// 从配置文件创建，故障转移等改变配置之后会重写这个文件
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SentinelError> {
+        let content = std::fs::read_to_string(path.as_ref())?;
+        let config = parse_sentinel_conf(&content)?;
+        let sentinel = Sentinel(Arc::new(SentinelState {
+            inner: Mutex::new(SentinelInner::new(&config, now_ms())),
+            config_file: Some(path.as_ref().to_path_buf()),
+        }));
+        // 保存自动生成的myid
+        sentinel.save_config()?;
+        Ok(sentinel)
+    }
+
+    pub fn myid(&self) -> String {
+        self.0.inner.lock().unwrap().myid.clone()
+    }
+
+    /// 监听的地址
+    pub fn bind_addr(&self) -> (String, u16) {
+        let inner = self.0.inner.lock().unwrap();
+        (inner.bind.clone(), inner.port)
+    }
+
+    /// 当前的配置
+    pub fn config(&self) -> SentinelConfig {
+        self.0.inner.lock().unwrap().to_config()
+    }
+
+    /// 当前的master地址，SENTINEL get-master-addr-by-name
+    pub fn master_addr(&self, name: &str) -> Option<(String, u16)> {
+        let inner = self.0.inner.lock().unwrap();
+        inner
+            .masters
+            .get(name)
+            .map(|m| (m.master.ip.clone(), m.master.port))
+    }
+
+    /// 重写配置文件
+    pub fn save_config(&self) -> Result<(), SentinelError> {
+        if let Some(path) = &self.0.config_file {
+            let content = self.config().to_string();
+            let tmp = path.with_extension("tmp");
+            std::fs::write(&tmp, content)?;
+            std::fs::rename(tmp, path)?;
+        }
+        Ok(())
+    }
+
+    fn tick(&self) -> Vec<Request> {
+        self.0.inner.lock().unwrap().tick(now_ms())
+    }
+
+    fn handle_reply(&self, request: &Request, reply: Option<RespFrame>) -> Vec<Request> {
+        self.0
+            .inner
+            .lock()
+            .unwrap()
+            .handle_reply(request, reply, now_ms())
+    }
+
+    /// 取出配置是否需要保存的标记
+    fn take_todo_save(&self) -> bool {
+        std::mem::take(&mut self.0.inner.lock().unwrap().todo_save)
+    }
+}
@@ -0,0 +1,252 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use futures::{SinkExt, StreamExt};
+use tokio::{
+    io::AsyncWriteExt,
+    net::{TcpListener, TcpStream},
+    time,
+};
+use tokio_util::codec::Framed;
+
+use crate::{cmd::ErrorCode, network::RedisCodec, BulkString, RespArray, RespEncode, RespFrame};
+
+use super::{instance::Request, Sentinel, SentinelCommand, SentinelError};
+
+/// 定时任务的间隔
+const CRON_PERIOD: Duration = Duration::from_millis(100);
+/// 发送给实例和其他sentinel的请求的超时时间
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 处理客户端连接，并在后台监控所有的master
+pub async fn run_sentinel(sentinel: Sentinel, listener: TcpListener) -> Result<(), SentinelError> {
+    tokio::spawn(run_sentinel_cron(sentinel.clone()));
+    loop {
+        let (stream, remote_addr) = listener.accept().await?;
+        let sentinel = sentinel.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_client(stream, &sentinel).await {
+                tracing::warn!("handle error for {}: {:?}", remote_addr, e);
+            }
+        });
+    }
+}
+
+async fn run_sentinel_cron(sentinel: Sentinel) {
+    let mut interval = time::interval(CRON_PERIOD);
+    loop {
+        interval.tick().await;
+        send_requests(&sentinel, sentinel.tick());
+        if sentinel.take_todo_save() {
+            if let Err(e) = sentinel.save_config() {
+                tracing::warn!("Failed to save sentinel config: {}", e);
+            }
+        }
+    }
+}
+
+/// 在后台发送请求，回复交给状态机处理
+fn send_requests(sentinel: &Sentinel, requests: Vec<Request>) {
+    for request in requests {
+        let sentinel = sentinel.clone();
+        tokio::spawn(async move {
+            let reply = match time::timeout(REQUEST_TIMEOUT, send_request(&request)).await {
+                Ok(Ok(reply)) => Some(reply),
+                Ok(Err(e)) => {
+                    tracing::debug!("Request to {} failed: {}", request.addr(), e);
+                    None
+                }
+                Err(_) => None,
+            };
+            let requests = sentinel.handle_reply(&request, reply);
+            send_requests(&sentinel, requests);
+        });
+    }
+}
+
+/// 每个请求使用单独的连接，请求超时或者失败不会影响后续的请求
+async fn send_request(request: &Request) -> Result<RespFrame> {
+    let stream = TcpStream::connect(request.addr()).await?;
+    let mut framed = Framed::new(stream, RedisCodec::default());
+    let args = RespArray::new(
+        request
+            .command()
+            .into_iter()
+            .map(|arg| BulkString::new(arg).into())
+            .collect::<Vec<RespFrame>>(),
+    );
+    framed.get_mut().write_all(&args.encode()).await?;
+    match framed.next().await {
+        Some(frame) => frame,
+        None => Err(anyhow!("connection closed")),
+    }
+}
+
+async fn serve_client(stream: TcpStream, sentinel: &Sentinel) -> Result<()> {
+    let mut framed = Framed::new(stream, RedisCodec::default());
+    while let Some(frame) = framed.next().await {
+        let reply = match frame? {
+            RespFrame::Array(args) => match SentinelCommand::try_from(args) {
+                Ok(cmd) => cmd.execute(sentinel),
+                Err(e) => e.into(),
+            },
+            _ => ErrorCode::Err.reply("Protocol error: expected array"),
+        };
+        framed.send(reply).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        replication::{self, ReplicationRole},
+        sentinel::{MasterConfig, SentinelConfig},
+        Backend,
+    };
+
+    fn bind() -> anyhow::Result<(std::net::TcpListener, u16)> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+        Ok((listener, port))
+    }
+
+    fn start_server(
+        backend: Backend,
+        listener: std::net::TcpListener,
+        handle: &tokio::runtime::Handle,
+    ) {
+        handle.spawn(async move {
+            let listener = TcpListener::from_std(listener).unwrap();
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(crate::network::stream_handler(stream, backend.clone()));
+            }
+        });
+    }
+
+    async fn wait_until(f: impl Fn() -> bool) {
+        for _ in 0..600 {
+            if f() {
+                return;
+            }
+            time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("condition not met in time");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_sentinel_failover() -> anyhow::Result<()> {
+        // master单独运行在一个运行时中，关闭运行时模拟进程退出
+        let master_runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()?;
+        let (listener, master_port) = bind()?;
+        start_server(Backend::new(), listener, master_runtime.handle());
+
+        let mut replicas = Vec::new();
+        for _ in 0..2 {
+            let (listener, port) = bind()?;
+            let backend = Backend::new();
+            backend.replication().set_listening_port(port);
+            backend.replication().set_master("127.0.0.1", master_port);
+            replication::start_replication(&backend);
+            start_server(
+                backend.clone(),
+                listener,
+                &tokio::runtime::Handle::current(),
+            );
+            replicas.push((backend, port));
+        }
+
+        wait_until(|| {
+            replicas.iter().all(|(backend, _)| {
+                matches!(backend.replication().role(), ReplicationRole::Replica(link) if link.state == replication::LinkState::Connected)
+            })
+        })
+        .await;
+
+        let mut listeners = Vec::new();
+        for _ in 0..3 {
+            listeners.push(bind()?);
+        }
+        let mut sentinels = Vec::new();
+        for (i, (_, port)) in listeners.iter().enumerate() {
+            let mut master = MasterConfig::new("mymaster", "127.0.0.1", master_port, 2);
+            master.down_after_ms = 500;
+            master.failover_timeout_ms = 2000;
+            master.known_sentinels = listeners
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(j, (_, port))| ("127.0.0.1".to_string(), *port, format!("{:040x}", j + 1)))
+                .collect();
+            let config = SentinelConfig {
+                bind: "127.0.0.1".to_string(),
+                port: *port,
+                myid: format!("{:040x}", i + 1),
+                masters: vec![master],
+                ..Default::default()
+            };
+            sentinels.push(Sentinel::new(&config));
+        }
+        for ((listener, _), sentinel) in listeners.into_iter().zip(&sentinels) {
+            tokio::spawn(run_sentinel(
+                sentinel.clone(),
+                TcpListener::from_std(listener)?,
+            ));
+        }
+
+        // 通过master的INFO发现replica
+        wait_until(|| {
+            sentinels
+                .iter()
+                .all(|s| s.config().masters[0].known_replicas.len() == 2)
+        })
+        .await;
+
+        master_runtime.shutdown_background();
+        wait_until(|| {
+            sentinels.iter().all(|s| {
+                s.master_addr("mymaster")
+                    .is_some_and(|(_, port)| port != master_port)
+            })
+        })
+        .await;
+
+        // 客户端通过sentinel获取新的master地址
+        let (_, sentinel_port) = sentinels[0].bind_addr();
+        let stream = TcpStream::connect(("127.0.0.1", sentinel_port)).await?;
+        let mut framed = Framed::new(stream, RedisCodec::default());
+        let cmd = RespArray::new(
+            ["SENTINEL", "get-master-addr-by-name", "mymaster"]
+                .into_iter()
+                .map(|arg| BulkString::new(arg).into())
+                .collect::<Vec<RespFrame>>(),
+        );
+        framed.get_mut().write_all(&cmd.encode()).await?;
+        let Some(Ok(RespFrame::Array(addr))) = framed.next().await else {
+            panic!("array expected");
+        };
+        let new_port: u16 = match &addr[1] {
+            RespFrame::BulkString(port) => String::from_utf8(port.to_vec())?.parse()?,
+            _ => panic!("bulk string expected"),
+        };
+        assert_eq!(
+            sentinels[0].master_addr("mymaster").map(|a| a.1),
+            Some(new_port)
+        );
+
+        // 被提升的replica成为master，另一个replica复制新的master
+        let (promoted, _) = replicas.iter().find(|(_, port)| *port == new_port).unwrap();
+        assert!(!promoted.replication().is_replica());
+        let (other, _) = replicas.iter().find(|(_, port)| *port != new_port).unwrap();
+        wait_until(|| {
+            matches!(other.replication().role(), ReplicationRole::Replica(link) if link.port == new_port)
+        })
+        .await;
+        Ok(())
+    }
+}
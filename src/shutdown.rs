@@ -0,0 +1,390 @@
+//! 优雅关闭
+//!
+//! SHUTDOWN 命令和 SIGTERM/SIGINT 使用同样的流程：暂停写命令等待replica追上复制偏移量，
+//! 按需保存数据，然后通知监听和所有连接退出。连接会处理完正在执行的命令并发送回复之后再断开。
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use thiserror::Error;
+use tokio::sync::watch;
+
+use crate::{
+    persistence::{self, PersistenceError},
+    Backend,
+};
+
+/// 等待replica期间检查的间隔
+const REPLICA_CHECK_PERIOD: Duration = Duration::from_millis(10);
+
+/// 关闭过程中的异常
+#[derive(Error, Debug)]
+pub enum ShutdownError {
+    #[error("Shutdown already in progress")]
+    InProgress,
+    #[error("Shutdown aborted")]
+    Aborted,
+    #[error("Error saving data before shutdown: {0}")]
+    Save(#[from] PersistenceError),
+}
+
+/// SHUTDOWN 的选项
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShutdownFlags {
+    /// Some(true)为SAVE，Some(false)为NOSAVE，None表示配置了save规则时才保存
+    pub save: Option<bool>,
+    /// 不等待replica
+    pub now: bool,
+    /// 保存失败时仍然退出
+    pub force: bool,
+}
+
+/// 关闭运行时状态，挂在Backend上共享
+#[derive(Debug)]
+pub struct ShutdownState {
+    /// 同一时间只有一个关闭流程
+    in_progress: AtomicBool,
+    /// SHUTDOWN ABORT 取消等待replica
+    aborted: AtomicBool,
+    /// 等待replica期间暂停写命令
+    write_paused: AtomicBool,
+    /// 可以退出了，监听和连接收到通知之后退出
+    closing: watch::Sender<bool>,
+    /// 当前的客户端连接数
+    connections: watch::Sender<usize>,
+}
+
+impl Default for ShutdownState {
+    fn default() -> Self {
+        ShutdownState {
+            in_progress: AtomicBool::new(false),
+            aborted: AtomicBool::new(false),
+            write_paused: AtomicBool::new(false),
+            closing: watch::channel(false).0,
+            connections: watch::channel(0).0,
+        }
+    }
+}
+
+/// 客户端连接存在期间持有，用于退出前等待连接处理完当前的命令
+#[derive(Debug)]
+pub struct ConnectionGuard<'a>(&'a watch::Sender<usize>);
+
+impl Drop for ConnectionGuard<'_> {
+    fn drop(&mut self) {
+        self.0.send_modify(|n| *n -= 1);
+    }
+}
+
+impl ShutdownState {
+    pub fn is_in_progress(&self) -> bool {
+        self.in_progress.load(Ordering::Acquire)
+    }
+
+    pub fn is_write_paused(&self) -> bool {
+        self.write_paused.load(Ordering::Relaxed)
+    }
+
+    pub fn is_closing(&self) -> bool {
+        *self.closing.borrow()
+    }
+
+    /// 等待关闭通知
+    pub async fn closed(&self) {
+        let mut rx = self.closing.subscribe();
+        // Sender一直存在，wait_for不会失败
+        let _ = rx.wait_for(|closing| *closing).await;
+    }
+
+    /// 取消正在等待replica的关闭流程，没有正在进行的关闭时返回false
+    pub fn abort(&self) -> bool {
+        if !self.is_in_progress() {
+            return false;
+        }
+        self.aborted.store(true, Ordering::Release);
+        true
+    }
+
+    pub fn connection_guard(&self) -> ConnectionGuard<'_> {
+        self.connections.send_modify(|n| *n += 1);
+        ConnectionGuard(&self.connections)
+    }
+
+    pub fn connections(&self) -> usize {
+        *self.connections.borrow()
+    }
+
+    /// 等待所有连接退出，超时返回false
+    pub async fn wait_connections(&self, timeout: Duration) -> bool {
+        let mut rx = self.connections.subscribe();
+        let ret = tokio::time::timeout(timeout, rx.wait_for(|n| *n == 0)).await;
+        ret.is_ok()
+    }
+}
+
+/// 执行关闭流程，成功之后通知监听和连接退出，失败时服务器继续运行
+pub async fn shutdown(backend: &Backend, flags: ShutdownFlags) -> Result<(), ShutdownError> {
+    let state = backend.shutdown();
+    if state
+        .in_progress
+        .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+        .is_err()
+    {
+        return Err(ShutdownError::InProgress);
+    }
+    state.aborted.store(false, Ordering::Release);
+
+    let ret = async {
+        if !flags.now {
+            wait_for_replicas(backend).await?;
+        }
+        prepare_exit(backend, flags).await
+    }
+    .await;
+
+    state.write_paused.store(false, Ordering::Relaxed);
+    match &ret {
+        Ok(()) => {
+            tracing::warn!("Simple-Redis is now ready to exit, bye bye...");
+            state.closing.send_replace(true);
+        }
+        Err(e) => {
+            tracing::warn!("Errors trying to shut down the server: {}", e);
+            state.in_progress.store(false, Ordering::Release);
+        }
+    }
+    ret
+}
+
+/// 暂停写命令，等待所有replica确认当前的复制偏移量，超时之后继续关闭
+async fn wait_for_replicas(backend: &Backend) -> Result<(), ShutdownError> {
+    let state = backend.shutdown();
+    let replication = backend.replication();
+    if replication.replicas().is_empty() {
+        return Ok(());
+    }
+
+    state.write_paused.store(true, Ordering::Relaxed);
+    let target = replication.offset();
+    // 要求replica立即回复ACK
+    replication.feed(b"*3\r\n$8\r\nREPLCONF\r\n$6\r\nGETACK\r\n$1\r\n*\r\n");
+    tracing::warn!("Waiting for replicas before shutting down.");
+
+    let timeout = Duration::from_secs(backend.config().current().shutdown_timeout);
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        if state.aborted.load(Ordering::Acquire) {
+            return Err(ShutdownError::Aborted);
+        }
+        let lagging = replication
+            .replicas()
+            .into_iter()
+            .filter(|r| r.ack_offset < target)
+            .count();
+        if lagging == 0 {
+            tracing::info!("Replicas are in sync, shutting down.");
+            return Ok(());
+        }
+        if tokio::time::Instant::now() >= deadline {
+            tracing::warn!("{} lagging replica(s), shutting down anyway.", lagging);
+            return Ok(());
+        }
+        tokio::time::sleep(REPLICA_CHECK_PERIOD).await;
+    }
+}
+
+/// 退出前保存数据并刷新AOF
+async fn prepare_exit(backend: &Backend, flags: ShutdownFlags) -> Result<(), ShutdownError> {
+    if backend.aof().is_enabled() {
+        if let Err(e) = backend.aof().fsync() {
+            tracing::warn!("Error flushing the append only file: {}", e);
+        }
+    }
+
+    let save = flags
+        .save
+        .unwrap_or_else(|| !backend.snapshot().config().save_rules.is_empty());
+    if !save {
+        return Ok(());
+    }
+    tracing::info!("Saving the final snapshot before exiting.");
+    let cloned = backend.clone();
+    let ret = match tokio::task::spawn_blocking(move || persistence::save(&cloned)).await {
+        Ok(ret) => ret,
+        Err(e) => Err(PersistenceError::Io(std::io::Error::other(e))),
+    };
+    match ret {
+        Err(e) if flags.force => {
+            tracing::warn!("Error saving the snapshot, exiting anyway: {}", e);
+            Ok(())
+        }
+        ret => Ok(ret?),
+    }
+}
+
+/// 收到 SIGTERM/SIGINT 时关闭服务器，关闭过程中再次收到则立即退出
+#[cfg(unix)]
+pub async fn handle_signals(backend: Backend) -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sigterm = signal(SignalKind::terminate())?;
+    let mut sigint = signal(SignalKind::interrupt())?;
+    loop {
+        let name = tokio::select! {
+            _ = sigterm.recv() => "SIGTERM",
+            _ = sigint.recv() => "SIGINT",
+        };
+        if backend.shutdown().is_in_progress() {
+            tracing::warn!("You insist... exiting now.");
+            std::process::exit(1);
+        }
+        tracing::warn!("Received {}, scheduling shutdown...", name);
+        let backend = backend.clone();
+        tokio::spawn(async move {
+            let _ = shutdown(&backend, ShutdownFlags::default()).await;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{network, BulkString, RespArray, RespEncode, RespFrame};
+    use anyhow::Result;
+    use futures::StreamExt;
+    use tokio::{
+        io::AsyncWriteExt,
+        net::{TcpListener, TcpStream},
+    };
+    use tokio_util::codec::Framed;
+
+    fn backend_in(dir: &std::path::Path) -> Backend {
+        let backend = Backend::new();
+        let mut config = backend.snapshot().config();
+        config.dir = dir.to_path_buf();
+        backend.snapshot().set_config(config);
+        backend.set("key".to_string(), BulkString::new("value").into());
+        backend
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_save() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let backend = backend_in(dir.path());
+        let path = backend.snapshot().config().path();
+
+        shutdown(
+            &backend,
+            ShutdownFlags {
+                save: Some(false),
+                ..Default::default()
+            },
+        )
+        .await?;
+        assert!(backend.shutdown().is_closing());
+        assert!(!path.exists());
+        assert!(matches!(
+            shutdown(&backend, ShutdownFlags::default()).await,
+            Err(ShutdownError::InProgress)
+        ));
+
+        // 默认配置了save规则，退出前保存
+        let backend = backend_in(dir.path());
+        shutdown(&backend, ShutdownFlags::default()).await?;
+        assert!(path.exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_abort() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let backend = backend_in(dir.path());
+        // 一个不会回复ACK的replica
+        let (_, _rx, _) = backend
+            .replication()
+            .add_replica("127.0.0.1".to_string(), 6380, "?", -1);
+        backend.replication().feed(b"*1\r\n$4\r\nPING\r\n");
+
+        let cloned = backend.clone();
+        let task = tokio::spawn(async move {
+            shutdown(
+                &cloned,
+                ShutdownFlags {
+                    save: Some(false),
+                    ..Default::default()
+                },
+            )
+            .await
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(backend.shutdown().is_write_paused());
+        assert!(backend.shutdown().abort());
+        assert!(matches!(task.await?, Err(ShutdownError::Aborted)));
+        assert!(!backend.shutdown().is_write_paused());
+        assert!(!backend.shutdown().is_closing());
+        assert!(!backend.shutdown().abort());
+
+        // NOW 不等待replica
+        shutdown(
+            &backend,
+            ShutdownFlags {
+                save: Some(false),
+                now: true,
+                force: false,
+            },
+        )
+        .await?;
+        assert!(backend.shutdown().is_closing());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_command() -> Result<()> {
+        let backend = Backend::new();
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let cloned = backend.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(network::stream_handler(stream, cloned.clone()));
+            }
+        });
+
+        let mut idle = Framed::new(
+            TcpStream::connect(addr).await?,
+            network::RedisCodec::default(),
+        );
+        let mut client = Framed::new(
+            TcpStream::connect(addr).await?,
+            network::RedisCodec::default(),
+        );
+        let cmd = |args: &[&str]| {
+            RespArray::new(
+                args.iter()
+                    .map(|arg| BulkString::new(*arg).into())
+                    .collect::<Vec<RespFrame>>(),
+            )
+            .encode()
+        };
+        client.get_mut().write_all(&cmd(&["ping"])).await?;
+        assert!(client.next().await.is_some());
+        assert_eq!(backend.shutdown().connections(), 2);
+
+        // 成功之后不回复，所有连接都会断开
+        client
+            .get_mut()
+            .write_all(&cmd(&["shutdown", "nosave"]))
+            .await?;
+        assert!(client.next().await.is_none());
+        assert!(idle.next().await.is_none());
+        assert!(
+            backend
+                .shutdown()
+                .wait_connections(Duration::from_secs(1))
+                .await
+        );
+        Ok(())
+    }
+}
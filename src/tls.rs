@@ -0,0 +1,745 @@
+//! TLS
+//!
+//! 在 `tls-port` 上监听TLS连接，握手完成之后和普通连接一样处理。
+//! 证书相关的配置通过CONFIG SET修改或者证书文件更新之后会重新加载，新的连接使用新的证书，
+//! 已经建立的连接不受影响。
+
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use rustls::{
+    crypto::{ring, CryptoProvider},
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    server::WebPkiClientVerifier,
+    version::{TLS12, TLS13},
+    CipherSuite, RootCertStore, ServerConfig, SupportedProtocolVersion,
+};
+use thiserror::Error;
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+
+use crate::{network, Backend};
+
+/// 检查证书文件是否更新的间隔
+const TLS_CRON_PERIOD: Duration = Duration::from_secs(5);
+
+/// OpenSSL风格的名字和对应的加密套件
+const CIPHER_NAMES: &[(&str, CipherSuite)] = &[
+    (
+        "TLS_AES_256_GCM_SHA384",
+        CipherSuite::TLS13_AES_256_GCM_SHA384,
+    ),
+    (
+        "TLS_AES_128_GCM_SHA256",
+        CipherSuite::TLS13_AES_128_GCM_SHA256,
+    ),
+    (
+        "TLS_CHACHA20_POLY1305_SHA256",
+        CipherSuite::TLS13_CHACHA20_POLY1305_SHA256,
+    ),
+    (
+        "ECDHE-ECDSA-AES256-GCM-SHA384",
+        CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384,
+    ),
+    (
+        "ECDHE-ECDSA-AES128-GCM-SHA256",
+        CipherSuite::TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256,
+    ),
+    (
+        "ECDHE-ECDSA-CHACHA20-POLY1305",
+        CipherSuite::TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256,
+    ),
+    (
+        "ECDHE-RSA-AES256-GCM-SHA384",
+        CipherSuite::TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384,
+    ),
+    (
+        "ECDHE-RSA-AES128-GCM-SHA256",
+        CipherSuite::TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256,
+    ),
+    (
+        "ECDHE-RSA-CHACHA20-POLY1305",
+        CipherSuite::TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256,
+    ),
+];
+
+/// TLS相关的异常
+#[derive(Error, Debug)]
+pub enum TlsError {
+    #[error("Failed to load {path}: {error}")]
+    File { path: String, error: std::io::Error },
+    #[error("No certificate found in {0}")]
+    NoCertificate(String),
+    #[error("No private key found in {0}")]
+    NoPrivateKey(String),
+    #[error("tls-cert-file and tls-key-file must be configured")]
+    MissingCertificate,
+    #[error(
+        "tls-ca-cert-file or tls-ca-cert-dir must be configured when tls-auth-clients is enabled"
+    )]
+    MissingCa,
+    #[error("Unsupported TLS protocol '{0}'")]
+    UnknownProtocol(String),
+    #[error("Unsupported TLS cipher '{0}'")]
+    UnknownCipher(String),
+    #[error("No TLS cipher available for the configured protocols")]
+    NoCipher,
+    #[error("TLS error: {0}")]
+    Rustls(#[from] rustls::Error),
+    #[error("Client certificate verifier error: {0}")]
+    Verifier(#[from] rustls::server::VerifierBuilderError),
+}
+
+/// 是否验证客户端证书
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlsAuthClients {
+    /// 必须提供证书
+    Yes,
+    /// 不验证
+    No,
+    /// 提供了证书时验证
+    Optional,
+}
+
+impl FromStr for TlsAuthClients {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "yes" => Ok(TlsAuthClients::Yes),
+            "no" => Ok(TlsAuthClients::No),
+            "optional" => Ok(TlsAuthClients::Optional),
+            _ => Err("argument(s) must be one of the following: no, yes, optional".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for TlsAuthClients {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TlsAuthClients::Yes => write!(f, "yes"),
+            TlsAuthClients::No => write!(f, "no"),
+            TlsAuthClients::Optional => write!(f, "optional"),
+        }
+    }
+}
+
+/// TLS配置
+#[derive(Debug, Clone, PartialEq)]
+pub struct TlsConfig {
+    /// 为0时不监听TLS端口
+    pub port: u16,
+    pub cert_file: String,
+    pub key_file: String,
+    pub ca_cert_file: String,
+    pub ca_cert_dir: String,
+    pub auth_clients: TlsAuthClients,
+    /// 允许的协议版本，例如 TLSv1.2 TLSv1.3，为空时两者都允许
+    pub protocols: String,
+    /// TLSv1.2的加密套件，冒号分隔，为空时使用默认值
+    pub ciphers: String,
+    /// TLSv1.3的加密套件，冒号分隔，为空时使用默认值
+    pub ciphersuites: String,
+    /// 使用服务端的加密套件顺序
+    pub prefer_server_ciphers: bool,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        TlsConfig {
+            port: 0,
+            cert_file: String::new(),
+            key_file: String::new(),
+            ca_cert_file: String::new(),
+            ca_cert_dir: String::new(),
+            auth_clients: TlsAuthClients::Yes,
+            protocols: String::new(),
+            ciphers: String::new(),
+            ciphersuites: String::new(),
+            prefer_server_ciphers: false,
+        }
+    }
+}
+
+impl TlsConfig {
+    /// 需要监控修改时间的文件
+    fn files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = [&self.cert_file, &self.key_file, &self.ca_cert_file]
+            .into_iter()
+            .filter(|f| !f.is_empty())
+            .map(PathBuf::from)
+            .collect();
+        if !self.ca_cert_dir.is_empty() {
+            files.push(PathBuf::from(&self.ca_cert_dir));
+        }
+        files
+    }
+}
+
+/// TLS运行时状态，挂在Backend上共享
+#[derive(Default)]
+pub struct TlsState {
+    config: RwLock<TlsConfig>,
+    /// 当前使用的证书，重新加载时整个替换
+    acceptor: RwLock<Option<TlsAcceptor>>,
+    /// 加载时文件的修改时间
+    mtimes: Mutex<Vec<(PathBuf, Option<SystemTime>)>>,
+}
+
+impl fmt::Debug for TlsState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsState")
+            .field("config", &self.config)
+            .field("loaded", &self.acceptor.read().unwrap().is_some())
+            .finish()
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl TlsState {
+    pub fn config(&self) -> TlsConfig {
+        self.config.read().unwrap().clone()
+    }
+
+    pub fn set_config(&self, config: TlsConfig) {
+        *self.config.write().unwrap() = config;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.read().unwrap().port != 0
+    }
+
+    /// 当前的证书，没有加载过时返回None
+    pub fn acceptor(&self) -> Option<TlsAcceptor> {
+        self.acceptor.read().unwrap().clone()
+    }
+
+    /// 按照当前配置重新加载证书，失败时继续使用原来的证书
+    pub fn reload(&self) -> Result<(), TlsError> {
+        let config = self.config();
+        // 先记录修改时间，加载期间文件再次更新时下次还会重新加载
+        let mtimes = config
+            .files()
+            .into_iter()
+            .map(|f| {
+                let mtime = modified(&f);
+                (f, mtime)
+            })
+            .collect();
+        let server_config = build_server_config(&config)?;
+        *self.acceptor.write().unwrap() = Some(TlsAcceptor::from(Arc::new(server_config)));
+        *self.mtimes.lock().unwrap() = mtimes;
+        Ok(())
+    }
+
+    /// 开启TLS时重新加载，用于CONFIG SET
+    pub fn reload_if_enabled(&self) -> Result<(), TlsError> {
+        if self.is_enabled() {
+            self.reload()?;
+        }
+        Ok(())
+    }
+
+    /// 加载之后证书文件是否有修改
+    fn files_changed(&self) -> bool {
+        self.mtimes
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|(f, mtime)| modified(f) != *mtime)
+    }
+}
+
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let error = |e: rustls::pki_types::pem::Error| TlsError::File {
+        path: path.to_string(),
+        error: std::io::Error::other(e.to_string()),
+    };
+    let certs = CertificateDer::pem_file_iter(path)
+        .map_err(error)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(error)?;
+    if certs.is_empty() {
+        return Err(TlsError::NoCertificate(path.to_string()));
+    }
+    Ok(certs)
+}
+
+fn load_roots(config: &TlsConfig) -> Result<RootCertStore, TlsError> {
+    let mut files = vec![];
+    if !config.ca_cert_file.is_empty() {
+        files.push(config.ca_cert_file.clone());
+    }
+    if !config.ca_cert_dir.is_empty() {
+        let entries = fs::read_dir(&config.ca_cert_dir).map_err(|error| TlsError::File {
+            path: config.ca_cert_dir.clone(),
+            error,
+        })?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_file() {
+                files.push(path.to_string_lossy().to_string());
+            }
+        }
+    }
+    if files.is_empty() {
+        return Err(TlsError::MissingCa);
+    }
+    let mut roots = RootCertStore::empty();
+    for file in files {
+        for cert in load_certs(&file)? {
+            roots.add(cert)?;
+        }
+    }
+    Ok(roots)
+}
+
+fn protocol_versions(protocols: &str) -> Result<Vec<&'static SupportedProtocolVersion>, TlsError> {
+    if protocols.trim().is_empty() {
+        return Ok(vec![&TLS12, &TLS13]);
+    }
+    protocols
+        .split_whitespace()
+        .map(|p| match p.to_ascii_lowercase().as_str() {
+            "tlsv1.2" => Ok(&TLS12),
+            "tlsv1.3" => Ok(&TLS13),
+            _ => Err(TlsError::UnknownProtocol(p.to_string())),
+        })
+        .collect()
+}
+
+/// 解析冒号分隔的加密套件列表，同时支持OpenSSL和IANA的名字
+fn parse_ciphers(ciphers: &str) -> Result<Vec<CipherSuite>, TlsError> {
+    ciphers
+        .split(':')
+        .filter(|c| !c.is_empty())
+        .map(|name| {
+            CIPHER_NAMES
+                .iter()
+                .find(|(n, suite)| {
+                    n.eq_ignore_ascii_case(name)
+                        || suite.as_str().is_some_and(|s| s.eq_ignore_ascii_case(name))
+                })
+                .map(|(_, suite)| *suite)
+                .ok_or_else(|| TlsError::UnknownCipher(name.to_string()))
+        })
+        .collect()
+}
+
+/// 根据配置的加密套件过滤provider，配置的顺序就是服务端的优先顺序
+fn crypto_provider(config: &TlsConfig) -> Result<CryptoProvider, TlsError> {
+    let mut provider = ring::default_provider();
+    let tls12 = parse_ciphers(&config.ciphers)?;
+    let tls13 = parse_ciphers(&config.ciphersuites)?;
+    let default_suites = provider.cipher_suites.clone();
+    let mut suites = vec![];
+    for (configured, is_tls13) in [(tls13, true), (tls12, false)] {
+        let available = default_suites
+            .iter()
+            .filter(|s| matches!(s, rustls::SupportedCipherSuite::Tls13(_)) == is_tls13);
+        if configured.is_empty() {
+            suites.extend(available.cloned());
+            continue;
+        }
+        for suite in configured {
+            if let Some(s) = available.clone().find(|s| s.suite() == suite) {
+                suites.push(*s);
+            }
+        }
+    }
+    if suites.is_empty() {
+        return Err(TlsError::NoCipher);
+    }
+    provider.cipher_suites = suites;
+    Ok(provider)
+}
+
+/// 根据配置创建rustls的ServerConfig
+pub fn build_server_config(config: &TlsConfig) -> Result<ServerConfig, TlsError> {
+    if config.cert_file.is_empty() || config.key_file.is_empty() {
+        return Err(TlsError::MissingCertificate);
+    }
+    let certs = load_certs(&config.cert_file)?;
+    let key = PrivateKeyDer::from_pem_file(&config.key_file).map_err(|e| match e {
+        rustls::pki_types::pem::Error::NoItemsFound => {
+            TlsError::NoPrivateKey(config.key_file.clone())
+        }
+        e => TlsError::File {
+            path: config.key_file.clone(),
+            error: std::io::Error::other(e.to_string()),
+        },
+    })?;
+
+    let provider = Arc::new(crypto_provider(config)?);
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_protocol_versions(&protocol_versions(&config.protocols)?)?;
+    let builder = match config.auth_clients {
+        TlsAuthClients::No => builder.with_no_client_auth(),
+        auth => {
+            let verifier = WebPkiClientVerifier::builder_with_provider(
+                Arc::new(load_roots(config)?),
+                provider,
+            );
+            let verifier = match auth {
+                TlsAuthClients::Optional => verifier.allow_unauthenticated().build()?,
+                _ => verifier.build()?,
+            };
+            builder.with_client_cert_verifier(verifier)
+        }
+    };
+    let mut server_config = builder.with_single_cert(certs, key)?;
+    server_config.ignore_client_order = config.prefer_server_ciphers;
+    Ok(server_config)
+}
+
+/// 接受TLS连接，每个连接使用接受时的证书
+pub async fn serve_tls(listener: TcpListener, backend: Backend) -> anyhow::Result<()> {
+    loop {
+        let (stream, remote_addr) = tokio::select! {
+            _ = backend.shutdown().closed() => return Ok(()),
+            ret = listener.accept() => ret?,
+        };
+        let Some(acceptor) = backend.tls().acceptor() else {
+            continue;
+        };
+        tracing::info!("Accepted TLS connection from: {}", remote_addr);
+        let keepalive = backend.config().read(|c| c.tcp_keepalive);
+        if let Err(e) = network::set_tcp_keepalive(&stream, keepalive) {
+            tracing::warn!("Failed to set keepalive for {}: {}", remote_addr, e);
+        }
+        let backend = backend.clone();
+        tokio::spawn(async move {
+            match network::tls_stream_handler(stream, acceptor, backend).await {
+                Ok(_) => tracing::info!("TLS connection from {} exited", remote_addr),
+                Err(e) => tracing::warn!("handle error for {}: {:?}", remote_addr, e),
+            }
+        });
+    }
+}
+
+/// 证书文件更新之后自动重新加载
+pub async fn run_tls_cron(backend: Backend) {
+    let mut interval = tokio::time::interval(TLS_CRON_PERIOD);
+    loop {
+        interval.tick().await;
+        let tls = backend.tls();
+        if !tls.is_enabled() || !tls.files_changed() {
+            continue;
+        }
+        match tls.reload() {
+            Ok(()) => tracing::info!("TLS certificates reloaded"),
+            Err(e) => tracing::warn!("Failed to reload TLS certificates: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config, BulkString, RespArray, RespEncode, RespFrame};
+    use anyhow::Result;
+    use futures::StreamExt;
+    use rcgen::{
+        BasicConstraints, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa, KeyPair,
+    };
+    use rustls::{pki_types::ServerName, ClientConfig};
+    use tokio::{io::AsyncWriteExt, net::TcpStream};
+    use tokio_rustls::TlsConnector;
+    use tokio_util::codec::Framed;
+
+    /// 测试用的CA，签发服务端和客户端证书
+    struct TestCa {
+        cert: rcgen::Certificate,
+        key: KeyPair,
+    }
+
+    impl TestCa {
+        fn new(name: &str) -> Result<Self> {
+            let key = KeyPair::generate()?;
+            let mut params = CertificateParams::new(vec![])?;
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            params.distinguished_name.push(DnType::CommonName, name);
+            let cert = params.self_signed(&key)?;
+            Ok(TestCa { cert, key })
+        }
+
+        /// 返回证书和私钥的PEM
+        fn issue(&self, name: &str, usage: ExtendedKeyUsagePurpose) -> Result<(String, String)> {
+            let key = KeyPair::generate()?;
+            let mut params = CertificateParams::new(vec![name.to_string()])?;
+            params.distinguished_name.push(DnType::CommonName, name);
+            params.extended_key_usages = vec![usage];
+            let cert = params.signed_by(&key, &self.cert, &self.key)?;
+            Ok((cert.pem(), key.serialize_pem()))
+        }
+    }
+
+    fn client_config(
+        ca: &TestCa,
+        client: Option<&(String, String)>,
+        versions: &[&'static SupportedProtocolVersion],
+    ) -> Result<ClientConfig> {
+        let mut roots = RootCertStore::empty();
+        roots.add(ca.cert.der().clone())?;
+        let builder = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_protocol_versions(versions)?
+            .with_root_certificates(roots);
+        let config = match client {
+            Some((cert, key)) => builder.with_client_auth_cert(
+                CertificateDer::pem_slice_iter(cert.as_bytes()).collect::<Result<Vec<_>, _>>()?,
+                PrivateKeyDer::from_pem_slice(key.as_bytes())?,
+            )?,
+            None => builder.with_no_client_auth(),
+        };
+        Ok(config)
+    }
+
+    /// 连接之后发送PING，返回收到的回复
+    async fn ping(addr: std::net::SocketAddr, config: ClientConfig) -> Result<Option<RespFrame>> {
+        let stream = TcpStream::connect(addr).await?;
+        let connector = TlsConnector::from(Arc::new(config));
+        let stream = connector
+            .connect(ServerName::try_from("localhost")?, stream)
+            .await?;
+        let mut framed = Framed::new(stream, network::RedisCodec::default());
+        let args = RespArray::new(vec![BulkString::new("ping").into()]);
+        framed.get_mut().write_all(&args.encode()).await?;
+        Ok(framed.next().await.and_then(|f| f.ok()))
+    }
+
+    /// 按照config启动TLS服务，返回监听的地址
+    async fn start_server(backend: &Backend, config: TlsConfig) -> Result<std::net::SocketAddr> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        backend.tls().set_config(TlsConfig {
+            port: addr.port(),
+            ..config
+        });
+        backend.tls().reload()?;
+        tokio::spawn(serve_tls(listener, backend.clone()));
+        Ok(addr)
+    }
+
+    #[tokio::test]
+    async fn test_tls_mutual_auth() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let ca = TestCa::new("test ca")?;
+        let (cert, key) = ca.issue("localhost", ExtendedKeyUsagePurpose::ServerAuth)?;
+        let client = ca.issue("client", ExtendedKeyUsagePurpose::ClientAuth)?;
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        fs::write(path("server.crt"), cert)?;
+        fs::write(path("server.key"), key)?;
+        fs::write(path("ca.crt"), ca.cert.pem())?;
+
+        let backend = Backend::new();
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        backend.tls().set_config(TlsConfig {
+            port: addr.port(),
+            cert_file: path("server.crt"),
+            key_file: path("server.key"),
+            ca_cert_file: path("ca.crt"),
+            protocols: "TLSv1.3".to_string(),
+            ..Default::default()
+        });
+        backend.tls().reload()?;
+        tokio::spawn(serve_tls(listener, backend.clone()));
+
+        let pong = RespFrame::SimpleString("PONG".into());
+        let ret = ping(addr, client_config(&ca, Some(&client), &[&TLS13])?).await?;
+        assert_eq!(ret, Some(pong.clone()));
+        // 没有客户端证书时连接被拒绝
+        let ret = ping(addr, client_config(&ca, None, &[&TLS13])?).await;
+        assert!(!matches!(ret, Ok(Some(_))));
+        // 只允许TLSv1.3
+        let ret = ping(addr, client_config(&ca, Some(&client), &[&TLS12])?).await;
+        assert!(ret.is_err());
+
+        // 不验证客户端证书，重新加载之后新的连接生效
+        let mut config = backend.tls().config();
+        config.auth_clients = TlsAuthClients::No;
+        backend.tls().set_config(config);
+        backend.tls().reload()?;
+        let ret = ping(addr, client_config(&ca, None, &[&TLS13])?).await?;
+        assert_eq!(ret, Some(pong));
+        Ok(())
+    }
+
+    #[test]
+    fn test_tls_reload() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let ca = TestCa::new("test ca")?;
+        let (cert, key) = ca.issue("localhost", ExtendedKeyUsagePurpose::ServerAuth)?;
+        let cert_file = dir.path().join("server.crt");
+        let key_file = dir.path().join("server.key");
+        fs::write(&cert_file, &cert)?;
+        fs::write(&key_file, &key)?;
+
+        let tls = TlsState::default();
+        tls.set_config(TlsConfig {
+            port: 6380,
+            cert_file: cert_file.to_string_lossy().to_string(),
+            key_file: key_file.to_string_lossy().to_string(),
+            auth_clients: TlsAuthClients::No,
+            ciphers: "ECDHE-RSA-AES256-GCM-SHA384:TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256"
+                .to_string(),
+            ..Default::default()
+        });
+        tls.reload()?;
+        assert!(!tls.files_changed());
+
+        // 证书文件更新之后需要重新加载
+        let (cert, key) = ca.issue("localhost", ExtendedKeyUsagePurpose::ServerAuth)?;
+        std::thread::sleep(Duration::from_millis(10));
+        fs::write(&cert_file, cert)?;
+        fs::write(&key_file, key)?;
+        assert!(tls.files_changed());
+        tls.reload()?;
+        assert!(!tls.files_changed());
+
+        // 错误的配置不影响当前使用的证书
+        let mut config = tls.config();
+        config.ciphers = "RC4-MD5".to_string();
+        tls.set_config(config.clone());
+        assert!(matches!(tls.reload(), Err(TlsError::UnknownCipher(_))));
+        config.ciphers = String::new();
+        config.auth_clients = TlsAuthClients::Yes;
+        tls.set_config(config);
+        assert!(matches!(tls.reload(), Err(TlsError::MissingCa)));
+        assert!(tls.acceptor().is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tls_bad_certificates() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let ca = TestCa::new("test ca")?;
+        let other_ca = TestCa::new("other ca")?;
+        let (cert, key) = ca.issue("localhost", ExtendedKeyUsagePurpose::ServerAuth)?;
+        let client = ca.issue("client", ExtendedKeyUsagePurpose::ClientAuth)?;
+        let untrusted = other_ca.issue("client", ExtendedKeyUsagePurpose::ClientAuth)?;
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        fs::write(path("server.crt"), cert)?;
+        fs::write(path("server.key"), key)?;
+        fs::write(path("ca.crt"), ca.cert.pem())?;
+
+        let backend = Backend::new();
+        let addr = start_server(
+            &backend,
+            TlsConfig {
+                cert_file: path("server.crt"),
+                key_file: path("server.key"),
+                ca_cert_file: path("ca.crt"),
+                ..Default::default()
+            },
+        )
+        .await?;
+
+        let pong = RespFrame::SimpleString("PONG".into());
+        let versions: &[&'static SupportedProtocolVersion] = &[&TLS12, &TLS13];
+        assert_eq!(
+            ping(addr, client_config(&ca, Some(&client), versions)?).await?,
+            Some(pong.clone())
+        );
+        // 客户端不信任服务端的证书，握手失败
+        let ret = ping(addr, client_config(&other_ca, Some(&client), versions)?).await;
+        assert!(ret.is_err());
+        // 客户端证书不是配置的CA签发的
+        let ret = ping(addr, client_config(&ca, Some(&untrusted), versions)?).await;
+        assert!(!matches!(ret, Ok(Some(_))));
+        // TLSv1.2下客户端证书在握手时验证
+        let ret = ping(addr, client_config(&ca, Some(&untrusted), &[&TLS12])?).await;
+        assert!(ret.is_err());
+
+        // optional: 可以不提供证书，提供了证书时必须通过验证
+        let mut config = backend.tls().config();
+        config.auth_clients = TlsAuthClients::Optional;
+        backend.tls().set_config(config);
+        backend.tls().reload()?;
+        assert_eq!(
+            ping(addr, client_config(&ca, None, versions)?).await?,
+            Some(pong.clone())
+        );
+        assert_eq!(
+            ping(addr, client_config(&ca, Some(&client), versions)?).await?,
+            Some(pong)
+        );
+        let ret = ping(addr, client_config(&ca, Some(&untrusted), versions)?).await;
+        assert!(!matches!(ret, Ok(Some(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tls_config_set() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let ca = TestCa::new("test ca")?;
+        let (cert, key) = ca.issue("localhost", ExtendedKeyUsagePurpose::ServerAuth)?;
+        let client = ca.issue("client", ExtendedKeyUsagePurpose::ClientAuth)?;
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        fs::write(path("server.crt"), cert)?;
+        fs::write(path("server.key"), key)?;
+        fs::write(path("ca.crt"), ca.cert.pem())?;
+
+        let backend = Backend::new();
+        let addr = start_server(
+            &backend,
+            TlsConfig {
+                cert_file: path("server.crt"),
+                key_file: path("server.key"),
+                ca_cert_file: path("ca.crt"),
+                ..Default::default()
+            },
+        )
+        .await?;
+        let pong = RespFrame::SimpleString("PONG".into());
+        let set = |name: &str, value: &str| {
+            config::config_set(&backend, vec![(name.to_string(), value.to_string())])
+        };
+
+        // 关闭客户端证书验证之后新的连接不需要证书
+        let ret = ping(addr, client_config(&ca, None, &[&TLS13])?).await;
+        assert!(!matches!(ret, Ok(Some(_))));
+        set("tls-auth-clients", "no")?;
+        assert_eq!(
+            ping(addr, client_config(&ca, None, &[&TLS13])?).await?,
+            Some(pong.clone())
+        );
+
+        // 加载失败时恢复原来的配置，继续使用原来的证书
+        assert!(set("tls-cert-file", &path("missing.crt")).is_err());
+        assert_eq!(backend.tls().config().cert_file, path("server.crt"));
+        assert_eq!(
+            ping(addr, client_config(&ca, Some(&client), &[&TLS13])?).await?,
+            Some(pong.clone())
+        );
+
+        // 更换证书之后只有信任新CA的客户端能连接
+        let new_ca = TestCa::new("new ca")?;
+        let (cert, key) = new_ca.issue("localhost", ExtendedKeyUsagePurpose::ServerAuth)?;
+        fs::write(path("new.crt"), cert)?;
+        fs::write(path("new.key"), key)?;
+        config::config_set(
+            &backend,
+            vec![
+                ("tls-cert-file".to_string(), path("new.crt")),
+                ("tls-key-file".to_string(), path("new.key")),
+            ],
+        )?;
+        assert_eq!(
+            ping(addr, client_config(&new_ca, None, &[&TLS13])?).await?,
+            Some(pong)
+        );
+        assert!(ping(addr, client_config(&ca, None, &[&TLS13])?)
+            .await
+            .is_err());
+        Ok(())
+    }
+}